@@ -40,7 +40,7 @@ pub struct File {
     pub description: Option<String>,
     /// The file size in bytes. Be careful parsing this integer as it can get very large and cause an integer overflow.
     #[serde(rename = "size", skip_serializing_if = "Option::is_none")]
-    pub size: Option<i32>,
+    pub size: Option<i64>,
     #[serde(rename = "path_collection", skip_serializing_if = "Option::is_none")]
     pub path_collection: Option<Box<models::FileAllOfPathCollection>>,
     /// The date and time when the file was created on Box.
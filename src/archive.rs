@@ -0,0 +1,880 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use r#box::apis::configuration::Configuration;
+use chrono::DateTime;
+use iced::{
+    Element, Font,
+    Length::Fill,
+    widget::{Column, button, column, container, row, scrollable, text},
+};
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+
+use crate::{
+    Message, media,
+    project_page::{InternalType, Node},
+    viewer::ViewerMessage,
+};
+
+/// Extensions listed as archives. ".tar.gz" is told apart from a lone ".gz" by name.
+pub(crate) const ARCHIVE_EXTENSIONS: [&str; 6] = ["zip", "tar", "tgz", "gz", "7z", "jar"];
+
+/// Bytes fetched per ranged read.
+const CHUNK: u64 = 256 * 1024;
+
+/// Compressed tarballs have to be read whole, so stop after about a gigabyte.
+const MAX_READS: usize = 4096;
+
+/// Entries listed before giving up on huge archives.
+const MAX_ENTRIES: usize = 20_000;
+
+/// A ZIP's central directory larger than this is not read.
+const MAX_DIRECTORY: u64 = 64 * 1024 * 1024;
+
+/// Seconds between 1601, where the file times in 7-Zip headers start, and 1970.
+const FILETIME_EPOCH: i64 = 11_644_473_600;
+
+/// Separates an archive's Box ID from an entry's path in the IDs of virtual nodes.
+const ENTRY_SEPARATOR: char = '/';
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ArchiveEntry {
+    pub path: String,
+    pub size: u64,
+    /// Size inside the archive, where entries are compressed one by one
+    pub compressed: Option<u64>,
+    pub modified: Option<String>,
+    pub method: String,
+    pub encrypted: bool,
+    /// Where a ZIP entry's local header starts
+    #[serde(skip)]
+    pub offset: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Listing {
+    pub format: String,
+    /// Files only. Directories are implied by the paths.
+    pub entries: Vec<ArchiveEntry>,
+    /// Why the listing is incomplete or missing
+    pub note: Option<String>,
+}
+
+/// ID of the virtual node for `path` inside archive `archive_id`.
+pub(crate) fn entry_id(archive_id: &str, path: &str) -> String {
+    format!("{archive_id}{ENTRY_SEPARATOR}{path}")
+}
+
+/// Box IDs are numeric, so only virtual entries contain the separator.
+pub(crate) fn is_entry(id: &str) -> bool {
+    id.contains(ENTRY_SEPARATOR)
+}
+
+/// Reads a Box file through ranged downloads, so only the parts asked for are fetched.
+///
+/// Blocks on the runtime, so it must only be used inside `spawn_blocking`.
+pub(crate) struct RangedReader {
+    handle: Handle,
+    config: Configuration,
+    id: String,
+    size: u64,
+    pos: u64,
+    buf: Vec<u8>,
+    buf_start: u64,
+    reads: usize,
+}
+
+impl RangedReader {
+    fn open(handle: Handle, config: Configuration, id: String) -> io::Result<Self> {
+        let mut reader = Self {
+            handle,
+            config,
+            id,
+            size: 0,
+            pos: 0,
+            buf: vec![],
+            buf_start: 0,
+            reads: 0,
+        };
+        reader.fill(0)?;
+        Ok(reader)
+    }
+
+    fn fill(&mut self, at: u64) -> io::Result<()> {
+        if self.reads >= MAX_READS {
+            return Err(io::Error::other("read limit reached"));
+        }
+        self.reads += 1;
+        let range = format!("bytes={at}-{}", at + CHUNK - 1);
+        let (bytes, total) = self
+            .handle
+            .block_on(media::fetch_range(&self.config, &self.id, Some(range)))
+            .map_err(io::Error::other)?;
+        match total {
+            Some(total) => {
+                self.size = total;
+                self.buf_start = at;
+            }
+            // Without a Content-Range the server sent the whole file
+            None => {
+                self.size = bytes.len() as u64;
+                self.buf_start = 0;
+            }
+        }
+        self.buf = bytes;
+        Ok(())
+    }
+}
+
+impl Read for RangedReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.size || out.is_empty() {
+            return Ok(0);
+        }
+        let end = self.buf_start + self.buf.len() as u64;
+        if self.pos < self.buf_start || self.pos >= end {
+            self.fill(self.pos)?;
+        }
+        let start = (self.pos - self.buf_start) as usize;
+        let n = out.len().min(self.buf.len().saturating_sub(start));
+        out[..n].copy_from_slice(&self.buf[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for RangedReader {
+    fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+        let pos = match from {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(d) => self.size.checked_add_signed(d),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+        };
+        self.pos = pos.ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "seek before the start of the file",
+        ))?;
+        Ok(self.pos)
+    }
+}
+
+fn le16(b: &[u8], at: usize) -> u64 {
+    b.get(at..at + 2)
+        .map_or(0, |s| u16::from_le_bytes([s[0], s[1]]) as u64)
+}
+
+fn le32(b: &[u8], at: usize) -> u64 {
+    b.get(at..at + 4)
+        .map_or(0, |s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]) as u64)
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+    b.get(at..at + 8)
+        .map_or(0, |s| u64::from_le_bytes(s.try_into().unwrap_or_default()))
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, at: u64, len: u64) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(at))?;
+    let mut buf = vec![];
+    reader.take(len).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+fn timestamp(secs: i64) -> Option<String> {
+    DateTime::from_timestamp(secs, 0).map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+}
+
+/// MS-DOS date and time, as stored in ZIP headers.
+fn dos_time(date: u64, time: u64) -> Option<String> {
+    if date == 0 {
+        return None;
+    }
+    Some(format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        (date >> 9) + 1980,
+        (date >> 5) & 0x0F,
+        date & 0x1F,
+        time >> 11,
+        (time >> 5) & 0x3F
+    ))
+}
+
+fn zip_method(method: u64) -> String {
+    match method {
+        0 => "Stored".to_string(),
+        1 => "Shrunk".to_string(),
+        6 => "Imploded".to_string(),
+        8 => "Deflate".to_string(),
+        9 => "Deflate64".to_string(),
+        12 => "BZIP2".to_string(),
+        14 => "LZMA".to_string(),
+        93 => "Zstandard".to_string(),
+        95 => "XZ".to_string(),
+        98 => "PPMd".to_string(),
+        99 => "AES".to_string(),
+        m => format!("Method {m}"),
+    }
+}
+
+/// List a ZIP from its central directory, which is at the end of the file.
+pub(crate) fn list_zip<R: Read + Seek>(reader: &mut R) -> io::Result<Listing> {
+    // The end of central directory record is 22 bytes plus a comment of up to 64 KiB
+    let size = reader.seek(SeekFrom::End(0))?;
+    let tail_len = size.min(22 + 65535);
+    let tail_start = size - tail_len;
+    let tail = read_at(reader, tail_start, tail_len)?;
+    let eocd = tail
+        .windows(4)
+        .rposition(|w| w == b"PK\x05\x06")
+        .ok_or(io::Error::other("no ZIP end of central directory record"))?;
+    let mut count = le16(&tail, eocd + 10);
+    let mut dir_size = le32(&tail, eocd + 12);
+    let mut dir_offset = le32(&tail, eocd + 16);
+
+    // ZIP64 archives leave 0xFFFF... in the old record and point to a larger one
+    if (dir_offset == 0xFFFF_FFFF || count == 0xFFFF) && eocd >= 20 {
+        let locator = eocd - 20;
+        if tail.get(locator..locator + 4) == Some(b"PK\x06\x07") {
+            let record = read_at(reader, le64(&tail, locator + 8), 56)?;
+            if record.starts_with(b"PK\x06\x06") {
+                count = le64(&record, 32);
+                dir_size = le64(&record, 40);
+                dir_offset = le64(&record, 48);
+            }
+        }
+    }
+    if dir_size > MAX_DIRECTORY {
+        return Err(io::Error::other("the ZIP's directory is too large to read"));
+    }
+
+    let dir = read_at(reader, dir_offset, dir_size)?;
+    let mut entries = vec![];
+    let mut at = 0;
+    while dir.get(at..at + 4) == Some(b"PK\x01\x02") && entries.len() < MAX_ENTRIES {
+        let flags = le16(&dir, at + 8);
+        let method = le16(&dir, at + 10);
+        let mut compressed = le32(&dir, at + 20);
+        let mut size = le32(&dir, at + 24);
+        let name_len = le16(&dir, at + 28) as usize;
+        let extra_len = le16(&dir, at + 30) as usize;
+        let comment_len = le16(&dir, at + 32) as usize;
+        let mut offset = le32(&dir, at + 42);
+        let name_start = at + 46;
+        let Some(name) = dir.get(name_start..name_start + name_len) else {
+            break;
+        };
+        let path = String::from_utf8_lossy(name).into_owned();
+
+        // The ZIP64 extra field holds sizes too large for the fixed fields, in this order
+        let extra = dir
+            .get(name_start + name_len..name_start + name_len + extra_len)
+            .unwrap_or_default();
+        let mut e = 0;
+        while e + 4 <= extra.len() {
+            let id = le16(extra, e);
+            let len = le16(extra, e + 2) as usize;
+            if id == 0x0001 {
+                let mut field = e + 4;
+                if size == 0xFFFF_FFFF {
+                    size = le64(extra, field);
+                    field += 8;
+                }
+                if compressed == 0xFFFF_FFFF {
+                    compressed = le64(extra, field);
+                    field += 8;
+                }
+                if offset == 0xFFFF_FFFF {
+                    offset = le64(extra, field);
+                }
+            }
+            e += 4 + len;
+        }
+
+        if !path.ends_with('/') {
+            entries.push(ArchiveEntry {
+                path,
+                size,
+                compressed: Some(compressed),
+                modified: dos_time(le16(&dir, at + 14), le16(&dir, at + 12)),
+                method: zip_method(method),
+                encrypted: flags & 1 != 0,
+                offset: Some(offset),
+            });
+        }
+        at = name_start + name_len + extra_len + comment_len;
+    }
+    Ok(Listing {
+        format: "ZIP".to_string(),
+        note: (count as usize > entries.len() && entries.len() == MAX_ENTRIES)
+            .then(|| format!("Only the first {MAX_ENTRIES} entries are listed")),
+        entries,
+    })
+}
+
+/// List a tarball. Plain ones skip over file contents with ranged reads; compressed ones
+/// have to be read through.
+fn list_tar<R: Read + Seek>(reader: R, gzipped: bool) -> Listing {
+    fn collect<T: Read>(
+        entries: io::Result<tar::Entries<'_, T>>,
+        method: &str,
+        out: &mut Vec<ArchiveEntry>,
+    ) -> Option<String> {
+        let entries = match entries {
+            Ok(entries) => entries,
+            Err(e) => return Some(format!("Could not read the archive: {e}")),
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => return Some(format!("Stopped early: {e}")),
+            };
+            let header = entry.header();
+            if !header.entry_type().is_file() {
+                continue;
+            }
+            out.push(ArchiveEntry {
+                path: entry
+                    .path()
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                size: entry.size(),
+                compressed: None,
+                modified: header.mtime().ok().and_then(|t| timestamp(t as i64)),
+                method: method.to_string(),
+                encrypted: false,
+                offset: None,
+            });
+            if out.len() == MAX_ENTRIES {
+                return Some(format!("Only the first {MAX_ENTRIES} entries are listed"));
+            }
+        }
+        None
+    }
+
+    let mut entries = vec![];
+    let note = if gzipped {
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(reader));
+        collect(archive.entries(), "gzip (whole archive)", &mut entries)
+    } else {
+        let mut archive = tar::Archive::new(reader);
+        collect(archive.entries_with_seek(), "Stored", &mut entries)
+    };
+    Listing {
+        format: if gzipped { "tar.gz" } else { "tar" }.to_string(),
+        entries,
+        note,
+    }
+}
+
+/// A lone gzip stream holds one file, named in its header.
+fn list_gzip(reader: &mut RangedReader, name: &str) -> io::Result<Listing> {
+    let head = read_at(reader, 0, 1024)?;
+    if !head.starts_with(b"\x1F\x8B") {
+        return Err(io::Error::other("not a gzip file"));
+    }
+    let flags = head.get(3).copied().unwrap_or(0);
+    let mut at = 10;
+    if flags & 0x04 != 0 {
+        at += 2 + le16(&head, 10) as usize;
+    }
+    let stored_name = (flags & 0x08 != 0)
+        .then(|| {
+            let rest = head.get(at..)?;
+            let end = rest.iter().position(|b| *b == 0)?;
+            Some(String::from_utf8_lossy(&rest[..end]).into_owned())
+        })
+        .flatten();
+    // The original size, modulo 4 GiB, is the last four bytes
+    let size_bytes = read_at(reader, reader.size.saturating_sub(4), 4)?;
+    Ok(Listing {
+        format: "gzip".to_string(),
+        entries: vec![ArchiveEntry {
+            path: stored_name.unwrap_or(name.trim_end_matches(".gz").to_string()),
+            size: le32(&size_bytes, 0),
+            compressed: Some(reader.size),
+            modified: Some(le32(&head, 4))
+                .filter(|t| *t > 0)
+                .and_then(|t| timestamp(t as i64)),
+            method: "Deflate".to_string(),
+            encrypted: false,
+            offset: None,
+        }],
+        note: None,
+    })
+}
+
+fn encrypted_7z() -> Listing {
+    Listing {
+        format: "7-Zip".to_string(),
+        entries: vec![],
+        note: Some("The listing is encrypted, so the contents are not shown".to_string()),
+    }
+}
+
+/// List a 7-Zip archive from its header, which is at the end of the file and usually
+/// compressed itself.
+fn list_7z<R: Read + Seek>(reader: &mut R) -> io::Result<Listing> {
+    let size = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let archive = match sevenz_rust::Archive::read(reader, size, &[]) {
+        Ok(archive) => archive,
+        // Without a decryptor, encrypted headers read as an unsupported method
+        Err(sevenz_rust::Error::PasswordRequired) => return Ok(encrypted_7z()),
+        Err(sevenz_rust::Error::UnsupportedCompressionMethod(m)) if m == "AES256SHA256" => {
+            return Ok(encrypted_7z());
+        }
+        Err(e) => return Err(io::Error::other(e.to_string())),
+    };
+    let mut entries = vec![];
+    for (i, file) in archive.files.iter().enumerate() {
+        if file.is_directory() || file.is_anti_item() {
+            continue;
+        }
+        if entries.len() == MAX_ENTRIES {
+            break;
+        }
+        // Files are compressed together in folders, each through a chain of coders
+        let coders = archive
+            .stream_map
+            .file_folder_index
+            .get(i)
+            .copied()
+            .flatten()
+            .and_then(|folder| archive.folders.get(folder))
+            .map(|folder| folder.coders.as_slice())
+            .unwrap_or_default();
+        let methods: Vec<_> = coders
+            .iter()
+            .map(|coder| {
+                let id = coder.decompression_method_id();
+                sevenz_rust::SevenZMethod::by_id(id)
+                    .map_or(format!("Method {id:02X?}"), |m| m.name().to_string())
+            })
+            .collect();
+        entries.push(ArchiveEntry {
+            path: file.name().to_string(),
+            size: file.size(),
+            compressed: None,
+            modified: file
+                .has_last_modified_date
+                .then(|| (file.last_modified_date().to_raw() / 10_000_000) as i64)
+                .and_then(|secs| timestamp(secs - FILETIME_EPOCH)),
+            method: if methods.is_empty() {
+                "Stored".to_string()
+            } else {
+                methods.join(" + ")
+            },
+            encrypted: coders.iter().any(|coder| {
+                coder.decompression_method_id() == sevenz_rust::SevenZMethod::ID_AES256SHA256
+            }),
+            offset: None,
+        });
+    }
+    Ok(Listing {
+        format: "7-Zip".to_string(),
+        note: (entries.len() == MAX_ENTRIES)
+            .then(|| format!("Only the first {MAX_ENTRIES} entries are listed")),
+        entries,
+    })
+}
+
+/// Read the listing of archive `id`, called `name` in Box.
+pub(crate) async fn list(
+    config: Configuration,
+    id: String,
+    name: String,
+) -> Result<Listing, String> {
+    let handle = Handle::current();
+    tokio::task::spawn_blocking(move || {
+        let mut reader = RangedReader::open(handle, config, id).map_err(|e| e.to_string())?;
+        let lower = name.to_ascii_lowercase();
+        let result = if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+            Ok(list_tar(reader, true))
+        } else if lower.ends_with(".tar") {
+            Ok(list_tar(reader, false))
+        } else if lower.ends_with(".gz") {
+            list_gzip(&mut reader, &name)
+        } else if lower.ends_with(".7z") {
+            list_7z(&mut reader)
+        } else {
+            list_zip(&mut reader)
+        };
+        result.map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Read the file at `path` out of a ZIP, if it is stored or deflated and at most `limit` bytes.
+pub(crate) fn zip_entry<R: Read + Seek>(
+    reader: &mut R,
+    path: &str,
+    limit: u64,
+) -> io::Result<Vec<u8>> {
+    let listing = list_zip(reader)?;
+    let entry = listing
+        .entries
+        .iter()
+        .find(|e| e.path == path)
+        .ok_or(io::Error::other(format!("no {path} in the ZIP")))?;
+    if entry.encrypted {
+        return Err(io::Error::other(format!("{path} is encrypted")));
+    }
+    if entry.size > limit {
+        return Err(io::Error::other(format!("{path} is too large to read")));
+    }
+    let offset = entry.offset.unwrap_or_default();
+    let header = read_at(reader, offset, 30)?;
+    if !header.starts_with(b"PK\x03\x04") {
+        return Err(io::Error::other(format!("no local header for {path}")));
+    }
+    // The local header's name and extra field can differ from the central directory's
+    let start = offset + 30 + le16(&header, 26) + le16(&header, 28);
+    let data = read_at(reader, start, entry.compressed.unwrap_or(entry.size))?;
+    match entry.method.as_str() {
+        "Stored" => Ok(data),
+        "Deflate" => {
+            let mut out = vec![];
+            flate2::read::DeflateDecoder::new(&data[..])
+                .take(limit)
+                .read_to_end(&mut out)?;
+            Ok(out)
+        }
+        method => Err(io::Error::other(format!("cannot decompress {method}"))),
+    }
+}
+
+/// Run `read` over Box file `id` on a blocking thread, fetching only the ranges it reads.
+pub(crate) async fn read_ranged<T: Send + 'static>(
+    config: Configuration,
+    id: String,
+    read: impl FnOnce(&mut RangedReader) -> io::Result<T> + Send + 'static,
+) -> Result<T, String> {
+    let handle = Handle::current();
+    tokio::task::spawn_blocking(move || {
+        let mut reader = RangedReader::open(handle, config, id).map_err(|e| e.to_string())?;
+        read(&mut reader).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Read the file at `path` out of ZIP `id` in Box, fetching only the parts needed.
+pub(crate) async fn read_zip_entry(
+    config: Configuration,
+    id: String,
+    path: String,
+    limit: u64,
+) -> Result<Vec<u8>, String> {
+    read_ranged(config, id, move |reader| zip_entry(reader, &path, limit)).await
+}
+
+/// Virtual nodes for an archive's entries, linking to the archive itself.
+pub(crate) fn entry_nodes(archive: &Node, listing: &Listing) -> Vec<Node> {
+    listing
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(idx, entry)| Node {
+            name: entry.path.clone(),
+            file_type: InternalType::ArchiveEntry,
+            id: entry_id(&archive.id, &entry.path),
+            idx,
+            web_link: archive.web_link.clone(),
+            children: None,
+        })
+        .collect()
+}
+
+/// A size for display, such as "1.5 MiB".
+pub(crate) fn size_text(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for u in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = u;
+    }
+    format!("{value:.1} {unit}")
+}
+
+/// The listing of archive `id`. `expanded` is whether its entries are in the project tree.
+pub(crate) fn view<'a>(listing: &'a Listing, id: &str, expanded: bool) -> Element<'a, Message> {
+    let total: u64 = listing.entries.iter().map(|e| e.size).sum();
+    let cell =
+        |s: String, width: f32| container(text(s).font(Font::MONOSPACE).size(12)).width(width);
+    let header = row![
+        container(text("Name").size(12)).width(Fill),
+        container(text("Size").size(12)).width(90.0),
+        container(text("Packed").size(12)).width(90.0),
+        container(text("Modified").size(12)).width(130.0),
+        container(text("Compression").size(12)).width(150.0),
+    ]
+    .spacing(10);
+    let rows = listing
+        .entries
+        .iter()
+        .fold(Column::new().spacing(2), |col, entry| {
+            let select = Message::ViewerMessage(ViewerMessage::SelectEntry(
+                id.to_string(),
+                entry.path.clone(),
+            ));
+            let method = if entry.encrypted {
+                format!("{} (encrypted)", entry.method)
+            } else {
+                entry.method.clone()
+            };
+            col.push(
+                row![
+                    container(
+                        button(text(&entry.path).size(12))
+                            .style(button::text)
+                            .padding(0)
+                            .on_press_maybe(expanded.then_some(select))
+                    )
+                    .width(Fill),
+                    cell(size_text(entry.size), 90.0),
+                    cell(entry.compressed.map(size_text).unwrap_or_default(), 90.0),
+                    cell(entry.modified.clone().unwrap_or_default(), 130.0),
+                    cell(method, 150.0),
+                ]
+                .spacing(10),
+            )
+        });
+
+    let action = if expanded {
+        button("Remove entries from project tree")
+            .style(button::secondary)
+            .on_press(Message::ViewerMessage(ViewerMessage::CollapseArchive(
+                id.to_string(),
+            )))
+    } else {
+        button("Add entries to project tree")
+            .style(button::primary)
+            .on_press_maybe(
+                (!listing.entries.is_empty())
+                    .then(|| Message::ViewerMessage(ViewerMessage::ExpandArchive(id.to_string()))),
+            )
+    };
+    let hint = if expanded {
+        "Click an entry to tag it. Entries are exported as rows below the archive."
+    } else {
+        "Add the entries to tag them and export them as rows of the sheet."
+    };
+
+    column![
+        text(format!(
+            "{} archive, {} files, {} uncompressed",
+            listing.format,
+            listing.entries.len(),
+            size_text(total)
+        )),
+        match &listing.note {
+            Some(note) => text(note.as_str()).style(text::secondary).size(12),
+            None => text(""),
+        },
+        row![action, text(hint).size(12)]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+        header,
+        scrollable(rows).height(Fill),
+    ]
+    .spacing(5)
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use super::*;
+
+    /// 2024-03-05 10:30 in MS-DOS form.
+    const DOS_DATE: u16 = ((2024 - 1980) << 9) | (3 << 5) | 5;
+    const DOS_TIME: u16 = (10 << 11) | (30 << 5);
+
+    /// A ZIP of stored entries. Names ending in "/" are directories.
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut out = vec![];
+        let mut directory = vec![];
+        for (name, data) in entries {
+            let offset = out.len() as u32;
+            let mut common = vec![];
+            common.extend(0u16.to_le_bytes()); // flags
+            common.extend(0u16.to_le_bytes()); // method
+            common.extend(DOS_TIME.to_le_bytes());
+            common.extend(DOS_DATE.to_le_bytes());
+            common.extend(0u32.to_le_bytes()); // CRC, which listing doesn't check
+            common.extend((data.len() as u32).to_le_bytes());
+            common.extend((data.len() as u32).to_le_bytes());
+            common.extend((name.len() as u16).to_le_bytes());
+            common.extend(0u16.to_le_bytes()); // extra field length
+
+            out.extend(b"PK\x03\x04");
+            out.extend(20u16.to_le_bytes());
+            out.extend(&common);
+            out.extend(name.as_bytes());
+            out.extend(*data);
+
+            directory.extend(b"PK\x01\x02");
+            directory.extend(20u16.to_le_bytes());
+            directory.extend(20u16.to_le_bytes());
+            directory.extend(&common);
+            directory.extend([0; 6]); // comment length, disk, internal attributes
+            directory.extend(0u32.to_le_bytes()); // external attributes
+            directory.extend(offset.to_le_bytes());
+            directory.extend(name.as_bytes());
+        }
+        let directory_offset = out.len() as u32;
+        out.extend(&directory);
+        out.extend(b"PK\x05\x06");
+        out.extend([0; 4]);
+        out.extend((entries.len() as u16).to_le_bytes());
+        out.extend((entries.len() as u16).to_le_bytes());
+        out.extend((directory.len() as u32).to_le_bytes());
+        out.extend(directory_offset.to_le_bytes());
+        out.extend(0u16.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn zip_listing() {
+        let bytes = zip(&[
+            ("docs/", b""),
+            ("docs/a.txt", b"hello"),
+            ("b.bin", b"\x00\x01"),
+        ]);
+        let listing = list_zip(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(listing.format, "ZIP");
+        assert_eq!(listing.note, None);
+        let entries: Vec<_> = listing
+            .entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.size, e.offset))
+            .collect();
+        assert_eq!(
+            entries,
+            [("docs/a.txt", 5, Some(35)), ("b.bin", 2, Some(80))]
+        );
+        assert_eq!(listing.entries[0].method, "Stored");
+        assert_eq!(
+            listing.entries[0].modified.as_deref(),
+            Some("2024-03-05 10:30")
+        );
+    }
+
+    #[test]
+    fn zip_entries_read_back() {
+        let bytes = zip(&[("docs/a.txt", b"hello")]);
+        let listing = list_zip(&mut Cursor::new(bytes.clone())).unwrap();
+        let offset = listing.entries[0].offset.unwrap() as usize;
+        assert_eq!(&bytes[offset..offset + 4], b"PK\x03\x04");
+    }
+
+    #[test]
+    fn not_a_zip() {
+        assert!(list_zip(&mut Cursor::new(vec![0u8; 100])).is_err());
+        assert!(list_zip(&mut Cursor::new(vec![])).is_err());
+    }
+
+    fn tar(gzipped: bool) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        let mut dir = tar::Header::new_gnu();
+        dir.set_entry_type(tar::EntryType::Directory);
+        dir.set_size(0);
+        dir.set_mtime(1_700_000_000);
+        builder.append_data(&mut dir, "docs/", io::empty()).unwrap();
+        let mut file = tar::Header::new_gnu();
+        file.set_size(5);
+        file.set_mtime(1_700_000_000);
+        builder
+            .append_data(&mut file, "docs/a.txt", &b"hello"[..])
+            .unwrap();
+        let bytes = builder.into_inner().unwrap();
+        if !gzipped {
+            return bytes;
+        }
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn tar_listing() {
+        for gzipped in [false, true] {
+            let listing = list_tar(Cursor::new(tar(gzipped)), gzipped);
+            assert_eq!(listing.format, if gzipped { "tar.gz" } else { "tar" });
+            assert_eq!(listing.note, None);
+            assert_eq!(listing.entries.len(), 1);
+            let entry = &listing.entries[0];
+            assert_eq!(entry.path, "docs/a.txt");
+            assert_eq!(entry.size, 5);
+            assert_eq!(entry.modified.as_deref(), Some("2023-11-14 22:13"));
+        }
+    }
+
+    #[test]
+    fn truncated_tar_is_noted() {
+        let bytes = tar(false);
+        let listing = list_tar(Cursor::new(bytes[..600].to_vec()), false);
+        assert!(listing.note.is_some());
+    }
+
+    /// A 7-Zip archive of an empty file and a directory, with a plain header.
+    fn seven_zip() -> Vec<u8> {
+        let modified = (1_700_000_000 + FILETIME_EPOCH as u64) * 10_000_000;
+        let names: Vec<u8> = "empty.txt\0dir\0"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let mut header = vec![0x01, 0x05, 2];
+        // Neither has a stream, and only the first is a file
+        header.extend([0x0E, 1, 0b1100_0000, 0x0F, 1, 0b1000_0000]);
+        header.extend([0x11, names.len() as u8 + 1, 0]);
+        header.extend(&names);
+        header.extend([0x14, 18, 1, 0]);
+        header.extend(modified.to_le_bytes());
+        header.extend(modified.to_le_bytes());
+        header.extend([0, 0]);
+
+        let crc = |b: &[u8]| {
+            let mut crc = flate2::Crc::new();
+            crc.update(b);
+            crc.sum()
+        };
+        let mut start = vec![];
+        start.extend(0u64.to_le_bytes());
+        start.extend((header.len() as u64).to_le_bytes());
+        start.extend(crc(&header).to_le_bytes());
+        let mut out = b"7z\xBC\xAF\x27\x1C\x00\x04".to_vec();
+        out.extend(crc(&start).to_le_bytes());
+        out.extend(start);
+        out.extend(header);
+        out
+    }
+
+    #[test]
+    fn seven_zip_listing() {
+        let listing = list_7z(&mut Cursor::new(seven_zip())).unwrap();
+        assert_eq!(listing.format, "7-Zip");
+        assert_eq!(listing.note, None);
+        assert_eq!(listing.entries.len(), 1);
+        let entry = &listing.entries[0];
+        assert_eq!(entry.path, "empty.txt");
+        assert_eq!(entry.size, 0);
+        assert_eq!(entry.method, "Stored");
+        assert_eq!(entry.modified.as_deref(), Some("2023-11-14 22:13"));
+        assert!(!entry.encrypted);
+    }
+
+    #[test]
+    fn not_a_seven_zip() {
+        assert!(list_7z(&mut Cursor::new(zip(&[("a.txt", b"a")]))).is_err());
+    }
+}
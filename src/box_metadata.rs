@@ -0,0 +1,708 @@
+use std::collections::{HashMap, HashSet};
+
+use r#box::{
+    apis::{
+        Error,
+        configuration::Configuration,
+        metadata_instances_files_api::{
+            DeleteFilesIdMetadataIdIdParams, PostFilesIdMetadataIdIdParams,
+            PutFilesIdMetadataIdIdParams, delete_files_id_metadata_id_id,
+            post_files_id_metadata_id_id, put_files_id_metadata_id_id,
+        },
+        metadata_instances_folders_api::{
+            DeleteFoldersIdMetadataIdIdParams, PostFoldersIdMetadataIdIdParams,
+            PutFoldersIdMetadataIdIdParams, delete_folders_id_metadata_id_id,
+            post_folders_id_metadata_id_id, put_folders_id_metadata_id_id,
+        },
+        metadata_templates_api::{
+            GetMetadataTemplatesIdIdSchemaParams, PostMetadataTemplatesSchemaParams,
+            PutMetadataTemplatesIdIdSchemaParams, get_metadata_templates_id_id_schema,
+            post_metadata_templates_schema, put_metadata_templates_id_id_schema,
+        },
+    },
+    models::{
+        AMetadataInstanceUpdateOperation, AMetadataTemplateUpdateOperation, MetadataFieldWrite,
+        MetadataOptionWrite, PostMetadataTemplatesSchemaRequest,
+        a_metadata_instance_update_operation::Op as InstanceOp,
+        a_metadata_template_update_operation::Op, metadata_field__write_::Type,
+    },
+};
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    futures::{StreamExt, stream},
+    widget::{Column, button, column, row, scrollable, text},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    Message, State,
+    metadata::ItemMetadata,
+    persist,
+    project::{Project, Run},
+    project_page::InternalType,
+    schema::{FieldDef, FieldKind, SEPARATOR},
+    sheet_plan,
+    subwindows::Subwindow,
+};
+
+/// Templates are created for the enterprise, so everyone in it can search by them.
+const SCOPE: &str = "enterprise";
+
+/// Items written to at the same time.
+const CONCURRENT_ITEMS: usize = 8;
+
+/// A field of the project's schema and the key it has in the Box template.
+struct BoxField<'a> {
+    key: String,
+    def: &'a FieldDef,
+}
+
+impl BoxField<'_> {
+    /// Choices become enum and multi-select fields so Box can filter by them. Dates the
+    /// validator cannot turn into timestamps, like EDTF ranges, stay text.
+    fn kind(&self) -> Type {
+        match self.def.kind {
+            FieldKind::Enum if self.def.repeatable => Type::MultiSelect,
+            FieldKind::Enum => Type::Enum,
+            FieldKind::Date if !self.def.repeatable => Type::Date,
+            FieldKind::Number if !self.def.repeatable => Type::Float,
+            _ => Type::String,
+        }
+    }
+
+    fn kind_name(&self) -> &'static str {
+        match self.kind() {
+            Type::String => "Text",
+            Type::Float => "Number",
+            Type::Date => "Date",
+            Type::Enum => "Choice",
+            Type::MultiSelect => "Choices",
+        }
+    }
+
+    fn write(&self) -> MetadataFieldWrite {
+        let kind = self.kind();
+        MetadataFieldWrite {
+            options: matches!(kind, Type::Enum | Type::MultiSelect).then(|| {
+                self.def
+                    .options
+                    .iter()
+                    .map(|option| MetadataOptionWrite::new(option.clone()))
+                    .collect()
+            }),
+            ..MetadataFieldWrite::new(kind, self.key.clone(), self.def.key.to_string())
+        }
+    }
+
+    /// The field's value on an item, or None when it has none Box would accept.
+    fn value(&self, metadata: &ItemMetadata) -> Option<Value> {
+        let values = self.def.values(metadata);
+        let first = values.first()?;
+        match self.kind() {
+            Type::MultiSelect => Some(Value::from(values)),
+            Type::Date => timestamp(first).map(Value::from),
+            Type::Float => first
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number),
+            Type::Enum => Some(Value::from(first.clone())),
+            Type::String => Some(Value::from(values.join(format!("{SEPARATOR} ").as_str()))),
+        }
+    }
+}
+
+/// A Box date for a YYYY, YYYY-MM or YYYY-MM-DD date. Box dates are full timestamps, so
+/// partial dates fall on the first day of the year or month.
+fn timestamp(date: &str) -> Option<String> {
+    let mut parts = date.split('-');
+    let year = parts.next().filter(|y| y.len() == 4)?;
+    let month = parts.next().unwrap_or("01");
+    let day = parts.next().unwrap_or("01");
+    let date = format!("{year}-{month}-{day}");
+    chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok()?;
+    Some(format!("{date}T00:00:00.000Z"))
+}
+
+/// An identifier Box accepts as a template or field key: letters and digits, starting with a
+/// letter, with words joined in camel case.
+fn key(name: &str, prefix: &str) -> String {
+    let mut key = String::new();
+    for word in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            if key.is_empty() {
+                key.extend(first.to_lowercase());
+            } else {
+                key.extend(first.to_uppercase());
+            }
+            key.push_str(chars.as_str());
+        }
+    }
+    if !key.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        key.insert_str(0, prefix);
+    }
+    key
+}
+
+/// The Box template a project's values are written to.
+pub(crate) fn template_key(project: &str) -> String {
+    let mut key = key(&format!("tagmonster {project}"), "");
+    key.truncate(64);
+    key
+}
+
+/// Every field of the schema, with unique keys.
+fn fields(project: &Project) -> Vec<BoxField<'_>> {
+    let mut taken = HashSet::new();
+    project
+        .schema
+        .fields
+        .iter()
+        .map(|def| {
+            let base = key(&def.key.to_string(), "field");
+            let mut key = base.clone();
+            let mut n = 1;
+            while !taken.insert(key.clone()) {
+                n += 1;
+                key = format!("{base}{n}");
+            }
+            BoxField { key, def }
+        })
+        .collect()
+}
+
+/// Create the project's template, or add the fields and choices the schema gained since it was.
+/// Fields that changed type are left as they are, since Box cannot convert them.
+async fn ensure_template(
+    config: Configuration,
+    template_key: String,
+    display_name: String,
+    fields: Vec<MetadataFieldWrite>,
+) -> Result<(), String> {
+    let existing = get_metadata_templates_id_id_schema(
+        &config,
+        GetMetadataTemplatesIdIdSchemaParams {
+            scope: SCOPE.to_string(),
+            template_key: template_key.clone(),
+        },
+    )
+    .await;
+    let existing = match existing {
+        Ok(template) => template.fields.unwrap_or_default(),
+        Err(e) if status(&e) == Some(404) => {
+            tracing::info!("Creating Box metadata template {}", template_key);
+            return post_metadata_templates_schema(
+                &config,
+                PostMetadataTemplatesSchemaParams {
+                    post_metadata_templates_schema_request: Some(
+                        PostMetadataTemplatesSchemaRequest {
+                            template_key: Some(template_key),
+                            fields: Some(fields),
+                            ..PostMetadataTemplatesSchemaRequest::new(
+                                SCOPE.to_string(),
+                                display_name,
+                            )
+                        },
+                    ),
+                },
+            )
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut operations = vec![];
+    for field in fields {
+        let Some(current) = existing.iter().find(|f| f.key == field.key) else {
+            let data = serde_json::to_value(&field)
+                .ok()
+                .and_then(|v| serde_json::from_value(v).ok());
+            operations.push(AMetadataTemplateUpdateOperation {
+                data,
+                ..AMetadataTemplateUpdateOperation::new(Op::AddField)
+            });
+            continue;
+        };
+        let op = match field.r#type {
+            Type::Enum => Op::AddEnumOption,
+            Type::MultiSelect => Op::AddMultiSelectOption,
+            _ => continue,
+        };
+        let known: Vec<&str> = current
+            .options
+            .iter()
+            .flatten()
+            .map(|option| option.key.as_str())
+            .collect();
+        for option in field.options.iter().flatten() {
+            if !known.contains(&option.key.as_str()) {
+                operations.push(AMetadataTemplateUpdateOperation {
+                    field_key: Some(field.key.clone()),
+                    data: Some(HashMap::from([(
+                        "key".to_string(),
+                        Value::from(option.key.clone()),
+                    )])),
+                    ..AMetadataTemplateUpdateOperation::new(op)
+                });
+            }
+        }
+    }
+    if operations.is_empty() {
+        return Ok(());
+    }
+    tracing::info!(
+        "Updating Box metadata template {} with {} changes",
+        template_key,
+        operations.len()
+    );
+    put_metadata_templates_id_id_schema(
+        &config,
+        PutMetadataTemplatesIdIdSchemaParams {
+            scope: SCOPE.to_string(),
+            template_key,
+            a_metadata_template_update_operation: Some(operations),
+        },
+    )
+    .await
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+/// The template fields TagMonster last wrote on each item, by item ID. An item is here only
+/// while it has an instance TagMonster put there.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct BoxMetadataStore {
+    pub written: HashMap<String, Vec<String>>,
+}
+
+pub(crate) async fn load(project: String) -> BoxMetadataStore {
+    match persist::load_project_store::<BoxMetadataStore>(&project, "box_metadata").await {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::debug!("No Box metadata writes loaded for {}: {}", project, e);
+            BoxMetadataStore::default()
+        }
+    }
+}
+
+fn save(project: String, store: BoxMetadataStore) -> Task<Message> {
+    let snapshot = persist::snapshot();
+    Task::perform(
+        async move {
+            if let Err(e) =
+                persist::save_project_store(&store, &project, "box_metadata", snapshot).await
+            {
+                tracing::error!("Error saving Box metadata writes for {}: {}", project, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+/// An item to write and the values it gets, empty when its instance should be removed.
+#[derive(Debug, Clone)]
+struct Instance {
+    id: String,
+    name: String,
+    folder: bool,
+    values: HashMap<String, Value>,
+    /// The fields TagMonster last wrote on the item, or None if it has not put an instance there
+    written: Option<Vec<String>>,
+}
+
+/// Every file and folder in the project with values, or with an instance TagMonster wrote
+/// that now needs updating or removing, with its values keyed as in the template.
+fn instances(state: &State, project: &Project) -> Vec<Instance> {
+    let Some(tree) = &state.project_tree else {
+        return vec![];
+    };
+    let fields = fields(project);
+    sheet_plan::flatten(tree)
+        .into_iter()
+        .filter(|item| matches!(item.file_type, InternalType::File | InternalType::Folder))
+        .map(|item| Instance {
+            folder: item.file_type == InternalType::Folder,
+            values: state
+                .metadata
+                .get(&item.id)
+                .map_or_else(HashMap::new, |metadata| {
+                    fields
+                        .iter()
+                        .filter_map(|field| Some((field.key.clone(), field.value(metadata)?)))
+                        .collect()
+                }),
+            written: state.box_metadata.written.get(&item.id).cloned(),
+            id: item.id,
+            name: item.name,
+        })
+        .filter(|instance| !instance.values.is_empty() || instance.written.is_some())
+        .collect()
+}
+
+/// The HTTP status Box answered with, if it answered.
+fn status<T>(e: &Error<T>) -> Option<u16> {
+    match e {
+        Error::ResponseError(e) => Some(e.status.as_u16()),
+        _ => None,
+    }
+}
+
+/// Remove an item's instance of the template. One that is already gone is fine.
+async fn remove(
+    config: &Configuration,
+    instance: &Instance,
+    template_key: &str,
+) -> Result<(), String> {
+    let removed = if instance.folder {
+        delete_folders_id_metadata_id_id(
+            config,
+            DeleteFoldersIdMetadataIdIdParams {
+                folder_id: instance.id.clone(),
+                scope: SCOPE.to_string(),
+                template_key: template_key.to_string(),
+            },
+        )
+        .await
+        .map_err(|e| (status(&e), e.to_string()))
+    } else {
+        delete_files_id_metadata_id_id(
+            config,
+            DeleteFilesIdMetadataIdIdParams {
+                file_id: instance.id.clone(),
+                scope: SCOPE.to_string(),
+                template_key: template_key.to_string(),
+            },
+        )
+        .await
+        .map_err(|e| (status(&e), e.to_string()))
+    };
+    match removed {
+        Ok(()) | Err((Some(404), _)) => Ok(()),
+        Err((_, e)) => Err(e),
+    }
+}
+
+/// Put `instance` on its item. Returns whether Box already had one, so it can be replaced.
+async fn add(
+    config: &Configuration,
+    instance: &Instance,
+    template_key: &str,
+) -> Result<bool, String> {
+    let added = if instance.folder {
+        post_folders_id_metadata_id_id(
+            config,
+            PostFoldersIdMetadataIdIdParams {
+                folder_id: instance.id.clone(),
+                scope: SCOPE.to_string(),
+                template_key: template_key.to_string(),
+                request_body: Some(instance.values.clone()),
+            },
+        )
+        .await
+        .map(|_| ())
+        .map_err(|e| (status(&e), e.to_string()))
+    } else {
+        post_files_id_metadata_id_id(
+            config,
+            PostFilesIdMetadataIdIdParams {
+                file_id: instance.id.clone(),
+                scope: SCOPE.to_string(),
+                template_key: template_key.to_string(),
+                request_body: Some(instance.values.clone()),
+            },
+        )
+        .await
+        .map(|_| ())
+        .map_err(|e| (status(&e), e.to_string()))
+    };
+    match added {
+        Ok(()) => Ok(false),
+        Err((Some(409), _)) => Ok(true),
+        Err((_, e)) => Err(e),
+    }
+}
+
+/// The JSON-Patch operations that bring an existing instance to `instance`'s values. Fields
+/// TagMonster wrote before are replaced, or removed once cleared. Others are added.
+fn update_operations(instance: &Instance) -> Vec<AMetadataInstanceUpdateOperation> {
+    let written = instance.written.as_deref().unwrap_or_default();
+    let mut operations: Vec<AMetadataInstanceUpdateOperation> = instance
+        .values
+        .iter()
+        .filter_map(|(key, value)| {
+            let op = if written.contains(key) {
+                InstanceOp::Replace
+            } else {
+                InstanceOp::Add
+            };
+            Some(AMetadataInstanceUpdateOperation {
+                op: Some(op),
+                path: Some(format!("/{key}")),
+                value: Some(Box::new(serde_json::from_value(value.clone()).ok()?)),
+                from: None,
+            })
+        })
+        .collect();
+    operations.extend(
+        written
+            .iter()
+            .filter(|key| !instance.values.contains_key(*key))
+            .map(|key| AMetadataInstanceUpdateOperation {
+                op: Some(InstanceOp::Remove),
+                path: Some(format!("/{key}")),
+                ..AMetadataInstanceUpdateOperation::new()
+            }),
+    );
+    operations
+}
+
+/// Update the instance already on an item in place, so it is never left without one.
+async fn update(
+    config: &Configuration,
+    instance: &Instance,
+    template_key: &str,
+) -> Result<(), String> {
+    let operations = Some(update_operations(instance));
+    if instance.folder {
+        put_folders_id_metadata_id_id(
+            config,
+            PutFoldersIdMetadataIdIdParams {
+                folder_id: instance.id.clone(),
+                scope: SCOPE.to_string(),
+                template_key: template_key.to_string(),
+                a_metadata_instance_update_operation: operations,
+            },
+        )
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    } else {
+        put_files_id_metadata_id_id(
+            config,
+            PutFilesIdMetadataIdIdParams {
+                file_id: instance.id.clone(),
+                scope: SCOPE.to_string(),
+                template_key: template_key.to_string(),
+                a_metadata_instance_update_operation: operations,
+            },
+        )
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Write an item's values to Box, the same way a sheet row is, so values cleared in TagMonster
+/// are cleared in Box too. Returns the fields now written, or None once the instance is gone.
+async fn write(
+    config: Configuration,
+    instance: Instance,
+    template_key: String,
+) -> Result<Option<Vec<String>>, String> {
+    if instance.values.is_empty() {
+        remove(&config, &instance, &template_key).await?;
+        return Ok(None);
+    }
+    if add(&config, &instance, &template_key).await? {
+        update(&config, &instance, &template_key).await?;
+    }
+    let mut keys: Vec<String> = instance.values.into_keys().collect();
+    keys.sort();
+    Ok(Some(keys))
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct BoxMetadataState {
+    run: Run,
+    writing: bool,
+    total: usize,
+    written: usize,
+    /// Items that could not be written, by name, and why
+    failed: Vec<(String, String)>,
+    /// Why the template could not be created or updated
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum BoxMetadataMessage {
+    /// Create or update the template, then write every described item to it
+    Write,
+    /// The template is set up for run `u64`
+    TemplateReady(u64, Result<(), String>),
+    /// An item's write during run `u64` finished, by ID and name, with the fields it now has
+    Written(u64, String, String, Result<Option<Vec<String>>, String>),
+    Loaded(BoxMetadataStore),
+}
+
+pub(crate) fn box_metadata_handle(state: &mut State, event: BoxMetadataMessage) -> Task<Message> {
+    let Some(project) = &state.project else {
+        return Task::none();
+    };
+    match event {
+        BoxMetadataMessage::Write => {
+            if state.box_metadata_state.writing {
+                return Task::none();
+            }
+            state.box_metadata_state = BoxMetadataState {
+                writing: true,
+                ..Default::default()
+            };
+            let fields = fields(project).iter().map(BoxField::write).collect();
+            let run = state.box_metadata_state.run.id();
+            return state.box_metadata_state.run.track(Task::perform(
+                ensure_template(
+                    state.box_config.clone(),
+                    template_key(&project.name),
+                    format!("TagMonster: {}", project.name),
+                    fields,
+                ),
+                move |result| {
+                    Message::BoxMetadataMessage(BoxMetadataMessage::TemplateReady(run, result))
+                },
+            ));
+        }
+        BoxMetadataMessage::TemplateReady(run, _) | BoxMetadataMessage::Written(run, ..)
+            if !state.box_metadata_state.run.is(run) => {}
+        BoxMetadataMessage::TemplateReady(_, Err(e)) => {
+            tracing::error!("Could not set up the Box metadata template: {}", e);
+            state.box_metadata_state.writing = false;
+            state.box_metadata_state.error = Some(e);
+        }
+        BoxMetadataMessage::TemplateReady(_, Ok(())) => {
+            let instances = instances(state, project);
+            tracing::info!("Writing Box metadata for {} items", instances.len());
+            state.box_metadata_state.writing = !instances.is_empty();
+            state.box_metadata_state.total = instances.len();
+            let run = state.box_metadata_state.run.id();
+            let config = state.box_config.clone();
+            let template_key = template_key(&project.name);
+            let writes = stream::iter(instances)
+                .map(move |instance| {
+                    let config = config.clone();
+                    let template_key = template_key.clone();
+                    async move {
+                        let (id, name) = (instance.id.clone(), instance.name.clone());
+                        (id, name, write(config, instance, template_key).await)
+                    }
+                })
+                .buffer_unordered(CONCURRENT_ITEMS);
+            return state.box_metadata_state.run.track(Task::run(
+                writes,
+                move |(id, name, result)| {
+                    Message::BoxMetadataMessage(BoxMetadataMessage::Written(run, id, name, result))
+                },
+            ));
+        }
+        BoxMetadataMessage::Written(_, id, name, result) => {
+            let run = &mut state.box_metadata_state;
+            match result {
+                Ok(Some(keys)) => {
+                    state.box_metadata.written.insert(id, keys);
+                }
+                Ok(None) => {
+                    state.box_metadata.written.remove(&id);
+                }
+                Err(e) => {
+                    tracing::error!("Could not write Box metadata for {}: {}", name, e);
+                    run.failed.push((name, e));
+                }
+            }
+            run.written += 1;
+            if run.written == run.total {
+                run.writing = false;
+                tracing::info!(
+                    "Wrote Box metadata for {} items, {} failed",
+                    run.total,
+                    run.failed.len()
+                );
+                return save(project.name.clone(), state.box_metadata.clone());
+            }
+        }
+        BoxMetadataMessage::Loaded(store) => state.box_metadata = store,
+    }
+    Task::none()
+}
+
+pub(crate) fn box_metadata(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::BoxMetadata));
+    let Some(project) = &state.project else {
+        return column![text("No project open"), close].padding(15).into();
+    };
+    let run = &state.box_metadata_state;
+
+    let mapping = fields(project)
+        .into_iter()
+        .fold(Column::new().spacing(5), |col, field| {
+            col.push(
+                row![
+                    text(field.def.key.to_string()).width(Fill),
+                    text(field.key.clone()).width(Fill),
+                    text(field.kind_name()).style(text::secondary),
+                ]
+                .spacing(10),
+            )
+        });
+
+    let status = if let Some(e) = &run.error {
+        text(format!("Could not set up the template: {e}")).style(text::danger)
+    } else if run.writing && run.total == 0 {
+        text("Setting up the template")
+    } else if run.writing {
+        text(format!("Wrote {} of {} items", run.written, run.total))
+    } else if run.total > 0 {
+        text(format!(
+            "Wrote {} items, {} failed",
+            run.total,
+            run.failed.len()
+        ))
+    } else {
+        text(format!(
+            "{} described items will be written to the template {}",
+            state.metadata.items.len(),
+            template_key(&project.name)
+        ))
+    };
+
+    let failed = run
+        .failed
+        .iter()
+        .fold(Column::new().spacing(5), |col, (name, e)| {
+            col.push(
+                row![
+                    text(name.clone()).width(Fill),
+                    text(e.clone()).style(text::danger).width(Fill),
+                ]
+                .spacing(10),
+            )
+        });
+
+    column![
+        text("Box metadata").size(20),
+        text(
+            "Writes the project's fields to a Box metadata template, so descriptions travel \
+             with the files and can be searched in Box. Each write replaces what TagMonster \
+             wrote before, and items without values have theirs removed.",
+        ),
+        row![
+            button("Write to Box").on_press_maybe(
+                (!run.writing).then_some(Message::BoxMetadataMessage(BoxMetadataMessage::Write))
+            ),
+            status,
+        ]
+        .spacing(10)
+        .align_y(Center),
+        scrollable(column![mapping, failed].spacing(15)).height(Fill),
+        close
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+use r#box::models::Item;
+use iced::{
+    Alignment::Center,
+    Element, Task,
+    widget::{TextInput, button, checkbox, column, pick_list, row, text},
+};
+
+use crate::{
+    Message, State, classification,
+    metadata::{self, Edit, Field, item_info},
+    project_page::Node,
+};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BulkEditState {
+    tag: String,
+    field: Option<Field>,
+    value: String,
+    note: String,
+    /// Also edit everything inside selected folders
+    recursive: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum BulkEditMessage {
+    SetTag(String),
+    AddTag,
+    RemoveTag,
+    SetField(Field),
+    SetValue(String),
+    ApplyField,
+    SetNote(String),
+    AppendNote,
+    SetRecursive(bool),
+}
+
+fn descendants(node: &Node, out: &mut Vec<String>) {
+    for child in node.children.iter().flatten() {
+        out.push(child.id.clone());
+        descendants(child, out);
+    }
+}
+
+/// IDs of every item a bulk edit applies to, without duplicates.
+pub(crate) fn targets(state: &State, selection: &[Item], recursive: bool) -> Vec<String> {
+    let mut ids: Vec<String> = selection
+        .iter()
+        .map(|item| item_info(item).0.to_string())
+        .collect();
+    if recursive {
+        let folders = selection
+            .iter()
+            .filter(|item| matches!(item, Item::FolderMini(_)))
+            .map(|item| item_info(item).0);
+        if let Some(tree) = &state.project_tree {
+            for folder in folders {
+                if let Some(node) = tree.find(folder) {
+                    descendants(node, &mut ids);
+                }
+            }
+        }
+    }
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
+    ids
+}
+
+fn apply(state: &mut State, edit: Edit) -> Task<Message> {
+    let ids = targets(
+        state,
+        &state.file_tree_state.selection,
+        state.bulk_edit_state.recursive,
+    );
+    if state.bulk_edit_state.recursive && state.project_tree.is_none() {
+        tracing::warn!("The project tree is not loaded, so folder contents were skipped");
+    }
+    tracing::info!("Applying {:?} to {} items", edit, ids.len());
+    metadata::apply_edit(state, &ids, edit)
+}
+
+pub(crate) fn bulk_edit_handle(state: &mut State, event: BulkEditMessage) -> Task<Message> {
+    let bulk = &mut state.bulk_edit_state;
+    match event {
+        BulkEditMessage::SetTag(t) => bulk.tag = t,
+        BulkEditMessage::SetField(f) => bulk.field = Some(f),
+        BulkEditMessage::SetValue(v) => bulk.value = v,
+        BulkEditMessage::SetNote(n) => bulk.note = n,
+        BulkEditMessage::SetRecursive(r) => bulk.recursive = r,
+        BulkEditMessage::AddTag => {
+            let Some(tag) = state.vocabularies.accept(&bulk.tag) else {
+                return Task::none();
+            };
+            bulk.tag.clear();
+            return apply(state, Edit::AddTag(tag));
+        }
+        BulkEditMessage::RemoveTag => {
+            let tag = bulk.tag.trim().to_string();
+            if tag.is_empty() {
+                return Task::none();
+            }
+            bulk.tag.clear();
+            return apply(state, Edit::RemoveTag(tag));
+        }
+        BulkEditMessage::ApplyField => {
+            let Some(field) = bulk.field else {
+                return Task::none();
+            };
+            let value = std::mem::take(&mut bulk.value);
+            return apply(state, Edit::Set(field, value));
+        }
+        BulkEditMessage::AppendNote => {
+            let note = bulk.note.trim().to_string();
+            if note.is_empty() {
+                return Task::none();
+            }
+            bulk.note.clear();
+            return apply(state, Edit::AppendNote(note));
+        }
+    }
+    Task::none()
+}
+
+pub(crate) fn bulk_edit(state: &State) -> Element<'_, Message> {
+    let bulk = &state.bulk_edit_state;
+    let selection = &state.file_tree_state.selection;
+    let heading = if bulk.recursive {
+        format!(
+            "{} items selected, {} including folder contents",
+            selection.len(),
+            targets(state, selection, true).len()
+        )
+    } else {
+        format!("{} items selected", selection.len())
+    };
+
+    column![
+        text(heading).size(18),
+        checkbox(bulk.recursive)
+            .label("Include everything inside selected folders")
+            .on_toggle(|r| Message::BulkEditMessage(BulkEditMessage::SetRecursive(r))),
+        column![
+            text("Tag").size(12),
+            row![
+                TextInput::new("Tag", &bulk.tag)
+                    .on_input(|t| Message::BulkEditMessage(BulkEditMessage::SetTag(t)))
+                    .on_submit(Message::BulkEditMessage(BulkEditMessage::AddTag)),
+                button("Apply").on_press(Message::BulkEditMessage(BulkEditMessage::AddTag)),
+                button("Remove")
+                    .style(button::danger)
+                    .on_press(Message::BulkEditMessage(BulkEditMessage::RemoveTag)),
+            ]
+            .spacing(5),
+        ]
+        .spacing(2),
+        column![
+            text("Field").size(12),
+            row![
+                pick_list(Field::ALL, bulk.field, |f| {
+                    Message::BulkEditMessage(BulkEditMessage::SetField(f))
+                })
+                .placeholder("Field"),
+                TextInput::new("Value", &bulk.value)
+                    .on_input(|v| Message::BulkEditMessage(BulkEditMessage::SetValue(v)))
+                    .on_submit(Message::BulkEditMessage(BulkEditMessage::ApplyField)),
+                button("Set").on_press_maybe(
+                    bulk.field
+                        .map(|_| Message::BulkEditMessage(BulkEditMessage::ApplyField))
+                ),
+            ]
+            .align_y(Center)
+            .spacing(5),
+        ]
+        .spacing(2),
+        column![
+            text("Note").size(12),
+            row![
+                TextInput::new("Appended to each item's notes", &bulk.note)
+                    .on_input(|n| Message::BulkEditMessage(BulkEditMessage::SetNote(n)))
+                    .on_submit(Message::BulkEditMessage(BulkEditMessage::AppendNote)),
+                button("Append").on_press(Message::BulkEditMessage(BulkEditMessage::AppendNote)),
+            ]
+            .spacing(5),
+        ]
+        .spacing(2),
+        column![
+            text("Classification in Box").size(12),
+            classification::controls(
+                state,
+                classification::only_files(state, targets(state, selection, bulk.recursive)),
+            ),
+        ]
+        .spacing(2),
+    ]
+    .spacing(10)
+    .into()
+}
@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+
+use r#box::{
+    apis::{
+        Error,
+        classifications_api::get_metadata_templates_enterprise_security_classification6_vm_vochw_uwo_schema as get_classification_template,
+        classifications_on_files_api::{
+            DeleteFilesIdMetadataEnterpriseSecurityClassification6VmVochwUwoParams as DeleteParams,
+            GetFilesIdMetadataEnterpriseSecurityClassification6VmVochwUwoParams as GetParams,
+            PostFilesIdMetadataEnterpriseSecurityClassification6VmVochwUwoParams as PostParams,
+            PutFilesIdMetadataEnterpriseSecurityClassification6VmVochwUwoParams as PutParams,
+            delete_files_id_metadata_enterprise_security_classification6_vm_vochw_uwo as delete_classification,
+            get_files_id_metadata_enterprise_security_classification6_vm_vochw_uwo as get_classification,
+            post_files_id_metadata_enterprise_security_classification6_vm_vochw_uwo as post_classification,
+            put_files_id_metadata_enterprise_security_classification6_vm_vochw_uwo as put_classification,
+        },
+        configuration::Configuration,
+    },
+    models::{
+        PostFilesIdMetadataEnterpriseSecurityClassification6VmVochwUwoRequest as PostRequest,
+        PutFilesIdMetadataEnterpriseSecurityClassification6VmVochwUwoRequestInner as PutRequest,
+        put_files_id_metadata_enterprise_security_classification_6_vm_vochw_uwo_request_inner::{
+            Op, Path,
+        },
+    },
+};
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    futures::{StreamExt, stream},
+    widget::{Column, button, column, pick_list, row, scrollable, text},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Message, State, persist,
+    project::Run,
+    project_page::{InternalType, Node},
+    sheet_plan,
+    subwindows::Subwindow,
+};
+
+/// Files read from or written to at the same time.
+const CONCURRENT_FILES: usize = 8;
+
+/// Classification labels of a project's files, by Box file ID, as last read from or written
+/// to Box. Box is the source of truth; this is what the file tree and the sheet show.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ClassificationStore {
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ClassificationState {
+    /// Reads and writes for the open project
+    run: Run,
+    /// Labels the enterprise defines
+    options: Vec<String>,
+    /// The label that Apply adds
+    chosen: Option<String>,
+    reading: bool,
+    total: usize,
+    read: usize,
+    /// Writes not yet answered
+    pending: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum ClassificationMessage {
+    Loaded(ClassificationStore),
+    LoadOptions,
+    OptionsLoaded(Vec<String>),
+    Choose(String),
+    ReadAll,
+    /// A file's label as read from Box during run `u64`, by ID
+    Read(u64, String, Result<Option<String>, String>),
+    /// Add the chosen label to files, by ID
+    Apply(Vec<String>),
+    Remove(Vec<String>),
+    /// Add the chosen label to every file the sensitive content scan flagged
+    ApplyToFlagged,
+    /// A file's label after a write during run `u64`, by ID
+    Written(u64, String, Result<Option<String>, String>),
+}
+
+pub(crate) async fn load(project: String) -> ClassificationStore {
+    match persist::load_project_store::<ClassificationStore>(&project, "classifications").await {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::debug!("No classifications loaded for {}: {}", project, e);
+            ClassificationStore::default()
+        }
+    }
+}
+
+fn save(project: String, store: ClassificationStore) -> Task<Message> {
+    let snapshot = persist::snapshot();
+    Task::perform(
+        async move {
+            if let Err(e) =
+                persist::save_project_store(&store, &project, "classifications", snapshot).await
+            {
+                tracing::error!("Error saving classifications for {}: {}", project, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+/// The classification labels the enterprise defines.
+async fn options(config: Configuration) -> Result<Vec<String>, String> {
+    let template = get_classification_template(&config)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(template
+        .fields
+        .into_iter()
+        .flat_map(|field| field.options)
+        .map(|option| option.key)
+        .collect())
+}
+
+/// The label on file `id`, or None when it is not classified.
+async fn read(config: Configuration, id: String) -> Result<Option<String>, String> {
+    match get_classification(&config, GetParams { file_id: id }).await {
+        Ok(classification) => Ok(classification.box__security__classification__key),
+        Err(Error::ResponseError(e)) if e.status.as_u16() == 404 => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Put `label` on file `id`, or take its label off when None. Returns the label it ends up
+/// with.
+async fn write(
+    config: Configuration,
+    id: String,
+    label: Option<String>,
+) -> Result<Option<String>, String> {
+    let Some(label) = label else {
+        return match delete_classification(&config, DeleteParams { file_id: id }).await {
+            Ok(()) => Ok(None),
+            // Already unclassified
+            Err(Error::ResponseError(e)) if e.status.as_u16() == 404 => Ok(None),
+            Err(e) => Err(e.to_string()),
+        };
+    };
+    let added = post_classification(
+        &config,
+        PostParams {
+            file_id: id.clone(),
+            post_files_id_metadata_enterprise_security_classification6_vm_vochw_uwo_request: Some(
+                PostRequest {
+                    box__security__classification__key: Some(label.clone()),
+                },
+            ),
+        },
+    )
+    .await;
+    let classification = match added {
+        Ok(classification) => classification,
+        // A file that already has a label gets it replaced
+        Err(Error::ResponseError(e)) if e.status.as_u16() == 409 => put_classification(
+            &config,
+            PutParams {
+                file_id: id,
+                put_files_id_metadata_enterprise_security_classification_6_vm_vochw_uwo_request_inner:
+                    Some(vec![PutRequest::new(
+                        Op::Replace,
+                        Path::SlashBoxSecurityClassificationKey,
+                        label,
+                    )]),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?,
+        Err(e) => return Err(e.to_string()),
+    };
+    Ok(classification.box__security__classification__key)
+}
+
+/// Every Box file in the project tree, by ID.
+fn files(tree: &Node) -> Vec<String> {
+    sheet_plan::flatten(tree)
+        .into_iter()
+        .filter(|item| item.file_type == InternalType::File)
+        .map(|item| item.id)
+        .collect()
+}
+
+/// Write `label` to every file in `ids`, reporting each as it is done.
+fn write_all(state: &mut State, ids: Vec<String>, label: Option<String>) -> Task<Message> {
+    if ids.is_empty() {
+        return Task::none();
+    }
+    match &label {
+        Some(label) => tracing::info!("Classifying {} files as {}", ids.len(), label),
+        None => tracing::info!("Removing the classification of {} files", ids.len()),
+    }
+    state.classification_state.pending += ids.len();
+    let run = state.classification_state.run.id();
+    let config = state.box_config.clone();
+    let writes = stream::iter(ids)
+        .map(move |id| {
+            let config = config.clone();
+            let label = label.clone();
+            async move {
+                let result = write(config, id.clone(), label).await;
+                (id, result)
+            }
+        })
+        .buffer_unordered(CONCURRENT_FILES);
+    state
+        .classification_state
+        .run
+        .track(Task::run(writes, move |(id, result)| {
+            Message::ClassificationMessage(ClassificationMessage::Written(run, id, result))
+        }))
+}
+
+fn save_current(state: &State) -> Task<Message> {
+    match &state.project {
+        Some(project) => save(project.name.clone(), state.classifications.clone()),
+        None => Task::none(),
+    }
+}
+
+pub(crate) fn classification_handle(
+    state: &mut State,
+    event: ClassificationMessage,
+) -> Task<Message> {
+    match event {
+        ClassificationMessage::Loaded(store) => state.classifications = store,
+        ClassificationMessage::LoadOptions => {
+            return Task::perform(options(state.box_config.clone()), |result| match result {
+                Ok(options) => {
+                    Message::ClassificationMessage(ClassificationMessage::OptionsLoaded(options))
+                }
+                Err(e) => {
+                    tracing::warn!("Could not load the enterprise's classifications: {}", e);
+                    Message::None
+                }
+            });
+        }
+        ClassificationMessage::OptionsLoaded(options) => {
+            let run = &mut state.classification_state;
+            if run.chosen.as_ref().is_some_and(|c| !options.contains(c)) {
+                run.chosen = None;
+            }
+            run.options = options;
+        }
+        ClassificationMessage::Choose(label) => state.classification_state.chosen = Some(label),
+        ClassificationMessage::ReadAll => {
+            if state.classification_state.reading {
+                return Task::none();
+            }
+            let Some(tree) = &state.project_tree else {
+                tracing::warn!("The project tree is not loaded, so there are no files to read");
+                return Task::none();
+            };
+            let files = files(tree);
+            tracing::info!("Reading the classifications of {} files", files.len());
+            let run = &mut state.classification_state;
+            run.reading = !files.is_empty();
+            run.total = files.len();
+            run.read = 0;
+            let run = state.classification_state.run.id();
+            let config = state.box_config.clone();
+            let reads = stream::iter(files)
+                .map(move |id| {
+                    let config = config.clone();
+                    async move {
+                        let result = read(config, id.clone()).await;
+                        (id, result)
+                    }
+                })
+                .buffer_unordered(CONCURRENT_FILES);
+            return state
+                .classification_state
+                .run
+                .track(Task::run(reads, move |(id, result)| {
+                    Message::ClassificationMessage(ClassificationMessage::Read(run, id, result))
+                }));
+        }
+        ClassificationMessage::Read(run, id, result) => {
+            if !state.classification_state.run.is(run) {
+                return Task::none();
+            }
+            match result {
+                Ok(Some(label)) => {
+                    state.classifications.labels.insert(id, label);
+                }
+                Ok(None) => {
+                    state.classifications.labels.remove(&id);
+                }
+                Err(e) => tracing::warn!("Could not read the classification of {}: {}", id, e),
+            }
+            let run = &mut state.classification_state;
+            run.read += 1;
+            if run.read == run.total {
+                run.reading = false;
+                tracing::info!(
+                    "Read classifications, {} files are classified",
+                    state.classifications.labels.len()
+                );
+                return save_current(state);
+            }
+        }
+        ClassificationMessage::Apply(ids) => {
+            let Some(label) = state.classification_state.chosen.clone() else {
+                return Task::none();
+            };
+            return write_all(state, ids, Some(label));
+        }
+        ClassificationMessage::Remove(ids) => return write_all(state, ids, None),
+        ClassificationMessage::ApplyToFlagged => {
+            let Some(label) = state.classification_state.chosen.clone() else {
+                return Task::none();
+            };
+            let ids = state.sensitive.restricted().into_keys().collect();
+            return write_all(state, ids, Some(label));
+        }
+        ClassificationMessage::Written(run, id, result) => {
+            if !state.classification_state.run.is(run) {
+                return Task::none();
+            }
+            match result {
+                Ok(Some(label)) => {
+                    state.classifications.labels.insert(id, label);
+                }
+                Ok(None) => {
+                    state.classifications.labels.remove(&id);
+                }
+                Err(e) => tracing::error!("Could not classify {}: {}", id, e),
+            }
+            let run = &mut state.classification_state;
+            run.pending = run.pending.saturating_sub(1);
+            if run.pending == 0 {
+                return save_current(state);
+            }
+        }
+    }
+    Task::none()
+}
+
+/// The IDs among `ids` that are Box files, since only files are classified here.
+pub(crate) fn only_files(state: &State, ids: Vec<String>) -> Vec<String> {
+    let Some(tree) = &state.project_tree else {
+        return ids;
+    };
+    ids.into_iter()
+        .filter(|id| {
+            tree.find(id)
+                .is_some_and(|node| node.file_type == InternalType::File)
+        })
+        .collect()
+}
+
+/// The label to apply, once the enterprise's labels are loaded.
+fn picker(state: &State) -> Element<'_, Message> {
+    let run = &state.classification_state;
+    if run.options.is_empty() {
+        button("Load labels")
+            .style(button::secondary)
+            .on_press(Message::ClassificationMessage(
+                ClassificationMessage::LoadOptions,
+            ))
+            .into()
+    } else {
+        pick_list(run.options.as_slice(), run.chosen.clone(), |label| {
+            Message::ClassificationMessage(ClassificationMessage::Choose(label))
+        })
+        .placeholder("Label")
+        .into()
+    }
+}
+
+/// Pick a label and apply it to, or remove it from, the files `ids`.
+pub(crate) fn controls(state: &State, ids: Vec<String>) -> Element<'_, Message> {
+    let run = &state.classification_state;
+    let any = !ids.is_empty();
+    row![
+        picker(state),
+        button("Classify").on_press_maybe((any && run.chosen.is_some()).then(|| {
+            Message::ClassificationMessage(ClassificationMessage::Apply(ids.clone()))
+        })),
+        button("Unclassify").style(button::danger).on_press_maybe(
+            any.then(|| Message::ClassificationMessage(ClassificationMessage::Remove(ids)))
+        ),
+    ]
+    .spacing(5)
+    .align_y(Center)
+    .into()
+}
+
+pub(crate) fn classifications(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::Classifications));
+    let run = &state.classification_state;
+    let flagged = state.sensitive.restricted().len();
+    let header = row![
+        button("Read from Box").on_press_maybe((!run.reading).then_some(
+            Message::ClassificationMessage(ClassificationMessage::ReadAll)
+        )),
+        picker(state),
+        button(text(format!("Classify {flagged} flagged files"))).on_press_maybe(
+            (flagged > 0 && run.chosen.is_some()).then_some(Message::ClassificationMessage(
+                ClassificationMessage::ApplyToFlagged
+            ))
+        ),
+    ]
+    .spacing(10)
+    .align_y(Center);
+
+    let status = if run.reading {
+        format!("Read {} of {} files", run.read, run.total)
+    } else if run.pending > 0 {
+        format!("Writing {} classifications", run.pending)
+    } else {
+        format!(
+            "{} files are classified",
+            state.classifications.labels.len()
+        )
+    };
+
+    let mut classified: Vec<(String, &String)> = state
+        .classifications
+        .labels
+        .iter()
+        .map(|(id, label)| {
+            let name = state
+                .project_tree
+                .as_ref()
+                .and_then(|tree| tree.find(id))
+                .map_or(id.clone(), |node| node.name.clone());
+            (name, label)
+        })
+        .collect();
+    classified.sort();
+    let body: Element<Message> = if classified.is_empty() {
+        text(
+            "Read from Box to see which files are classified. Files flagged by the sensitive \
+             content scan can be classified from here, and any file from its details.",
+        )
+        .into()
+    } else {
+        scrollable(
+            classified
+                .into_iter()
+                .fold(Column::new().spacing(5), |col, (name, label)| {
+                    col.push(
+                        row![
+                            text(name).width(Fill),
+                            text(label.clone()).style(text::danger),
+                        ]
+                        .spacing(10),
+                    )
+                }),
+        )
+        .height(Fill)
+        .into()
+    };
+
+    column![
+        text("Classifications").size(20),
+        header,
+        text(status),
+        body,
+        close
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+
+use r#box::models::Item;
+use iced::{
+    Alignment::Center,
+    Element, Task,
+    widget::{Column, Row, TextInput, button, column, pick_list, row, text, text_editor},
+};
+
+use crate::{
+    Message, State, bulk_edit, classification,
+    embedded::{self, Embedded, EmbeddedStore},
+    metadata::{self, Edit, Field, ItemMetadata, MetadataStore},
+    schema::{FieldDef, FieldKey, FieldKind, Issue, SEPARATOR},
+    update,
+};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DataEntryState {
+    pub notes: text_editor::Content,
+    pub tag_input: String,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum DataEntryMessage {
+    /// The project's saved metadata, or why it could not be read
+    Loaded(Result<MetadataStore, String>),
+    /// Edits have settled since the save with this number was scheduled
+    SaveDue(u64),
+    Set(Field, String),
+    SetCustom(String, String),
+    EditNotes(text_editor::Action),
+    SetTagInput(String),
+    AddTag,
+    AddSuggestedTag(String),
+    RemoveTag(String),
+    EmbeddedLoaded(EmbeddedStore),
+    /// Metadata read from files while building the sheet, keyed by item ID
+    EmbeddedRead(HashMap<String, Embedded>),
+    /// Fill the selected item's empty fields from its embedded metadata
+    FillFromFile,
+}
+
+/// The embedded values for the fields of `entry` that are still empty.
+fn fillable(found: &Embedded, entry: &ItemMetadata) -> Vec<(Field, String)> {
+    found
+        .fields()
+        .into_iter()
+        .filter(|(field, _)| field.value(entry).is_empty())
+        .collect()
+}
+
+/// Load the form for the newly selected item.
+pub(crate) fn selection_changed(state: &mut State) {
+    let notes = state
+        .selected
+        .as_ref()
+        .and_then(|item| state.metadata.get(metadata::item_info(item).0))
+        .map(|m| m.notes.as_str())
+        .unwrap_or_default();
+    state.data_entry_state = DataEntryState {
+        notes: text_editor::Content::with_text(notes),
+        tag_input: String::new(),
+    };
+}
+
+/// Apply `edit` to the selected item.
+fn edit(state: &mut State, edit: Edit) -> Task<Message> {
+    let Some(item) = &state.selected else {
+        return Task::none();
+    };
+    let id = metadata::item_info(item).0.to_string();
+    metadata::apply_edit(state, &[id], edit)
+}
+
+pub(crate) fn data_entry_handle(state: &mut State, event: DataEntryMessage) -> Task<Message> {
+    match event {
+        DataEntryMessage::Loaded(Ok(store)) => {
+            tracing::info!("Loaded metadata for {} items", store.items.len());
+            state.metadata = store;
+            state.metadata_saves.load_error = None;
+            selection_changed(state);
+            Task::none()
+        }
+        DataEntryMessage::Loaded(Err(e)) => {
+            tracing::error!(
+                "Could not read the project's saved metadata, so edits will not be saved until \
+                 it is fixed: {}",
+                e
+            );
+            state.metadata_saves.load_error = Some(e);
+            Task::none()
+        }
+        DataEntryMessage::SaveDue(generation) => metadata::save_due(state, generation),
+        DataEntryMessage::Set(field, value) => edit(state, Edit::Set(field, value)),
+        DataEntryMessage::SetCustom(name, value) => edit(state, Edit::SetCustom(name, value)),
+        DataEntryMessage::EditNotes(action) => {
+            let is_edit = action.is_edit();
+            state.data_entry_state.notes.perform(action);
+            if is_edit {
+                let notes = state.data_entry_state.notes.text();
+                edit(state, Edit::Set(Field::Notes, notes))
+            } else {
+                Task::none()
+            }
+        }
+        DataEntryMessage::SetTagInput(t) => {
+            state.data_entry_state.tag_input = t;
+            Task::none()
+        }
+        DataEntryMessage::AddSuggestedTag(tag) => {
+            state.data_entry_state.tag_input = tag;
+            update(state, Message::DataEntryMessage(DataEntryMessage::AddTag))
+        }
+        DataEntryMessage::AddTag => {
+            let Some(tag) = state.vocabularies.accept(&state.data_entry_state.tag_input) else {
+                return Task::none();
+            };
+            state.data_entry_state.tag_input.clear();
+            edit(state, Edit::AddTag(tag))
+        }
+        DataEntryMessage::RemoveTag(tag) => edit(state, Edit::RemoveTag(tag)),
+        DataEntryMessage::EmbeddedLoaded(store) => {
+            tracing::info!("Loaded embedded metadata for {} files", store.items.len());
+            state.embedded = store;
+            Task::none()
+        }
+        DataEntryMessage::EmbeddedRead(found) => {
+            let Some(project) = &state.project else {
+                return Task::none();
+            };
+            tracing::info!("Found embedded metadata in {} files", found.len());
+            state.embedded.items.extend(found);
+            embedded::save(project.name.clone(), state.embedded.clone())
+        }
+        DataEntryMessage::FillFromFile => {
+            let Some(item) = &state.selected else {
+                return Task::none();
+            };
+            let id = metadata::item_info(item).0;
+            let Some(found) = state.embedded.get(id) else {
+                return Task::none();
+            };
+            let values = fillable(found, &state.metadata.get(id).cloned().unwrap_or_default());
+            if values.is_empty() {
+                return Task::none();
+            }
+            edit(state, Edit::Fill(values))
+        }
+    }
+}
+
+fn label<'a>(def: &FieldDef) -> Element<'a, Message> {
+    text(format!(
+        "{}{}",
+        def.key,
+        if def.required { " *" } else { "" }
+    ))
+    .size(12)
+    .into()
+}
+
+/// The field's problems, shown under its input.
+fn issues<'a>(def: &FieldDef, issues: &[Issue]) -> Column<'a, Message> {
+    issues
+        .iter()
+        .filter(|i| i.key == def.key)
+        .fold(Column::new(), |col, issue| {
+            col.push(text(issue.to_string()).size(12).style(text::danger))
+        })
+}
+
+fn field<'a>(def: &FieldDef, value: &str, problems: &[Issue]) -> Element<'a, Message> {
+    let key = def.key.clone();
+    let on_input = move |v: String| match &key {
+        FieldKey::Builtin(field) => Message::DataEntryMessage(DataEntryMessage::Set(*field, v)),
+        FieldKey::Custom(name) => {
+            Message::DataEntryMessage(DataEntryMessage::SetCustom(name.clone(), v))
+        }
+        FieldKey::Tags => Message::None,
+    };
+    let input: Element<Message> = if def.kind == FieldKind::Enum && !def.repeatable {
+        let selected = def.options.iter().find(|o| *o == value).cloned();
+        row![
+            pick_list(def.options.clone(), selected, on_input.clone()).placeholder("Choose"),
+            button("Clear")
+                .style(button::text)
+                .on_press(on_input(String::new())),
+        ]
+        .spacing(5)
+        .into()
+    } else {
+        let placeholder = match (def.repeatable, def.kind) {
+            (true, _) => format!("Separate values with \"{SEPARATOR}\""),
+            (false, FieldKind::Enum) => String::new(),
+            (false, kind) => kind.placeholder().to_string(),
+        };
+        TextInput::new(&placeholder, value)
+            .on_input(on_input)
+            .into()
+    };
+    let hint: Element<Message> = if def.kind == FieldKind::Enum && def.repeatable {
+        text(format!("One or more of: {}", def.options.join(", ")))
+            .size(12)
+            .into()
+    } else {
+        column![].into()
+    };
+    column![label(def), input, hint, issues(def, problems)]
+        .spacing(2)
+        .into()
+}
+
+pub(crate) fn data_entry(state: &State) -> Element<'_, Message> {
+    let Some(project) = &state.project else {
+        return text("No project open").into();
+    };
+    if state.file_tree_state.selection.len() > 1 {
+        return bulk_edit::bulk_edit(state);
+    }
+    if let Some(e) = &state.metadata_saves.load_error {
+        return text(format!(
+            "The metadata saved for this project could not be read, so nothing can be described \
+             until the file is fixed: {e}"
+        ))
+        .style(text::danger)
+        .into();
+    }
+    let Some(item) = &state.selected else {
+        return text("Select an item in the file list to describe it").into();
+    };
+    let (id, name) = metadata::item_info(item);
+    let entry = state.metadata.get(id).cloned().unwrap_or_default();
+    let schema = &project.schema;
+    let problems = schema.validator().validate(&entry);
+    let builtin = |f: Field| {
+        field(
+            &schema.get(&FieldKey::Builtin(f)),
+            f.value(&entry),
+            &problems,
+        )
+    };
+    let custom = schema.custom().fold(Column::new().spacing(10), |col, def| {
+        let FieldKey::Custom(name) = &def.key else {
+            return col;
+        };
+        col.push(field(
+            def,
+            entry.custom.get(name).map_or("", |v| v.as_str()),
+            &problems,
+        ))
+    });
+    let tags_def = schema.get(&FieldKey::Tags);
+    let notes_def = schema.get(&FieldKey::Builtin(Field::Notes));
+
+    let tags = entry
+        .tags
+        .iter()
+        .fold(Row::new().spacing(5).align_y(Center), |r, tag| {
+            let unknown = state.vocabularies.is_unknown(tag);
+            r.push(
+                button(text(format!("{}{tag} ×", if unknown { "⚠ " } else { "" })).size(12))
+                    .style(if unknown {
+                        button::warning
+                    } else {
+                        button::secondary
+                    })
+                    .on_press(Message::DataEntryMessage(DataEntryMessage::RemoveTag(
+                        tag.clone(),
+                    ))),
+            )
+        })
+        .wrap();
+
+    let suggestions = state
+        .vocabularies
+        .suggest(&state.data_entry_state.tag_input)
+        .into_iter()
+        .filter(|s| !entry.tags.contains(s))
+        .fold(Row::new().spacing(5), |r, label| {
+            r.push(
+                button(text(label.clone()).size(12))
+                    .style(button::text)
+                    .on_press(Message::DataEntryMessage(
+                        DataEntryMessage::AddSuggestedTag(label),
+                    )),
+            )
+        })
+        .wrap();
+
+    let from_file: Element<Message> =
+        match state.embedded.get(id).filter(|e| !e.is_empty()) {
+            Some(found) => {
+                let sources = found
+                    .sources
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let fill = !fillable(found, &entry).is_empty();
+                found
+                    .lines()
+                    .into_iter()
+                    .fold(
+                        column![text(format!("In the file ({sources})")).size(12)].spacing(2),
+                        |col, (label, value)| col.push(text(format!("{label}: {value}")).size(12)),
+                    )
+                    .push(
+                        button(text("Fill empty fields").size(12))
+                            .style(button::secondary)
+                            .on_press_maybe(fill.then_some(Message::DataEntryMessage(
+                                DataEntryMessage::FillFromFile,
+                            ))),
+                    )
+                    .into()
+            }
+            None => column![].into(),
+        };
+
+    let classified: Element<Message> = if matches!(item, Item::FileFull(_)) {
+        let label = match state.classifications.labels.get(id) {
+            Some(label) => format!("Classified as {label} in Box"),
+            None => "Not classified in Box".to_string(),
+        };
+        column![
+            text(label).size(12),
+            classification::controls(state, vec![id.to_string()]),
+        ]
+        .spacing(2)
+        .into()
+    } else {
+        column![].into()
+    };
+
+    column![
+        text(name).size(18),
+        classified,
+        from_file,
+        builtin(Field::Title),
+        builtin(Field::Description),
+        row![builtin(Field::DateStart), builtin(Field::DateEnd)].spacing(10),
+        builtin(Field::Creator),
+        custom,
+        column![
+            label(&tags_def),
+            tags,
+            issues(&tags_def, &problems),
+            row![
+                TextInput::new("Add a tag", &state.data_entry_state.tag_input)
+                    .on_input(|t| Message::DataEntryMessage(DataEntryMessage::SetTagInput(t)))
+                    .on_submit(Message::DataEntryMessage(DataEntryMessage::AddTag)),
+                button("Add").on_press(Message::DataEntryMessage(DataEntryMessage::AddTag)),
+            ]
+            .spacing(5),
+            suggestions,
+        ]
+        .spacing(5),
+        column![
+            label(&notes_def),
+            text_editor(&state.data_entry_state.notes)
+                .placeholder("Notes")
+                .height(150)
+                .on_action(|a| Message::DataEntryMessage(DataEntryMessage::EditNotes(a))),
+            issues(&notes_def, &problems),
+        ]
+        .spacing(2),
+    ]
+    .spacing(10)
+    .into()
+}
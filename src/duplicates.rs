@@ -0,0 +1,676 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+use r#box::{
+    apis::{
+        configuration::Configuration,
+        files_api::{
+            GetFilesIdParams, GetFilesIdThumbnailIdParams, get_files_id, get_files_id_thumbnail_id,
+        },
+    },
+    models::{FileFull, Item},
+};
+use iced::{
+    Element,
+    Length::Fill,
+    Padding, Task,
+    futures::{StreamExt, stream},
+    widget::{Column, button, column, row, scrollable, text},
+};
+use image::imageops::FilterType;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Message, State, persist,
+    project::Run,
+    project_page::{InternalType, Node},
+    sheet_plan,
+    subwindows::Subwindow,
+    viewer::IMAGE_EXTENSIONS,
+};
+
+/// Files looked up at the same time.
+const CONCURRENT_FILES: usize = 8;
+
+/// Edge length of the thumbnails perceptual hashes are made from.
+const HASH_THUMBNAIL_SIZE: i32 = 64;
+
+/// Images whose hashes differ in at most this many of 64 bits look the same.
+const IMAGE_DISTANCE: u32 = 6;
+
+/// Files with the same name are only grouped if their sizes are within this fraction of the
+/// larger one, since a name like "report.pdf" is often reused for unrelated files.
+const NAME_SIZE_TOLERANCE: f64 = 0.2;
+
+/// Every empty file has this SHA-1, and they are not worth reviewing as duplicates.
+const EMPTY_SHA1: &str = "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+
+/// "Copy of", "(1)", " - Copy 2" and similar marks that copying adds to a name.
+static COPY_MARKS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?:copy of\s+)?(.*?)(?:(?:\s*[-_]\s*|\s+)(?:copy|kopie|copie)(?:\s*\d+)?|\s*\(\d+\))*$",
+    )
+    .expect("valid regex")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Decision {
+    Keep,
+    Discard,
+}
+
+/// Why items were grouped.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Basis {
+    /// Identical SHA-1
+    Checksum,
+    /// Same name once copy marks are removed, but different content
+    Name,
+    /// Similar perceptual hashes of Box's thumbnails
+    Image,
+}
+
+impl Basis {
+    fn describe(&self) -> &'static str {
+        match self {
+            Basis::Checksum => "Exact duplicates",
+            Basis::Name => "Same name, different content",
+            Basis::Image => "Similar images",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Member {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct DuplicateGroup {
+    pub basis: Basis,
+    /// In project tree order
+    pub members: Vec<Member>,
+}
+
+/// The groups found by the last scan and the reviewers' decisions, by item ID.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct DuplicateStore {
+    pub groups: Vec<DuplicateGroup>,
+    pub decisions: HashMap<String, Decision>,
+}
+
+impl DuplicateStore {
+    /// The member the others duplicate: the first one kept, or else the first one.
+    fn canonical<'a>(&self, group: &'a DuplicateGroup) -> Option<&'a Member> {
+        group
+            .members
+            .iter()
+            .find(|m| self.decisions.get(&m.id) == Some(&Decision::Keep))
+            .or(group.members.first())
+    }
+
+    /// The canonical item each duplicate points to, by item ID.
+    ///
+    /// Exact duplicates always point to their canonical item. Likely duplicates only do
+    /// once a reviewer discards them.
+    pub(crate) fn duplicate_of(&self) -> HashMap<String, String> {
+        let mut out = HashMap::new();
+        for group in &self.groups {
+            let Some(canonical) = self.canonical(group) else {
+                continue;
+            };
+            for member in &group.members {
+                let discarded = self.decisions.get(&member.id) == Some(&Decision::Discard);
+                if member.id != canonical.id && (group.basis == Basis::Checksum || discarded) {
+                    out.entry(member.id.clone())
+                        .or_insert_with(|| canonical.id.clone());
+                }
+            }
+        }
+        out
+    }
+}
+
+/// What a scan learned about one file.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Facts {
+    sha1: Option<String>,
+    size: Option<u64>,
+    /// Difference hash of the thumbnail, for images
+    image_hash: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DuplicatesState {
+    run: Run,
+    /// Until the scanned files are grouped
+    scanning: bool,
+    total: usize,
+    scanned: Vec<(Member, Facts)>,
+    failed: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum DuplicatesMessage {
+    Loaded(DuplicateStore),
+    Find,
+    /// A file looked up during run `u64`
+    Scanned(u64, Member, Result<Facts, String>),
+    Grouped(u64, Result<Vec<DuplicateGroup>, String>),
+    /// Set or clear a reviewer's decision, by item ID
+    Decide(String, Option<Decision>),
+    Select(Member),
+}
+
+pub(crate) async fn load(project: String) -> DuplicateStore {
+    match persist::load_project_store::<DuplicateStore>(&project, "duplicates").await {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::debug!("No duplicates loaded for {}: {}", project, e);
+            DuplicateStore::default()
+        }
+    }
+}
+
+fn save(project: String, store: DuplicateStore) -> Task<Message> {
+    let snapshot = persist::snapshot();
+    Task::perform(
+        async move {
+            if let Err(e) =
+                persist::save_project_store(&store, &project, "duplicates", snapshot).await
+            {
+                tracing::error!("Error saving duplicates for {}: {}", project, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+fn is_image(name: &str) -> bool {
+    name.rsplit_once('.')
+        .is_some_and(|(_, ext)| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// The name with copy marks removed, lowercased, as (stem, extension).
+fn normalized_name(name: &str) -> (String, String) {
+    let lower = name.to_lowercase();
+    let (stem, ext) = lower.rsplit_once('.').unwrap_or((&lower, ""));
+    let stem = COPY_MARKS
+        .captures(stem.trim())
+        .and_then(|c| c.get(1))
+        .map_or(stem, |m| m.as_str());
+    (stem.trim().to_string(), ext.to_string())
+}
+
+/// A 64-bit difference hash: whether each pixel is brighter than its right neighbour,
+/// on a 9x8 grayscale copy of the image.
+fn difference_hash(bytes: &[u8]) -> Option<u64> {
+    let small = image::load_from_memory(bytes)
+        .ok()?
+        .grayscale()
+        .resize_exact(9, 8, FilterType::Triangle)
+        .to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Some(hash)
+}
+
+async fn image_hash(config: &Configuration, id: &str) -> Option<u64> {
+    let resp = get_files_id_thumbnail_id(
+        config,
+        GetFilesIdThumbnailIdParams {
+            file_id: id.to_string(),
+            extension: "png".to_string(),
+            min_height: None,
+            min_width: None,
+            max_height: Some(HASH_THUMBNAIL_SIZE),
+            max_width: Some(HASH_THUMBNAIL_SIZE),
+        },
+    )
+    .await
+    .inspect_err(|e| tracing::debug!("No thumbnail for {}: {}", id, e))
+    .ok()?;
+    if resp.status() != reqwest::StatusCode::OK {
+        return None;
+    }
+    let bytes = resp.bytes().await.ok()?;
+    difference_hash(&bytes)
+}
+
+/// Ask Box for a file's SHA-1 and size, unless they were already calculated, and hash its
+/// thumbnail.
+async fn facts(
+    config: Configuration,
+    member: Member,
+    known: Option<(String, u64)>,
+) -> Result<Facts, String> {
+    let (sha1, size) = match known {
+        Some((sha1, size)) => (Some(sha1), Some(size)),
+        None => {
+            let file = get_files_id(
+                &config,
+                GetFilesIdParams {
+                    file_id: member.id.clone(),
+                    fields: Some(vec!["sha1".to_string(), "size".to_string()]),
+                    if_none_match: None,
+                    boxapi: None,
+                    x_rep_hints: None,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            (
+                file.sha1.map(|s| s.to_ascii_lowercase()),
+                file.size.and_then(|s| u64::try_from(s).ok()),
+            )
+        }
+    };
+    let image_hash = if is_image(&member.name) {
+        image_hash(&config, &member.id).await
+    } else {
+        None
+    };
+    Ok(Facts {
+        sha1,
+        size,
+        image_hash,
+    })
+}
+
+fn near_sizes(a: u64, b: u64) -> bool {
+    a.abs_diff(b) as f64 <= a.max(b) as f64 * NAME_SIZE_TOLERANCE
+}
+
+/// Split files with the same name into runs of near sizes, each in tree order. Files whose
+/// size is unknown are left out.
+fn split_by_size(scanned: &[(Member, Facts)], indices: Vec<usize>) -> Vec<Vec<usize>> {
+    let mut sized: Vec<(u64, usize)> = indices
+        .into_iter()
+        .filter_map(|i| scanned[i].1.size.map(|size| (size, i)))
+        .collect();
+    sized.sort();
+    let mut runs: Vec<Vec<usize>> = vec![];
+    let mut last = None;
+    for (size, i) in sized {
+        match runs.last_mut() {
+            Some(run) if last.is_some_and(|last| near_sizes(last, size)) => run.push(i),
+            _ => runs.push(vec![i]),
+        }
+        last = Some(size);
+    }
+    for run in &mut runs {
+        run.sort();
+    }
+    runs
+}
+
+/// Group the scanned files. `scanned` is in project tree order, and so are the groups.
+fn group(scanned: &[(Member, Facts)]) -> Vec<DuplicateGroup> {
+    let mut groups = vec![];
+    let collect = |indices: &[usize]| -> Vec<Member> {
+        indices.iter().map(|i| scanned[*i].0.clone()).collect()
+    };
+
+    let mut by_sha1: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut by_name: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for (i, (member, facts)) in scanned.iter().enumerate() {
+        if let Some(sha1) = facts.sha1.as_deref().filter(|s| *s != EMPTY_SHA1) {
+            by_sha1.entry(sha1).or_default().push(i);
+        }
+        by_name
+            .entry(normalized_name(&member.name))
+            .or_default()
+            .push(i);
+    }
+    let mut exact: Vec<_> = by_sha1.into_values().filter(|g| g.len() > 1).collect();
+    exact.sort();
+    groups.extend(exact.iter().map(|g| DuplicateGroup {
+        basis: Basis::Checksum,
+        members: collect(g),
+    }));
+
+    // Same name only counts when the content differs, or exact duplicates would show twice
+    let mut named: Vec<_> = by_name
+        .into_values()
+        .flat_map(|g| split_by_size(scanned, g))
+        .filter(|g| {
+            let contents: HashSet<_> = g.iter().map(|i| &scanned[*i].1.sha1).collect();
+            contents.len() > 1 || (g.len() > 1 && contents.contains(&None))
+        })
+        .collect();
+    named.sort();
+    groups.extend(named.iter().map(|g| DuplicateGroup {
+        basis: Basis::Name,
+        members: collect(g),
+    }));
+
+    // Join similar images into clusters, leaving out pairs that are exact duplicates
+    let images: Vec<(usize, u64)> = scanned
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (_, facts))| facts.image_hash.map(|h| (i, h)))
+        .collect();
+    let mut parent: Vec<usize> = (0..images.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for a in 0..images.len() {
+        for b in a + 1..images.len() {
+            let ((ia, ha), (ib, hb)) = (images[a], images[b]);
+            let same_content =
+                scanned[ia].1.sha1.is_some() && scanned[ia].1.sha1 == scanned[ib].1.sha1;
+            if !same_content && (ha ^ hb).count_ones() <= IMAGE_DISTANCE {
+                let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+                parent[ra.max(rb)] = ra.min(rb);
+            }
+        }
+    }
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (a, (i, _)) in images.iter().enumerate() {
+        clusters.entry(root(&mut parent, a)).or_default().push(*i);
+    }
+    let mut similar: Vec<_> = clusters.into_values().filter(|g| g.len() > 1).collect();
+    similar.sort();
+    groups.extend(similar.iter().map(|g| DuplicateGroup {
+        basis: Basis::Image,
+        members: collect(g),
+    }));
+
+    groups
+}
+
+/// Every Box file in the project tree, in tree order.
+fn files(tree: &Node) -> Vec<Member> {
+    sheet_plan::flatten(tree)
+        .into_iter()
+        .filter(|item| item.file_type == InternalType::File)
+        .map(|item| Member {
+            id: item.id,
+            name: item.name,
+        })
+        .collect()
+}
+
+pub(crate) fn duplicates_handle(state: &mut State, event: DuplicatesMessage) -> Task<Message> {
+    match event {
+        DuplicatesMessage::Loaded(store) => state.duplicates = store,
+        DuplicatesMessage::Find => {
+            if state.duplicates_state.scanning {
+                return Task::none();
+            }
+            let Some(tree) = &state.project_tree else {
+                tracing::warn!("The project tree is not loaded, so there are no files to compare");
+                return Task::none();
+            };
+            let files = files(tree);
+            tracing::info!("Looking for duplicates among {} files", files.len());
+            state.duplicates_state = DuplicatesState {
+                scanning: !files.is_empty(),
+                total: files.len(),
+                ..Default::default()
+            };
+            let run = state.duplicates_state.run.id();
+            let config = state.box_config.clone();
+            // Checksums calculated for fixity save asking Box
+            let known: HashMap<String, (String, u64)> = state
+                .fixity
+                .records
+                .iter()
+                .map(|(id, r)| (id.clone(), (r.checksums.sha1.clone(), r.checksums.size)))
+                .collect();
+            let scans = stream::iter(files)
+                .map(move |member| {
+                    let config = config.clone();
+                    let known = known.get(&member.id).cloned();
+                    async move {
+                        let result = facts(config, member.clone(), known).await;
+                        (member, result)
+                    }
+                })
+                .buffered(CONCURRENT_FILES);
+            return state
+                .duplicates_state
+                .run
+                .track(Task::run(scans, move |(member, result)| {
+                    Message::DuplicatesMessage(DuplicatesMessage::Scanned(run, member, result))
+                }));
+        }
+        DuplicatesMessage::Scanned(run, member, result) => {
+            let scan = &mut state.duplicates_state;
+            if !scan.run.is(run) {
+                return Task::none();
+            }
+            match result {
+                Ok(facts) => scan.scanned.push((member, facts)),
+                Err(e) => {
+                    tracing::warn!("Could not look up {}: {}", member.name, e);
+                    scan.failed += 1;
+                }
+            }
+            if scan.scanning && scan.scanned.len() + scan.failed == scan.total {
+                // Comparing every pair of images takes a while in large projects
+                let scanned = scan.scanned.clone();
+                let grouping = async move {
+                    tokio::task::spawn_blocking(move || group(&scanned))
+                        .await
+                        .map_err(|e| e.to_string())
+                };
+                return scan.run.track(Task::perform(grouping, move |groups| {
+                    Message::DuplicatesMessage(DuplicatesMessage::Grouped(run, groups))
+                }));
+            }
+        }
+        DuplicatesMessage::Grouped(run, groups) => {
+            if !state.duplicates_state.run.is(run) {
+                return Task::none();
+            }
+            state.duplicates_state.scanning = false;
+            match groups {
+                Ok(groups) => {
+                    tracing::info!("Found {} groups of possible duplicates", groups.len());
+                    state.duplicates.groups = groups;
+                    if let Some(project) = &state.project {
+                        return save(project.name.clone(), state.duplicates.clone());
+                    }
+                }
+                Err(e) => tracing::error!("Failed to group duplicates: {}", e),
+            }
+        }
+        DuplicatesMessage::Decide(id, decision) => {
+            match decision {
+                Some(decision) => state.duplicates.decisions.insert(id, decision),
+                None => state.duplicates.decisions.remove(&id),
+            };
+            if let Some(project) = &state.project {
+                return save(project.name.clone(), state.duplicates.clone());
+            }
+        }
+        DuplicatesMessage::Select(member) => {
+            let file = FileFull {
+                id: member.id,
+                name: Some(member.name),
+                ..Default::default()
+            };
+            return Task::done(Message::Select(Item::FileFull(Box::new(file))));
+        }
+    }
+    Task::none()
+}
+
+fn member_row<'a>(
+    store: &'a DuplicateStore,
+    member: &'a Member,
+    canonical: bool,
+) -> Element<'a, Message> {
+    let decision = store.decisions.get(&member.id).copied();
+    let decide = |d: Decision| {
+        // Pressing the current decision again clears it
+        let next = (decision != Some(d)).then_some(d);
+        Message::DuplicatesMessage(DuplicatesMessage::Decide(member.id.clone(), next))
+    };
+    let style = |d: Decision| {
+        if decision == Some(d) {
+            button::primary
+        } else {
+            button::secondary
+        }
+    };
+    row![
+        button(text(member.name.as_str()))
+            .style(button::text)
+            .width(Fill)
+            .on_press(Message::DuplicatesMessage(DuplicatesMessage::Select(
+                member.clone()
+            ))),
+        text(if canonical { "Canonical" } else { "" })
+            .size(12)
+            .style(text::secondary),
+        button("Keep")
+            .style(style(Decision::Keep))
+            .on_press(decide(Decision::Keep)),
+        button("Discard")
+            .style(style(Decision::Discard))
+            .on_press(decide(Decision::Discard)),
+    ]
+    .spacing(10)
+    .into()
+}
+
+pub(crate) fn duplicates_review(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::Duplicates));
+    let scan = &state.duplicates_state;
+    let store = &state.duplicates;
+
+    let find = button("Find duplicates").on_press_maybe(
+        (!scan.scanning).then_some(Message::DuplicatesMessage(DuplicatesMessage::Find)),
+    );
+    let status = if scan.scanning {
+        format!(
+            "Looked at {} of {} files",
+            scan.scanned.len() + scan.failed,
+            scan.total
+        )
+    } else if scan.failed > 0 {
+        format!(
+            "{} groups; {} files could not be looked up",
+            store.groups.len(),
+            scan.failed
+        )
+    } else {
+        format!("{} groups", store.groups.len())
+    };
+
+    let body: Element<Message> = if store.groups.is_empty() {
+        text("No duplicates found yet").into()
+    } else {
+        let groups = store
+            .groups
+            .iter()
+            .fold(Column::new().spacing(15), |col, group| {
+                let canonical = store.canonical(group).map(|m| m.id.as_str());
+                col.push(group.members.iter().fold(
+                    column![text(group.basis.describe()).size(16)].spacing(5),
+                    |c, member| {
+                        c.push(member_row(
+                            store,
+                            member,
+                            canonical == Some(member.id.as_str()),
+                        ))
+                    },
+                ))
+            });
+        scrollable(groups).height(Fill).into()
+    };
+
+    column![
+        text("Duplicates").size(20),
+        row![find, text(status)].spacing(10),
+        text("Exact duplicates are recorded as duplicates of the canonical item. Likely ones only once discarded.")
+            .size(12)
+            .style(text::secondary),
+        body,
+        close
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(id: &str, name: &str, sha1: &str, size: u64) -> (Member, Facts) {
+        (
+            Member {
+                id: id.to_string(),
+                name: name.to_string(),
+            },
+            Facts {
+                sha1: Some(sha1.to_string()),
+                size: Some(size),
+                image_hash: None,
+            },
+        )
+    }
+
+    fn ids(group: &DuplicateGroup) -> Vec<&str> {
+        group.members.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    #[test]
+    fn copies_with_near_sizes_are_grouped_by_name() {
+        let scanned = [
+            file("1", "Report.pdf", "a", 1000),
+            file("2", "Copy of report.pdf", "b", 1100),
+            file("3", "report (1).pdf", "c", 50_000),
+            file("4", "report - Copy.pdf", "a", 1000),
+            file("5", "notes.txt", "d", 10),
+        ];
+        let groups = group(&scanned);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].basis, Basis::Checksum);
+        assert_eq!(ids(&groups[0]), ["1", "4"]);
+        // The much larger file only shares the name
+        assert_eq!(groups[1].basis, Basis::Name);
+        assert_eq!(ids(&groups[1]), ["1", "2", "4"]);
+    }
+
+    #[test]
+    fn same_name_same_content_is_not_grouped_by_name() {
+        let scanned = [file("1", "a.txt", "x", 5), file("2", "a (2).txt", "x", 5)];
+        let groups = group(&scanned);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].basis, Basis::Checksum);
+    }
+
+    #[test]
+    fn size_runs() {
+        let scanned = [
+            file("1", "a", "", 100),
+            file("2", "a", "", 1000),
+            file("3", "a", "", 90),
+            file("4", "a", "", 1150),
+        ];
+        assert_eq!(
+            split_by_size(&scanned, vec![0, 1, 2, 3]),
+            [vec![0, 2], vec![1, 3]]
+        );
+    }
+}
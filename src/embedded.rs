@@ -0,0 +1,1325 @@
+use std::{collections::HashMap, io::Cursor, sync::LazyLock};
+
+use r#box::apis::configuration::Configuration;
+use exif::{In, Tag, Value};
+use iced::Task;
+use regex::bytes::Regex;
+use roxmltree::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::{Message, archive, media, metadata::Field, persist, sampling::Sample};
+
+/// Bytes read from the end of PDFs and MP3s, for the trailer and the ID3v1 tag.
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// Bytes read at a PDF object found through the cross-reference table.
+const OBJECT_BYTES: u64 = 8 * 1024;
+
+/// Cross-reference tables larger than this are not read.
+const MAX_XREF: u64 = 1024 * 1024;
+
+/// Office property files larger than this are not read.
+const MAX_PROPERTIES: u64 = 1024 * 1024;
+
+/// Office Open XML keeps its properties in docProps/core.xml.
+pub(crate) const OOXML_EXTENSIONS: [&str; 12] = [
+    "docx", "docm", "dotx", "dotm", "xlsx", "xlsm", "xltx", "xltm", "pptx", "pptm", "potx", "potm",
+];
+
+/// OpenDocument keeps its properties in meta.xml.
+pub(crate) const ODF_EXTENSIONS: [&str; 8] =
+    ["odt", "ods", "odp", "odg", "ott", "ots", "otp", "otg"];
+
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const DC: &str = "http://purl.org/dc/elements/1.1/";
+const XMP: &str = "http://ns.adobe.com/xap/1.0/";
+const PHOTOSHOP: &str = "http://ns.adobe.com/photoshop/1.0/";
+const EXIF: &str = "http://ns.adobe.com/exif/1.0/";
+const PDF: &str = "http://ns.adobe.com/pdf/1.3/";
+
+static INFO_REF: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?-u)/Info\s+(\d+)\s+(\d+)\s+R").unwrap());
+
+static STARTXREF: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?-u)startxref\s+(\d+)").unwrap());
+
+/// Where an embedded value was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Source {
+    Exif,
+    Xmp,
+    Iptc,
+    Id3,
+    Vorbis,
+    Pdf,
+    Office,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Exif => write!(f, "EXIF"),
+            Source::Xmp => write!(f, "XMP"),
+            Source::Iptc => write!(f, "IPTC"),
+            Source::Id3 => write!(f, "ID3"),
+            Source::Vorbis => write!(f, "Vorbis comments"),
+            Source::Pdf => write!(f, "PDF info"),
+            Source::Office => write!(f, "document properties"),
+        }
+    }
+}
+
+/// Descriptive metadata read out of a file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Embedded {
+    pub title: String,
+    pub creator: String,
+    /// Creation date as YYYY, YYYY-MM or YYYY-MM-DD
+    pub date: String,
+    pub description: String,
+    pub keywords: Vec<String>,
+    /// Other values worth keeping, such as the camera or the album, by label
+    pub other: Vec<(String, String)>,
+    /// Where any of the values came from
+    pub sources: Vec<Source>,
+}
+
+impl Embedded {
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Fill in whatever is still missing from `found`, read from `source`.
+    fn merge(&mut self, source: Source, found: Embedded) {
+        let mut used = false;
+        for (mine, theirs) in [
+            (&mut self.title, found.title),
+            (&mut self.creator, found.creator),
+            (&mut self.date, found.date),
+            (&mut self.description, found.description),
+        ] {
+            let theirs = theirs.trim();
+            if mine.is_empty() && !theirs.is_empty() {
+                *mine = theirs.to_string();
+                used = true;
+            }
+        }
+        if self.keywords.is_empty() && !found.keywords.is_empty() {
+            self.keywords = found.keywords;
+            used = true;
+        }
+        for (label, value) in found.other {
+            let value = value.trim();
+            if !value.is_empty() && !self.other.iter().any(|(l, _)| *l == label) {
+                self.other.push((label, value.to_string()));
+                used = true;
+            }
+        }
+        if used && !self.sources.contains(&source) {
+            self.sources.push(source);
+        }
+    }
+
+    /// Every value found, labelled.
+    pub fn lines(&self) -> Vec<(String, String)> {
+        [
+            ("Title", self.title.clone()),
+            ("Creator", self.creator.clone()),
+            ("Date", self.date.clone()),
+            ("Description", self.description.clone()),
+            ("Keywords", self.keywords.join(", ")),
+        ]
+        .into_iter()
+        .map(|(label, value)| (label.to_string(), value))
+        .chain(self.other.iter().cloned())
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+    }
+
+    /// Every value found as a single cell, e.g. "Title: Harbour; Camera: Nikon D70".
+    pub fn summary(&self) -> String {
+        self.lines()
+            .iter()
+            .map(|(label, value)| format!("{label}: {value}"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// The values that can pre-fill the data-entry form.
+    pub fn fields(&self) -> Vec<(Field, String)> {
+        [
+            (Field::Title, &self.title),
+            (Field::Description, &self.description),
+            (Field::DateStart, &self.date),
+            (Field::Creator, &self.creator),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(field, value)| (field, value.clone()))
+        .collect()
+    }
+}
+
+fn be16(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// ID3 sizes are "synchsafe": 7 bits per byte.
+fn synchsafe(b: &[u8]) -> usize {
+    b.iter()
+        .fold(0usize, |acc, byte| (acc << 7) | (*byte & 0x7F) as usize)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn latin1(b: &[u8]) -> String {
+    b.iter().map(|&c| c as char).collect()
+}
+
+/// UTF-16 in the byte order of its BOM, or `big_endian` without one.
+pub(crate) fn utf16(b: &[u8], big_endian: bool) -> String {
+    let (b, big_endian) = match b {
+        [0xFE, 0xFF, rest @ ..] => (rest, true),
+        [0xFF, 0xFE, rest @ ..] => (rest, false),
+        _ => (b, big_endian),
+    };
+    let units: Vec<u16> = b
+        .chunks_exact(2)
+        .map(|c| {
+            if big_endian {
+                u16::from_be_bytes([c[0], c[1]])
+            } else {
+                u16::from_le_bytes([c[0], c[1]])
+            }
+        })
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Trim padding, and join values that formats separate with NULs.
+fn clean(s: &str) -> String {
+    s.split('\0')
+        .map(|part| part.trim_matches(|c: char| c.is_whitespace() || c == '\u{FEFF}'))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Keywords kept in one string, separated by commas or semicolons.
+fn split_keywords(s: &str) -> Vec<String> {
+    s.split([',', ';'])
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// The date part of a timestamp in any of the formats files use, as YYYY, YYYY-MM or
+/// YYYY-MM-DD. EXIF writes "2019:07:04 13:22:10", PDF "D:20190704132210+02'00'", IPTC
+/// "20190704" and XMP ISO 8601. Empty when there is no usable year.
+fn iso_date(s: &str) -> String {
+    let s = s.trim().trim_start_matches("D:");
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | ':' | '.')))
+        .unwrap_or(s.len());
+    let parts: Vec<&str> = s[..end].split(['-', ':', '.']).collect();
+    let (year, month, day) = match parts.as_slice() {
+        [compact] if compact.len() >= 4 => (&compact[..4], compact.get(4..6), compact.get(6..8)),
+        [year, rest @ ..] => (*year, rest.first().copied(), rest.get(1).copied()),
+        _ => return String::new(),
+    };
+    if year.len() != 4 || year == "0000" {
+        return String::new();
+    }
+    let month = month.filter(|m| m.len() == 2 && ("01"..="12").contains(m));
+    let day = day.filter(|d| d.len() == 2 && ("01"..="31").contains(d));
+    match (month, day) {
+        (Some(month), Some(day)) => format!("{year}-{month}-{day}"),
+        (Some(month), None) => format!("{year}-{month}"),
+        _ => year.to_string(),
+    }
+}
+
+/// EXIF from JPEG, TIFF, PNG, WebP or HEIF.
+fn exif(b: &[u8]) -> Option<Embedded> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(b))
+        .ok()?;
+    let ascii = |tag: Tag| {
+        exif.get_field(tag, In::PRIMARY)
+            .and_then(|field| match &field.value {
+                Value::Ascii(values) => values.first().map(|v| clean(&String::from_utf8_lossy(v))),
+                _ => None,
+            })
+            .unwrap_or_default()
+    };
+    let (make, model) = (ascii(Tag::Make), ascii(Tag::Model));
+    // Most cameras repeat the make in the model
+    let camera = if model.starts_with(&make) {
+        model
+    } else {
+        format!("{make} {model}").trim().to_string()
+    };
+    let date = match iso_date(&ascii(Tag::DateTimeOriginal)) {
+        date if date.is_empty() => iso_date(&ascii(Tag::DateTime)),
+        date => date,
+    };
+    Some(Embedded {
+        creator: ascii(Tag::Artist),
+        date,
+        description: ascii(Tag::ImageDescription),
+        other: vec![
+            ("Camera".to_string(), camera),
+            ("Software".to_string(), ascii(Tag::Software)),
+            ("Copyright".to_string(), ascii(Tag::Copyright)),
+        ],
+        ..Default::default()
+    })
+}
+
+/// The XMP packet in a file, wherever it is. Most formats store it uncompressed.
+fn xmp_packet(b: &[u8]) -> Option<&str> {
+    let (open, close): (&[u8], &[u8]) = match find(b, b"<x:xmpmeta") {
+        Some(_) => (b"<x:xmpmeta", b"</x:xmpmeta>"),
+        None => (b"<rdf:RDF", b"</rdf:RDF>"),
+    };
+    let start = find(b, open)?;
+    let end = start + find(&b[start..], close)? + close.len();
+    std::str::from_utf8(&b[start..end]).ok()
+}
+
+fn xmp(b: &[u8]) -> Option<Embedded> {
+    let doc = Document::parse(xmp_packet(b)?).ok()?;
+    // A property is an attribute of rdf:Description, or an element holding text or rdf:li
+    let values = |ns: &str, name: &str| -> Vec<String> {
+        let mut values = vec![];
+        for description in doc
+            .descendants()
+            .filter(|n| n.has_tag_name((RDF, "Description")))
+        {
+            values.extend(description.attribute((ns, name)).map(clean));
+            for property in description
+                .children()
+                .filter(|n| n.has_tag_name((ns, name)))
+            {
+                let items: Vec<String> = property
+                    .descendants()
+                    .filter(|n| n.has_tag_name((RDF, "li")))
+                    .filter_map(|n| n.text())
+                    .map(clean)
+                    .collect();
+                if items.is_empty() {
+                    values.extend(property.text().map(clean));
+                } else {
+                    values.extend(items);
+                }
+            }
+        }
+        values.retain(|v| !v.is_empty());
+        values
+    };
+    let first = |properties: &[(&str, &str)]| {
+        properties
+            .iter()
+            .find_map(|(ns, name)| values(ns, name).into_iter().next())
+            .unwrap_or_default()
+    };
+    let mut keywords = values(DC, "subject");
+    if keywords.is_empty() {
+        keywords = values(PDF, "Keywords")
+            .iter()
+            .flat_map(|k| split_keywords(k))
+            .collect();
+    }
+    Some(Embedded {
+        // dc:title and dc:description are language alternatives, the first is the default
+        title: first(&[(DC, "title")]),
+        creator: values(DC, "creator").join("; "),
+        date: iso_date(&first(&[
+            (PHOTOSHOP, "DateCreated"),
+            (EXIF, "DateTimeOriginal"),
+            (XMP, "CreateDate"),
+            (DC, "date"),
+        ])),
+        description: first(&[(DC, "description")]),
+        keywords,
+        other: vec![
+            ("Software".to_string(), first(&[(XMP, "CreatorTool")])),
+            ("Copyright".to_string(), first(&[(DC, "rights")])),
+        ],
+        ..Default::default()
+    })
+}
+
+/// The segments of a JPEG before its image data, as marker and contents.
+fn jpeg_segments(b: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut segments = vec![];
+    if !b.starts_with(&[0xFF, 0xD8]) {
+        return segments;
+    }
+    let mut at = 2;
+    while b.get(at) == Some(&0xFF) {
+        let Some(&marker) = b.get(at + 1) else {
+            break;
+        };
+        // Markers may be padded with any number of 0xFF
+        if marker == 0xFF {
+            at += 1;
+            continue;
+        }
+        // Start of scan: the rest is image data
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let Some(len) = be16(b, at + 2).map(usize::from).filter(|l| *l >= 2) else {
+            break;
+        };
+        let Some(data) = b.get(at + 4..at + 2 + len) else {
+            break;
+        };
+        segments.push((marker, data));
+        at += 2 + len;
+    }
+    segments
+}
+
+/// IPTC IIM, kept in a JPEG's Photoshop resources.
+fn iptc(b: &[u8]) -> Option<Embedded> {
+    let resources = jpeg_segments(b)
+        .into_iter()
+        .find(|(marker, data)| *marker == 0xED && data.starts_with(b"Photoshop 3.0\0"))?
+        .1;
+    let mut at = 14;
+    let mut iim = None;
+    while resources.get(at..at + 4) == Some(b"8BIM") {
+        let id = be16(resources, at + 4)?;
+        // A Pascal string name, padded to an even length
+        let name_len = *resources.get(at + 6)? as usize;
+        let size_at = at + 6 + ((name_len + 2) & !1);
+        let size = be32(resources, size_at)? as usize;
+        let data = resources.get(size_at + 4..size_at + 4 + size)?;
+        if id == 0x0404 {
+            iim = Some(data);
+            break;
+        }
+        at = size_at + 4 + size + (size & 1);
+    }
+
+    let iim = iim?;
+    let mut datasets: Vec<(u8, String)> = vec![];
+    let mut at = 0;
+    while iim.get(at) == Some(&0x1C) {
+        let (Some(&record), Some(&dataset), Some(len)) =
+            (iim.get(at + 1), iim.get(at + 2), be16(iim, at + 3))
+        else {
+            break;
+        };
+        // Extended lengths are only used for binary data
+        if len & 0x8000 != 0 {
+            break;
+        }
+        let Some(value) = iim.get(at + 5..at + 5 + len as usize) else {
+            break;
+        };
+        if record == 2 {
+            datasets.push((dataset, clean(&String::from_utf8_lossy(value))));
+        }
+        at += 5 + len as usize;
+    }
+    let all = |dataset: u8| -> Vec<String> {
+        datasets
+            .iter()
+            .filter(|(d, v)| *d == dataset && !v.is_empty())
+            .map(|(_, v)| v.clone())
+            .collect()
+    };
+    let first = |dataset: u8| all(dataset).into_iter().next().unwrap_or_default();
+    let title = match first(5) {
+        title if title.is_empty() => first(105),
+        title => title,
+    };
+    Some(Embedded {
+        title,
+        creator: all(80).join("; "),
+        date: iso_date(&first(55)),
+        description: first(120),
+        keywords: all(25),
+        other: vec![("Copyright".to_string(), first(116))],
+        ..Default::default()
+    })
+}
+
+/// Reverse ID3 unsynchronisation, which puts a zero after every 0xFF.
+fn resynchronise(b: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(b.len());
+    for (i, &byte) in b.iter().enumerate() {
+        if !(byte == 0 && i > 0 && b[i - 1] == 0xFF) {
+            out.push(byte);
+        }
+    }
+    out
+}
+
+/// An ID3 string in the encoding named by its first byte.
+fn id3_string(encoding: u8, b: &[u8]) -> String {
+    let s = match encoding {
+        0 => latin1(b),
+        1 => utf16(b, false),
+        2 => utf16(b, true),
+        _ => String::from_utf8_lossy(b).into_owned(),
+    };
+    clean(&s)
+}
+
+/// The text of a comment frame, after its language and short description.
+fn id3_comment(body: &[u8]) -> Option<String> {
+    let (&encoding, rest) = body.split_first()?;
+    let rest = rest.get(3..)?;
+    let text_start = if matches!(encoding, 1 | 2) {
+        (0..rest.len().saturating_sub(1))
+            .step_by(2)
+            .find(|&i| rest[i] == 0 && rest[i + 1] == 0)
+            .map(|i| i + 2)
+    } else {
+        rest.iter().position(|&c| c == 0).map(|i| i + 1)
+    }?;
+    Some(id3_string(encoding, rest.get(text_start..)?))
+}
+
+/// An ID3v2 tag at the start of an MP3, AIFF or WAV file.
+fn id3v2(b: &[u8]) -> Option<Embedded> {
+    if !b.starts_with(b"ID3") || b.len() < 10 {
+        return None;
+    }
+    let version = b[3];
+    let flags = b[5];
+    let size = synchsafe(&b[6..10]);
+    let mut tag = b[10..(10 + size).min(b.len())].to_vec();
+    if flags & 0x80 != 0 && version < 4 {
+        tag = resynchronise(&tag);
+    }
+    let mut at = match (flags & 0x40 != 0, version) {
+        (true, 3) => be32(&tag, 0)? as usize + 4,
+        (true, 4) => synchsafe(tag.get(0..4)?),
+        _ => 0,
+    };
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+
+    let mut frames: Vec<(String, String)> = vec![];
+    while at + header_len <= tag.len() && tag[at] != 0 {
+        let id = String::from_utf8_lossy(&tag[at..at + id_len]).into_owned();
+        let size = match version {
+            2 => (be32(&tag, at + 2)? & 0xFF_FFFF) as usize,
+            3 => be32(&tag, at + 4)? as usize,
+            _ => synchsafe(&tag[at + 4..at + 8]),
+        };
+        let Some(mut body) = tag.get(at + header_len..at + header_len + size) else {
+            break;
+        };
+        let format = if version == 2 { 0 } else { tag[at + 9] };
+        at += header_len + size;
+        // Skip compressed and encrypted frames, and step over group IDs and data lengths
+        let resynchronised;
+        match version {
+            3 => {
+                if format & 0xC0 != 0 {
+                    continue;
+                }
+                if format & 0x20 != 0 {
+                    body = body.get(1..).unwrap_or_default();
+                }
+            }
+            4 => {
+                if format & 0x0C != 0 {
+                    continue;
+                }
+                if format & 0x40 != 0 {
+                    body = body.get(1..).unwrap_or_default();
+                }
+                if format & 0x01 != 0 {
+                    body = body.get(4..).unwrap_or_default();
+                }
+                if format & 0x02 != 0 {
+                    resynchronised = resynchronise(body);
+                    body = &resynchronised;
+                }
+            }
+            _ => {}
+        }
+        let value = match id.as_str() {
+            "COMM" | "COM" => id3_comment(body),
+            "TXXX" | "TXX" => None,
+            id if id.starts_with('T') => body
+                .split_first()
+                .map(|(&encoding, text)| id3_string(encoding, text)),
+            _ => None,
+        };
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            frames.push((id, value));
+        }
+    }
+
+    let first = |ids: &[&str]| {
+        ids.iter()
+            .find_map(|id| frames.iter().find(|(f, _)| f == id))
+            .map(|(_, v)| v.clone())
+            .unwrap_or_default()
+    };
+    Some(Embedded {
+        title: first(&["TIT2", "TT2"]),
+        creator: first(&["TPE1", "TP1", "TCOM", "TCM"]),
+        date: iso_date(&first(&["TDRC", "TDOR", "TYER", "TYE", "TORY", "TOR"])),
+        description: first(&["COMM", "COM"]),
+        other: vec![
+            ("Album".to_string(), first(&["TALB", "TAL"])),
+            ("Genre".to_string(), first(&["TCON", "TCO"])),
+            ("Copyright".to_string(), first(&["TCOP", "TCR"])),
+        ],
+        ..Default::default()
+    })
+}
+
+/// The fixed 128-byte ID3v1 tag at the end of an MP3.
+fn id3v1(tail: &[u8]) -> Option<Embedded> {
+    let tag = tail.get(tail.len().checked_sub(128)?..)?;
+    if !tag.starts_with(b"TAG") {
+        return None;
+    }
+    let field = |start: usize, len: usize| clean(&latin1(&tag[start..start + len]));
+    Some(Embedded {
+        title: field(3, 30),
+        creator: field(33, 30),
+        date: iso_date(&field(93, 4)),
+        // ID3v1.1 puts a track number in the last two bytes of the comment
+        description: field(97, if tag[125] == 0 { 28 } else { 30 }),
+        other: vec![("Album".to_string(), field(63, 30))],
+        ..Default::default()
+    })
+}
+
+/// A Vorbis comment block, as used by FLAC, Ogg Vorbis and Opus.
+fn vorbis_comments(b: &[u8]) -> Option<Embedded> {
+    let vendor_len = le32(b, 0)? as usize;
+    let mut at = 4 + vendor_len;
+    let count = le32(b, at)?;
+    at += 4;
+    let mut comments: Vec<(String, String)> = vec![];
+    for _ in 0..count {
+        let Some(len) = le32(b, at).map(|l| l as usize) else {
+            break;
+        };
+        let Some(comment) = b.get(at + 4..at + 4 + len) else {
+            break;
+        };
+        at += 4 + len;
+        if let Some((key, value)) = String::from_utf8_lossy(comment).split_once('=') {
+            comments.push((key.to_ascii_uppercase(), clean(value)));
+        }
+    }
+    let all = |key: &str| -> Vec<String> {
+        comments
+            .iter()
+            .filter(|(k, v)| k == key && !v.is_empty())
+            .map(|(_, v)| v.clone())
+            .collect()
+    };
+    let first = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| all(key).into_iter().next())
+            .unwrap_or_default()
+    };
+    let creator = match all("ARTIST") {
+        artists if artists.is_empty() => all("COMPOSER").join("; "),
+        artists => artists.join("; "),
+    };
+    Some(Embedded {
+        title: first(&["TITLE"]),
+        creator,
+        date: iso_date(&first(&["DATE"])),
+        description: first(&["DESCRIPTION", "COMMENT"]),
+        other: vec![
+            ("Album".to_string(), first(&["ALBUM"])),
+            ("Genre".to_string(), first(&["GENRE"])),
+            ("Copyright".to_string(), first(&["COPYRIGHT"])),
+            ("Organization".to_string(), first(&["ORGANIZATION"])),
+        ],
+        ..Default::default()
+    })
+}
+
+/// The VORBIS_COMMENT metadata block of a FLAC file.
+fn flac_comments(b: &[u8]) -> Option<&[u8]> {
+    if !b.starts_with(b"fLaC") {
+        return None;
+    }
+    let mut at = 4;
+    loop {
+        let header = *b.get(at)?;
+        let len = (be32(b, at)? & 0xFF_FFFF) as usize;
+        if header & 0x7F == 4 {
+            return b.get(at + 4..at + 4 + len);
+        }
+        if header & 0x80 != 0 {
+            return None;
+        }
+        at += 4 + len;
+    }
+}
+
+/// The comment header of an Ogg Vorbis or Opus file, which may span several pages.
+fn ogg_comments(b: &[u8]) -> Option<Vec<u8>> {
+    let serial = b.get(14..18)?;
+    let mut packets = vec![];
+    let mut at = 0;
+    while b.get(at..at + 4) == Some(b"OggS") && b.get(at + 14..at + 18) == Some(serial) {
+        let segments = *b.get(at + 26)? as usize;
+        let Some(lacing) = b.get(at + 27..at + 27 + segments) else {
+            break;
+        };
+        let body_start = at + 27 + segments;
+        let body_len: usize = lacing.iter().map(|&l| l as usize).sum();
+        let body_end = (body_start + body_len).min(b.len());
+        packets.extend_from_slice(&b[body_start..body_end]);
+        at = body_start + body_len;
+    }
+    let (start, skip) = match find(&packets, b"\x03vorbis") {
+        Some(start) => (start, 7),
+        None => (find(&packets, b"OpusTags")?, 8),
+    };
+    Some(packets.split_off(start + skip))
+}
+
+/// A PDF literal string, starting after its "(". Returns the bytes and how far it ran.
+pub(crate) fn pdf_literal(b: &[u8]) -> (Vec<u8>, usize) {
+    let mut out = vec![];
+    let mut depth = 1;
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'\\' => {
+                i += 1;
+                match b.get(i) {
+                    Some(b'n') => out.push(b'\n'),
+                    Some(b'r') => out.push(b'\r'),
+                    Some(b't') => out.push(b'\t'),
+                    Some(b'b') => out.push(0x08),
+                    Some(b'f') => out.push(0x0C),
+                    Some(d @ b'0'..=b'7') => {
+                        let mut value = (d - b'0') as u32;
+                        for _ in 0..2 {
+                            match b.get(i + 1) {
+                                Some(d @ b'0'..=b'7') => {
+                                    value = value * 8 + (d - b'0') as u32;
+                                    i += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        out.push(value as u8);
+                    }
+                    // A backslash before a line break continues the line
+                    Some(b'\r') => {
+                        if b.get(i + 1) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    Some(b'\n') => {}
+                    Some(&c) => out.push(c),
+                    None => break,
+                }
+            }
+            b'(' => {
+                depth += 1;
+                out.push(b'(');
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return (out, i + 1);
+                }
+                out.push(b')');
+            }
+            c => out.push(c),
+        }
+        i += 1;
+    }
+    (out, i)
+}
+
+/// A PDF hex string, starting after its "<". Returns the bytes and how far it ran.
+pub(crate) fn pdf_hex(b: &[u8]) -> (Vec<u8>, usize) {
+    let end = b.iter().position(|&c| c == b'>').unwrap_or(b.len());
+    let mut digits: Vec<u8> = b[..end]
+        .iter()
+        .filter_map(|&c| (c as char).to_digit(16).map(|d| d as u8))
+        .collect();
+    // An odd final digit is followed by an implied 0
+    if digits.len() % 2 == 1 {
+        digits.push(0);
+    }
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect();
+    (bytes, (end + 1).min(b.len()))
+}
+
+/// Text strings are UTF-16 with a BOM, UTF-8 with one, or PDFDocEncoding, which matches
+/// Latin-1 for printable text.
+fn pdf_text(b: &[u8]) -> String {
+    if b.starts_with(&[0xFE, 0xFF]) {
+        clean(&utf16(b, true))
+    } else if let Some(utf8) = b.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        clean(&String::from_utf8_lossy(utf8))
+    } else {
+        clean(&latin1(b))
+    }
+}
+
+/// The string-valued entries of the PDF dictionary at the start of `b`.
+fn pdf_dictionary(b: &[u8]) -> Vec<(String, String)> {
+    let Some(start) = find(b, b"<<") else {
+        return vec![];
+    };
+    let delimiter = |c: u8| c.is_ascii_whitespace() || b"()<>[]{}/%".contains(&c);
+    let mut entries = vec![];
+    let mut key: Option<String> = None;
+    let mut i = start + 2;
+    while i < b.len() {
+        match b[i] {
+            c if c.is_ascii_whitespace() => i += 1,
+            b'>' if b.get(i + 1) == Some(&b'>') => break,
+            b'/' => {
+                let end = b[i + 1..]
+                    .iter()
+                    .position(|&c| delimiter(c))
+                    .map_or(b.len(), |p| i + 1 + p);
+                let name = String::from_utf8_lossy(&b[i + 1..end]).into_owned();
+                // A name is either a key or the value of the previous one
+                key = match key {
+                    None => Some(name),
+                    Some(_) => None,
+                };
+                i = end;
+            }
+            b'(' => {
+                let (bytes, len) = pdf_literal(&b[i + 1..]);
+                if let Some(key) = key.take() {
+                    entries.push((key, pdf_text(&bytes)));
+                }
+                i += 1 + len;
+            }
+            b'<' if b.get(i + 1) == Some(&b'<') => {
+                // Skip nested dictionaries
+                let mut depth = 0;
+                while i < b.len() {
+                    if b[i..].starts_with(b"<<") {
+                        depth += 1;
+                        i += 2;
+                    } else if b[i..].starts_with(b">>") {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+                key = None;
+            }
+            b'<' => {
+                let (bytes, len) = pdf_hex(&b[i + 1..]);
+                if let Some(key) = key.take() {
+                    entries.push((key, pdf_text(&bytes)));
+                }
+                i += 1 + len;
+            }
+            _ => {
+                // Numbers, references, arrays and keywords aren't text
+                key = None;
+                i += 1;
+                while i < b.len() && !delimiter(b[i]) {
+                    i += 1;
+                }
+            }
+        }
+    }
+    entries
+}
+
+/// The object number and generation of the Info dictionary, from the last trailer in `b`.
+fn pdf_info_ref(b: &[u8]) -> Option<(u64, u64)> {
+    let captures = INFO_REF.captures_iter(b).last()?;
+    let number = |i: usize| std::str::from_utf8(&captures[i]).ok()?.parse().ok();
+    Some((number(1)?, number(2)?))
+}
+
+/// The body of object `number` `generation`, if it is in `b`.
+fn pdf_object(b: &[u8], (number, generation): (u64, u64)) -> Option<&[u8]> {
+    let header = Regex::new(&format!(r"(?-u)(?:^|[^0-9]){number}\s+{generation}\s+obj")).ok()?;
+    let found = header.find_iter(b).last()?;
+    Some(&b[found.end()..])
+}
+
+/// Where object `number` starts, from a classic cross-reference table at the start of `b`.
+/// Cross-reference streams are compressed and not read.
+fn pdf_xref_offset(b: &[u8], number: u64) -> Option<u64> {
+    let text = String::from_utf8_lossy(b.strip_prefix(b"xref")?);
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    while let Some(line) = lines.next() {
+        let mut subsection = line.split_whitespace();
+        let first: u64 = subsection.next()?.parse().ok()?;
+        let count: u64 = subsection.next()?.parse().ok()?;
+        if (first..first.saturating_add(count)).contains(&number) {
+            let entry = lines.nth((number - first) as usize)?;
+            let mut fields = entry.split_whitespace();
+            let offset = fields.next()?.parse().ok()?;
+            return (fields.nth(1)? == "n").then_some(offset);
+        }
+        for _ in 0..count {
+            lines.next()?;
+        }
+    }
+    None
+}
+
+fn pdf_info(object: &[u8]) -> Embedded {
+    let entries = pdf_dictionary(object);
+    let get = |key: &str| {
+        entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+            .unwrap_or_default()
+    };
+    Embedded {
+        title: get("Title"),
+        creator: get("Author"),
+        date: iso_date(&get("CreationDate")),
+        description: get("Subject"),
+        keywords: split_keywords(&get("Keywords")),
+        other: vec![
+            ("Application".to_string(), get("Creator")),
+            ("Producer".to_string(), get("Producer")),
+        ],
+        ..Default::default()
+    }
+}
+
+/// docProps/core.xml from Office Open XML, or meta.xml from OpenDocument. Both use Dublin
+/// Core, so elements are matched by local name.
+fn office_properties(xml: &str) -> Option<Embedded> {
+    let doc = Document::parse(xml).ok()?;
+    let all = |name: &str| -> Vec<String> {
+        doc.descendants()
+            .filter(|n| n.is_element() && n.tag_name().name() == name)
+            .filter_map(|n| n.text())
+            .map(clean)
+            .filter(|v| !v.is_empty())
+            .collect()
+    };
+    let first = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| all(name).into_iter().next())
+            .unwrap_or_default()
+    };
+    Some(Embedded {
+        title: first(&["title"]),
+        // OpenDocument's dc:creator is whoever saved it last
+        creator: first(&["initial-creator", "creator"]),
+        date: iso_date(&first(&["created", "creation-date"])),
+        description: first(&["description"]),
+        keywords: ["keywords", "keyword"]
+            .iter()
+            .flat_map(|name| all(name))
+            .flat_map(|k| split_keywords(&k))
+            .collect(),
+        other: vec![
+            ("Subject".to_string(), first(&["subject"])),
+            ("Last modified by".to_string(), first(&["lastModifiedBy"])),
+        ],
+        ..Default::default()
+    })
+}
+
+/// The end of a file: the sample's tail when one was read, otherwise its last `TAIL_BYTES`.
+async fn tail(config: &Configuration, id: &str, sample: &Sample) -> Option<Vec<u8>> {
+    if !sample.end().is_empty() {
+        return Some(sample.end().to_vec());
+    }
+    match media::fetch_range(config, id, Some(format!("bytes=-{TAIL_BYTES}"))).await {
+        Ok((tail, _)) => Some(tail),
+        Err(e) => {
+            tracing::warn!("Could not read the end of file {}: {}", id, e);
+            None
+        }
+    }
+}
+
+/// The Info dictionary of a PDF, looked up through the cross-reference table when it is in
+/// neither the first nor the last bytes.
+async fn pdf(config: &Configuration, id: &str, head: &[u8], tail: &[u8]) -> Option<Embedded> {
+    let info = pdf_info_ref(tail).or(pdf_info_ref(head))?;
+    if let Some(object) = pdf_object(tail, info).or(pdf_object(head, info)) {
+        return Some(pdf_info(object));
+    }
+    let captures = STARTXREF.captures_iter(tail).last()?;
+    let xref: u64 = std::str::from_utf8(&captures[1]).ok()?.parse().ok()?;
+    // Each entry is 20 bytes, with room for subsection headers
+    let xref_len = info.0.saturating_mul(20).saturating_add(4096).min(MAX_XREF);
+    let range = format!("bytes={xref}-{}", xref.saturating_add(xref_len - 1));
+    let (table, _) = media::fetch_range(config, id, Some(range)).await.ok()?;
+    let offset = pdf_xref_offset(&table, info.0)?;
+    let range = format!("bytes={offset}-{}", offset.saturating_add(OBJECT_BYTES - 1));
+    let (object, _) = media::fetch_range(config, id, Some(range)).await.ok()?;
+    pdf_object(&object, info).map(pdf_info)
+}
+
+/// The properties file of an Office or OpenDocument file, read from the sample when the
+/// central directory and the entry are both in it.
+async fn office(config: &Configuration, id: &str, path: &str, sample: &Sample) -> Option<Embedded> {
+    let xml = match archive::zip_entry(&mut sample.reader(), path, MAX_PROPERTIES) {
+        Ok(xml) => Ok(xml),
+        Err(e) if sample.complete() => Err(e.to_string()),
+        Err(_) => {
+            archive::read_zip_entry(
+                config.clone(),
+                id.to_string(),
+                path.to_string(),
+                MAX_PROPERTIES,
+            )
+            .await
+        }
+    };
+    match xml {
+        Ok(xml) => office_properties(&String::from_utf8_lossy(&xml)),
+        Err(e) => {
+            tracing::warn!("Could not read {} from file {}: {}", path, id, e);
+            None
+        }
+    }
+}
+
+/// Read the descriptive metadata embedded in file `id`, called `name`, from the bytes
+/// sampled from it.
+///
+/// PDFs, MP3s and Office documents keep some or all of theirs away from the start, so those
+/// take a few more ranged downloads when the sample has no tail.
+pub(crate) async fn extract(
+    config: &Configuration,
+    id: &str,
+    name: &str,
+    sample: &Sample,
+) -> Embedded {
+    let head = sample.head.as_slice();
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    let mut found = Embedded::default();
+    if head.starts_with(b"%PDF") {
+        if let Some(tail) = tail(config, id, sample).await {
+            if let Some(xmp) = xmp(head).or(xmp(&tail)) {
+                found.merge(Source::Xmp, xmp);
+            }
+            if let Some(info) = pdf(config, id, head, &tail).await {
+                found.merge(Source::Pdf, info);
+            }
+        }
+    } else if head.starts_with(b"PK") && OOXML_EXTENSIONS.contains(&extension.as_str()) {
+        if let Some(properties) = office(config, id, "docProps/core.xml", sample).await {
+            found.merge(Source::Office, properties);
+        }
+    } else if head.starts_with(b"PK") && ODF_EXTENSIONS.contains(&extension.as_str()) {
+        if let Some(properties) = office(config, id, "meta.xml", sample).await {
+            found.merge(Source::Office, properties);
+        }
+    } else {
+        let comments = flac_comments(head)
+            .map(<[u8]>::to_vec)
+            .or_else(|| ogg_comments(head));
+        for (source, result) in [
+            (Source::Xmp, xmp(head)),
+            (Source::Exif, exif(head)),
+            (Source::Iptc, iptc(head)),
+            (Source::Id3, id3v2(head)),
+            (Source::Vorbis, comments.and_then(|c| vorbis_comments(&c))),
+        ] {
+            if let Some(result) = result {
+                found.merge(source, result);
+            }
+        }
+        if extension == "mp3"
+            && let Some(tail) = tail(config, id, sample).await
+            && let Some(id3) = id3v1(&tail)
+        {
+            found.merge(Source::Id3, id3);
+        }
+    }
+    found
+}
+
+/// Metadata found embedded in a project's files, keyed by Box file ID.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct EmbeddedStore {
+    pub items: HashMap<String, Embedded>,
+}
+
+impl EmbeddedStore {
+    pub fn get(&self, id: &str) -> Option<&Embedded> {
+        self.items.get(id)
+    }
+}
+
+pub(crate) async fn load(project: String) -> EmbeddedStore {
+    match persist::load_project_store::<EmbeddedStore>(&project, "embedded").await {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::debug!("No embedded metadata loaded for {}: {}", project, e);
+            EmbeddedStore::default()
+        }
+    }
+}
+
+pub(crate) fn save(project: String, store: EmbeddedStore) -> Task<Message> {
+    let snapshot = persist::snapshot();
+    Task::perform(
+        async move {
+            if let Err(e) =
+                persist::save_project_store(&store, &project, "embedded", snapshot).await
+            {
+                tracing::error!("Error saving embedded metadata for {}: {}", project, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An ID3v2.3 or v2.4 tag holding `frames`.
+    fn id3v2_tag(version: u8, frames: &[(&str, &[u8])]) -> Vec<u8> {
+        let size = |len: usize| -> [u8; 4] {
+            if version == 4 {
+                [21, 14, 7, 0].map(|shift| ((len >> shift) & 0x7F) as u8)
+            } else {
+                (len as u32).to_be_bytes()
+            }
+        };
+        let mut body = vec![];
+        for (id, frame) in frames {
+            body.extend_from_slice(id.as_bytes());
+            body.extend_from_slice(&size(frame.len()));
+            body.extend_from_slice(&[0, 0]);
+            body.extend_from_slice(frame);
+        }
+        let len = body.len();
+        let mut tag = vec![b'I', b'D', b'3', version, 0, 0];
+        tag.extend_from_slice(&[21, 14, 7, 0].map(|shift| ((len >> shift) & 0x7F) as u8));
+        tag.extend(body);
+        tag
+    }
+
+    /// An ID3v1.1 tag, padded with NULs.
+    fn id3v1_tag(title: &str, artist: &str, year: &str, comment: &str, track: u8) -> Vec<u8> {
+        let field = |s: &str, len: usize| {
+            let mut f = s.as_bytes().to_vec();
+            f.resize(len, 0);
+            f
+        };
+        let mut tag = b"TAG".to_vec();
+        tag.extend(field(title, 30));
+        tag.extend(field(artist, 30));
+        tag.extend(field("Album", 30));
+        tag.extend(field(year, 4));
+        tag.extend(field(comment, 28));
+        tag.extend([0, track, 12]);
+        tag
+    }
+
+    #[test]
+    fn id3v2_frames() {
+        let utf16_artist: &[u8] = &[1, 0xFF, 0xFE, b'A', 0, b'n', 0, b'n', 0];
+        let v3 = id3v2_tag(
+            3,
+            &[
+                ("TIT2", b"\0Harbour"),
+                ("TPE1", utf16_artist),
+                ("TYER", b"\x001968"),
+                ("COMM", b"\0engnote\0Field recording"),
+                ("TALB", b"\0Coast"),
+            ],
+        );
+        let v4 = id3v2_tag(
+            4,
+            &[("TIT2", b"\x03Harbour"), ("TDRC", b"\x032019-07-04T13:22")],
+        );
+        let cases: [(&[u8], &str, &str, &str, &str); 2] = [
+            (&v3, "Harbour", "Ann", "1968", "Field recording"),
+            (&v4, "Harbour", "", "2019-07-04", ""),
+        ];
+        for (tag, title, creator, date, description) in cases {
+            let found = id3v2(tag).unwrap();
+            assert_eq!(found.title, title);
+            assert_eq!(found.creator, creator);
+            assert_eq!(found.date, date);
+            assert_eq!(found.description, description);
+        }
+        assert_eq!(
+            id3v2(&v3).unwrap().other[0],
+            ("Album".into(), "Coast".into())
+        );
+    }
+
+    #[test]
+    fn id3v2_truncated_and_malformed() {
+        let whole = id3v2_tag(3, &[("TIT2", b"\0Harbour"), ("TPE1", b"\0Ann")]);
+        // Cut inside the second frame: the first is still read
+        let cut = id3v2(&whole[..whole.len() - 2]).unwrap();
+        assert_eq!(cut.title, "Harbour");
+        assert_eq!(cut.creator, "");
+        // Cut inside the first frame's header
+        assert_eq!(id3v2(&whole[..14]).unwrap().title, "");
+
+        let mut huge = whole.clone();
+        huge[14..18].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(id3v2(&huge).unwrap().title, "");
+
+        // An extended header flag with no room for the header
+        let mut extended = id3v2_tag(3, &[]);
+        extended[5] = 0x40;
+        assert!(id3v2(&extended).is_none());
+
+        for short in [
+            &b""[..],
+            b"ID3",
+            b"ID3\x03\0\0\0\0\0",
+            b"TAG\x03\0\0\0\0\0\0",
+        ] {
+            assert!(id3v2(short).is_none());
+        }
+    }
+
+    #[test]
+    fn id3v1_fields() {
+        let tag = id3v1_tag("Harbour", "Ann", "1968", "Field recording", 3);
+        let mut tail = vec![0xAA; 50];
+        tail.extend(&tag);
+        let found = id3v1(&tail).unwrap();
+        assert_eq!(found.title, "Harbour");
+        assert_eq!(found.creator, "Ann");
+        assert_eq!(found.date, "1968");
+        assert_eq!(found.description, "Field recording");
+        assert_eq!(found.other, [("Album".to_string(), "Album".to_string())]);
+
+        let blank_year = id3v1(&id3v1_tag("Harbour", "", "", "", 0)).unwrap();
+        assert_eq!(blank_year.date, "");
+
+        assert!(id3v1(&tag[1..]).is_none());
+        assert!(id3v1(&[0; 128]).is_none());
+        assert!(id3v1(b"TAG").is_none());
+    }
+
+    #[test]
+    fn iso_dates() {
+        for (input, expected) in [
+            ("2019:07:04 13:22:10", "2019-07-04"),
+            ("D:20190704132210+02'00'", "2019-07-04"),
+            ("20190704", "2019-07-04"),
+            ("2019-07-04T13:22:10Z", "2019-07-04"),
+            ("2019-07", "2019-07"),
+            ("D:2019", "2019"),
+            ("1968", "1968"),
+            (" 1968 ", "1968"),
+            ("1968-13-01", "1968"),
+            ("1968-02-32", "1968-02"),
+            ("0000:00:00 00:00:00", ""),
+            ("201", ""),
+            ("19680", "1968"),
+            ("circa 1968", ""),
+            ("", ""),
+        ] {
+            assert_eq!(iso_date(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn pdf_info_refs() {
+        for (trailer, expected) in [
+            (
+                &b"trailer\n<< /Size 5 /Root 1 0 R /Info 4 0 R >>"[..],
+                Some((4, 0)),
+            ),
+            (
+                b"/Info 2 0 R\n%%EOF\ntrailer << /Info 9 1 R >>",
+                Some((9, 1)),
+            ),
+            (b"trailer << /Info 4 R >>", None),
+            (b"trailer << /Info 99999999999999999999999 0 R >>", None),
+            (b"trailer << /Root 1 0 R >>", None),
+            (b"", None),
+        ] {
+            assert_eq!(pdf_info_ref(trailer), expected);
+        }
+    }
+
+    #[test]
+    fn pdf_info_objects() {
+        let file = b"14 0 obj\n<< /Title (Wrong) >>\nendobj\n\
+            4 0 obj\n<< /Title (Harbour \\(draft\\)) /Author <FEFF0041006E006E> \
+            /CreationDate (D:19680101) /Keywords (coast; boats) /Count 3 >>\nendobj\n";
+        let found = pdf_info(pdf_object(file, (4, 0)).unwrap());
+        assert_eq!(found.title, "Harbour (draft)");
+        assert_eq!(found.creator, "Ann");
+        assert_eq!(found.date, "1968-01-01");
+        assert_eq!(found.keywords, ["coast", "boats"]);
+
+        assert!(pdf_object(file, (4, 1)).is_none());
+        assert!(pdf_object(file, (5, 0)).is_none());
+
+        // Unterminated strings and dictionaries end at the end of the bytes
+        let truncated = pdf_info(b"<< /Title (Harb");
+        assert_eq!(truncated.title, "Harb");
+        let truncated = pdf_info(b"<< /Title <4861");
+        assert_eq!(truncated.title, "Ha");
+        assert!(pdf_info(b"no dictionary here").title.is_empty());
+    }
+
+    #[test]
+    fn pdf_xref_offsets() {
+        let table = b"xref\n0 5\n0000000000 65535 f \n0000000015 00000 n \n\
+            0000000079 00000 n \n0000000000 00001 f \n0000000200 00000 n \ntrailer";
+        let split = b"xref\n0 1\n0000000000 65535 f \n7 2\n0000000300 00000 n \n\
+            0000000400 00000 n \n";
+        for (xref, number, expected) in [
+            (&table[..], 1, Some(15)),
+            (table, 4, Some(200)),
+            (table, 3, None),
+            (table, 5, None),
+            (split, 8, Some(400)),
+            (split, 1, None),
+            (&table[..40], 4, None),
+            (b"xref\n18446744073709551615 5\n", 4, None),
+            (b"xref\nnot a table", 1, None),
+            (b"1 0 obj", 1, None),
+        ] {
+            assert_eq!(pdf_xref_offset(xref, number), expected);
+        }
+    }
+}
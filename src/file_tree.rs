@@ -13,17 +13,23 @@ use iced::{
     Length::{self, Fill},
     Task,
     advanced::{Widget, widget::Text},
+    keyboard::Modifiers,
     widget::{Button, Column, Row, Space, button, column, row, text},
 };
 use tracing::info;
 
-use crate::{Message, State, update};
+use crate::{Message, State, metadata::item_info, update};
 
 #[derive(Debug, Clone)]
 pub(crate) struct FileTreeState {
     pub current_folder: FolderFull, //FolderFull,
     pub parents: Vec<usize>,        //Vec<usize>
     pub contents: Vec<Item>,
+    /// Items picked for bulk editing, all from the current folder
+    pub selection: Vec<Item>,
+    /// Index into `contents` that shift-clicks extend from
+    pub anchor: Option<usize>,
+    pub modifiers: Modifiers,
 }
 
 impl Default for FileTreeState {
@@ -32,10 +38,25 @@ impl Default for FileTreeState {
             current_folder: FolderFull::default(), //FolderFull::default(),
             parents: vec![],
             contents: vec![],
+            selection: vec![],
+            anchor: None,
+            modifiers: Modifiers::default(),
         }
     }
 }
 
+impl FileTreeState {
+    pub fn is_selected(&self, item: &Item) -> bool {
+        let id = item_info(item).0;
+        self.selection.iter().any(|s| item_info(s).0 == id)
+    }
+
+    fn clear_selection(&mut self) {
+        self.selection.clear();
+        self.anchor = None;
+    }
+}
+
 #[derive(Debug, Clone)]
 
 pub(crate) enum FileTreeMessage {
@@ -45,6 +66,9 @@ pub(crate) enum FileTreeMessage {
     InitFolder(usize),
     Update,
     UpdateReceived(Items),
+    Click(usize),
+    SelectAll,
+    ModifiersChanged(Modifiers),
 }
 
 pub(crate) fn file_tree(state: &State) -> Element<Message> {
@@ -52,32 +76,39 @@ pub(crate) fn file_tree(state: &State) -> Element<Message> {
         .file_tree_state
         .contents
         .iter()
-        .fold(Column::new().spacing(8), |acc, x| {
+        .enumerate()
+        .fold(Column::new().spacing(8), |acc, (i, x)| {
+            let click = Message::FileTreeMessage(FileTreeMessage::Click(i));
             let b = match x {
-                file @ Item::FileFull(file_full) => Button::new(Text::new(
+                Item::FileFull(file_full) => Button::new(Text::new(
                     file_full
                         .name
                         .clone()
                         .unwrap_or(format!("!!UNNAMED FILE - ID {}", file_full.id)),
                 ))
                 .width(Length::Fill)
-                .on_press(Message::Select(file.clone())),
-                folder @ Item::FolderMini(folder_mini) => Button::new(Text::new(
+                .on_press(click),
+                Item::FolderMini(folder_mini) => Button::new(Text::new(
                     folder_mini
                         .name
                         .clone()
                         .unwrap_or(format!("!!UNNAMED FOLDER - ID {}", folder_mini.id)),
                 ))
                 .width(Length::Fill)
-                .on_press(Message::Select(folder.clone())),
-                link @ Item::WebLink(web_link) => Button::new(Text::new(
+                .on_press(click),
+                Item::WebLink(web_link) => Button::new(Text::new(
                     web_link
                         .name
                         .clone()
                         .unwrap_or(format!("!!UNNAMED WEB LINK - ID {}", web_link.id)),
                 ))
                 .width(Length::Fill)
-                .on_press(Message::Select(link.clone())),
+                .on_press(click),
+            };
+            let b = if state.file_tree_state.is_selected(x) {
+                b.style(button::success)
+            } else {
+                b
             };
 
             acc.push(
@@ -94,6 +125,16 @@ pub(crate) fn file_tree(state: &State) -> Element<Message> {
                         None
                     })
                     .push(b)
+                    .push(if let Item::FileFull(f) = x {
+                        state
+                            .classifications
+                            .labels
+                            .get(&f.id)
+                            .map(|label| text(label.as_str()).size(12).style(text::danger))
+                    } else {
+                        None
+                    })
+                    .align_y(Center)
                     .width(Length::Fill)
                     .spacing(5),
             )
@@ -122,6 +163,10 @@ pub(crate) fn title_bar(state: &State) -> Element<Message> {
             .align_y(Center)
             .width(Length::Shrink),
         Space::new().width(Fill),
+        button("Select all")
+            .height(Length::Fill)
+            .on_press(Message::FileTreeMessage(FileTreeMessage::SelectAll)),
+        Space::new().width(5),
         button("⟳")
             .height(Length::Fill)
             .on_press(Message::FileTreeMessage(FileTreeMessage::Update)),
@@ -154,8 +199,46 @@ pub(crate) fn file_tree_handle(state: &mut State, event: FileTreeMessage) -> Tas
         }
         FileTreeMessage::UpdateReceived(items) => {
             state.file_tree_state.contents = items.entries.unwrap_or(vec![]);
+            state.file_tree_state.clear_selection();
             Task::none()
         }
+        FileTreeMessage::ModifiersChanged(modifiers) => {
+            state.file_tree_state.modifiers = modifiers;
+            Task::none()
+        }
+        FileTreeMessage::SelectAll => {
+            let tree = &mut state.file_tree_state;
+            tree.selection = tree.contents.clone();
+            tree.anchor = None;
+            Task::none()
+        }
+        FileTreeMessage::Click(i) => {
+            let tree = &mut state.file_tree_state;
+            let Some(item) = tree.contents.get(i).cloned() else {
+                return Task::none();
+            };
+            match tree.anchor {
+                Some(anchor) if tree.modifiers.shift() => {
+                    let end = anchor.max(i).min(tree.contents.len() - 1);
+                    tree.selection = tree.contents[anchor.min(i)..=end].to_vec();
+                }
+                _ if tree.modifiers.command() => {
+                    if tree.is_selected(&item) {
+                        let id = item_info(&item).0;
+                        tree.selection.retain(|s| item_info(s).0 != id);
+                    } else {
+                        tree.selection.push(item.clone());
+                    }
+                    tree.anchor = Some(i);
+                }
+                _ => {
+                    tree.selection = vec![item.clone()];
+                    tree.anchor = Some(i);
+                }
+            }
+            // The clicked item is the one shown in the data entry pane
+            update(state, Message::Select(item))
+        }
     }
 }
 
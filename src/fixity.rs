@@ -0,0 +1,536 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use r#box::apis::{
+    configuration::Configuration,
+    downloads_api::{GetFilesIdContentParams, get_files_id_content},
+    files_api::{GetFilesIdParams, get_files_id},
+};
+use iced::{
+    Element,
+    Length::Fill,
+    Padding, Task,
+    futures::{StreamExt, stream},
+    widget::{Column, button, column, row, scrollable, text},
+};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    Message, State, persist,
+    project::Run,
+    project_page::{InternalType, Node},
+    sheet_plan,
+    subwindows::Subwindow,
+};
+
+/// Files downloaded and hashed at the same time.
+const CONCURRENT_FILES: usize = 4;
+
+/// A pass saves what it has recorded every this many files, so a long one that is cut short
+/// isn't lost.
+const SAVE_EVERY: usize = 50;
+
+fn hex(bytes: impl IntoIterator<Item = u8>) -> String {
+    bytes.into_iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn date(secs: u64) -> String {
+    chrono::DateTime::from_timestamp(secs as i64, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+/// Checksums of a file's whole content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Checksums {
+    pub size: u64,
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+/// The checksums of a file, updated as its content streams in.
+#[derive(Default)]
+struct Hashers {
+    size: u64,
+    md5: Md5,
+    sha1: Sha1,
+    sha256: Sha256,
+}
+
+impl Hashers {
+    fn update(&mut self, data: &[u8]) {
+        self.size += data.len() as u64;
+        self.md5.update(data);
+        self.sha1.update(data);
+        self.sha256.update(data);
+    }
+
+    fn finish(self) -> Checksums {
+        Checksums {
+            size: self.size,
+            md5: hex(self.md5.finalize()),
+            sha1: hex(self.sha1.finalize()),
+            sha256: hex(self.sha256.finalize()),
+        }
+    }
+}
+
+/// What a pass read for one file: its checksums and the SHA-1 Box reported for it.
+#[derive(Debug, Clone)]
+pub(crate) struct Hashed {
+    checksums: Checksums,
+    box_sha1: Option<String>,
+}
+
+/// The checksums recorded for a file, with when they were calculated and last checked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FixityRecord {
+    pub name: String,
+    #[serde(flatten)]
+    pub checksums: Checksums,
+    pub box_sha1: Option<String>,
+    /// Seconds since the Unix epoch
+    pub calculated: u64,
+    pub verified: Option<u64>,
+}
+
+/// Recorded checksums by Box file ID.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct FixityStore {
+    pub records: HashMap<String, FixityRecord>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Pass {
+    Calculate,
+    Verify,
+}
+
+#[derive(Debug, Clone)]
+enum Outcome {
+    Recorded,
+    /// Box reports a different SHA-1 from the one calculated
+    BoxMismatch,
+    Unchanged,
+    /// Names of the values that changed since they were recorded
+    Drift(Vec<&'static str>),
+    NotRecorded,
+    Failed(String),
+}
+
+impl Outcome {
+    fn is_problem(&self) -> bool {
+        !matches!(self, Outcome::Recorded | Outcome::Unchanged)
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Outcome::Recorded => "Checksums recorded".to_string(),
+            Outcome::BoxMismatch => "SHA-1 differs from the one Box reports".to_string(),
+            Outcome::Unchanged => "Unchanged".to_string(),
+            Outcome::Drift(changed) => format!("Changed: {}", changed.join(", ")),
+            Outcome::NotRecorded => "No checksums recorded yet".to_string(),
+            Outcome::Failed(e) => format!("Could not be read: {e}"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct FixityState {
+    run: Run,
+    pass: Option<Pass>,
+    total: usize,
+    results: Vec<(String, Outcome)>,
+    /// When the last pass finished
+    finished: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum FixityMessage {
+    Loaded(FixityStore),
+    Run(Pass),
+    /// A file hashed during run `u64`, by ID and name
+    Hashed(u64, Pass, String, String, Result<Hashed, String>),
+    Export,
+}
+
+pub(crate) async fn load(project: String) -> FixityStore {
+    match persist::load_project_store::<FixityStore>(&project, "fixity").await {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::debug!("No checksums loaded for {}: {}", project, e);
+            FixityStore::default()
+        }
+    }
+}
+
+fn save(project: String, store: FixityStore) -> Task<Message> {
+    let number = persist::snapshot();
+    Task::perform(
+        async move {
+            if let Err(e) = persist::save_project_store(&store, &project, "fixity", number).await {
+                tracing::error!("Error saving checksums for {}: {}", project, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+/// Stream file `id` from Box and hash it, and ask Box for its SHA-1.
+async fn hash(config: Configuration, id: String) -> Result<Hashed, String> {
+    let info = get_files_id(
+        &config,
+        GetFilesIdParams {
+            file_id: id.clone(),
+            fields: Some(vec!["sha1".to_string()]),
+            if_none_match: None,
+            boxapi: None,
+            x_rep_hints: None,
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let resp = get_files_id_content(
+        &config,
+        GetFilesIdContentParams {
+            file_id: id,
+            range: None,
+            boxapi: None,
+            version: None,
+            access_token: None,
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut hashers = Hashers::default();
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        hashers.update(&chunk.map_err(|e| e.to_string())?);
+    }
+    Ok(Hashed {
+        checksums: hashers.finish(),
+        box_sha1: info.sha1.map(|s| s.to_ascii_lowercase()),
+    })
+}
+
+/// Every Box file in the project tree, as (ID, name).
+fn files(tree: &Node) -> Vec<(String, String)> {
+    sheet_plan::flatten(tree)
+        .into_iter()
+        .filter(|item| item.file_type == InternalType::File)
+        .map(|item| (item.id, item.name))
+        .collect()
+}
+
+pub(crate) fn fixity_handle(state: &mut State, event: FixityMessage) -> Task<Message> {
+    match event {
+        FixityMessage::Loaded(store) => state.fixity = store,
+        FixityMessage::Run(pass) => {
+            if state.fixity_state.pass.is_some() {
+                return Task::none();
+            }
+            let Some(tree) = &state.project_tree else {
+                tracing::warn!("The project tree is not loaded, so there are no files to check");
+                return Task::none();
+            };
+            let files = files(tree);
+            tracing::info!(
+                "{} {} files",
+                match pass {
+                    Pass::Calculate => "Calculating checksums of",
+                    Pass::Verify => "Verifying the fixity of",
+                },
+                files.len()
+            );
+            state.fixity_state = FixityState {
+                pass: Some(pass),
+                total: files.len(),
+                ..Default::default()
+            };
+            if files.is_empty() {
+                state.fixity_state.pass = None;
+                return Task::none();
+            }
+            let run = state.fixity_state.run.id();
+            let config = state.box_config.clone();
+            let hashes = stream::iter(files)
+                .map(move |(id, name)| {
+                    let config = config.clone();
+                    async move {
+                        let result = hash(config, id.clone()).await;
+                        (id, name, result)
+                    }
+                })
+                .buffer_unordered(CONCURRENT_FILES);
+            return state
+                .fixity_state
+                .run
+                .track(Task::run(hashes, move |(id, name, result)| {
+                    Message::FixityMessage(FixityMessage::Hashed(run, pass, id, name, result))
+                }));
+        }
+        FixityMessage::Hashed(run, pass, id, name, result) => {
+            if !state.fixity_state.run.is(run) {
+                return Task::none();
+            }
+            let outcome = match (pass, result) {
+                (_, Err(e)) => {
+                    tracing::warn!("Could not hash {}: {}", name, e);
+                    Outcome::Failed(e)
+                }
+                (Pass::Calculate, Ok(hashed)) => {
+                    let mismatch = hashed
+                        .box_sha1
+                        .as_ref()
+                        .is_some_and(|s| *s != hashed.checksums.sha1);
+                    state.fixity.records.insert(
+                        id,
+                        FixityRecord {
+                            name: name.clone(),
+                            checksums: hashed.checksums,
+                            box_sha1: hashed.box_sha1,
+                            calculated: now(),
+                            verified: None,
+                        },
+                    );
+                    if mismatch {
+                        Outcome::BoxMismatch
+                    } else {
+                        Outcome::Recorded
+                    }
+                }
+                (Pass::Verify, Ok(hashed)) => match state.fixity.records.get_mut(&id) {
+                    None => Outcome::NotRecorded,
+                    Some(record) => {
+                        let (old, new) = (&record.checksums, &hashed.checksums);
+                        let changed: Vec<&'static str> = [
+                            ("size", old.size != new.size),
+                            ("MD5", old.md5 != new.md5),
+                            ("SHA-1", old.sha1 != new.sha1),
+                            ("SHA-256", old.sha256 != new.sha256),
+                            ("Box SHA-1", record.box_sha1 != hashed.box_sha1),
+                        ]
+                        .into_iter()
+                        .filter_map(|(label, differs)| differs.then_some(label))
+                        .collect();
+                        if changed.is_empty() {
+                            record.verified = Some(now());
+                            Outcome::Unchanged
+                        } else {
+                            tracing::warn!(
+                                "{} has changed since its checksums were recorded",
+                                name
+                            );
+                            Outcome::Drift(changed)
+                        }
+                    }
+                },
+            };
+            let run = &mut state.fixity_state;
+            run.results.push((name, outcome));
+            let done = run.results.len() == run.total;
+            if done {
+                run.pass = None;
+                run.finished = Some(now());
+                let problems = run.results.iter().filter(|(_, o)| o.is_problem()).count();
+                tracing::info!(
+                    "Checked {} files, {} need attention",
+                    run.results.len(),
+                    problems
+                );
+            }
+            if let Some(project) = &state.project
+                && (done || run.results.len().is_multiple_of(SAVE_EVERY))
+            {
+                return save(project.name.clone(), state.fixity.clone());
+            }
+        }
+        FixityMessage::Export => {
+            let Some(project) = &state.project else {
+                return Task::none();
+            };
+            let name = project.name.clone();
+            let store = state.fixity.clone();
+            return Task::perform(export(name, store), |_| Message::None);
+        }
+    }
+    Task::none()
+}
+
+/// Write the recorded checksums to a CSV file the user picks.
+async fn export(project: String, store: FixityStore) {
+    let Some(file) = rfd::AsyncFileDialog::new()
+        .add_filter("CSV", &["csv"])
+        .set_file_name(format!("{project}_checksums.csv"))
+        .save_file()
+        .await
+    else {
+        return;
+    };
+    let mut records: Vec<_> = store.records.iter().collect();
+    records.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let _ = writer.write_record([
+        "Box ID",
+        "Name",
+        "Size",
+        "MD5",
+        "SHA-1",
+        "SHA-256",
+        "Box SHA-1",
+        "Calculated",
+        "Last verified",
+    ]);
+    for (id, r) in records {
+        let _ = writer.write_record([
+            id.as_str(),
+            &r.name,
+            &r.checksums.size.to_string(),
+            &r.checksums.md5,
+            &r.checksums.sha1,
+            &r.checksums.sha256,
+            r.box_sha1.as_deref().unwrap_or_default(),
+            &date(r.calculated),
+            &r.verified.map(date).unwrap_or_default(),
+        ]);
+    }
+    let result = match writer.into_inner() {
+        Ok(bytes) => tokio::fs::write(file.path(), bytes)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match result {
+        Ok(()) => tracing::info!("Exported checksums for {}", project),
+        Err(e) => tracing::error!("Failed to export checksums: {}", e),
+    }
+}
+
+pub(crate) fn fixity_report(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::Fixity));
+    let run = &state.fixity_state;
+    let idle = run.pass.is_none();
+    let header = row![
+        button("Calculate checksums").on_press_maybe(
+            idle.then_some(Message::FixityMessage(FixityMessage::Run(Pass::Calculate)))
+        ),
+        button("Verify fixity").on_press_maybe(
+            (idle && !state.fixity.records.is_empty())
+                .then_some(Message::FixityMessage(FixityMessage::Run(Pass::Verify)))
+        ),
+        button("Export checksums").on_press_maybe(
+            (!state.fixity.records.is_empty())
+                .then_some(Message::FixityMessage(FixityMessage::Export))
+        ),
+    ]
+    .spacing(10);
+
+    let status = match (run.pass, run.finished) {
+        (Some(_), _) => format!("Checked {} of {} files", run.results.len(), run.total),
+        (None, Some(finished)) => format!(
+            "Checked {} files, finished {}",
+            run.results.len(),
+            date(finished)
+        ),
+        (None, None) => format!(
+            "Checksums recorded for {} files",
+            state.fixity.records.len()
+        ),
+    };
+
+    let problems: Vec<_> = run
+        .results
+        .iter()
+        .filter(|(_, outcome)| outcome.is_problem())
+        .collect();
+    let body: Element<Message> = if run.results.is_empty() {
+        text("Calculate checksums to record them, then verify later to detect changes").into()
+    } else if problems.is_empty() {
+        text("No problems found").into()
+    } else {
+        scrollable(
+            problems
+                .iter()
+                .fold(Column::new().spacing(8), |col, (name, outcome)| {
+                    col.push(column![
+                        text(name.clone()),
+                        text(format!("    {}", outcome.describe()))
+                            .size(12)
+                            .style(text::danger),
+                    ])
+                }),
+        )
+        .height(Fill)
+        .into()
+    };
+
+    column![text("Fixity").size(20), header, text(status), body, close]
+        .padding(Padding::new(15.0))
+        .spacing(15.0)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checksums(chunks: &[&[u8]]) -> Checksums {
+        let mut hashers = Hashers::default();
+        for chunk in chunks {
+            hashers.update(chunk);
+        }
+        hashers.finish()
+    }
+
+    #[test]
+    fn empty_file() {
+        let sums = checksums(&[]);
+        assert_eq!(sums.size, 0);
+        assert_eq!(sums.md5, "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(sums.sha1, "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            sums.sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn abc() {
+        let sums = checksums(&[b"abc"]);
+        assert_eq!(sums.md5, "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(sums.sha1, "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(
+            sums.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn chunks_hash_like_the_whole() {
+        let text = b"The quick brown fox jumps over the lazy dog";
+        let whole = checksums(&[text]);
+        assert_eq!(whole, checksums(&[&text[..7], &text[7..40], &text[40..]]));
+        assert_eq!(whole.size, 43);
+        assert_eq!(whole.md5, "9e107d9d372bb6826bd81d3542a419d6");
+        assert_eq!(whole.sha1, "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12");
+    }
+}
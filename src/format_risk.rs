@@ -0,0 +1,740 @@
+use std::{collections::HashMap, fmt};
+
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    widget::{Column, Space, TextInput, button, checkbox, column, row, scrollable, text},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Message, State, archive::size_text, data_entry::DataEntryMessage, embedded::Embedded,
+    integrity::FileIssue, persist, program_settings, sheet_plan, subwindows::Subwindow, update,
+};
+
+/// MIME types that say nothing about the format.
+const GENERIC_MIME: [&str; 2] = ["", "application/octet-stream"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) enum Risk {
+    Obsolete,
+    Proprietary,
+    Unknown,
+    Encrypted,
+    PasswordProtected,
+}
+
+impl Risk {
+    pub const ALL: [Risk; 5] = [
+        Risk::Obsolete,
+        Risk::Proprietary,
+        Risk::Unknown,
+        Risk::Encrypted,
+        Risk::PasswordProtected,
+    ];
+}
+
+impl fmt::Display for Risk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Risk::Obsolete => "Obsolete",
+            Risk::Proprietary => "Proprietary",
+            Risk::Unknown => "Unknown",
+            Risk::Encrypted => "Encrypted",
+            Risk::PasswordProtected => "Password protected",
+        })
+    }
+}
+
+/// A format in the local risk registry, by PUID or MIME type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RiskEntry {
+    /// A PUID such as "fmt/40", or a MIME type. A MIME type ending in `*` matches by prefix.
+    pub format: String,
+    pub risks: Vec<Risk>,
+    pub note: String,
+}
+
+impl RiskEntry {
+    fn new(format: &str, risks: &[Risk], note: &str) -> Self {
+        Self {
+            format: format.to_string(),
+            risks: risks.to_vec(),
+            note: note.to_string(),
+        }
+    }
+
+    fn matches(&self, puid: &str, mime: &str) -> bool {
+        let format = self.format.trim();
+        match format.strip_suffix('*') {
+            _ if format.is_empty() => false,
+            Some(prefix) => mime
+                .to_ascii_lowercase()
+                .starts_with(&prefix.to_ascii_lowercase()),
+            None => format.eq_ignore_ascii_case(puid) || format.eq_ignore_ascii_case(mime),
+        }
+    }
+}
+
+/// The formats this institution considers at risk, configured in Program Settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct RiskRegistry {
+    pub entries: Vec<RiskEntry>,
+}
+
+impl Default for RiskRegistry {
+    fn default() -> Self {
+        use Risk::*;
+        Self {
+            entries: vec![
+                RiskEntry::new(
+                    "fmt/494",
+                    &[Encrypted],
+                    "Microsoft Office Encrypted Document",
+                ),
+                RiskEntry::new("fmt/40", &[Proprietary], "Word 97-2003"),
+                RiskEntry::new("fmt/61", &[Proprietary], "Excel 97"),
+                RiskEntry::new("fmt/126", &[Proprietary], "PowerPoint 97-2003"),
+                RiskEntry::new(
+                    "application/x-shockwave-flash",
+                    &[Obsolete, Proprietary],
+                    "Flash",
+                ),
+                RiskEntry::new(
+                    "application/vnd.rn-realmedia",
+                    &[Obsolete, Proprietary],
+                    "RealMedia",
+                ),
+                RiskEntry::new(
+                    "application/vnd.wordperfect",
+                    &[Obsolete, Proprietary],
+                    "WordPerfect",
+                ),
+                RiskEntry::new("application/x-msaccess", &[Proprietary], "Access database"),
+            ],
+        }
+    }
+}
+
+impl RiskRegistry {
+    /// Every risk the registry lists for a format, and the notes of the entries that matched.
+    fn assess(&self, puid: &str, mime: &str) -> (Vec<Risk>, Vec<String>) {
+        let mut risks = vec![];
+        let mut notes = vec![];
+        for entry in self.entries.iter().filter(|e| e.matches(puid, mime)) {
+            for risk in &entry.risks {
+                if !risks.contains(risk) {
+                    risks.push(*risk);
+                }
+            }
+            if !entry.note.is_empty() {
+                notes.push(entry.note.clone());
+            }
+        }
+        if puid.is_empty() && GENERIC_MIME.contains(&mime) && !risks.contains(&Risk::Unknown) {
+            risks.push(Risk::Unknown);
+        }
+        risks.sort_by_key(|r| Risk::ALL.iter().position(|a| a == r));
+        (risks, notes)
+    }
+}
+
+/// What a file was identified as when the project was last scanned.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct FileFormat {
+    pub name: String,
+    pub size: u64,
+    /// Empty when PRONOM identification did not run or found nothing
+    pub puid: String,
+    /// PRONOM format name and version
+    pub format: String,
+    pub mime: String,
+    /// Why the file may not open, if anything
+    #[serde(default)]
+    pub issues: Vec<FileIssue>,
+    #[serde(default)]
+    pub web_link: String,
+}
+
+/// Identified formats of a project's files, by Box file ID.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct FormatStore {
+    pub files: HashMap<String, FileFormat>,
+}
+
+/// One format in a project, with how much of the project is in it.
+#[derive(Debug, Clone)]
+pub(crate) struct FormatRow {
+    pub puid: String,
+    pub mime: String,
+    pub label: String,
+    pub files: usize,
+    pub bytes: u64,
+    pub risks: Vec<Risk>,
+    pub notes: Vec<String>,
+}
+
+/// The project's files grouped by PUID, or by MIME type where there is none, largest first.
+fn distribution(store: &FormatStore, registry: &RiskRegistry) -> Vec<FormatRow> {
+    let mut rows: HashMap<(String, String), FormatRow> = HashMap::new();
+    // Files that could not be downloaded were not identified
+    let readable = store
+        .files
+        .values()
+        .filter(|f| !f.issues.contains(&FileIssue::Unreadable));
+    for file in readable {
+        let mime = if GENERIC_MIME.contains(&file.mime.as_str()) {
+            ""
+        } else {
+            file.mime.as_str()
+        };
+        let key = if file.puid.is_empty() {
+            (String::new(), mime.to_string())
+        } else {
+            (file.puid.clone(), String::new())
+        };
+        let row = rows.entry(key).or_insert_with(|| {
+            let (risks, notes) = registry.assess(&file.puid, mime);
+            FormatRow {
+                puid: file.puid.clone(),
+                mime: mime.to_string(),
+                label: [file.format.as_str(), mime]
+                    .into_iter()
+                    .find(|l| !l.is_empty())
+                    .unwrap_or("Unidentified")
+                    .to_string(),
+                files: 0,
+                bytes: 0,
+                risks,
+                notes,
+            }
+        });
+        row.files += 1;
+        row.bytes += file.size;
+        if row.mime.is_empty() && !mime.is_empty() {
+            row.mime = mime.to_string();
+        }
+        for risk in file.issues.iter().filter_map(FileIssue::risk) {
+            if !row.risks.contains(&risk) {
+                row.risks.push(risk);
+            }
+        }
+    }
+    let mut rows: Vec<FormatRow> = rows.into_values().collect();
+    for row in &mut rows {
+        row.risks
+            .sort_by_key(|r| Risk::ALL.iter().position(|a| a == r));
+    }
+    rows.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.label.cmp(&b.label)));
+    rows
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct FormatRiskState {
+    scanning: bool,
+    at_risk_only: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum FormatRiskMessage {
+    Loaded(FormatStore),
+    /// Formats identified while planning a sheet or scanning, by item ID
+    Read(HashMap<String, FileFormat>),
+    Identify,
+    Identified(HashMap<String, FileFormat>, HashMap<String, Embedded>),
+    AtRiskOnly(bool),
+    Export,
+    SetFormat(usize, String),
+    SetRisk(usize, Risk, bool),
+    SetNote(usize, String),
+    AddEntry,
+    RemoveEntry(usize),
+    ResetRegistry,
+}
+
+pub(crate) async fn load(project: String) -> FormatStore {
+    match persist::load_project_store::<FormatStore>(&project, "formats").await {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::debug!("No identified formats loaded for {}: {}", project, e);
+            FormatStore::default()
+        }
+    }
+}
+
+fn save(project: String, store: FormatStore) -> Task<Message> {
+    let snapshot = persist::snapshot();
+    Task::perform(
+        async move {
+            if let Err(e) = persist::save_project_store(&store, &project, "formats", snapshot).await
+            {
+                tracing::error!("Error saving identified formats for {}: {}", project, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+pub(crate) fn format_risk_handle(state: &mut State, event: FormatRiskMessage) -> Task<Message> {
+    let registry = &mut state.program_set_state.risk_registry;
+    match event {
+        FormatRiskMessage::Loaded(store) => {
+            state.formats = store;
+            return Task::none();
+        }
+        FormatRiskMessage::Read(formats) => {
+            if formats.is_empty() {
+                return Task::none();
+            }
+            state.formats.files.extend(formats);
+            return match &state.project {
+                Some(project) => save(project.name.clone(), state.formats.clone()),
+                None => Task::none(),
+            };
+        }
+        FormatRiskMessage::Identify => {
+            if state.format_risk_state.scanning {
+                return Task::none();
+            }
+            let Some(tree) = state.project_tree.clone() else {
+                tracing::warn!("The project tree is not loaded, so there are no files to identify");
+                return Task::none();
+            };
+            state.format_risk_state.scanning = true;
+            tracing::info!("Identifying the formats of the project's files");
+            return Task::perform(
+                sheet_plan::identify_formats(
+                    state.box_config.clone(),
+                    tree,
+                    state.program_set_state.droid_signatures.clone(),
+                    state.program_set_state.sampling.clone(),
+                ),
+                |(formats, embedded)| {
+                    Message::FormatRiskMessage(FormatRiskMessage::Identified(formats, embedded))
+                },
+            );
+        }
+        FormatRiskMessage::Identified(formats, embedded) => {
+            state.format_risk_state.scanning = false;
+            tracing::info!("Identified the formats of {} files", formats.len());
+            // A fresh scan replaces the last one, so removed files drop out of the report
+            state.formats.files.clear();
+            return Task::batch([
+                update(
+                    state,
+                    Message::FormatRiskMessage(FormatRiskMessage::Read(formats)),
+                ),
+                update(
+                    state,
+                    Message::DataEntryMessage(DataEntryMessage::EmbeddedRead(embedded)),
+                ),
+            ]);
+        }
+        FormatRiskMessage::AtRiskOnly(only) => {
+            state.format_risk_state.at_risk_only = only;
+            return Task::none();
+        }
+        FormatRiskMessage::Export => {
+            let Some(project) = &state.project else {
+                return Task::none();
+            };
+            let rows = distribution(&state.formats, registry);
+            return Task::perform(export(project.name.clone(), rows), |_| Message::None);
+        }
+        FormatRiskMessage::SetFormat(i, format) => match registry.entries.get_mut(i) {
+            Some(entry) => entry.format = format,
+            None => return Task::none(),
+        },
+        FormatRiskMessage::SetRisk(i, risk, set) => match registry.entries.get_mut(i) {
+            Some(entry) => {
+                entry.risks.retain(|r| *r != risk);
+                if set {
+                    entry.risks.push(risk);
+                }
+            }
+            None => return Task::none(),
+        },
+        FormatRiskMessage::SetNote(i, note) => match registry.entries.get_mut(i) {
+            Some(entry) => entry.note = note,
+            None => return Task::none(),
+        },
+        FormatRiskMessage::AddEntry => registry.entries.push(RiskEntry::new("", &[], "")),
+        FormatRiskMessage::RemoveEntry(i) => {
+            if i < registry.entries.len() {
+                registry.entries.remove(i);
+            }
+        }
+        FormatRiskMessage::ResetRegistry => {
+            tracing::info!("Reset the format risk registry to the defaults");
+            *registry = RiskRegistry::default();
+        }
+    }
+    // Only registry edits get this far
+    program_settings::save(state.program_set_state.clone())
+}
+
+/// Write the format distribution to a CSV file the user picks.
+async fn export(project: String, rows: Vec<FormatRow>) {
+    let Some(file) = rfd::AsyncFileDialog::new()
+        .add_filter("CSV", &["csv"])
+        .set_file_name(format!("{project}_formats.csv"))
+        .save_file()
+        .await
+    else {
+        return;
+    };
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let _ = writer.write_record([
+        "PUID",
+        "MIME type",
+        "Format",
+        "Files",
+        "Bytes",
+        "Risks",
+        "Notes",
+    ]);
+    for r in rows {
+        let risks: Vec<String> = r.risks.iter().map(Risk::to_string).collect();
+        let _ = writer.write_record([
+            r.puid.as_str(),
+            &r.mime,
+            &r.label,
+            &r.files.to_string(),
+            &r.bytes.to_string(),
+            &risks.join("; "),
+            &r.notes.join("; "),
+        ]);
+    }
+    let result = match writer.into_inner() {
+        Ok(bytes) => tokio::fs::write(file.path(), bytes)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match result {
+        Ok(()) => tracing::info!("Exported the format report for {}", project),
+        Err(e) => tracing::error!("Failed to export the format report: {}", e),
+    }
+}
+
+fn risk_text(risks: &[Risk]) -> String {
+    risks
+        .iter()
+        .map(Risk::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(crate) fn format_risks(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::FormatRisks));
+    let scan = &state.format_risk_state;
+    let rows = distribution(&state.formats, &state.program_set_state.risk_registry);
+
+    let header = row![
+        button("Identify formats").on_press_maybe(
+            (!scan.scanning).then_some(Message::FormatRiskMessage(FormatRiskMessage::Identify))
+        ),
+        button("Export CSV").on_press_maybe(
+            (!rows.is_empty()).then_some(Message::FormatRiskMessage(FormatRiskMessage::Export))
+        ),
+        button("Risk registry").on_press(Message::OpenWindow(Subwindow::RiskRegistry)),
+        button("File issues").on_press(Message::OpenWindow(Subwindow::FileIssues)),
+        Space::new().width(Fill),
+        checkbox(scan.at_risk_only)
+            .label("Only formats at risk")
+            .on_toggle(|only| Message::FormatRiskMessage(FormatRiskMessage::AtRiskOnly(only))),
+    ]
+    .spacing(10)
+    .align_y(Center);
+
+    let total = |rows: &[&FormatRow]| {
+        (
+            rows.iter().map(|r| r.files).sum::<usize>(),
+            rows.iter().map(|r| r.bytes).sum::<u64>(),
+        )
+    };
+    let all: Vec<&FormatRow> = rows.iter().collect();
+    let at_risk: Vec<&FormatRow> = rows.iter().filter(|r| !r.risks.is_empty()).collect();
+    let (files, bytes) = total(&all);
+    let (risky_files, risky_bytes) = total(&at_risk);
+    let status = if scan.scanning {
+        "Identifying formats...".to_string()
+    } else {
+        format!(
+            "{files} files, {} in {} formats. {risky_files} files, {} in {} formats at risk.",
+            size_text(bytes),
+            rows.len(),
+            size_text(risky_bytes),
+            at_risk.len()
+        )
+    };
+    let by_risk = Risk::ALL.iter().fold(row![].spacing(15), |r, risk| {
+        let with: Vec<&FormatRow> = rows.iter().filter(|f| f.risks.contains(risk)).collect();
+        let (files, bytes) = total(&with);
+        r.push(text(format!("{risk}: {files} files, {}", size_text(bytes))).size(12))
+    });
+
+    let table_row = |format: String, id: String, files: String, size: String, risks: String| {
+        row![
+            text(format).width(Fill),
+            text(id).width(180),
+            text(files).width(60),
+            text(size).width(80),
+            text(risks).width(180).style(text::danger),
+        ]
+        .spacing(10)
+    };
+    let body: Element<Message> = if rows.is_empty() {
+        text("Identify formats, or preview the sheet, to see the project's formats").into()
+    } else {
+        let shown = if scan.at_risk_only { &at_risk } else { &all };
+        scrollable(shown.iter().fold(Column::new().spacing(5), |col, r| {
+            let id = if r.puid.is_empty() { &r.mime } else { &r.puid };
+            let col = col.push(table_row(
+                r.label.clone(),
+                id.clone(),
+                r.files.to_string(),
+                size_text(r.bytes),
+                risk_text(&r.risks),
+            ));
+            if r.notes.is_empty() {
+                col
+            } else {
+                col.push(
+                    text(format!("    {}", r.notes.join("; ")))
+                        .size(12)
+                        .style(text::secondary),
+                )
+            }
+        }))
+        .height(Fill)
+        .into()
+    };
+
+    column![
+        text("Format risks").size(20),
+        header,
+        text(status),
+        by_risk,
+        table_row(
+            "Format".to_string(),
+            "PUID or MIME type".to_string(),
+            "Files".to_string(),
+            "Size".to_string(),
+            "Risks".to_string(),
+        ),
+        body,
+        close
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
+
+pub(crate) fn risk_registry(state: &State) -> Element<'_, Message> {
+    let registry = &state.program_set_state.risk_registry;
+    let entries =
+        registry
+            .entries
+            .iter()
+            .enumerate()
+            .fold(Column::new().spacing(10), |col, (i, entry)| {
+                let risks = Risk::ALL.iter().fold(row![].spacing(10), |r, risk| {
+                    let risk = *risk;
+                    r.push(
+                        checkbox(entry.risks.contains(&risk))
+                            .label(risk.to_string())
+                            .on_toggle(move |set| {
+                                Message::FormatRiskMessage(FormatRiskMessage::SetRisk(i, risk, set))
+                            }),
+                    )
+                });
+                col.push(
+                    column![
+                        row![
+                            TextInput::new("PUID or MIME type", &entry.format)
+                                .on_input(move |f| {
+                                    Message::FormatRiskMessage(FormatRiskMessage::SetFormat(i, f))
+                                })
+                                .width(250),
+                            TextInput::new("Note", &entry.note).on_input(move |n| {
+                                Message::FormatRiskMessage(FormatRiskMessage::SetNote(i, n))
+                            }),
+                            button("Remove").style(button::text).on_press(
+                                Message::FormatRiskMessage(FormatRiskMessage::RemoveEntry(i))
+                            ),
+                        ]
+                        .spacing(5)
+                        .align_y(Center),
+                        risks,
+                    ]
+                    .spacing(5),
+                )
+            });
+
+    column![
+        text("Format risk registry").size(20),
+        text("Formats are matched by PUID, or by MIME type. End a MIME type with * to match all that start with it, such as video/*. Files with no PUID and no MIME type are always unknown.")
+            .size(12)
+            .style(text::secondary),
+        scrollable(entries).height(Fill),
+        row![
+            button("Add").on_press(Message::FormatRiskMessage(FormatRiskMessage::AddEntry)),
+            button("Reset to defaults")
+                .style(button::secondary)
+                .on_press(Message::FormatRiskMessage(FormatRiskMessage::ResetRegistry)),
+            Space::new().width(Fill),
+            button("Close").on_press(Message::CloseWindow(Subwindow::RiskRegistry)),
+        ]
+        .spacing(10),
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assessments() {
+        use Risk::*;
+        let mut registry = RiskRegistry::default();
+        registry.entries.extend([
+            RiskEntry::new("video/*", &[Obsolete], "Old video"),
+            RiskEntry::new("fmt/1", &[Proprietary, Obsolete], ""),
+            RiskEntry::new("  ", &[Encrypted], "Never matches"),
+            RiskEntry::new("application/octet-stream", &[Unknown], "Unidentified"),
+        ]);
+        let cases: [(&str, &str, &[Risk], &[&str]); 10] = [
+            (
+                "fmt/40",
+                "application/msword",
+                &[Proprietary],
+                &["Word 97-2003"],
+            ),
+            ("FMT/40", "", &[Proprietary], &["Word 97-2003"]),
+            (
+                "",
+                "application/x-shockwave-flash",
+                &[Obsolete, Proprietary],
+                &["Flash"],
+            ),
+            ("", "", &[Unknown], &[]),
+            (
+                "",
+                "application/octet-stream",
+                &[Unknown],
+                &["Unidentified"],
+            ),
+            ("fmt/999", "", &[], &[]),
+            ("fmt/18", "application/pdf", &[], &[]),
+            (
+                "fmt/1",
+                "VIDEO/x-flv",
+                &[Obsolete, Proprietary],
+                &["Old video"],
+            ),
+            ("", "video/mp4", &[Obsolete], &["Old video"]),
+            ("", "videos", &[], &[]),
+        ];
+        for (puid, mime, risks, notes) in cases {
+            assert_eq!(
+                registry.assess(puid, mime),
+                (
+                    risks.to_vec(),
+                    notes.iter().map(|n| n.to_string()).collect()
+                ),
+                "{puid} {mime}"
+            );
+        }
+    }
+
+    #[test]
+    fn distributions() {
+        let file =
+            |puid: &str, format: &str, mime: &str, size: u64, issues: &[FileIssue]| FileFormat {
+                puid: puid.to_string(),
+                format: format.to_string(),
+                mime: mime.to_string(),
+                size,
+                issues: issues.to_vec(),
+                ..Default::default()
+            };
+        let store = FormatStore {
+            files: [
+                file("fmt/40", "Word 97", "application/msword", 100, &[]),
+                file("fmt/40", "Word 97", "", 50, &[]),
+                file("", "", "application/octet-stream", 10, &[]),
+                file("", "", "", 5, &[]),
+                file("", "", "image/x-foo", 300, &[]),
+                file(
+                    "fmt/494",
+                    "Encrypted Office",
+                    "",
+                    20,
+                    &[FileIssue::PasswordProtectedOffice],
+                ),
+                file(
+                    "fmt/18",
+                    "PDF 1.4",
+                    "application/pdf",
+                    40,
+                    &[FileIssue::EncryptedPdf],
+                ),
+                file(
+                    "fmt/18",
+                    "PDF 1.4",
+                    "application/pdf",
+                    1000,
+                    &[FileIssue::Unreadable],
+                ),
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(i, f)| (i.to_string(), f))
+            .collect(),
+        };
+
+        let rows: Vec<_> = distribution(&store, &RiskRegistry::default())
+            .into_iter()
+            .map(|r| (r.label, r.mime, r.files, r.bytes, r.risks))
+            .collect();
+        use Risk::*;
+        assert_eq!(
+            rows,
+            [
+                ("image/x-foo".into(), "image/x-foo".into(), 1, 300, vec![]),
+                (
+                    "Word 97".into(),
+                    "application/msword".into(),
+                    2,
+                    150,
+                    vec![Proprietary]
+                ),
+                (
+                    "PDF 1.4".into(),
+                    "application/pdf".into(),
+                    1,
+                    40,
+                    vec![Encrypted]
+                ),
+                (
+                    "Encrypted Office".into(),
+                    String::new(),
+                    1,
+                    20,
+                    vec![Encrypted, PasswordProtected]
+                ),
+                ("Unidentified".into(), String::new(), 2, 15, vec![Unknown]),
+            ]
+        );
+
+        assert!(distribution(&FormatStore::default(), &RiskRegistry::default()).is_empty());
+    }
+}
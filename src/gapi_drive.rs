@@ -0,0 +1,159 @@
+use google_sheets4::{
+    Sheets,
+    api::{Spreadsheet, SpreadsheetProperties},
+    hyper_rustls::HttpsConnector,
+    hyper_util::client::legacy::connect::HttpConnector,
+};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+const DRIVE_SCOPE: &str = "https://www.googleapis.com/auth/drive";
+const DRIVE_FILES_URL: &str = "https://www.googleapis.com/drive/v3/files";
+
+/// A spreadsheet created by TagMonster, with the ID of its first sheet.
+#[derive(Debug, Clone)]
+pub(crate) struct CreatedSpreadsheet {
+    pub spreadsheet_id: String,
+    pub sheet_id: i32,
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct DriveParents {
+    #[serde(default)]
+    parents: Vec<String>,
+}
+
+async fn drive_token(hub: &Sheets<HttpsConnector<HttpConnector>>) -> anyhow::Result<String> {
+    hub.auth
+        .get_token(&[DRIVE_SCOPE])
+        .await
+        .map_err(|e| anyhow::format_err!("Failed to get Google Drive token: {e}"))?
+        .ok_or(anyhow::format_err!("Google did not issue a Drive token"))
+}
+
+/// Create a new spreadsheet, optionally move it into a Drive folder and share it.
+///
+/// Moving and sharing go through the Drive API, which needs a broader scope than the
+/// Sheets client was authorised with, so Google may ask the user to consent again.
+pub(crate) async fn create_spreadsheet(
+    hub: Sheets<HttpsConnector<HttpConnector>>,
+    title: String,
+    folder_id: Option<String>,
+    collaborators: Vec<String>,
+) -> anyhow::Result<CreatedSpreadsheet> {
+    let req = Spreadsheet {
+        properties: Some(SpreadsheetProperties {
+            title: Some(title.clone()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let (_resp, sheet) = hub.spreadsheets().create(req).doit().await?;
+
+    let spreadsheet_id = sheet
+        .spreadsheet_id
+        .ok_or(anyhow::format_err!("Created spreadsheet has no ID"))?;
+    let sheet_id = sheet
+        .sheets
+        .as_ref()
+        .and_then(|s| s.first())
+        .and_then(|s| s.properties.as_ref())
+        .and_then(|p| p.sheet_id)
+        .unwrap_or(0);
+    let url = sheet.spreadsheet_url.unwrap_or(format!(
+        "https://docs.google.com/spreadsheets/d/{spreadsheet_id}/edit#gid={sheet_id}"
+    ));
+    info!("Created spreadsheet \"{title}\" ({spreadsheet_id})");
+
+    let created = CreatedSpreadsheet {
+        spreadsheet_id,
+        sheet_id,
+        url,
+    };
+    if folder_id.is_none() && collaborators.is_empty() {
+        return Ok(created);
+    }
+
+    // The spreadsheet exists by now, so failing to file or share it must not lose it
+    let spreadsheet_id = &created.spreadsheet_id;
+    let token = match drive_token(&hub).await {
+        Ok(token) => token,
+        Err(e) => {
+            warn!("Spreadsheet {spreadsheet_id} was left in the root of your Drive, unshared: {e}");
+            return Ok(created);
+        }
+    };
+    let client = reqwest::Client::new();
+
+    if let Some(folder_id) = folder_id {
+        match move_to_folder(&client, &token, spreadsheet_id, &folder_id).await {
+            Ok(()) => info!("Moved spreadsheet {spreadsheet_id} into Drive folder {folder_id}"),
+            Err(e) => warn!(
+                "Failed to move spreadsheet {spreadsheet_id} into Drive folder {folder_id}, \
+                 it is in the root of your Drive: {e}"
+            ),
+        }
+    }
+
+    for email in collaborators {
+        match share(&client, &token, spreadsheet_id, &email).await {
+            Ok(()) => info!("Shared spreadsheet {spreadsheet_id} with {email}"),
+            Err(e) => warn!("Failed to share spreadsheet with {email}: {e}"),
+        }
+    }
+
+    Ok(created)
+}
+
+async fn move_to_folder(
+    client: &reqwest::Client,
+    token: &str,
+    file_id: &str,
+    folder_id: &str,
+) -> anyhow::Result<()> {
+    let current = client
+        .get(format!("{DRIVE_FILES_URL}/{file_id}"))
+        .query(&[("fields", "parents"), ("supportsAllDrives", "true")])
+        .bearer_auth(token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<DriveParents>()
+        .await?;
+
+    client
+        .patch(format!("{DRIVE_FILES_URL}/{file_id}"))
+        .query(&[
+            ("addParents", folder_id),
+            ("removeParents", current.parents.join(",").as_str()),
+            ("supportsAllDrives", "true"),
+        ])
+        .bearer_auth(token)
+        .json(&serde_json::json!({}))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+async fn share(
+    client: &reqwest::Client,
+    token: &str,
+    file_id: &str,
+    email: &str,
+) -> anyhow::Result<()> {
+    client
+        .post(format!("{DRIVE_FILES_URL}/{file_id}/permissions"))
+        .query(&[("supportsAllDrives", "true")])
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "role": "writer",
+            "type": "user",
+            "emailAddress": email,
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -23,6 +23,7 @@ impl Default for HomepageState {
 
 pub(crate) enum HomepageMessage {
     AddProject(Project),
+    UpdateProject(Project),
     InitProjects(Vec<Project>),
 }
 
@@ -31,6 +32,16 @@ pub(crate) fn handle_homepage_message(state: &mut State, msg: HomepageMessage) -
         HomepageMessage::AddProject(proj) => {
             state.homepage_state.projects.push(proj);
         }
+        HomepageMessage::UpdateProject(proj) => {
+            if let Some(p) = state
+                .homepage_state
+                .projects
+                .iter_mut()
+                .find(|p| p.name == proj.name)
+            {
+                *p = proj;
+            }
+        }
         HomepageMessage::InitProjects(projects) => {
             state.homepage_state.projects = projects;
         }
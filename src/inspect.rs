@@ -0,0 +1,289 @@
+use std::{fmt::Write, io::Cursor};
+
+use r#box::apis::{
+    configuration::Configuration,
+    downloads_api::{GetFilesIdContentParams, get_files_id_content},
+};
+use iced::{
+    Element, Font,
+    Length::Fill,
+    widget::{Column, TextInput, button, column, row, scrollable, text},
+};
+
+use crate::{Message, viewer::ViewerMessage};
+
+/// Bytes downloaded and searched for candidates, strings and signatures.
+const INSPECT_BYTES: usize = 64 * 1024;
+
+/// Bytes shown in the hex dump.
+const HEX_BYTES: usize = 16 * 1024;
+
+/// Shorter runs of printable characters are mostly noise.
+const MIN_STRING: usize = 6;
+const MAX_STRINGS: usize = 300;
+const MAX_SIGNATURES: usize = 100;
+
+/// Signatures of formats often found inside other files, e.g. images in documents.
+const SIGNATURES: [(&[u8], &str); 24] = [
+    (b"PK\x03\x04", "ZIP entry"),
+    (b"%PDF-", "PDF document"),
+    (b"\x89PNG\r\n\x1A\n", "PNG image"),
+    (b"\xFF\xD8\xFF", "JPEG image"),
+    (b"GIF87a", "GIF image"),
+    (b"GIF89a", "GIF image"),
+    (b"II*\x00", "TIFF image (little-endian)"),
+    (b"MM\x00*", "TIFF image (big-endian)"),
+    (b"8BPS", "Photoshop document"),
+    (
+        b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1",
+        "OLE2 compound document",
+    ),
+    (b"{\\rtf", "RTF document"),
+    (b"<?xml", "XML document"),
+    (b"7z\xBC\xAF\x27\x1C", "7-Zip archive"),
+    (b"Rar!\x1A\x07", "RAR archive"),
+    (b"\x1F\x8B\x08", "gzip stream"),
+    (b"BZh", "bzip2 stream"),
+    (b"\xFD7zXZ\x00", "xz stream"),
+    (b"SQLite format 3\x00", "SQLite database"),
+    (b"\x7FELF", "ELF executable"),
+    (b"ID3", "ID3 tag"),
+    (b"OggS", "Ogg page"),
+    (b"fLaC", "FLAC stream"),
+    (b"\x1A\x45\xDF\xA3", "Matroska/WebM"),
+    (b"%!PS", "PostScript"),
+];
+
+/// What the start of a file looks like, for identifying it by hand.
+#[derive(Debug, Clone)]
+pub(crate) struct Inspection {
+    hex: String,
+    /// Matches from the magic database, best first, as (description, MIME type)
+    candidates: Vec<(String, String)>,
+    /// Printable runs by offset
+    strings: Vec<(usize, String)>,
+    /// Known signatures found after the start of the file, by offset
+    signatures: Vec<(usize, &'static str)>,
+    /// Whether the file is longer than the bytes looked at
+    truncated: bool,
+}
+
+impl Inspection {
+    /// Roughly how much memory the inspection takes.
+    pub fn bytes(&self) -> usize {
+        let candidates: usize = self.candidates.iter().map(|(d, m)| d.len() + m.len()).sum();
+        let strings: usize = self.strings.iter().map(|(_, s)| s.len()).sum();
+        self.hex.len() + candidates + strings
+    }
+}
+
+/// A classic hex dump: offset, sixteen bytes in hex, then the printable ones.
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 5);
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x}  ", i * 16);
+        for j in 0..16 {
+            match line.get(j) {
+                Some(b) => {
+                    let _ = write!(out, "{b:02x} ");
+                }
+                None => out.push_str("   "),
+            }
+            if j == 7 {
+                out.push(' ');
+            }
+        }
+        out.push_str(" |");
+        out.extend(line.iter().map(|b| {
+            if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push_str("|\n");
+    }
+    out
+}
+
+fn strings(bytes: &[u8]) -> Vec<(usize, String)> {
+    let mut out = vec![];
+    let mut start = None;
+    for (i, b) in bytes.iter().chain([&0]).enumerate() {
+        let printable = b.is_ascii_graphic() || *b == b' ' || *b == b'\t';
+        match (printable, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                if i - s >= MIN_STRING {
+                    out.push((s, String::from_utf8_lossy(&bytes[s..i]).into_owned()));
+                    if out.len() == MAX_STRINGS {
+                        break;
+                    }
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+fn signatures(bytes: &[u8]) -> Vec<(usize, &'static str)> {
+    let mut out = vec![];
+    // Offset 0 is the file's own signature, which the candidates already cover
+    for at in 1..bytes.len() {
+        for (magic, name) in SIGNATURES {
+            if bytes[at..].starts_with(magic) {
+                out.push((at, name));
+            }
+        }
+        if out.len() >= MAX_SIGNATURES {
+            break;
+        }
+    }
+    out
+}
+
+fn inspect(bytes: &[u8], truncated: bool) -> Result<Inspection, String> {
+    let db = magic_db::load().map_err(|e| e.to_string())?;
+    let candidates = db
+        .all_magics(&mut Cursor::new(bytes))
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|m| (m.message(), m.mime_type().to_string()))
+        .collect();
+    Ok(Inspection {
+        hex: hex_dump(&bytes[..bytes.len().min(HEX_BYTES)]),
+        candidates,
+        strings: strings(bytes),
+        signatures: signatures(bytes),
+        truncated,
+    })
+}
+
+pub(crate) async fn fetch(
+    config: Configuration,
+    id: String,
+    size: Option<i64>,
+) -> Result<Inspection, String> {
+    let resp = get_files_id_content(
+        &config,
+        GetFilesIdContentParams {
+            file_id: id,
+            range: Some(format!("bytes=0-{}", INSPECT_BYTES - 1)),
+            boxapi: None,
+            version: None,
+            access_token: None,
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+    let truncated = size.is_some_and(|s| s as usize > bytes.len());
+    // Loading the magic database and matching every rule takes a moment
+    tokio::task::spawn_blocking(move || inspect(&bytes, truncated))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn heading(label: &str) -> iced::widget::Text<'_> {
+    text(label).size(16)
+}
+
+/// The inspection of file `id`, with a form to record a manual identification.
+pub(crate) fn view<'a>(
+    inspection: &'a Inspection,
+    id: &str,
+    recorded: &str,
+    draft: &'a str,
+) -> Element<'a, Message> {
+    let identify =
+        |format: String| Message::ViewerMessage(ViewerMessage::Identify(id.to_string(), format));
+
+    let candidates = if inspection.candidates.is_empty() {
+        Column::new().push(text("No matches in the magic database").style(text::secondary))
+    } else {
+        inspection.candidates.iter().enumerate().fold(
+            Column::new().spacing(5),
+            |col, (i, (message, mime))| {
+                col.push(
+                    row![
+                        text(format!("{}. {message} ({mime})", i + 1)).width(Fill),
+                        button("Use")
+                            .style(button::secondary)
+                            .on_press(identify(message.clone())),
+                    ]
+                    .spacing(10),
+                )
+            },
+        )
+    };
+
+    let signatures = if inspection.signatures.is_empty() {
+        Column::new().push(text("None found").style(text::secondary))
+    } else {
+        inspection
+            .signatures
+            .iter()
+            .fold(Column::new(), |col, (offset, name)| {
+                col.push(text(format!("0x{offset:08x}  {name}")).font(Font::MONOSPACE))
+            })
+    };
+
+    let strings = inspection
+        .strings
+        .iter()
+        .fold(String::new(), |mut out, (offset, s)| {
+            let _ = writeln!(out, "0x{offset:08x}  {s}");
+            out
+        });
+
+    let identification = column![
+        heading("Identification"),
+        text(if recorded.is_empty() {
+            "Not identified by hand".to_string()
+        } else {
+            format!("Recorded as \"{recorded}\"")
+        }),
+        row![
+            TextInput::new("Format, e.g. \"WordPerfect 5.1 document\"", draft)
+                .on_input(|f| Message::ViewerMessage(ViewerMessage::SetIdentification(f))),
+            button("Record")
+                .style(button::primary)
+                .on_press(identify(draft.trim().to_string())),
+            button("Clear")
+                .style(button::secondary)
+                .on_press_maybe((!recorded.is_empty()).then(|| identify(String::new()))),
+        ]
+        .spacing(10),
+    ]
+    .spacing(5);
+
+    let note = if inspection.truncated {
+        format!(
+            "Looked at the first {} KiB of the file; the dump shows the first {} KiB",
+            INSPECT_BYTES / 1024,
+            HEX_BYTES / 1024
+        )
+    } else {
+        "Looked at the whole file".to_string()
+    };
+
+    scrollable(
+        column![
+            identification,
+            heading("Candidate formats"),
+            candidates,
+            heading("Embedded signatures"),
+            signatures,
+            heading("Strings"),
+            text(strings).font(Font::MONOSPACE).size(12),
+            heading("Hex dump"),
+            text(&inspection.hex).font(Font::MONOSPACE).size(12),
+            text(note).size(12),
+        ]
+        .spacing(10),
+    )
+    .height(Fill)
+    .into()
+}
@@ -0,0 +1,668 @@
+use std::{
+    fmt,
+    io::{self, Read, Seek, SeekFrom},
+    sync::LazyLock,
+};
+
+use r#box::apis::configuration::Configuration;
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    widget::{Column, Space, button, column, pick_list, row, scrollable, text},
+};
+use image::ImageError;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Message, State,
+    archive::{self, RangedReader},
+    embedded::{ODF_EXTENSIONS, OOXML_EXTENSIONS},
+    format_risk::{FormatRiskMessage, Risk},
+    media,
+    sampling::{Sample, SampleReader},
+    subwindows::Subwindow,
+    viewer::IMAGE_EXTENSIONS,
+};
+
+/// Signature of the compound files legacy Office documents and encrypted OOXML are stored in.
+const CFB_MAGIC: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+/// Directory sectors of a compound file read before giving up.
+const MAX_DIRECTORY_SECTORS: usize = 1024;
+
+/// Bytes read from the end of a PDF when the sample has no tail, for the trailer.
+const PDF_TAIL_BYTES: u64 = 64 * 1024;
+
+const MAX_MANIFEST: u64 = 1024 * 1024;
+
+/// The trailer of an encrypted PDF refers to its encryption dictionary.
+static PDF_ENCRYPT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"/Encrypt\s*(?:\d+\s+\d+\s+R|<<)").expect("valid regex"));
+
+/// A problem with a file that needs following up with whoever supplied it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) enum FileIssue {
+    Empty,
+    EncryptedPdf,
+    PasswordProtectedOffice,
+    EncryptedZip,
+    TruncatedImage,
+    CorruptImage,
+    /// Box would not send the file
+    Unreadable,
+}
+
+impl FileIssue {
+    pub const ALL: [FileIssue; 7] = [
+        FileIssue::Empty,
+        FileIssue::EncryptedPdf,
+        FileIssue::PasswordProtectedOffice,
+        FileIssue::EncryptedZip,
+        FileIssue::TruncatedImage,
+        FileIssue::CorruptImage,
+        FileIssue::Unreadable,
+    ];
+
+    /// The format risk a file with this issue adds to its format.
+    pub fn risk(&self) -> Option<Risk> {
+        match self {
+            FileIssue::EncryptedPdf | FileIssue::EncryptedZip => Some(Risk::Encrypted),
+            FileIssue::PasswordProtectedOffice => Some(Risk::PasswordProtected),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FileIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileIssue::Empty => "Zero bytes",
+            FileIssue::EncryptedPdf => "Encrypted PDF",
+            FileIssue::PasswordProtectedOffice => "Password-protected Office document",
+            FileIssue::EncryptedZip => "Password-protected ZIP",
+            FileIssue::TruncatedImage => "Truncated image",
+            FileIssue::CorruptImage => "Corrupt image",
+            FileIssue::Unreadable => "Could not be downloaded",
+        })
+    }
+}
+
+fn le16(b: &[u8], at: usize) -> u64 {
+    b.get(at..at + 2)
+        .map_or(0, |s| u16::from_le_bytes([s[0], s[1]]) as u64)
+}
+
+fn le32(b: &[u8], at: usize) -> u64 {
+    b.get(at..at + 4)
+        .map_or(0, |s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]) as u64)
+}
+
+fn be16(b: &[u8], at: usize) -> u64 {
+    b.get(at..at + 2)
+        .map_or(0, |s| u16::from_be_bytes([s[0], s[1]]) as u64)
+}
+
+fn be32(b: &[u8], at: usize) -> u64 {
+    b.get(at..at + 4)
+        .map_or(0, |s| u32::from_be_bytes([s[0], s[1], s[2], s[3]]) as u64)
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, at: u64, len: usize) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(at))?;
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Whether a compound file is encrypted: encrypted OOXML and PowerPoint by their streams,
+/// Word by its FIB and Excel by a FILEPASS record straight after the workbook's BOF.
+fn cfb_encrypted<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+    let header = read_at(reader, 0, 512)?;
+    let shift = le16(&header, 0x1E);
+    if !header.starts_with(&CFB_MAGIC) || !(7..=16).contains(&shift) {
+        return Err(io::Error::other("not a compound file"));
+    }
+    let sector = 1u64 << shift;
+    let offset = |n: u64| (n + 1) << shift;
+    // The header lists the first 109 FAT sectors, which covers files up to 6.8 MiB with
+    // 512-byte sectors and 3.5 GiB with 4096-byte ones
+    let next = |reader: &mut R, n: u64| -> io::Result<u64> {
+        let index = n / (sector / 4);
+        let fat = le32(&header, 0x4C + index as usize * 4);
+        if index >= 109 || fat >= 0xFFFF_FFFA {
+            return Err(io::Error::other(
+                "directory is beyond the listed FAT sectors",
+            ));
+        }
+        let entry = read_at(reader, offset(fat) + (n % (sector / 4)) * 4, 4)?;
+        Ok(le32(&entry, 0))
+    };
+
+    // Streams as (name, first sector, size)
+    let mut streams = vec![];
+    let mut dir = le32(&header, 0x30);
+    for _ in 0..MAX_DIRECTORY_SECTORS {
+        if dir >= 0xFFFF_FFFA {
+            break;
+        }
+        let entries = read_at(reader, offset(dir), sector as usize)?;
+        for entry in entries.chunks_exact(128) {
+            if entry[0x42] != 2 {
+                continue;
+            }
+            let len = (le16(entry, 0x40) as usize).clamp(2, 64) - 2;
+            let name: Vec<u16> = entry[..len]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            streams.push((
+                String::from_utf16_lossy(&name),
+                le32(entry, 0x74),
+                le32(entry, 0x78),
+            ));
+        }
+        dir = next(reader, dir)?;
+    }
+
+    let find = |name: &str| streams.iter().find(|(n, ..)| n == name);
+    if find("EncryptedPackage").is_some() || find("EncryptedSummary").is_some() {
+        return Ok(true);
+    }
+    // Smaller streams live in the mini stream, and these are never that small
+    let cutoff = le32(&header, 0x38);
+    let start = |(_, first, size): &(String, u64, u64)| (*size >= cutoff).then(|| offset(*first));
+    if let Some(at) = find("WordDocument").and_then(start) {
+        let fib = read_at(reader, at, 12)?;
+        return Ok(le16(&fib, 0) == 0xA5EC && le16(&fib, 0x0A) & 0x0100 != 0);
+    }
+    if let Some(at) = find("Workbook").or(find("Book")).and_then(start) {
+        let bof = read_at(reader, at, 4)?;
+        let record = read_at(reader, at + 4 + le16(&bof, 2), 2)?;
+        return Ok(le16(&bof, 0) & 0xFF == 0x09 && le16(&record, 0) == 0x002F);
+    }
+    Ok(false)
+}
+
+fn zip_encrypted<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+    Ok(archive::list_zip(reader)?
+        .entries
+        .iter()
+        .any(|e| e.encrypted))
+}
+
+/// OpenDocument encrypts its parts itself and lists how in the manifest.
+fn odf_encrypted<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+    let manifest = archive::zip_entry(reader, "META-INF/manifest.xml", MAX_MANIFEST)?;
+    Ok(contains(&manifest, b"encryption-data"))
+}
+
+/// Run a check on the sample, or on ranged downloads when it needs bytes the sample doesn't
+/// have. `None` when the file can't be read either way.
+async fn read_either<T: Send + 'static>(
+    config: &Configuration,
+    id: &str,
+    sample: &Sample,
+    on_sample: impl FnOnce(&mut SampleReader<'_>) -> io::Result<T>,
+    ranged: fn(&mut RangedReader) -> io::Result<T>,
+) -> Option<T> {
+    match on_sample(&mut sample.reader()) {
+        Ok(value) => Some(value),
+        Err(_) if sample.complete() => None,
+        Err(_) => match archive::read_ranged(config.clone(), id.to_string(), ranged).await {
+            Ok(value) => Some(value),
+            Err(e) => {
+                tracing::debug!("Could not check file {}: {}", id, e);
+                None
+            }
+        },
+    }
+}
+
+async fn pdf_encrypted(config: &Configuration, id: &str, sample: &Sample) -> bool {
+    if PDF_ENCRYPT.is_match(&sample.head) || PDF_ENCRYPT.is_match(sample.end()) {
+        return true;
+    }
+    if !sample.end().is_empty() {
+        return false;
+    }
+    let range = format!("bytes=-{PDF_TAIL_BYTES}");
+    match media::fetch_range(config, id, Some(range)).await {
+        Ok((tail, _)) => PDF_ENCRYPT.is_match(&tail),
+        Err(e) => {
+            tracing::warn!("Could not read the end of file {}: {}", id, e);
+            false
+        }
+    }
+}
+
+/// Whether a JPEG's segments up to the first scan are well formed, as far as `head` goes.
+fn jpeg_segments_valid(head: &[u8]) -> bool {
+    let mut at = 2;
+    while at + 4 <= head.len() {
+        if head[at] != 0xFF {
+            return false;
+        }
+        match head[at + 1] {
+            // Fill bytes
+            0xFF => at += 1,
+            // Start of scan. The entropy-coded data after it has no segments.
+            0xDA => return true,
+            0x01 | 0xD0..=0xD7 => at += 2,
+            0x00 | 0xD8 | 0xD9 => return false,
+            _ => {
+                let len = be16(head, at + 2);
+                if len < 2 {
+                    return false;
+                }
+                at += 2 + len as usize;
+            }
+        }
+    }
+    true
+}
+
+/// Whether a file with an image extension was cut short or is malformed, judged from its
+/// first and last bytes. Images sampled whole are also decoded.
+async fn image_issue(sample: &Sample, extension: &str) -> Option<FileIssue> {
+    let head = sample.head.as_slice();
+    let end = sample.end();
+    let has_end = !end.is_empty();
+    let trimmed = &end[..end.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1)];
+
+    let found = if head.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        // Progressive JPEGs have several scans; the last one ends at the EOI marker
+        let last_scan = end.windows(2).rposition(|w| w == [0xFF, 0xDA]).unwrap_or(0);
+        if !jpeg_segments_valid(head) {
+            Some(FileIssue::CorruptImage)
+        } else if has_end && !contains(&end[last_scan..], &[0xFF, 0xD9]) {
+            Some(FileIssue::TruncatedImage)
+        } else {
+            None
+        }
+    } else if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let mut crc = flate2::Crc::new();
+        crc.update(head.get(12..29).unwrap_or_default());
+        if head.len() < 33 {
+            Some(FileIssue::TruncatedImage)
+        } else if &head[12..16] != b"IHDR" || crc.sum() as u64 != be32(head, 29) {
+            Some(FileIssue::CorruptImage)
+        } else if has_end && !contains(end, b"IEND") {
+            Some(FileIssue::TruncatedImage)
+        } else {
+            None
+        }
+    } else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        if le16(head, 6) == 0 || le16(head, 8) == 0 {
+            Some(FileIssue::CorruptImage)
+        } else if has_end && trimmed.last() != Some(&0x3B) {
+            Some(FileIssue::TruncatedImage)
+        } else {
+            None
+        }
+    } else if head.starts_with(b"RIFF") && head.get(8..12) == Some(&b"WEBP"[..]) {
+        if le32(head, 4) + 8 > sample.size {
+            Some(FileIssue::TruncatedImage)
+        } else if head.get(12..15) != Some(&b"VP8"[..]) {
+            Some(FileIssue::CorruptImage)
+        } else {
+            None
+        }
+    } else if head.starts_with(b"II*\0") || head.starts_with(b"MM\0*") {
+        let ifd = if head[0] == b'I' {
+            le32(head, 4)
+        } else {
+            be32(head, 4)
+        };
+        (ifd < 8 || ifd >= sample.size).then_some(FileIssue::CorruptImage)
+    } else if head.starts_with(b"BM") {
+        (le32(head, 2) > sample.size).then_some(FileIssue::TruncatedImage)
+    } else {
+        // Icons and Targa images have no reliable signature
+        (!matches!(extension, "ico" | "tga") && image::guess_format(head).is_err())
+            .then_some(FileIssue::CorruptImage)
+    };
+    if found.is_some() || !sample.complete() {
+        return found;
+    }
+
+    let format = image::guess_format(head).ok()?;
+    let bytes = sample.head.clone();
+    let decoded = tokio::task::spawn_blocking(move || {
+        image::load_from_memory_with_format(&bytes, format).map(|_| ())
+    })
+    .await
+    .ok()?;
+    match decoded {
+        Err(ImageError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+            Some(FileIssue::TruncatedImage)
+        }
+        Err(ImageError::IoError(_) | ImageError::Decoding(_)) => Some(FileIssue::CorruptImage),
+        _ => None,
+    }
+}
+
+/// Check file `id`, called `name`, for problems that need following up with whoever
+/// supplied it, from the bytes sampled from it.
+pub(crate) async fn check(
+    config: &Configuration,
+    id: &str,
+    name: &str,
+    sample: &Sample,
+) -> Vec<FileIssue> {
+    if sample.size == 0 {
+        return vec![FileIssue::Empty];
+    }
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    let ooxml = OOXML_EXTENSIONS.contains(&extension.as_str());
+    let odf = ODF_EXTENSIONS.contains(&extension.as_str());
+    let head = sample.head.as_slice();
+
+    let issue = if head.starts_with(b"%PDF") {
+        pdf_encrypted(config, id, sample)
+            .await
+            .then_some(FileIssue::EncryptedPdf)
+    } else if head.starts_with(&CFB_MAGIC) {
+        // Encrypted OOXML is a compound file rather than a ZIP
+        read_either(config, id, sample, |r| cfb_encrypted(r), cfb_encrypted)
+            .await
+            .unwrap_or(ooxml)
+            .then_some(FileIssue::PasswordProtectedOffice)
+    } else if head.starts_with(b"PK\x03\x04") {
+        let zip = read_either(config, id, sample, |r| zip_encrypted(r), zip_encrypted).await;
+        let manifest = if odf {
+            read_either(config, id, sample, |r| odf_encrypted(r), odf_encrypted).await
+        } else {
+            None
+        };
+        match (zip == Some(true) || manifest == Some(true), ooxml || odf) {
+            (true, true) => Some(FileIssue::PasswordProtectedOffice),
+            (true, false) => Some(FileIssue::EncryptedZip),
+            (false, _) => None,
+        }
+    } else if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        image_issue(sample, &extension).await
+    } else {
+        None
+    };
+    issue.into_iter().collect()
+}
+
+/// Which issues the list shows.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) enum IssueFilter {
+    #[default]
+    All,
+    Only(FileIssue),
+}
+
+impl fmt::Display for IssueFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueFilter::All => f.write_str("All issues"),
+            IssueFilter::Only(issue) => issue.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct IssuesState {
+    filter: IssueFilter,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum IssuesMessage {
+    Filter(IssueFilter),
+}
+
+pub(crate) fn issues_handle(state: &mut State, event: IssuesMessage) -> Task<Message> {
+    match event {
+        IssuesMessage::Filter(filter) => state.issues_state.filter = filter,
+    }
+    Task::none()
+}
+
+pub(crate) fn file_issues(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::FileIssues));
+    let filter = state.issues_state.filter;
+    let mut files: Vec<_> = state
+        .formats
+        .files
+        .values()
+        .flat_map(|file| file.issues.iter().map(move |issue| (file, *issue)))
+        .collect();
+    let total = files.len();
+    files.retain(|(_, issue)| filter == IssueFilter::All || filter == IssueFilter::Only(*issue));
+    files.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+
+    let filters: Vec<IssueFilter> = std::iter::once(IssueFilter::All)
+        .chain(FileIssue::ALL.map(IssueFilter::Only))
+        .collect();
+    let header = row![
+        button("Check files").on_press(Message::FormatRiskMessage(FormatRiskMessage::Identify)),
+        pick_list(filters, Some(filter), |f| {
+            Message::IssuesMessage(IssuesMessage::Filter(f))
+        }),
+        Space::new().width(Fill),
+        text(format!("Showing {} of {} issues", files.len(), total)),
+    ]
+    .spacing(10)
+    .align_y(Center);
+
+    let body: Element<Message> = if total == 0 {
+        text(
+            "No issues found. Files are checked when the sheet is previewed or built, or with \
+             Check files.",
+        )
+        .into()
+    } else {
+        scrollable(
+            files
+                .iter()
+                .fold(Column::new().spacing(8), |col, (file, issue)| {
+                    let name = button(text(file.name.clone()))
+                        .style(button::text)
+                        .on_press_maybe(
+                            (!file.web_link.is_empty())
+                                .then(|| Message::OpenLink(file.web_link.clone())),
+                        );
+                    col.push(column![
+                        name,
+                        text(format!("    {issue}")).size(12).style(text::danger),
+                    ])
+                }),
+        )
+        .height(Fill)
+        .into()
+    };
+
+    column![text("File issues").size(20), header, body, close]
+        .padding(Padding::new(15.0))
+        .spacing(15.0)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, RgbImage};
+
+    use super::*;
+
+    fn whole(bytes: &[u8]) -> Sample {
+        Sample {
+            head: bytes.to_vec(),
+            tail: vec![],
+            size: bytes.len() as u64,
+        }
+    }
+
+    /// The first bytes of a much larger file, with no tail.
+    fn start_of(bytes: &[u8]) -> Sample {
+        Sample {
+            size: 1_000_000,
+            ..whole(bytes)
+        }
+    }
+
+    fn encoded(format: ImageFormat) -> Vec<u8> {
+        let mut out = Cursor::new(vec![]);
+        RgbImage::from_fn(16, 16, |x, y| image::Rgb([x as u8 * 16, y as u8 * 16, 128]))
+            .write_to(&mut out, format)
+            .unwrap();
+        out.into_inner()
+    }
+
+    fn issue(sample: Sample, extension: &str) -> Option<FileIssue> {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(image_issue(&sample, extension))
+    }
+
+    #[test]
+    fn jpeg_segments() {
+        for (head, valid) in [
+            (&b"\xFF\xD8"[..], true),
+            (b"\xFF\xD8\xFF\xE0\x00\x04\x00\x00\xFF\xDA\x00\x08", true),
+            // Fill bytes before a marker
+            (
+                b"\xFF\xD8\xFF\xFF\xE0\x00\x04\x00\x00\xFF\xDA\x00\x08",
+                true,
+            ),
+            (b"\xFF\xD8\xFF\xD0\xFF\xDA\x00\x08", true),
+            // A segment running past the head can't be checked further
+            (b"\xFF\xD8\xFF\xE1\x7F\xFF\x00\x00", true),
+            (b"\xFF\xD8\x00\xE0\x00\x04\x00\x00", false),
+            (b"\xFF\xD8\xFF\xE0\x00\x01\x00\x00", false),
+            (b"\xFF\xD8\xFF\xD9\x00\x00", false),
+            (b"\xFF\xD8\xFF\x00\x00\x00", false),
+            (b"\xFF\xD8\xFF\xE0\x00\x04\x00\x00\x12\x34\x56\x78", false),
+        ] {
+            assert_eq!(jpeg_segments_valid(head), valid, "{head:02X?}");
+        }
+    }
+
+    #[test]
+    fn whole_images() {
+        let png = encoded(ImageFormat::Png);
+        let jpeg = encoded(ImageFormat::Jpeg);
+        let gif = encoded(ImageFormat::Gif);
+        let bmp = encoded(ImageFormat::Bmp);
+        let tiff = encoded(ImageFormat::Tiff);
+
+        let mut bad_crc = png.clone();
+        bad_crc[20] ^= 0xFF;
+        let mut bad_length = jpeg.clone();
+        bad_length[4..6].copy_from_slice(&[0, 1]);
+        let mut bad_data = png.clone();
+        let idat = png.windows(4).position(|w| w == b"IDAT").unwrap();
+        bad_data[idat + 8] ^= 0xFF;
+        let mut bad_ifd = tiff.clone();
+        bad_ifd[4..8].copy_from_slice(&4u32.to_le_bytes());
+
+        for (bytes, extension, expected) in [
+            (&png[..], "png", None),
+            (&jpeg, "jpg", None),
+            (&gif, "gif", None),
+            (&bmp, "bmp", None),
+            (&tiff, "tif", None),
+            (
+                &png[..png.len() - 12],
+                "png",
+                Some(FileIssue::TruncatedImage),
+            ),
+            (&png[..30], "png", Some(FileIssue::TruncatedImage)),
+            (&bad_crc, "png", Some(FileIssue::CorruptImage)),
+            (&bad_data, "png", Some(FileIssue::CorruptImage)),
+            (
+                &jpeg[..jpeg.len() - 2],
+                "jpg",
+                Some(FileIssue::TruncatedImage),
+            ),
+            (&bad_length, "jpg", Some(FileIssue::CorruptImage)),
+            (
+                &gif[..gif.len() - 1],
+                "gif",
+                Some(FileIssue::TruncatedImage),
+            ),
+            (
+                &bmp[..bmp.len() - 10],
+                "bmp",
+                Some(FileIssue::TruncatedImage),
+            ),
+            (&bad_ifd, "tif", Some(FileIssue::CorruptImage)),
+            (b"not an image", "png", Some(FileIssue::CorruptImage)),
+            (b"not an image", "tga", None),
+        ] {
+            assert_eq!(issue(whole(bytes), extension), expected, "{extension}");
+        }
+    }
+
+    #[test]
+    fn sampled_images() {
+        let png = encoded(ImageFormat::Png);
+        let jpeg = encoded(ImageFormat::Jpeg);
+
+        // Only the head was read, so only the head is judged
+        assert_eq!(issue(start_of(&png[..40]), "png"), None);
+        assert_eq!(issue(start_of(&jpeg[..40]), "jpg"), None);
+
+        let sampled = |head: &[u8], tail: &[u8]| Sample {
+            head: head.to_vec(),
+            tail: tail.to_vec(),
+            size: 1_000_000,
+        };
+        for (sample, expected) in [
+            (sampled(&png[..40], &png[png.len() - 20..]), None),
+            (
+                sampled(&png[..40], b"\0\0\0\0"),
+                Some(FileIssue::TruncatedImage),
+            ),
+            (sampled(&jpeg[..40], &jpeg[jpeg.len() - 20..]), None),
+            (
+                sampled(&jpeg[..40], &jpeg[jpeg.len() - 20..jpeg.len() - 2]),
+                Some(FileIssue::TruncatedImage),
+            ),
+            (
+                sampled(b"GIF89a\0\0\x10\0", b";"),
+                Some(FileIssue::CorruptImage),
+            ),
+            (sampled(b"GIF89a\x10\0\x10\0", b";\0\0"), None),
+            (
+                sampled(b"GIF89a\x10\0\x10\0", b"\x00\x01"),
+                Some(FileIssue::TruncatedImage),
+            ),
+            (
+                sampled(b"RIFF\xFF\xFF\xFF\x00WEBPVP8 ", b""),
+                Some(FileIssue::TruncatedImage),
+            ),
+            (
+                sampled(b"RIFF\x00\x01\x00\x00WEBPXXXX", b""),
+                Some(FileIssue::CorruptImage),
+            ),
+            (sampled(b"RIFF\x00\x01\x00\x00WEBPVP8L", b""), None),
+            (
+                sampled(b"MM\0*\xFF\xFF\xFF\xFF", b""),
+                Some(FileIssue::CorruptImage),
+            ),
+            (sampled(b"II*\0\x08\0\0\0", b""), None),
+            (
+                sampled(b"BM\xFF\xFF\xFF\x7F", b""),
+                Some(FileIssue::TruncatedImage),
+            ),
+        ] {
+            assert_eq!(issue(sample, "img"), expected);
+        }
+    }
+}
@@ -0,0 +1,544 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    keyboard::{self, Key},
+    widget::{Column, Space, button, column, row, scrollable, text},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Message, State, data_entry,
+    metadata::{self, Edit, ItemMetadata, MetadataStore},
+    persist,
+    subwindows::Subwindow,
+};
+
+/// Consecutive edits to the same field of the same item this close together are one entry,
+/// so undo reverts a burst of typing rather than a single keystroke.
+const COALESCE_SECS: u64 = 5;
+
+/// Most edits kept. Older ones are dropped so the history stays quick to save.
+const MAX_ENTRIES: usize = 1000;
+
+/// One item's metadata before and after an edit. `None` means the item had no metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Change {
+    pub id: String,
+    pub before: Option<ItemMetadata>,
+    pub after: Option<ItemMetadata>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct JournalEntry {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    pub edit: Edit,
+    pub changes: Vec<Change>,
+}
+
+impl JournalEntry {
+    fn describe(&self) -> String {
+        let what = match &self.edit {
+            Edit::Set(field, value) if value.is_empty() => format!("Cleared {field}"),
+            Edit::Set(field, value) => format!("Set {field} to \"{}\"", first_line(value)),
+            Edit::SetCustom(name, value) if value.is_empty() => format!("Cleared {name}"),
+            Edit::SetCustom(name, value) => format!("Set {name} to \"{}\"", first_line(value)),
+            Edit::AddTag(tag) => format!("Added tag \"{tag}\""),
+            Edit::RemoveTag(tag) => format!("Removed tag \"{tag}\""),
+            Edit::AppendNote(note) => format!("Appended note \"{}\"", first_line(note)),
+            Edit::Identify(format) if format.is_empty() => "Cleared the format".to_string(),
+            Edit::Identify(format) => format!("Identified as \"{format}\""),
+            Edit::Fill(values) => format!(
+                "Filled {} from the file",
+                values
+                    .iter()
+                    .map(|(field, _)| field.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        match self.changes.len() {
+            1 => what,
+            n => format!("{what} on {n} items"),
+        }
+    }
+}
+
+fn same_field(a: &Edit, b: &Edit) -> bool {
+    match (a, b) {
+        (Edit::Set(a, _), Edit::Set(b, _)) => a == b,
+        (Edit::SetCustom(a, _), Edit::SetCustom(b, _)) => a == b,
+        (Edit::Identify(_), Edit::Identify(_)) => true,
+        _ => false,
+    }
+}
+
+fn first_line(s: &str) -> String {
+    let line = s.lines().next().unwrap_or_default();
+    if line.chars().count() > 40 || s.lines().nth(1).is_some() {
+        format!("{}...", line.chars().take(40).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn ago(time: u64) -> String {
+    match now().saturating_sub(time) {
+        s if s < 60 => "just now".to_string(),
+        s if s < 3600 => format!("{} min ago", s / 60),
+        s if s < 86400 => format!("{} h ago", s / 3600),
+        s => format!("{} days ago", s / 86400),
+    }
+}
+
+/// Every metadata edit made in a project, in order.
+///
+/// `entries[..cursor]` are applied and `entries[cursor..]` have been undone and can be redone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Journal {
+    pub entries: Vec<JournalEntry>,
+    pub cursor: usize,
+}
+
+impl Journal {
+    pub fn can_undo(&self) -> bool {
+        self.cursor > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.cursor < self.entries.len()
+    }
+
+    /// Add an edit, discarding anything that had been undone.
+    pub fn record(&mut self, edit: Edit, changes: Vec<Change>) {
+        let changes: Vec<Change> = changes
+            .into_iter()
+            .filter(|c| c.before != c.after)
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+        self.entries.truncate(self.cursor);
+        let time = now();
+
+        if let Some(last) = self.entries.last_mut()
+            && same_field(&last.edit, &edit)
+            && last.changes.len() == 1
+            && changes.len() == 1
+            && last.changes[0].id == changes[0].id
+            && time.saturating_sub(last.time) <= COALESCE_SECS
+        {
+            // A burst that ends where it started changed nothing
+            if last.changes[0].before == changes[0].after {
+                self.entries.pop();
+                self.cursor = self.entries.len();
+                return;
+            }
+            last.time = time;
+            last.edit = edit;
+            last.changes[0].after = changes[0].after.clone();
+            return;
+        }
+
+        self.entries.push(JournalEntry {
+            time,
+            edit,
+            changes,
+        });
+        self.trim();
+        self.cursor = self.entries.len();
+    }
+
+    /// Undo or redo until only the first `target` entries are applied, putting the items they
+    /// changed in `metadata` back to match. Returns what was undone and redone, in order.
+    fn move_to(&mut self, target: usize, metadata: &mut MetadataStore) -> Vec<String> {
+        let target = target.min(self.entries.len());
+        let mut done = vec![];
+        while self.cursor > target {
+            self.cursor -= 1;
+            self.restore(self.cursor, false, metadata);
+            done.push(format!("Undid: {}", self.entries[self.cursor].describe()));
+        }
+        while self.cursor < target {
+            self.restore(self.cursor, true, metadata);
+            done.push(format!("Redid: {}", self.entries[self.cursor].describe()));
+            self.cursor += 1;
+        }
+        done
+    }
+
+    /// Put every item in `entry` back to its state before or after the edit.
+    fn restore(&self, entry: usize, after: bool, metadata: &mut MetadataStore) {
+        for change in &self.entries[entry].changes {
+            let value = if after { &change.after } else { &change.before };
+            metadata.set(&change.id, value.clone().unwrap_or_default());
+        }
+    }
+
+    /// Drop the oldest edits beyond [`MAX_ENTRIES`].
+    fn trim(&mut self) {
+        let excess = self.entries.len().saturating_sub(MAX_ENTRIES);
+        self.entries.drain(..excess);
+        self.cursor = self.cursor.saturating_sub(excess);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum JournalMessage {
+    Loaded(Journal),
+    Undo,
+    Redo,
+    /// Undo or redo until only the first `n` entries are applied
+    RevertTo(usize),
+}
+
+pub(crate) async fn load(project: String) -> Journal {
+    match persist::load_project_store::<Journal>(&project, "journal").await {
+        Ok(mut journal) => {
+            journal.trim();
+            journal
+        }
+        Err(e) => {
+            tracing::debug!("No edit history loaded for {}: {}", project, e);
+            Journal::default()
+        }
+    }
+}
+
+/// Write the history, taken as `snapshot`. Saved alongside the metadata by [`metadata::flush`].
+pub(crate) fn save(project: String, journal: Journal, snapshot: u64) -> Task<Message> {
+    Task::perform(
+        async move {
+            if let Err(e) =
+                persist::save_project_store(&journal, &project, "journal", snapshot).await
+            {
+                tracing::error!("Error saving edit history for {}: {}", project, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+/// Ctrl+Z undoes and Ctrl+Shift+Z or Ctrl+Y redoes. Only called for key presses no widget
+/// handled, so text inputs keep their own undo.
+pub(crate) fn shortcut(event: &keyboard::Event) -> Option<Message> {
+    let keyboard::Event::KeyPressed { key, modifiers, .. } = event else {
+        return None;
+    };
+    if !modifiers.command() {
+        return None;
+    }
+    match key.as_ref() {
+        Key::Character(c) if c.eq_ignore_ascii_case("z") && modifiers.shift() => {
+            Some(Message::JournalMessage(JournalMessage::Redo))
+        }
+        Key::Character(c) if c.eq_ignore_ascii_case("z") => {
+            Some(Message::JournalMessage(JournalMessage::Undo))
+        }
+        Key::Character(c) if c.eq_ignore_ascii_case("y") => {
+            Some(Message::JournalMessage(JournalMessage::Redo))
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn journal_handle(state: &mut State, event: JournalMessage) -> Task<Message> {
+    let Some(project) = &state.project else {
+        return Task::none();
+    };
+    let target = match event {
+        JournalMessage::Loaded(journal) => {
+            tracing::info!(
+                "Loaded {} edits for {}",
+                journal.entries.len(),
+                project.name
+            );
+            state.journal = journal;
+            return Task::none();
+        }
+        JournalMessage::Undo if state.journal.can_undo() => state.journal.cursor - 1,
+        JournalMessage::Redo if state.journal.can_redo() => state.journal.cursor + 1,
+        JournalMessage::RevertTo(n) if n <= state.journal.entries.len() => n,
+        _ => return Task::none(),
+    };
+
+    for done in state.journal.move_to(target, &mut state.metadata) {
+        tracing::info!("{}", done);
+    }
+    // The notes editor keeps its own copy of the text
+    data_entry::selection_changed(state);
+
+    metadata::schedule_save(state)
+}
+
+pub(crate) fn history(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::History));
+    if state.project.is_none() {
+        return column![text("No project open"), Space::new().height(Fill), close]
+            .padding(Padding::new(15.0))
+            .spacing(15.0)
+            .into();
+    }
+    let journal = &state.journal;
+
+    let entries = journal.entries.iter().enumerate().rev().fold(
+        Column::new().spacing(5),
+        |col, (i, entry)| {
+            let applied = i < journal.cursor;
+            // Reverting to an applied entry keeps it; picking an undone one redoes up to it
+            let revert_to = i + 1;
+            col.push(
+                row![
+                    column![
+                        text(entry.describe()).style(if applied {
+                            text::default
+                        } else {
+                            text::secondary
+                        }),
+                        text(format!(
+                            "{}{}",
+                            ago(entry.time),
+                            if applied { "" } else { ", undone" }
+                        ))
+                        .size(12),
+                    ]
+                    .width(Fill),
+                    button(if applied {
+                        "Revert to here"
+                    } else {
+                        "Redo to here"
+                    })
+                    .style(button::secondary)
+                    .on_press_maybe(
+                        (revert_to != journal.cursor).then_some(Message::JournalMessage(
+                            JournalMessage::RevertTo(revert_to)
+                        ),)
+                    ),
+                ]
+                .align_y(Center)
+                .spacing(10),
+            )
+        },
+    );
+
+    column![
+        text("Edit history").size(20),
+        row![
+            button("Undo").on_press_maybe(
+                journal
+                    .can_undo()
+                    .then_some(Message::JournalMessage(JournalMessage::Undo))
+            ),
+            button("Redo").on_press_maybe(
+                journal
+                    .can_redo()
+                    .then_some(Message::JournalMessage(JournalMessage::Redo))
+            ),
+            Space::new().width(Fill),
+            button("Undo everything")
+                .style(button::danger)
+                .on_press_maybe(
+                    journal
+                        .can_undo()
+                        .then_some(Message::JournalMessage(JournalMessage::RevertTo(0)))
+                ),
+        ]
+        .spacing(10),
+        scrollable(entries).height(Fill),
+        close,
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Field;
+
+    /// Apply `edit` to `ids` and record it, the way data entry does.
+    fn edit(journal: &mut Journal, metadata: &mut MetadataStore, ids: &[&str], edit: Edit) {
+        let changes = ids
+            .iter()
+            .map(|id| {
+                let before = metadata.get(id).cloned();
+                let mut entry = before.clone().unwrap_or_default();
+                edit.apply(&mut entry);
+                metadata.set(id, entry);
+                Change {
+                    id: id.to_string(),
+                    before,
+                    after: metadata.get(id).cloned(),
+                }
+            })
+            .collect();
+        journal.record(edit, changes);
+    }
+
+    fn title(metadata: &MetadataStore, id: &str) -> String {
+        metadata
+            .get(id)
+            .map(|m| m.title.clone())
+            .unwrap_or_default()
+    }
+
+    fn set_title(value: &str) -> Edit {
+        Edit::Set(Field::Title, value.to_string())
+    }
+
+    #[test]
+    fn typing_is_coalesced() {
+        let mut journal = Journal::default();
+        let mut metadata = MetadataStore::default();
+        for typed in ["H", "Ha", "Har"] {
+            edit(&mut journal, &mut metadata, &["a"], set_title(typed));
+        }
+        assert_eq!(journal.entries.len(), 1);
+        assert_eq!(journal.entries[0].changes[0].before, None);
+        assert_eq!(title(&metadata, "a"), "Har");
+
+        // Another field, another item, several items or a pause each start a new entry
+        let creator = Edit::Set(Field::Creator, "Ann".to_string());
+        edit(&mut journal, &mut metadata, &["a"], creator.clone());
+        edit(&mut journal, &mut metadata, &["b"], creator.clone());
+        edit(&mut journal, &mut metadata, &["a", "b"], creator);
+        edit(&mut journal, &mut metadata, &["a", "b"], set_title("Boat"));
+        edit(&mut journal, &mut metadata, &["a"], set_title("Boats"));
+        journal.entries.last_mut().unwrap().time -= COALESCE_SECS + 1;
+        edit(&mut journal, &mut metadata, &["a"], set_title("Boats!"));
+        // Setting a value an item already has is not an edit
+        edit(&mut journal, &mut metadata, &["b"], set_title("Boat"));
+        let described: Vec<_> = journal.entries.iter().map(|e| e.describe()).collect();
+        assert_eq!(
+            described,
+            [
+                "Set Title to \"Har\"",
+                "Set Creator to \"Ann\"",
+                "Set Creator to \"Ann\"",
+                "Set Title to \"Boat\" on 2 items",
+                "Set Title to \"Boats\"",
+                "Set Title to \"Boats!\"",
+            ]
+        );
+        assert_eq!(journal.cursor, journal.entries.len());
+    }
+
+    #[test]
+    fn typing_back_to_the_start_is_not_an_edit() {
+        let mut journal = Journal::default();
+        let mut metadata = MetadataStore::default();
+        edit(&mut journal, &mut metadata, &["a"], set_title("Harbour"));
+        journal.entries[0].time -= COALESCE_SECS + 1;
+        for typed in ["Harbou", "Harbour"] {
+            edit(&mut journal, &mut metadata, &["a"], set_title(typed));
+        }
+        assert_eq!(journal.entries.len(), 1);
+        assert_eq!(journal.cursor, 1);
+
+        // Down to nothing and back on an item that had no metadata
+        for typed in ["H", ""] {
+            edit(&mut journal, &mut metadata, &["b"], set_title(typed));
+        }
+        assert_eq!(journal.entries.len(), 1);
+        assert!(metadata.get("b").is_none());
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut journal = Journal::default();
+        let mut metadata = MetadataStore::default();
+        for i in 0..MAX_ENTRIES + 5 {
+            edit(
+                &mut journal,
+                &mut metadata,
+                &[&i.to_string()],
+                set_title("x"),
+            );
+        }
+        assert_eq!(journal.entries.len(), MAX_ENTRIES);
+        assert_eq!(journal.cursor, MAX_ENTRIES);
+        assert_eq!(journal.entries[0].changes[0].id, "5");
+
+        // Histories saved before the cap, or with undone edits, keep the cursor in range
+        journal.cursor = 3;
+        journal.entries.extend(journal.entries[..10].to_vec());
+        journal.trim();
+        assert_eq!(journal.entries.len(), MAX_ENTRIES);
+        assert_eq!(journal.cursor, 0);
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut journal = Journal::default();
+        let mut metadata = MetadataStore::default();
+        edit(&mut journal, &mut metadata, &["a"], set_title("One"));
+        journal.entries[0].time -= COALESCE_SECS + 1;
+        edit(&mut journal, &mut metadata, &["a"], set_title("Two"));
+        edit(
+            &mut journal,
+            &mut metadata,
+            &["a", "b"],
+            Edit::AddTag("boat".into()),
+        );
+        assert_eq!(journal.entries.len(), 3);
+        assert!(journal.can_undo() && !journal.can_redo());
+
+        for (target, a, b_tagged, done) in [
+            (
+                2,
+                "Two",
+                false,
+                vec!["Undid: Added tag \"boat\" on 2 items"],
+            ),
+            (
+                0,
+                "",
+                false,
+                vec!["Undid: Set Title to \"Two\"", "Undid: Set Title to \"One\""],
+            ),
+            (1, "One", false, vec!["Redid: Set Title to \"One\""]),
+            (
+                9,
+                "Two",
+                true,
+                vec![
+                    "Redid: Set Title to \"Two\"",
+                    "Redid: Added tag \"boat\" on 2 items",
+                ],
+            ),
+            (3, "Two", true, vec![]),
+        ] {
+            assert_eq!(journal.move_to(target, &mut metadata), done);
+            assert_eq!(title(&metadata, "a"), a);
+            assert_eq!(metadata.get("b").is_some(), b_tagged);
+        }
+        assert!(
+            metadata
+                .get("a")
+                .unwrap()
+                .tags
+                .contains(&"boat".to_string())
+        );
+
+        // A new edit after undoing drops what was undone
+        journal.move_to(1, &mut metadata);
+        assert!(journal.can_redo());
+        edit(&mut journal, &mut metadata, &["b"], set_title("Other"));
+        assert_eq!(journal.entries.len(), 2);
+        assert!(!journal.can_redo());
+        assert_eq!(title(&metadata, "a"), "One");
+    }
+}
@@ -63,17 +63,46 @@ use crate::project::Project;
 use crate::screens::Screen;
 use crate::subwindows::Subwindow;
 
+mod archive;
 mod box_login;
+mod box_metadata;
+mod bulk_edit;
+mod classification;
+mod data_entry;
+mod duplicates;
+mod embedded;
+mod fixity;
+mod format_risk;
+mod gapi_drive;
 mod gapi_login;
 mod homepage;
+mod inspect;
+mod integrity;
+mod journal;
 mod log;
+mod media;
+mod metadata;
+mod pdf_render;
 mod persist;
 mod program_settings;
 mod project_page;
 mod project_settings;
+mod pronom;
+mod representations;
+mod sampling;
+mod schema;
 mod screens;
+mod sensitive;
+mod sheet_format;
+mod sheet_plan;
+mod sheet_shard;
 mod subwindows;
+mod templates;
+mod text_preview;
 mod top_bar;
+mod urls;
+mod viewer;
+mod vocabulary;
 
 mod file_tree;
 mod project;
@@ -97,6 +126,22 @@ enum Message {
     FileTreeMessage(file_tree::FileTreeMessage),
     #[debug("Can't")]
     ProgSetMessage(program_settings::ProgramSettingsMessage),
+    ProjSetMessage(project_settings::ProjectSettingsMessage),
+    TemplatesMessage(templates::TemplatesMessage),
+    DataEntryMessage(data_entry::DataEntryMessage),
+    VocabularyMessage(vocabulary::VocabularyMessage),
+    BulkEditMessage(bulk_edit::BulkEditMessage),
+    JournalMessage(journal::JournalMessage),
+    SchemaMessage(schema::SchemaMessage),
+    FixityMessage(fixity::FixityMessage),
+    DuplicatesMessage(duplicates::DuplicatesMessage),
+    FormatRiskMessage(format_risk::FormatRiskMessage),
+    IssuesMessage(integrity::IssuesMessage),
+    SensitiveMessage(sensitive::SensitiveMessage),
+    ClassificationMessage(classification::ClassificationMessage),
+    BoxMetadataMessage(box_metadata::BoxMetadataMessage),
+    SamplingMessage(sampling::SamplingMessage),
+    ViewerMessage(viewer::ViewerMessage),
     Select(Item),
     CloseProj,
     PaneResized(pane_grid::ResizeEvent),
@@ -122,10 +167,44 @@ struct State {
     panes: pane_grid::State<Pane>,
     selected: Option<Item>,
     project: Option<project::Project>,
+    /// The tree saved when the project's sheet was generated
+    project_tree: Option<project_page::Node>,
+    metadata: metadata::MetadataStore,
+    journal: journal::Journal,
+    metadata_saves: metadata::SaveQueue,
+    /// Checksums recorded for the project's files
+    fixity: fixity::FixityStore,
+    fixity_state: fixity::FixityState,
+    /// Duplicate groups found in the project and reviewers' decisions on them
+    duplicates: duplicates::DuplicateStore,
+    duplicates_state: duplicates::DuplicatesState,
+    /// Metadata found embedded in the project's files when its sheet was built
+    embedded: embedded::EmbeddedStore,
+    /// What the project's files were last identified as
+    formats: format_risk::FormatStore,
+    format_risk_state: format_risk::FormatRiskState,
+    issues_state: integrity::IssuesState,
+    sensitive: sensitive::SensitiveStore,
+    sensitive_state: sensitive::SensitiveState,
+    classifications: classification::ClassificationStore,
+    classification_state: classification::ClassificationState,
+    box_metadata: box_metadata::BoxMetadataStore,
+    box_metadata_state: box_metadata::BoxMetadataState,
+    data_entry_state: data_entry::DataEntryState,
+    bulk_edit_state: bulk_edit::BulkEditState,
+    vocabularies: vocabulary::VocabularyStore,
+    vocabulary_state: vocabulary::VocabularyState,
     new_proj_state: project_page::NewProjState,
+    sheet_preview: Option<sheet_plan::SheetPlan>,
     homepage_state: homepage::HomepageState,
     file_tree_state: file_tree::FileTreeState,
     program_set_state: program_settings::ProgramSettingsState,
+    templates_state: templates::TemplatesState,
+    schema_state: schema::SchemaState,
+    viewer_state: viewer::ViewerState,
+    /// Audio playing from the viewer
+    #[debug(skip)]
+    playback: Option<media::Playback>,
     box_token: Option<AccessToken>,
     box_config: Configuration,
     #[debug(skip)]
@@ -133,8 +212,6 @@ struct State {
     log_receiver: Receiver<(String, tracing::Level)>,
 }
 
-pub static TEMPLATE_ID: &str = "1q_tfznc0LUGesvm2Yb5EqUCdhhpimJFLUkrZZJ8XvWY";
-
 static CONFIG_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
     let cd = directories::ProjectDirs::from("org", "GenEq", "TagMonster")
         .map(|pd| pd.config_local_dir().to_path_buf())
@@ -150,13 +227,40 @@ impl State {
             windows: vec![],
             panes: pane_grid::State::new(Pane::FileList).0,
             project: None,
+            project_tree: None,
+            metadata: metadata::MetadataStore::default(),
+            journal: journal::Journal::default(),
+            metadata_saves: metadata::SaveQueue::default(),
+            fixity: fixity::FixityStore::default(),
+            fixity_state: fixity::FixityState::default(),
+            duplicates: duplicates::DuplicateStore::default(),
+            duplicates_state: duplicates::DuplicatesState::default(),
+            embedded: embedded::EmbeddedStore::default(),
+            formats: format_risk::FormatStore::default(),
+            format_risk_state: format_risk::FormatRiskState::default(),
+            issues_state: integrity::IssuesState::default(),
+            sensitive: sensitive::SensitiveStore::default(),
+            sensitive_state: sensitive::SensitiveState::default(),
+            classifications: classification::ClassificationStore::default(),
+            classification_state: classification::ClassificationState::default(),
+            box_metadata: box_metadata::BoxMetadataStore::default(),
+            box_metadata_state: box_metadata::BoxMetadataState::default(),
+            data_entry_state: data_entry::DataEntryState::default(),
+            bulk_edit_state: bulk_edit::BulkEditState::default(),
+            vocabularies: vocabulary::VocabularyStore::default(),
+            vocabulary_state: vocabulary::VocabularyState::default(),
             screen: Screen::Home,
             new_proj_state: project_page::NewProjState::default(),
+            sheet_preview: None,
             statusline: Content::new(),
             show_logs: false,
             file_tree_state: FileTreeState::default(),
             homepage_state: homepage::HomepageState::default(),
             program_set_state: ProgramSettingsState::default(),
+            templates_state: templates::TemplatesState::default(),
+            schema_state: schema::SchemaState::default(),
+            viewer_state: viewer::ViewerState::default(),
+            playback: None,
             selected: None,
             box_token: None,
             box_config: Configuration::default(),
@@ -175,6 +279,8 @@ pub fn main() -> anyhow::Result<()> {
     let (tx, rx) = tokio::sync::broadcast::channel::<(String, tracing::Level)>(2usize.pow(16));
     let log_guard = log::init_logging(tx)?;
     let rx = std::sync::Arc::new(std::sync::Mutex::new(Some(rx)));
+    // Copies left by an earlier run that could not remove them
+    media::clear_temp();
     iced::daemon(
         move || {
             // This should only be called once, so it's safe to unwrap here
@@ -187,9 +293,14 @@ pub fn main() -> anyhow::Result<()> {
     .title("TagMonster")
     .theme(theme)
     .subscription(|state| {
-        Subscription::batch([window_subscription(state), log_subscription(state)])
+        Subscription::batch([
+            window_subscription(state),
+            log_subscription(state),
+            keyboard_subscription(state),
+        ])
     })
     .run()?;
+    media::clear_temp();
     drop(log_guard);
     Ok(())
 }
@@ -202,6 +313,18 @@ fn window_subscription(state: &State) -> Subscription<Message> {
     })
 }
 
+fn keyboard_subscription(_state: &State) -> Subscription<Message> {
+    iced::event::listen_with(|event, status, _id| match event {
+        iced::Event::Keyboard(iced::keyboard::Event::ModifiersChanged(modifiers)) => Some(
+            Message::FileTreeMessage(file_tree::FileTreeMessage::ModifiersChanged(modifiers)),
+        ),
+        iced::Event::Keyboard(event) if status == iced::event::Status::Ignored => {
+            journal::shortcut(&event)
+        }
+        _ => None,
+    })
+}
+
 struct LogSubscription(tokio::sync::broadcast::Receiver<(String, tracing::Level)>);
 
 impl iced_futures::subscription::Recipe for LogSubscription {
@@ -339,9 +462,48 @@ pub(crate) fn update(state: &mut State, message: Message) -> Task<Message> {
         Message::ProgSetMessage(prog_set_event) => {
             program_settings::handle_prog_settings(state, prog_set_event)
         }
+        Message::ProjSetMessage(proj_set_event) => {
+            project_settings::handle_project_settings(state, proj_set_event)
+        }
+        Message::TemplatesMessage(templates_event) => {
+            templates::templates_handle(state, templates_event)
+        }
+        Message::DataEntryMessage(data_entry_event) => {
+            data_entry::data_entry_handle(state, data_entry_event)
+        }
+        Message::VocabularyMessage(vocabulary_event) => {
+            vocabulary::vocabulary_handle(state, vocabulary_event)
+        }
+        Message::BulkEditMessage(bulk_edit_event) => {
+            bulk_edit::bulk_edit_handle(state, bulk_edit_event)
+        }
+        Message::JournalMessage(journal_event) => journal::journal_handle(state, journal_event),
+        Message::SchemaMessage(schema_event) => schema::schema_handle(state, schema_event),
+        Message::FixityMessage(fixity_event) => fixity::fixity_handle(state, fixity_event),
+        Message::DuplicatesMessage(duplicates_event) => {
+            duplicates::duplicates_handle(state, duplicates_event)
+        }
+        Message::FormatRiskMessage(format_risk_event) => {
+            format_risk::format_risk_handle(state, format_risk_event)
+        }
+        Message::IssuesMessage(issues_event) => integrity::issues_handle(state, issues_event),
+        Message::SensitiveMessage(sensitive_event) => {
+            sensitive::sensitive_handle(state, sensitive_event)
+        }
+        Message::ClassificationMessage(classification_event) => {
+            classification::classification_handle(state, classification_event)
+        }
+        Message::BoxMetadataMessage(box_metadata_event) => {
+            box_metadata::box_metadata_handle(state, box_metadata_event)
+        }
+        Message::SamplingMessage(sampling_event) => {
+            sampling::sampling_handle(state, sampling_event)
+        }
+        Message::ViewerMessage(viewer_event) => viewer::viewer_handle(state, viewer_event),
         Message::Select(item) => {
             state.selected = Some(item);
-            Task::none()
+            data_entry::selection_changed(state);
+            viewer::selection_changed(state)
         }
         Message::InitProgramSettings(program_settings_state) => {
             state.program_set_state = program_settings_state;
@@ -407,6 +569,22 @@ fn view(state: &State, window_id: window::Id) -> Element<Message> {
             Subwindow::ProjectSettings => project_settings::project_settings(state),
             Subwindow::ProgramSettings => program_settings::program_settings(state),
             Subwindow::NewProject => project_page::new_project_view(state),
+            Subwindow::Templates => templates::templates(state),
+            Subwindow::Vocabularies => vocabulary::vocabularies(state),
+            Subwindow::History => journal::history(state),
+            Subwindow::Schema => schema::schema(state),
+            Subwindow::ValidationReport => schema::validation_report(state),
+            Subwindow::Fixity => fixity::fixity_report(state),
+            Subwindow::Duplicates => duplicates::duplicates_review(state),
+            Subwindow::Sampling => sampling::sampling_settings(state),
+            Subwindow::FormatRisks => format_risk::format_risks(state),
+            Subwindow::RiskRegistry => format_risk::risk_registry(state),
+            Subwindow::FileIssues => integrity::file_issues(state),
+            Subwindow::SensitiveContent => sensitive::sensitive_report(state),
+            Subwindow::Detectors => sensitive::detectors(state),
+            Subwindow::Classifications => classification::classifications(state),
+            Subwindow::BoxMetadata => box_metadata::box_metadata(state),
+            Subwindow::SheetPreview => sheet_plan::sheet_preview(state),
         }
     } else {
         text(format!(
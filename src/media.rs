@@ -0,0 +1,1748 @@
+use std::{
+    fmt::Write as _,
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use r#box::apis::{
+    Error,
+    configuration::Configuration,
+    downloads_api::{GetFilesIdContentParams, get_files_id_content},
+};
+use rodio::{Decoder, OutputStream, OutputStreamBuilder, Sink, Source};
+use tokio_stream::StreamExt;
+
+use crate::sampling::Sample;
+
+/// Extensions probed for audio and video metadata.
+pub(crate) const MEDIA_EXTENSIONS: [&str; 19] = [
+    "wav", "wave", "aif", "aiff", "aifc", "flac", "mp3", "ogg", "oga", "opus", "m4a", "mp4", "m4v",
+    "mov", "mkv", "mka", "webm", "avi", "3gp",
+];
+
+/// Container headers are almost always in the first megabyte.
+const HEAD_BYTES: u64 = 1024 * 1024;
+
+/// MP4s written without "fast start" keep their index at the end, and Ogg durations come from
+/// the last page.
+const TAIL_BYTES: u64 = 1024 * 1024;
+
+/// Columns of the waveform overview.
+const WAVEFORM_BUCKETS: usize = 600;
+
+/// Longer recordings are sampled in this many evenly spaced windows rather than downloaded.
+const WAVEFORM_WINDOWS: usize = 150;
+const WINDOW_BYTES: u64 = 128 * 1024;
+
+/// Compressed recordings up to this size are downloaded and decoded for a waveform.
+const MAX_DECODE_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct AudioStream {
+    pub codec: String,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    pub bits: Option<u16>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct VideoStream {
+    pub codec: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
+}
+
+/// Where the samples of an uncompressed file are, so a waveform can be drawn from them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PcmLayout {
+    pub offset: u64,
+    pub length: u64,
+    pub channels: u16,
+    pub bits: u16,
+    pub big_endian: bool,
+    pub float: bool,
+}
+
+/// Technical metadata read from a media file's container headers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct MediaInfo {
+    pub container: String,
+    /// Seconds
+    pub duration: Option<f64>,
+    pub audio: Vec<AudioStream>,
+    pub video: Vec<VideoStream>,
+    pub pcm: Option<PcmLayout>,
+}
+
+impl MediaInfo {
+    /// Duration as h:mm:ss, which is how catalogues usually record it.
+    pub fn duration_text(&self) -> Option<String> {
+        self.duration.map(|d| {
+            let secs = d.round() as u64;
+            format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+        })
+    }
+
+    /// All streams, e.g. "H.264 1920×1080 25 fps; AAC 48 kHz stereo".
+    pub fn summary(&self) -> String {
+        let video = self.video.iter().map(VideoStream::describe);
+        let audio = self.audio.iter().map(AudioStream::describe);
+        video.chain(audio).collect::<Vec<_>>().join("; ")
+    }
+}
+
+impl VideoStream {
+    pub fn describe(&self) -> String {
+        let mut s = self.codec.clone();
+        if let (Some(w), Some(h)) = (self.width, self.height) {
+            let _ = write!(s, " {w}×{h}");
+        }
+        if let Some(fps) = self.frame_rate {
+            let _ = write!(s, " {} fps", trim_float(fps, 3));
+        }
+        s
+    }
+}
+
+impl AudioStream {
+    pub fn describe(&self) -> String {
+        let mut s = self.codec.clone();
+        if let Some(rate) = self.sample_rate {
+            let _ = write!(s, " {} kHz", trim_float(rate as f64 / 1000.0, 3));
+        }
+        if let Some(bits) = self.bits {
+            let _ = write!(s, " {bits}-bit");
+        }
+        if let Some(channels) = self.channels {
+            s.push(' ');
+            s.push_str(&channel_name(channels));
+        }
+        s
+    }
+}
+
+fn trim_float(value: f64, places: usize) -> String {
+    let s = format!("{value:.places$}");
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn channel_name(channels: u16) -> String {
+    match channels {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        n => format!("{n} channels"),
+    }
+}
+
+fn be16(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be64(b: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(b.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn le16(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le64(b: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(b.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn fourcc(b: &[u8], at: usize) -> Option<&str> {
+    std::str::from_utf8(b.get(at..at + 4)?).ok()
+}
+
+/// Read a media file's headers from its first and last bytes.
+///
+/// `tail` may be empty, and `size` is the whole file's size in bytes.
+pub(crate) fn parse(head: &[u8], tail: &[u8], size: u64) -> Option<MediaInfo> {
+    let magic = head.get(..12)?;
+    if magic.starts_with(b"RIFF") && &magic[8..12] == b"WAVE" {
+        riff_wave(head)
+    } else if magic.starts_with(b"RIFF") && &magic[8..12] == b"AVI " {
+        avi(head)
+    } else if magic.starts_with(b"FORM") && matches!(&magic[8..12], b"AIFF" | b"AIFC") {
+        aiff(head)
+    } else if magic.starts_with(b"OggS") {
+        ogg(head, tail)
+    } else if magic.starts_with(b"\x1A\x45\xDF\xA3") {
+        matroska(head)
+    } else if &magic[4..8] == b"ftyp" || matches!(&magic[4..8], b"moov" | b"mdat" | b"wide") {
+        mp4(head, tail)
+    } else {
+        let start = id3_len(head);
+        let rest = head.get(start..)?;
+        if rest.starts_with(b"fLaC") {
+            flac(rest)
+        } else {
+            mp3(head, start, size)
+        }
+    }
+}
+
+/// RIFF and IFF chunks, as (id, body offset, declared body length).
+fn chunks(b: &[u8], start: usize, big_endian: bool) -> Vec<(&str, usize, u64)> {
+    let mut out = vec![];
+    let mut at = start;
+    while let Some(id) = fourcc(b, at) {
+        let Some(len) = (if big_endian {
+            be32(b, at + 4)
+        } else {
+            le32(b, at + 4)
+        }) else {
+            break;
+        };
+        out.push((id, at + 8, len as u64));
+        // Chunks are padded to an even length
+        at += 8 + len as usize + (len as usize & 1);
+    }
+    out
+}
+
+fn wave_codec(tag: u16) -> String {
+    match tag {
+        1 => "PCM".to_string(),
+        2 => "MS ADPCM".to_string(),
+        3 => "PCM float".to_string(),
+        6 => "A-law".to_string(),
+        7 => "µ-law".to_string(),
+        0x11 => "IMA ADPCM".to_string(),
+        0x50 => "MPEG audio".to_string(),
+        0x55 => "MP3".to_string(),
+        0xFF | 0x1610 => "AAC".to_string(),
+        0x2000 => "AC-3".to_string(),
+        t => format!("WAVE format 0x{t:04X}"),
+    }
+}
+
+/// WAVEFORMATEX, shared by WAV files and AVI audio streams.
+fn wave_format(fmt: &[u8]) -> Option<(u16, AudioStream, u32)> {
+    let mut tag = le16(fmt, 0)?;
+    // WAVE_FORMAT_EXTENSIBLE keeps the real tag at the start of the sub-format GUID
+    if tag == 0xFFFE {
+        tag = le16(fmt, 24).unwrap_or(tag);
+    }
+    let bits = le16(fmt, 14).filter(|b| *b > 0);
+    Some((
+        tag,
+        AudioStream {
+            codec: wave_codec(tag),
+            sample_rate: le32(fmt, 4),
+            channels: le16(fmt, 2),
+            bits: bits.filter(|_| matches!(tag, 1 | 3)),
+        },
+        le32(fmt, 8)?,
+    ))
+}
+
+fn riff_wave(b: &[u8]) -> Option<MediaInfo> {
+    let mut info = MediaInfo {
+        container: "WAV".to_string(),
+        ..Default::default()
+    };
+    let mut format = None;
+    for (id, at, len) in chunks(b, 12, false) {
+        match id {
+            "fmt " => format = wave_format(b.get(at..)?),
+            "data" => {
+                let Some((tag, audio, byte_rate)) = format.clone() else {
+                    break;
+                };
+                if byte_rate > 0 {
+                    info.duration = Some(len as f64 / byte_rate as f64);
+                }
+                if matches!(tag, 1 | 3) {
+                    info.pcm = Some(PcmLayout {
+                        offset: at as u64,
+                        length: len,
+                        channels: audio.channels.unwrap_or(1),
+                        bits: audio.bits.unwrap_or(16),
+                        big_endian: false,
+                        float: tag == 3,
+                    });
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+    info.audio.extend(format.map(|(_, audio, _)| audio));
+    Some(info)
+}
+
+/// The 80-bit extended float AIFF uses for its sample rate.
+fn extended(b: &[u8], at: usize) -> Option<f64> {
+    let exponent = (be16(b, at)? & 0x7FFF) as i32 - 16383;
+    let mantissa = be64(b, at + 2)?;
+    Some(mantissa as f64 * 2f64.powi(exponent - 63))
+}
+
+fn aiff(b: &[u8]) -> Option<MediaInfo> {
+    let compressed = &b[8..12] == b"AIFC";
+    let mut info = MediaInfo {
+        container: if compressed { "AIFF-C" } else { "AIFF" }.to_string(),
+        ..Default::default()
+    };
+    let mut comm = None;
+    for (id, at, len) in chunks(b, 12, true) {
+        match id {
+            "COMM" => {
+                let channels = be16(b, at)?;
+                let frames = be32(b, at + 2)?;
+                let bits = be16(b, at + 6)?;
+                let rate = extended(b, at + 8)?;
+                let compression = if compressed {
+                    fourcc(b, at + 18).unwrap_or("NONE")
+                } else {
+                    "NONE"
+                };
+                let codec = match compression {
+                    "NONE" | "twos" | "sowt" => "PCM",
+                    "fl32" | "FL32" | "fl64" => "PCM float",
+                    "ulaw" | "ULAW" => "µ-law",
+                    "alaw" | "ALAW" => "A-law",
+                    "ima4" => "IMA ADPCM",
+                    other => other,
+                };
+                if rate > 0.0 {
+                    info.duration = Some(frames as f64 / rate);
+                }
+                info.audio.push(AudioStream {
+                    codec: codec.to_string(),
+                    sample_rate: Some(rate.round() as u32),
+                    channels: Some(channels),
+                    bits: Some(bits),
+                });
+                comm = Some((channels, bits, compression.to_string()));
+            }
+            "SSND" => {
+                let Some((channels, bits, compression)) = comm.clone() else {
+                    continue;
+                };
+                // The chunk starts with an offset and block size before the samples
+                let skip = be32(b, at)? as u64;
+                if matches!(compression.as_str(), "NONE" | "twos" | "sowt") {
+                    info.pcm = Some(PcmLayout {
+                        offset: at as u64 + 8 + skip,
+                        length: len.saturating_sub(8 + skip),
+                        channels,
+                        bits,
+                        big_endian: compression != "sowt",
+                        float: false,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+/// Length of a leading ID3v2 tag, or 0.
+fn id3_len(b: &[u8]) -> usize {
+    if !b.starts_with(b"ID3") || b.len() < 10 {
+        return 0;
+    }
+    // Sizes are "synchsafe": 7 bits per byte
+    let size = b[6..10]
+        .iter()
+        .fold(0usize, |acc, byte| (acc << 7) | (*byte & 0x7F) as usize);
+    let footer = if b[5] & 0x10 != 0 { 10 } else { 0 };
+    10 + size + footer
+}
+
+fn flac(b: &[u8]) -> Option<MediaInfo> {
+    // STREAMINFO is always the first metadata block
+    let s = b.get(8..8 + 34)?;
+    let rate = (s[10] as u32) << 12 | (s[11] as u32) << 4 | (s[12] as u32) >> 4;
+    let channels = ((s[12] >> 1) & 0x07) as u16 + 1;
+    let bits = (((s[12] & 1) << 4) | (s[13] >> 4)) as u16 + 1;
+    let samples = ((s[13] & 0x0F) as u64) << 32 | be32(s, 14)? as u64;
+    Some(MediaInfo {
+        container: "FLAC".to_string(),
+        duration: (rate > 0 && samples > 0).then(|| samples as f64 / rate as f64),
+        audio: vec![AudioStream {
+            codec: "FLAC".to_string(),
+            sample_rate: Some(rate),
+            channels: Some(channels),
+            bits: Some(bits),
+        }],
+        ..Default::default()
+    })
+}
+
+/// Kilobits per second by index, for MPEG-1 and MPEG-2/2.5 layers I, II and III.
+const MP3_BITRATES: [[[u16; 16]; 3]; 2] = [
+    [
+        [
+            0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448, 0,
+        ],
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 0,
+        ],
+        [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 0,
+        ],
+    ],
+    [
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256, 0,
+        ],
+        [
+            0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0,
+        ],
+        [
+            0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0,
+        ],
+    ],
+];
+
+/// An MPEG audio frame header.
+struct MpegFrame {
+    mpeg1: bool,
+    layer: u8,
+    /// Bits per second
+    bitrate: u32,
+    rate: u32,
+    mono: bool,
+    /// Bytes, including the header
+    length: usize,
+}
+
+fn mpeg_frame(b: &[u8], at: usize) -> Option<MpegFrame> {
+    let h = b.get(at..at + 4)?;
+    let version = (h[1] >> 3) & 3;
+    let layer_bits = (h[1] >> 1) & 3;
+    let bitrate_index = (h[2] >> 4) as usize;
+    let rate_index = ((h[2] >> 2) & 3) as usize;
+    if h[0] != 0xFF
+        || h[1] & 0xE0 != 0xE0
+        || version == 1
+        || layer_bits == 0
+        || bitrate_index == 0
+        || bitrate_index == 15
+        || rate_index == 3
+    {
+        return None;
+    }
+    let mpeg1 = version == 3;
+    let layer = 4 - layer_bits;
+    let bitrate =
+        MP3_BITRATES[usize::from(!mpeg1)][layer as usize - 1][bitrate_index] as u32 * 1000;
+    let rate = [44100, 48000, 32000][rate_index]
+        / match version {
+            3 => 1,
+            2 => 2,
+            _ => 4,
+        };
+    let padding = ((h[2] >> 1) & 1) as u32;
+    let length = match (layer, mpeg1) {
+        (1, _) => (12 * bitrate / rate + padding) * 4,
+        (3, false) => 72 * bitrate / rate + padding,
+        _ => 144 * bitrate / rate + padding,
+    } as usize;
+    Some(MpegFrame {
+        mpeg1,
+        layer,
+        bitrate,
+        rate,
+        mono: h[3] >> 6 == 3,
+        length,
+    })
+}
+
+fn mp3(b: &[u8], start: usize, size: u64) -> Option<MediaInfo> {
+    // Sync bits turn up by chance in other data, so a frame only counts if another follows it
+    let (at, frame) = (start..b.len()).find_map(|i| {
+        let frame = mpeg_frame(b, i)?;
+        mpeg_frame(b, i + frame.length).map(|_| (i, frame))
+    })?;
+    let MpegFrame {
+        mpeg1,
+        layer,
+        bitrate,
+        rate,
+        mono,
+        ..
+    } = frame;
+    let samples_per_frame = match (layer, mpeg1) {
+        (1, _) => 384,
+        (3, false) => 576,
+        _ => 1152,
+    };
+
+    // VBR files say how many frames they have in a Xing or VBRI header in the first frame
+    let side_info = match (mpeg1, mono) {
+        (true, false) => 32,
+        (true, true) | (false, false) => 17,
+        (false, true) => 9,
+    };
+    let xing = at + 4 + side_info;
+    let frames = if matches!(fourcc(b, xing), Some("Xing" | "Info")) && be32(b, xing + 4)? & 1 != 0
+    {
+        be32(b, xing + 8)
+    } else if fourcc(b, at + 36) == Some("VBRI") {
+        be32(b, at + 36 + 14)
+    } else {
+        None
+    };
+    let duration = match frames {
+        Some(frames) => Some(frames as f64 * samples_per_frame as f64 / rate as f64),
+        None if size > at as u64 => Some((size - at as u64) as f64 * 8.0 / bitrate as f64),
+        None => None,
+    };
+    Some(MediaInfo {
+        container: "MPEG audio".to_string(),
+        duration,
+        audio: vec![AudioStream {
+            codec: format!("MP{layer}"),
+            sample_rate: Some(rate),
+            channels: Some(if mono { 1 } else { 2 }),
+            bits: None,
+        }],
+        ..Default::default()
+    })
+}
+
+fn ogg(head: &[u8], tail: &[u8]) -> Option<MediaInfo> {
+    // Short files are downloaded whole, so the head is also the tail
+    let tail = if tail.is_empty() { head } else { tail };
+    // The first page holds the identification header of the first stream
+    let segments = *head.get(26)? as usize;
+    let packet = head.get(27 + segments..)?;
+    let (codec, rate, channels, pre_skip) = if packet.starts_with(b"\x01vorbis") {
+        ("Vorbis", le32(packet, 12)?, *packet.get(11)? as u16, 0)
+    } else if packet.starts_with(b"OpusHead") {
+        // Opus always runs at 48 kHz; the header's rate is only the input's
+        ("Opus", 48000, *packet.get(9)? as u16, le16(packet, 10)?)
+    } else if packet.starts_with(b"\x7FFLAC") {
+        let mut info = flac(packet.get(9..)?)?;
+        info.container = "Ogg".to_string();
+        let rate = info.audio.first()?.sample_rate.unwrap_or(0);
+        info.duration = last_granule(tail)
+            .filter(|_| rate > 0)
+            .map(|g| g as f64 / rate as f64);
+        return Some(info);
+    } else if packet.starts_with(b"\x80theora") {
+        return Some(MediaInfo {
+            container: "Ogg".to_string(),
+            video: vec![VideoStream {
+                codec: "Theora".to_string(),
+                width: be16(packet, 10).map(|w| w as u32 * 16),
+                height: be16(packet, 12).map(|h| h as u32 * 16),
+                frame_rate: None,
+            }],
+            ..Default::default()
+        });
+    } else {
+        return None;
+    };
+    let duration = last_granule(tail)
+        .filter(|_| rate > 0)
+        .map(|g| g.saturating_sub(pre_skip as u64) as f64 / rate as f64);
+    Some(MediaInfo {
+        container: "Ogg".to_string(),
+        duration,
+        audio: vec![AudioStream {
+            codec: codec.to_string(),
+            sample_rate: Some(rate),
+            channels: Some(channels),
+            bits: None,
+        }],
+        ..Default::default()
+    })
+}
+
+/// The granule position of the last Ogg page, which counts samples for audio streams.
+fn last_granule(tail: &[u8]) -> Option<u64> {
+    let at = tail.windows(4).rposition(|w| w == b"OggS")?;
+    le64(tail, at + 6).filter(|g| *g != u64::MAX)
+}
+
+/// Complete ISO base media boxes in `b[start..end]`, as (type, body start, body end).
+fn boxes(b: &[u8], start: usize, end: usize) -> Vec<(&str, usize, usize)> {
+    let mut out = vec![];
+    let mut at = start;
+    while at + 8 <= end {
+        let (Some(size), Some(kind)) = (be32(b, at), fourcc(b, at + 4)) else {
+            break;
+        };
+        let (header, size) = match size {
+            0 => (8, (end - at) as u64),
+            1 => match be64(b, at + 8) {
+                Some(large) => (16, large),
+                None => break,
+            },
+            s => (8, s as u64),
+        };
+        let body_end = at.saturating_add(size as usize);
+        // A box cut off by the end of the download can't be read
+        if size < header as u64 || body_end > end {
+            break;
+        }
+        out.push((kind, at + header, body_end));
+        at = body_end;
+    }
+    out
+}
+
+fn find_box(b: &[u8], start: usize, end: usize, kind: &str) -> Option<(usize, usize)> {
+    boxes(b, start, end)
+        .into_iter()
+        .find(|(k, _, _)| *k == kind)
+        .map(|(_, s, e)| (s, e))
+}
+
+fn mp4_codec(format: &str) -> String {
+    match format {
+        "avc1" | "avc3" => "H.264",
+        "hvc1" | "hev1" => "H.265",
+        "mp4v" => "MPEG-4 Visual",
+        "av01" => "AV1",
+        "vp08" => "VP8",
+        "vp09" => "VP9",
+        "apch" | "apcn" | "apcs" | "apco" | "ap4h" | "ap4x" => "ProRes",
+        "jpeg" | "mjpa" | "mjpb" => "Motion JPEG",
+        "dvc " | "dvcp" | "dv5n" | "dv5p" => "DV",
+        "s263" => "H.263",
+        "mp4a" => "AAC",
+        "ac-3" => "AC-3",
+        "ec-3" => "E-AC-3",
+        "alac" => "ALAC",
+        "Opus" => "Opus",
+        "fLaC" => "FLAC",
+        ".mp3" => "MP3",
+        "samr" => "AMR",
+        "sowt" | "twos" | "lpcm" | "in24" | "in32" | "raw " => "PCM",
+        "fl32" | "fl64" => "PCM float",
+        "ulaw" => "µ-law",
+        "alaw" => "A-law",
+        other => other.trim(),
+    }
+    .to_string()
+}
+
+fn mp4(head: &[u8], tail: &[u8]) -> Option<MediaInfo> {
+    let major = fourcc(head, 8).filter(|_| &head[4..8] == b"ftyp");
+    let container = match major {
+        Some("qt  ") => "QuickTime",
+        Some("M4A " | "M4B ") => "MPEG-4 audio",
+        Some(b) if b.starts_with("3g") => "3GPP",
+        _ => "MPEG-4",
+    };
+    // Without "fast start" the index comes after the media data
+    let moov = find_box(head, 0, head.len(), "moov")
+        .map(|range| (head, range))
+        .or_else(|| {
+            // The tail starts mid-box, so look for a complete "moov" box anywhere in it
+            tail.windows(4)
+                .enumerate()
+                .filter(|(at, w)| *w == b"moov" && *at >= 4)
+                .find_map(|(at, _)| find_box(tail, at - 4, tail.len(), "moov"))
+                .map(|range| (tail, range))
+        })?;
+    let (b, (start, end)) = moov;
+    let mut info = MediaInfo {
+        container: container.to_string(),
+        ..Default::default()
+    };
+    if let Some((at, _)) = find_box(b, start, end, "mvhd") {
+        let (scale, duration) = if *b.get(at)? == 1 {
+            (be32(b, at + 20)?, be64(b, at + 24)?)
+        } else {
+            (be32(b, at + 12)?, be32(b, at + 16)? as u64)
+        };
+        if scale > 0 {
+            info.duration = Some(duration as f64 / scale as f64);
+        }
+    }
+    for (kind, at, track_end) in boxes(b, start, end) {
+        if kind == "trak" {
+            mp4_track(b, at, track_end, &mut info);
+        }
+    }
+    Some(info)
+}
+
+fn mp4_track(b: &[u8], start: usize, end: usize, info: &mut MediaInfo) -> Option<()> {
+    let (mdia, mdia_end) = find_box(b, start, end, "mdia")?;
+    let (hdlr, _) = find_box(b, mdia, mdia_end, "hdlr")?;
+    let handler = fourcc(b, hdlr + 8)?;
+    let timescale = find_box(b, mdia, mdia_end, "mdhd")
+        .and_then(|(at, _)| be32(b, if *b.get(at)? == 1 { at + 20 } else { at + 12 }));
+    let (minf, minf_end) = find_box(b, mdia, mdia_end, "minf")?;
+    let (stbl, stbl_end) = find_box(b, minf, minf_end, "stbl")?;
+    let (stsd, _) = find_box(b, stbl, stbl_end, "stsd")?;
+    // The first sample entry, after the full box header and entry count
+    let entry = stsd + 8;
+    let format = fourcc(b, entry + 4)?;
+    let fields = entry + 8;
+    match handler {
+        "vide" => {
+            // The frame rate follows from the first run of sample durations
+            let frame_rate = find_box(b, stbl, stbl_end, "stts").and_then(|(at, _)| {
+                let delta = be32(b, at + 12)?;
+                let scale = timescale?;
+                (delta > 0).then(|| scale as f64 / delta as f64)
+            });
+            info.video.push(VideoStream {
+                codec: mp4_codec(format),
+                width: be16(b, fields + 24).map(u32::from),
+                height: be16(b, fields + 26).map(u32::from),
+                frame_rate,
+            });
+        }
+        "soun" => {
+            let codec = mp4_codec(format);
+            let bits = be16(b, fields + 18).filter(|_| codec.starts_with("PCM"));
+            info.audio.push(AudioStream {
+                codec,
+                sample_rate: be16(b, fields + 24).map(u32::from),
+                channels: be16(b, fields + 16),
+                bits,
+            });
+        }
+        _ => {}
+    }
+    Some(())
+}
+
+/// An EBML variable-length integer, as (value, length). IDs keep their marker bits.
+fn vint(b: &[u8], at: usize, keep_marker: bool) -> Option<(u64, usize)> {
+    let first = *b.get(at)?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 {
+        return None;
+    }
+    let mut value = if keep_marker {
+        first as u64
+    } else if len == 8 {
+        // The first byte of an eight-byte vint is only its marker
+        0
+    } else {
+        (first & (0xFF >> len)) as u64
+    };
+    for i in 1..len {
+        value = (value << 8) | *b.get(at + i)? as u64;
+    }
+    // All ones means the size is unknown, as in live recordings
+    if !keep_marker && value == (1 << (7 * len)) - 1 {
+        value = u64::MAX;
+    }
+    Some((value, len))
+}
+
+/// EBML elements in `b[start..end]`, as (ID, body range).
+fn elements(b: &[u8], start: usize, end: usize) -> Vec<(u64, usize, usize)> {
+    let mut out = vec![];
+    let mut at = start;
+    while at < end {
+        let Some((id, id_len)) = vint(b, at, true) else {
+            break;
+        };
+        let Some((size, size_len)) = vint(b, at + id_len, false) else {
+            break;
+        };
+        let body = at + id_len + size_len;
+        let body_end = if size == u64::MAX {
+            end
+        } else {
+            body.saturating_add(size as usize).min(end)
+        };
+        out.push((id, body, body_end));
+        // Clusters hold the media itself, and the headers come before them
+        if id == 0x1F43B675 || body_end <= at {
+            break;
+        }
+        at = body_end;
+    }
+    out
+}
+
+fn ebml_uint(b: &[u8], start: usize, end: usize) -> Option<u64> {
+    Some(
+        b.get(start..end)?
+            .iter()
+            .fold(0, |acc, byte| (acc << 8) | *byte as u64),
+    )
+}
+
+fn ebml_float(b: &[u8], start: usize, end: usize) -> Option<f64> {
+    let bytes = b.get(start..end)?;
+    match bytes.len() {
+        4 => Some(f32::from_be_bytes(bytes.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(bytes.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+fn matroska_codec(id: &str) -> String {
+    match id {
+        "V_MPEG4/ISO/AVC" => "H.264",
+        "V_MPEGH/ISO/HEVC" => "H.265",
+        "V_VP8" => "VP8",
+        "V_VP9" => "VP9",
+        "V_AV1" => "AV1",
+        "V_THEORA" => "Theora",
+        "V_MPEG2" => "MPEG-2",
+        "V_MJPEG" => "Motion JPEG",
+        "A_AAC" => "AAC",
+        "A_OPUS" => "Opus",
+        "A_VORBIS" => "Vorbis",
+        "A_FLAC" => "FLAC",
+        "A_AC3" => "AC-3",
+        "A_EAC3" => "E-AC-3",
+        "A_MPEG/L3" => "MP3",
+        "A_PCM/INT/LIT" | "A_PCM/INT/BIG" => "PCM",
+        "A_PCM/FLOAT/IEEE" => "PCM float",
+        other => other
+            .strip_prefix("A_")
+            .or(other.strip_prefix("V_"))
+            .unwrap_or(other),
+    }
+    .to_string()
+}
+
+fn matroska(b: &[u8]) -> Option<MediaInfo> {
+    let top = elements(b, 0, b.len());
+    let doc_type = top
+        .iter()
+        .find(|(id, _, _)| *id == 0x1A45DFA3)
+        .and_then(|(_, s, e)| {
+            elements(b, *s, *e)
+                .into_iter()
+                .find(|(id, _, _)| *id == 0x4282)
+                .and_then(|(_, s, e)| Some(String::from_utf8_lossy(b.get(s..e)?).into_owned()))
+        });
+    let mut info = MediaInfo {
+        container: if doc_type.as_deref() == Some("webm") {
+            "WebM"
+        } else {
+            "Matroska"
+        }
+        .to_string(),
+        ..Default::default()
+    };
+    let (_, seg, seg_end) = *top.iter().find(|(id, _, _)| *id == 0x18538067)?;
+    for (id, start, end) in elements(b, seg, seg_end) {
+        match id {
+            // Info
+            0x1549A966 => {
+                let mut scale = 1_000_000.0;
+                let mut duration = None;
+                for (id, s, e) in elements(b, start, end) {
+                    match id {
+                        0x2AD7B1 => scale = ebml_uint(b, s, e).map_or(scale, |s| s as f64),
+                        0x4489 => duration = ebml_float(b, s, e),
+                        _ => {}
+                    }
+                }
+                info.duration = duration.map(|d| d * scale / 1e9);
+            }
+            // Tracks
+            0x1654AE6B => {
+                for (id, s, e) in elements(b, start, end) {
+                    if id == 0xAE {
+                        matroska_track(b, s, e, &mut info);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+fn matroska_track(b: &[u8], start: usize, end: usize, info: &mut MediaInfo) {
+    let mut kind = 0;
+    let mut codec = String::new();
+    let mut video = VideoStream::default();
+    let mut audio = AudioStream::default();
+    for (id, s, e) in elements(b, start, end) {
+        match id {
+            0x83 => kind = ebml_uint(b, s, e).unwrap_or_default(),
+            0x86 => {
+                codec = matroska_codec(&String::from_utf8_lossy(b.get(s..e).unwrap_or_default()))
+            }
+            // DefaultDuration, nanoseconds per frame
+            0x23E383 => {
+                let ns = ebml_uint(b, s, e).unwrap_or_default();
+                video.frame_rate = (ns > 0).then(|| 1e9 / ns as f64);
+            }
+            0xE0 => {
+                for (id, s, e) in elements(b, s, e) {
+                    match id {
+                        0xB0 => video.width = ebml_uint(b, s, e).map(|w| w as u32),
+                        0xBA => video.height = ebml_uint(b, s, e).map(|h| h as u32),
+                        _ => {}
+                    }
+                }
+            }
+            0xE1 => {
+                for (id, s, e) in elements(b, s, e) {
+                    match id {
+                        0xB5 => audio.sample_rate = ebml_float(b, s, e).map(|r| r as u32),
+                        0x9F => audio.channels = ebml_uint(b, s, e).map(|c| c as u16),
+                        0x6264 => audio.bits = ebml_uint(b, s, e).map(|b| b as u16),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    match kind {
+        1 => info.video.push(VideoStream { codec, ..video }),
+        2 => info.audio.push(AudioStream { codec, ..audio }),
+        _ => {}
+    }
+}
+
+fn avi(b: &[u8]) -> Option<MediaInfo> {
+    let mut info = MediaInfo {
+        container: "AVI".to_string(),
+        ..Default::default()
+    };
+    let (_, hdrl, hdrl_len) = chunks(b, 12, false)
+        .into_iter()
+        .find(|(id, at, _)| *id == "LIST" && fourcc(b, *at) == Some("hdrl"))?;
+    let hdrl_end = (hdrl + hdrl_len as usize).min(b.len());
+    let mut frame_rate = None;
+    for (id, at, len) in chunks(&b[..hdrl_end], hdrl + 4, false) {
+        match id {
+            "avih" => {
+                let micros = le32(b, at)?;
+                let frames = le32(b, at + 16)?;
+                if micros > 0 {
+                    frame_rate = Some(1e6 / micros as f64);
+                    info.duration = Some(frames as f64 * micros as f64 / 1e6);
+                }
+            }
+            "LIST" if fourcc(b, at) == Some("strl") => {
+                let strl_end = (at + len as usize).min(hdrl_end);
+                let strl = chunks(&b[..strl_end], at + 4, false);
+                let Some((_, strh, _)) = strl.iter().find(|(id, _, _)| *id == "strh") else {
+                    continue;
+                };
+                let strf = strl.iter().find(|(id, _, _)| *id == "strf").map(|c| c.1);
+                match fourcc(b, *strh) {
+                    Some("vids") => {
+                        let handler = fourcc(b, *strh + 4).unwrap_or("").trim().to_string();
+                        let compression = strf.and_then(|at| fourcc(b, at + 16));
+                        info.video.push(VideoStream {
+                            codec: avi_codec(compression.unwrap_or(&handler)),
+                            width: strf.and_then(|at| le32(b, at + 4)),
+                            height: strf.and_then(|at| le32(b, at + 8)).map(|h| {
+                                // Negative heights mean the image is stored top down
+                                (h as i32).unsigned_abs()
+                            }),
+                            frame_rate,
+                        });
+                    }
+                    Some("auds") => {
+                        if let Some((_, audio, _)) = strf.and_then(|at| wave_format(b.get(at..)?)) {
+                            info.audio.push(audio);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+fn avi_codec(code: &str) -> String {
+    match code.to_ascii_uppercase().trim() {
+        "H264" | "X264" | "AVC1" => "H.264".to_string(),
+        "XVID" | "DIVX" | "DX50" | "FMP4" | "MP4V" => "MPEG-4 Visual".to_string(),
+        "MJPG" => "Motion JPEG".to_string(),
+        "DVSD" | "DV25" | "DV50" => "DV".to_string(),
+        "" | "\0\0\0\0" => "Uncompressed".to_string(),
+        _ => code.trim().to_string(),
+    }
+}
+
+/// Peak level of each of `buckets` slices of the samples, between 0 and 1.
+fn peaks(samples: &[u8], layout: &PcmLayout, buckets: usize) -> Vec<f32> {
+    let width = (layout.bits as usize).div_ceil(8);
+    let frame = width * layout.channels.max(1) as usize;
+    if width == 0 || width > 8 || frame == 0 || samples.len() < frame {
+        return vec![];
+    }
+    let frames = samples.len() / frame;
+    let per_bucket = frames.div_ceil(buckets).max(1);
+    let level = |s: &[u8]| -> f32 {
+        let mut bytes = [0u8; 8];
+        if layout.big_endian {
+            bytes[..width].copy_from_slice(s);
+            bytes[..width].reverse();
+        } else {
+            bytes[..width].copy_from_slice(s);
+        }
+        match (layout.float, width) {
+            (true, 4) => f32::from_le_bytes(bytes[..4].try_into().unwrap_or_default()).abs(),
+            (true, 8) => f64::from_le_bytes(bytes).abs() as f32,
+            // 8-bit PCM in WAV is unsigned, but signed in AIFF
+            (false, 1) if !layout.big_endian => (bytes[0] as f32 - 128.0).abs() / 128.0,
+            (false, _) => {
+                let shift = 64 - width * 8;
+                let value = (i64::from_le_bytes(bytes) << shift) >> shift;
+                value.unsigned_abs() as f32 / (1u64 << (width * 8 - 1)) as f32
+            }
+            _ => 0.0,
+        }
+    };
+    samples[..frames * frame]
+        .chunks(per_bucket * frame)
+        .map(|bucket| {
+            bucket
+                .chunks_exact(width)
+                .map(level)
+                .fold(0.0f32, f32::max)
+                .min(1.0)
+        })
+        .collect()
+}
+
+/// The total size from a Content-Range header such as "bytes 0-1023/4096".
+fn content_range_total(resp: &reqwest::Response) -> Option<u64> {
+    resp.headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Download `range` of a file, returning the bytes and the size of the whole file.
+///
+/// A range past the end of the file reads as no bytes, which is how every range of an empty
+/// file reads.
+pub(crate) async fn fetch_range(
+    config: &Configuration,
+    id: &str,
+    range: Option<String>,
+) -> Result<(Vec<u8>, Option<u64>), String> {
+    let resp = match get_files_id_content(
+        config,
+        GetFilesIdContentParams {
+            file_id: id.to_string(),
+            range,
+            boxapi: None,
+            version: None,
+            access_token: None,
+        },
+    )
+    .await
+    {
+        Ok(resp) => resp,
+        Err(Error::ResponseError(e)) if e.status.as_u16() == 416 => return Ok((vec![], None)),
+        Err(e) => return Err(e.to_string()),
+    };
+    let total = content_range_total(&resp);
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+    Ok((bytes.to_vec(), total))
+}
+
+/// Whether `info` has a duration and at least one stream, so nothing more needs reading.
+fn complete(info: &Option<MediaInfo>) -> bool {
+    info.as_ref()
+        .is_some_and(|i| i.duration.is_some() && !(i.audio.is_empty() && i.video.is_empty()))
+}
+
+/// Read a media file's technical metadata with at most two ranged downloads.
+pub(crate) async fn probe(config: &Configuration, id: &str) -> Result<MediaInfo, String> {
+    let (head, total) =
+        fetch_range(config, id, Some(format!("bytes=0-{}", HEAD_BYTES - 1))).await?;
+    let size = total.unwrap_or(head.len() as u64);
+    let info = parse(&head, &[], size);
+    if complete(&info) || size <= HEAD_BYTES {
+        return info.ok_or("Not a recognised audio or video format".to_string());
+    }
+    let tail_start = size.saturating_sub(TAIL_BYTES).max(HEAD_BYTES);
+    let (tail, _) =
+        fetch_range(config, id, Some(format!("bytes={tail_start}-{}", size - 1))).await?;
+    parse(&head, &tail, size)
+        .or(info)
+        .ok_or("Not a recognised audio or video format".to_string())
+}
+
+/// Read a media file's technical metadata from bytes already sampled from it, downloading
+/// more only when the sample is not enough.
+pub(crate) async fn probe_sample(
+    config: &Configuration,
+    id: &str,
+    sample: &Sample,
+) -> Result<MediaInfo, String> {
+    let info = parse(&sample.head, &sample.tail, sample.size);
+    if complete(&info) || sample.complete() {
+        return info.ok_or("Not a recognised audio or video format".to_string());
+    }
+    match probe(config, id).await {
+        Ok(probed) => Ok(probed),
+        Err(e) => info.ok_or(e),
+    }
+}
+
+/// Peak levels across the whole recording for a waveform overview.
+///
+/// Short recordings are downloaded whole. Longer ones are sampled in windows, which is enough
+/// to see where speech and silence are.
+pub(crate) async fn waveform(
+    config: Configuration,
+    id: String,
+    layout: PcmLayout,
+) -> Result<Vec<f32>, String> {
+    let frame = (layout.bits as u64).div_ceil(8) * layout.channels.max(1) as u64;
+    let whole = WAVEFORM_WINDOWS as u64 * WINDOW_BYTES;
+    if layout.length <= whole {
+        let end = layout.offset + layout.length - 1;
+        let (samples, _) =
+            fetch_range(&config, &id, Some(format!("bytes={}-{end}", layout.offset))).await?;
+        return Ok(peaks(&samples, &layout, WAVEFORM_BUCKETS));
+    }
+    let stride = layout.length / WAVEFORM_WINDOWS as u64;
+    let windows = (0..WAVEFORM_WINDOWS as u64).map(|i| {
+        // Windows start on a frame so channels and bytes line up
+        let start = layout.offset + (i * stride) / frame * frame;
+        let range = format!("bytes={start}-{}", start + WINDOW_BYTES - 1);
+        let config = config.clone();
+        let id = id.clone();
+        async move { fetch_range(&config, &id, Some(range)).await }
+    });
+    let per_window = WAVEFORM_BUCKETS / WAVEFORM_WINDOWS;
+    let mut out = Vec::with_capacity(WAVEFORM_BUCKETS);
+    for window in iced::futures::future::join_all(windows).await {
+        let (samples, _) = window?;
+        out.extend(peaks(&samples, &layout, per_window));
+    }
+    Ok(out)
+}
+
+/// Peak levels of a compressed recording, such as an MP3, which is downloaded and decoded in
+/// full.
+pub(crate) async fn decoded_waveform(
+    config: Configuration,
+    id: String,
+) -> Result<Vec<f32>, String> {
+    let too_large = || format!("it is larger than {} MiB", MAX_DECODE_BYTES / 1024 / 1024);
+    let resp = get_files_id_content(
+        &config,
+        GetFilesIdContentParams {
+            file_id: id,
+            range: None,
+            boxapi: None,
+            version: None,
+            access_token: None,
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    if resp
+        .content_length()
+        .is_some_and(|len| len > MAX_DECODE_BYTES)
+    {
+        return Err(too_large());
+    }
+    let mut bytes = vec![];
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        bytes.extend_from_slice(&chunk.map_err(|e| e.to_string())?);
+        if bytes.len() as u64 > MAX_DECODE_BYTES {
+            return Err(too_large());
+        }
+    }
+    tokio::task::spawn_blocking(move || decoded_peaks(bytes, WAVEFORM_BUCKETS))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Peak level of each of `buckets` slices of a compressed recording, between 0 and 1.
+fn decoded_peaks(bytes: Vec<u8>, buckets: usize) -> Result<Vec<f32>, String> {
+    let decoder = Decoder::new(std::io::Cursor::new(bytes))
+        .map_err(|e| format!("it couldn't be decoded ({e})"))?;
+    // The length isn't always known up front, so take the peaks of 10 ms blocks, then
+    // merge those
+    let block = (decoder.sample_rate() as usize * decoder.channels() as usize / 100).max(1);
+    let mut blocks = vec![];
+    let mut peak = 0.0f32;
+    let mut count = 0;
+    for sample in decoder {
+        peak = peak.max(sample.abs());
+        count += 1;
+        if count == block {
+            blocks.push(peak.min(1.0));
+            (peak, count) = (0.0, 0);
+        }
+    }
+    if count > 0 {
+        blocks.push(peak.min(1.0));
+    }
+    let per_bucket = blocks.len().div_ceil(buckets).max(1);
+    Ok(blocks
+        .chunks(per_bucket)
+        .map(|c| c.iter().copied().fold(0.0f32, f32::max))
+        .collect())
+}
+
+/// Where files are downloaded to be played.
+fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join("tagmonster")
+}
+
+/// Remove the copies downloaded for playing, including any the system's player was given.
+pub(crate) fn clear_temp() {
+    match std::fs::remove_dir_all(temp_dir()) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => tracing::warn!("Could not remove the files downloaded for playing: {}", e),
+    }
+}
+
+/// Download a file to the temporary directory to be played.
+pub(crate) async fn download(
+    config: Configuration,
+    id: String,
+    name: String,
+) -> Result<PathBuf, String> {
+    let dir = temp_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("{id}-{}", name.replace(['/', '\\'], "_")));
+    if !path.exists() {
+        let resp = get_files_id_content(
+            &config,
+            GetFilesIdContentParams {
+                file_id: id,
+                range: None,
+                boxapi: None,
+                version: None,
+                access_token: None,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        // Recordings can be gigabytes, so they go to disk as they arrive
+        let partial = path.with_extension("part");
+        let mut file = std::fs::File::create(&partial).map_err(|e| e.to_string())?;
+        let mut stream = resp.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| e.to_string())?;
+            file.write_all(&chunk).map_err(|e| e.to_string())?;
+        }
+        std::fs::rename(&partial, &path).map_err(|e| e.to_string())?;
+    }
+    Ok(path)
+}
+
+/// Open a downloaded file in the system's default player.
+///
+/// The copy stays until [`clear_temp`], as there is no telling when the player is done with it.
+pub(crate) fn open_externally(path: &Path) -> Result<(), String> {
+    let url = url::Url::from_file_path(path).map_err(|_| "Invalid temporary path".to_string())?;
+    webbrowser::open(url.as_str()).map_err(|e| e.to_string())
+}
+
+/// Audio playing in the app from a downloaded copy, which is removed when playback is dropped.
+pub(crate) struct Playback {
+    /// The file being played
+    pub id: String,
+    sink: Arc<Sink>,
+    stream: Option<OutputStream>,
+    path: PathBuf,
+}
+
+impl Playback {
+    /// Play a downloaded file on the default output device.
+    pub fn start(id: String, path: PathBuf) -> Result<Playback, String> {
+        let file = std::fs::File::open(&path).map_err(|e| e.to_string())?;
+        let source = Decoder::try_from(file).map_err(|e| e.to_string())?;
+        let mut stream = OutputStreamBuilder::open_default_stream().map_err(|e| e.to_string())?;
+        stream.log_on_drop(false);
+        let sink = Sink::connect_new(stream.mixer());
+        sink.append(source);
+        Ok(Playback {
+            id,
+            sink: Arc::new(sink),
+            stream: Some(stream),
+            path,
+        })
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.sink.empty()
+    }
+
+    /// Wait until the audio has played to the end or been stopped.
+    pub fn finished(&self) -> impl Future<Output = ()> + use<> {
+        let sink = self.sink.clone();
+        async move {
+            let _ = tokio::task::spawn_blocking(move || sink.sleep_until_end()).await;
+        }
+    }
+}
+
+impl Drop for Playback {
+    fn drop(&mut self) {
+        self.sink.stop();
+        // Close the output, and with it the file, before removing the file
+        drop(self.stream.take());
+        // Where open files can't be removed, clear_temp gets it at exit
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A RIFF or IFF chunk.
+    fn chunk(id: &[u8; 4], body: &[u8], big_endian: bool) -> Vec<u8> {
+        let len = body.len() as u32;
+        let mut out = id.to_vec();
+        out.extend(if big_endian {
+            len.to_be_bytes()
+        } else {
+            len.to_le_bytes()
+        });
+        out.extend(body);
+        if body.len() % 2 == 1 {
+            out.push(0);
+        }
+        out
+    }
+
+    fn wav() -> Vec<u8> {
+        let mut fmt = vec![];
+        fmt.extend(1u16.to_le_bytes()); // PCM
+        fmt.extend(2u16.to_le_bytes());
+        fmt.extend(44100u32.to_le_bytes());
+        fmt.extend(176400u32.to_le_bytes());
+        fmt.extend(4u16.to_le_bytes());
+        fmt.extend(16u16.to_le_bytes());
+        let mut body = b"WAVE".to_vec();
+        body.extend(chunk(b"fmt ", &fmt, false));
+        // Only the data chunk's header, as when just the head was downloaded
+        body.extend(b"data");
+        body.extend(17640u32.to_le_bytes());
+        let mut out = b"RIFF".to_vec();
+        out.extend((body.len() as u32 + 17640).to_le_bytes());
+        out.extend(body);
+        out
+    }
+
+    fn aiff_file() -> Vec<u8> {
+        let mut comm = vec![];
+        comm.extend(1u16.to_be_bytes());
+        comm.extend(8000u32.to_be_bytes());
+        comm.extend(16u16.to_be_bytes());
+        // 8000 as an 80-bit extended float
+        comm.extend(0x400Bu16.to_be_bytes());
+        comm.extend(0xFA00_0000_0000_0000u64.to_be_bytes());
+        let mut body = b"AIFF".to_vec();
+        body.extend(chunk(b"COMM", &comm, true));
+        body.extend(b"SSND");
+        body.extend((8u32 + 16000).to_be_bytes());
+        body.extend([0; 8]); // offset and block size
+        let mut out = b"FORM".to_vec();
+        out.extend((body.len() as u32 + 16000).to_be_bytes());
+        out.extend(body);
+        out
+    }
+
+    fn flac_file() -> Vec<u8> {
+        let mut out = b"fLaC".to_vec();
+        out.extend([0x80, 0, 0, 34]); // last block, STREAMINFO, 34 bytes
+        let mut info = [0u8; 34];
+        // 44.1 kHz, stereo, 16 bits, 88200 samples
+        info[10..14].copy_from_slice(&[0x0A, 0xC4, 0x42, 0xF0]);
+        info[14..18].copy_from_slice(&88200u32.to_be_bytes());
+        out.extend(info);
+        out
+    }
+
+    /// MPEG-1 layer III frames at 128 kbit/s and 44.1 kHz, each 417 bytes.
+    fn mp3_frames(count: usize) -> Vec<u8> {
+        let mut out = vec![];
+        for _ in 0..count {
+            let mut frame = vec![0; 417];
+            frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+            out.extend(frame);
+        }
+        out
+    }
+
+    fn ogg_page(granule: u64, packet: &[u8]) -> Vec<u8> {
+        let mut out = b"OggS".to_vec();
+        out.extend([0, 2]); // version, beginning of stream
+        out.extend(granule.to_le_bytes());
+        out.extend([0; 12]); // serial, sequence, CRC
+        out.extend([1, packet.len() as u8]);
+        out.extend(packet);
+        out
+    }
+
+    fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = (body.len() as u32 + 8).to_be_bytes().to_vec();
+        out.extend(kind);
+        out.extend(body);
+        out
+    }
+
+    /// A track whose sample entry has `fields` after its format.
+    fn trak(handler: &[u8; 4], timescale: u32, format: &[u8; 4], fields: &[u8]) -> Vec<u8> {
+        let mut hdlr = vec![0; 8];
+        hdlr.extend(handler);
+        hdlr.extend([0; 13]);
+        let mut mdhd = vec![0; 12];
+        mdhd.extend(timescale.to_be_bytes());
+        mdhd.extend([0; 8]);
+        let mut entry = ((fields.len() + 8) as u32).to_be_bytes().to_vec();
+        entry.extend(format);
+        entry.extend(fields);
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(entry);
+        let mut stts = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stts.extend(1000u32.to_be_bytes());
+        stts.extend(512u32.to_be_bytes());
+        let mut stbl = mp4_box(b"stsd", &stsd);
+        stbl.extend(mp4_box(b"stts", &stts));
+        let minf = mp4_box(b"stbl", &stbl);
+        let mut mdia = mp4_box(b"hdlr", &hdlr);
+        mdia.extend(mp4_box(b"mdhd", &mdhd));
+        mdia.extend(mp4_box(b"minf", &minf));
+        mp4_box(b"trak", &mp4_box(b"mdia", &mdia))
+    }
+
+    fn moov() -> Vec<u8> {
+        let mut mvhd = vec![0; 12];
+        mvhd.extend(1000u32.to_be_bytes());
+        mvhd.extend(5000u32.to_be_bytes());
+        mvhd.extend([0; 80]);
+        let mut video = vec![0; 78];
+        video[24..26].copy_from_slice(&1920u16.to_be_bytes());
+        video[26..28].copy_from_slice(&1080u16.to_be_bytes());
+        let mut audio = vec![0; 28];
+        audio[16..18].copy_from_slice(&2u16.to_be_bytes());
+        audio[18..20].copy_from_slice(&16u16.to_be_bytes());
+        audio[24..26].copy_from_slice(&48000u16.to_be_bytes());
+        let mut body = mp4_box(b"mvhd", &mvhd);
+        body.extend(trak(b"vide", 12800, b"avc1", &video));
+        body.extend(trak(b"soun", 48000, b"mp4a", &audio));
+        mp4_box(b"moov", &body)
+    }
+
+    fn ftyp() -> Vec<u8> {
+        mp4_box(b"ftyp", b"isom\0\0\0\0isom")
+    }
+
+    /// An EBML element with a two-byte size.
+    fn ebml(id: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut out = id.to_vec();
+        out.extend([0x40 | (body.len() >> 8) as u8, body.len() as u8]);
+        out.extend(body);
+        out
+    }
+
+    fn webm() -> Vec<u8> {
+        let mut info = ebml(&[0x2A, 0xD7, 0xB1], &[0x0F, 0x42, 0x40]);
+        info.extend(ebml(&[0x44, 0x89], &2000f32.to_be_bytes()));
+        let mut video = ebml(&[0x83], &[1]);
+        video.extend(ebml(&[0x86], b"V_VP9"));
+        video.extend(ebml(&[0x23, 0xE3, 0x83], &40_000_000u32.to_be_bytes()));
+        let mut size = ebml(&[0xB0], &1280u16.to_be_bytes());
+        size.extend(ebml(&[0xBA], &720u16.to_be_bytes()));
+        video.extend(ebml(&[0xE0], &size));
+        let mut audio = ebml(&[0x83], &[2]);
+        audio.extend(ebml(&[0x86], b"A_OPUS"));
+        let mut settings = ebml(&[0xB5], &48000f64.to_be_bytes());
+        settings.extend(ebml(&[0x9F], &[2]));
+        audio.extend(ebml(&[0xE1], &settings));
+        let mut tracks = ebml(&[0xAE], &video);
+        tracks.extend(ebml(&[0xAE], &audio));
+        let mut segment = ebml(&[0x15, 0x49, 0xA9, 0x66], &info);
+        segment.extend(ebml(&[0x16, 0x54, 0xAE, 0x6B], &tracks));
+
+        let mut out = ebml(&[0x1A, 0x45, 0xDF, 0xA3], &ebml(&[0x42, 0x82], b"webm"));
+        out.extend([0x18, 0x53, 0x80, 0x67]);
+        // Muxers often write the segment's size in eight bytes
+        out.push(0x01);
+        out.extend(&(segment.len() as u64).to_be_bytes()[1..]);
+        out.extend(segment);
+        out
+    }
+
+    fn pcm(channels: u16, bits: u16, offset: u64, length: u64, big_endian: bool) -> PcmLayout {
+        PcmLayout {
+            offset,
+            length,
+            channels,
+            bits,
+            big_endian,
+            float: false,
+        }
+    }
+
+    fn audio(codec: &str, rate: u32, channels: u16, bits: Option<u16>) -> AudioStream {
+        AudioStream {
+            codec: codec.to_string(),
+            sample_rate: Some(rate),
+            channels: Some(channels),
+            bits,
+        }
+    }
+
+    #[test]
+    fn vints() {
+        type Case = (&'static [u8], bool, Option<(u64, usize)>);
+        let cases: [Case; 9] = [
+            (&[0x81], false, Some((1, 1))),
+            (&[0x40, 0x02], false, Some((2, 2))),
+            (&[0x01, 0, 0, 0, 0, 0, 0, 5], false, Some((5, 8))),
+            (
+                &[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+                false,
+                Some((u64::MAX, 8)),
+            ),
+            (&[0xFF], false, Some((u64::MAX, 1))),
+            (&[0x1A, 0x45, 0xDF, 0xA3], true, Some((0x1A45DFA3, 4))),
+            (&[0x00], false, None),
+            (&[0x40], false, None),
+            (&[], false, None),
+        ];
+        for (bytes, keep_marker, expected) in cases {
+            assert_eq!(vint(bytes, 0, keep_marker), expected, "{bytes:02X?}");
+        }
+    }
+
+    #[test]
+    fn element_bodies_stop_at_the_end() {
+        let mut b = ebml(&[0x83], &[1]);
+        // Declares 16 bytes but only 2 follow
+        b.extend([0x86, 0x90, b'A', b'B']);
+        assert_eq!(elements(&b, 0, b.len()), vec![(0x83, 3, 4), (0x86, 6, 8)]);
+        // An unknown size runs to the end
+        let live = [0x18, 0x53, 0x80, 0x67, 0xFF, 1, 2, 3];
+        assert_eq!(elements(&live, 0, live.len()), vec![(0x18538067, 5, 8)]);
+        assert_eq!(elements(&[0x00, 0x81], 0, 2), vec![]);
+    }
+
+    #[test]
+    fn webm_with_eight_byte_segment_size() {
+        assert_eq!(
+            parse(&webm(), &[], 0),
+            Some(MediaInfo {
+                container: "WebM".to_string(),
+                duration: Some(2.0),
+                audio: vec![audio("Opus", 48000, 2, None)],
+                video: vec![VideoStream {
+                    codec: "VP9".to_string(),
+                    width: Some(1280),
+                    height: Some(720),
+                    frame_rate: Some(25.0),
+                }],
+                pcm: None,
+            })
+        );
+    }
+
+    #[test]
+    fn wave() {
+        assert_eq!(
+            parse(&wav(), &[], 17684),
+            Some(MediaInfo {
+                container: "WAV".to_string(),
+                duration: Some(0.1),
+                audio: vec![audio("PCM", 44100, 2, Some(16))],
+                pcm: Some(pcm(2, 16, 44, 17640, false)),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn aiff_sound() {
+        assert_eq!(
+            parse(&aiff_file(), &[], 16054),
+            Some(MediaInfo {
+                container: "AIFF".to_string(),
+                duration: Some(1.0),
+                audio: vec![audio("PCM", 8000, 1, Some(16))],
+                pcm: Some(pcm(1, 16, 54, 16000, true)),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn flac_stream_info() {
+        let expected = MediaInfo {
+            container: "FLAC".to_string(),
+            duration: Some(2.0),
+            audio: vec![audio("FLAC", 44100, 2, Some(16))],
+            ..Default::default()
+        };
+        assert_eq!(parse(&flac_file(), &[], 0), Some(expected.clone()));
+        // After an ID3 tag
+        let mut tagged = b"ID3\x04\0\0\0\0\0\x0A".to_vec();
+        tagged.extend([0; 10]);
+        tagged.extend(flac_file());
+        assert_eq!(parse(&tagged, &[], 0), Some(expected));
+    }
+
+    #[test]
+    fn mp3_duration() {
+        let stream = |duration| MediaInfo {
+            container: "MPEG audio".to_string(),
+            duration: Some(duration),
+            audio: vec![audio("MP3", 44100, 2, None)],
+            ..Default::default()
+        };
+        // Constant bit rate, from the file size
+        let frames = mp3_frames(2);
+        assert_eq!(
+            parse(&frames, &[], 834),
+            Some(stream(834.0 * 8.0 / 128000.0))
+        );
+
+        let mut tagged = b"ID3\x04\0\0\0\0\0\x0A".to_vec();
+        tagged.extend([0; 10]);
+        tagged.extend(&frames);
+        assert_eq!(
+            parse(&tagged, &[], 854),
+            Some(stream(834.0 * 8.0 / 128000.0))
+        );
+
+        // A Xing header gives the frame count
+        let mut vbr = mp3_frames(2);
+        vbr[36..40].copy_from_slice(b"Xing");
+        vbr[40..44].copy_from_slice(&1u32.to_be_bytes());
+        vbr[44..48].copy_from_slice(&100u32.to_be_bytes());
+        assert_eq!(parse(&vbr, &[], 834), Some(stream(115200.0 / 44100.0)));
+
+        // A single frame might be chance
+        assert_eq!(parse(&mp3_frames(1), &[], 417), None);
+    }
+
+    #[test]
+    fn ogg_streams() {
+        let mut vorbis = b"\x01vorbis".to_vec();
+        vorbis.extend(0u32.to_le_bytes());
+        vorbis.push(2);
+        vorbis.extend(44100u32.to_le_bytes());
+        vorbis.extend([0; 14]);
+        let mut file = ogg_page(0, &vorbis);
+        file.extend(ogg_page(88200, &[0; 4]));
+        let expected = MediaInfo {
+            container: "Ogg".to_string(),
+            duration: Some(2.0),
+            audio: vec![audio("Vorbis", 44100, 2, None)],
+            ..Default::default()
+        };
+        assert_eq!(parse(&file, &[], 0), Some(expected.clone()));
+        // The last page can come from a separate tail download
+        let tail = ogg_page(88200, &[0; 4]);
+        assert_eq!(parse(&ogg_page(0, &vorbis), &tail, 0), Some(expected));
+
+        // Opus durations leave out the pre-skip
+        let mut opus = b"OpusHead\x01\x01".to_vec();
+        opus.extend(312u16.to_le_bytes());
+        opus.extend(44100u32.to_le_bytes());
+        opus.extend([0; 3]);
+        let mut file = ogg_page(0, &opus);
+        file.extend(ogg_page(96312, &[0; 4]));
+        assert_eq!(
+            parse(&file, &[], 0),
+            Some(MediaInfo {
+                container: "Ogg".to_string(),
+                duration: Some(2.0),
+                audio: vec![audio("Opus", 48000, 1, None)],
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn mp4_index() {
+        let expected = MediaInfo {
+            container: "MPEG-4".to_string(),
+            duration: Some(5.0),
+            audio: vec![audio("AAC", 48000, 2, None)],
+            video: vec![VideoStream {
+                codec: "H.264".to_string(),
+                width: Some(1920),
+                height: Some(1080),
+                frame_rate: Some(25.0),
+            }],
+            pcm: None,
+        };
+        let mut fast_start = ftyp();
+        fast_start.extend(moov());
+        assert_eq!(parse(&fast_start, &[], 0), Some(expected.clone()));
+
+        // The index after the media data is only in the tail
+        let mut head = ftyp();
+        head.extend(0x0100_0000u32.to_be_bytes());
+        head.extend(b"mdat");
+        head.extend([0; 64]);
+        let mut tail = vec![0; 5];
+        tail.extend(moov());
+        assert_eq!(parse(&head, &tail, 0), Some(expected));
+        assert_eq!(parse(&head, &[], 0), None);
+    }
+
+    #[test]
+    fn compressed_waveform_peaks() {
+        // Anything the decoder reads will do, and WAV is simplest to write
+        let mut fmt = vec![];
+        fmt.extend(1u16.to_le_bytes());
+        fmt.extend(1u16.to_le_bytes());
+        fmt.extend(1000u32.to_le_bytes());
+        fmt.extend(2000u32.to_le_bytes());
+        fmt.extend(2u16.to_le_bytes());
+        fmt.extend(16u16.to_le_bytes());
+        let samples: Vec<u8> = (0..100)
+            .flat_map(|i| if i < 50 { 16384i16 } else { 0 }.to_le_bytes())
+            .collect();
+        let mut body = b"WAVE".to_vec();
+        body.extend(chunk(b"fmt ", &fmt, false));
+        body.extend(chunk(b"data", &samples, false));
+        let mut file = b"RIFF".to_vec();
+        file.extend((body.len() as u32).to_le_bytes());
+        file.extend(body);
+
+        assert_eq!(decoded_peaks(file, 2), Ok(vec![0.5, 0.0]));
+        assert!(decoded_peaks(b"not audio".to_vec(), 2).is_err());
+    }
+
+    #[test]
+    fn truncated_headers_do_not_panic() {
+        let mut mp3 = b"ID3\x04\0\0\0\0\0\x0A".to_vec();
+        mp3.extend([0; 10]);
+        mp3.extend(mp3_frames(2));
+        let mut mp4 = ftyp();
+        mp4.extend(moov());
+        let mut ogg = ogg_page(0, b"OpusHead\x01\x02\x38\x01");
+        ogg.extend(ogg_page(96312, &[]));
+        for file in [wav(), aiff_file(), flac_file(), mp3, ogg, mp4, webm()] {
+            for end in 0..=file.len() {
+                parse(&file[..end], &file[end..], file.len() as u64);
+            }
+        }
+    }
+}
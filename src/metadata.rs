@@ -0,0 +1,314 @@
+use std::{collections::BTreeMap, io::ErrorKind, time::Duration};
+
+use r#box::models::Item;
+use iced::Task;
+use serde::{Deserialize, Serialize};
+
+use crate::{Message, State, data_entry::DataEntryMessage, journal, persist};
+
+/// How long edits settle before the store is written, so a burst of typing is saved once.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Descriptive metadata entered for one Box item.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ItemMetadata {
+    pub title: String,
+    pub description: String,
+    /// Date the item was created, or the start of a range
+    pub date_start: String,
+    /// End of a date range, empty for a single date
+    pub date_end: String,
+    pub creator: String,
+    pub notes: String,
+    pub tags: Vec<String>,
+    /// Values of the project's custom fields, keyed by field name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, String>,
+    /// File format identified by hand, used instead of the detected one
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub format: String,
+}
+
+impl ItemMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The dates as a single cell value, e.g. "1968" or "1968/1972".
+    pub fn dates(&self) -> String {
+        match (self.date_start.trim(), self.date_end.trim()) {
+            (start, "") => start.to_string(),
+            ("", end) => format!("/{end}"),
+            (start, end) => format!("{start}/{end}"),
+        }
+    }
+}
+
+/// A single-valued metadata field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Field {
+    Title,
+    Description,
+    DateStart,
+    DateEnd,
+    Creator,
+    Notes,
+}
+
+impl Field {
+    pub const ALL: [Field; 6] = [
+        Field::Title,
+        Field::Description,
+        Field::DateStart,
+        Field::DateEnd,
+        Field::Creator,
+        Field::Notes,
+    ];
+
+    pub fn value<'a>(&self, metadata: &'a ItemMetadata) -> &'a str {
+        match self {
+            Field::Title => &metadata.title,
+            Field::Description => &metadata.description,
+            Field::DateStart => &metadata.date_start,
+            Field::DateEnd => &metadata.date_end,
+            Field::Creator => &metadata.creator,
+            Field::Notes => &metadata.notes,
+        }
+    }
+
+    fn value_mut<'a>(&self, metadata: &'a mut ItemMetadata) -> &'a mut String {
+        match self {
+            Field::Title => &mut metadata.title,
+            Field::Description => &mut metadata.description,
+            Field::DateStart => &mut metadata.date_start,
+            Field::DateEnd => &mut metadata.date_end,
+            Field::Creator => &mut metadata.creator,
+            Field::Notes => &mut metadata.notes,
+        }
+    }
+}
+
+impl std::fmt::Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Field::Title => write!(f, "Title"),
+            Field::Description => write!(f, "Description"),
+            Field::DateStart => write!(f, "Date"),
+            Field::DateEnd => write!(f, "End date"),
+            Field::Creator => write!(f, "Creator"),
+            Field::Notes => write!(f, "Notes"),
+        }
+    }
+}
+
+/// One change to an item's metadata.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Edit {
+    Set(Field, String),
+    /// Set a custom field, named as in the project's schema
+    SetCustom(String, String),
+    AddTag(String),
+    RemoveTag(String),
+    AppendNote(String),
+    /// Record the file's format, or clear it when empty
+    Identify(String),
+    /// Set each field that is still empty, with values read from the file
+    Fill(Vec<(Field, String)>),
+}
+
+impl Edit {
+    pub fn apply(&self, metadata: &mut ItemMetadata) {
+        match self {
+            Edit::Set(field, value) => *field.value_mut(metadata) = value.clone(),
+            Edit::SetCustom(name, value) if value.is_empty() => {
+                metadata.custom.remove(name);
+            }
+            Edit::SetCustom(name, value) => {
+                metadata.custom.insert(name.clone(), value.clone());
+            }
+            Edit::AddTag(tag) => {
+                if !metadata.tags.contains(tag) {
+                    metadata.tags.push(tag.clone());
+                }
+            }
+            Edit::RemoveTag(tag) => metadata.tags.retain(|t| t != tag),
+            Edit::AppendNote(note) => {
+                if !metadata.notes.is_empty() && !metadata.notes.ends_with('\n') {
+                    metadata.notes.push('\n');
+                }
+                metadata.notes.push_str(note);
+            }
+            Edit::Identify(format) => metadata.format = format.clone(),
+            Edit::Fill(values) => {
+                for (field, value) in values {
+                    let current = field.value_mut(metadata);
+                    if current.is_empty() {
+                        *current = value.clone();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Every item's metadata in a project, keyed by Box item ID.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct MetadataStore {
+    pub items: BTreeMap<String, ItemMetadata>,
+}
+
+impl MetadataStore {
+    pub fn get(&self, id: &str) -> Option<&ItemMetadata> {
+        self.items.get(id)
+    }
+
+    /// Replace an item's metadata, dropping the entry once it is empty.
+    pub fn set(&mut self, id: &str, metadata: ItemMetadata) {
+        if metadata.is_empty() {
+            self.items.remove(id);
+        } else {
+            self.items.insert(id.to_string(), metadata);
+        }
+    }
+}
+
+/// The ID and display name of a Box item.
+pub(crate) fn item_info(item: &Item) -> (&str, String) {
+    match item {
+        Item::FileFull(f) => (
+            &f.id,
+            f.name
+                .clone()
+                .unwrap_or(format!("!!UNNAMED FILE - ID {}", f.id)),
+        ),
+        Item::FolderMini(f) => (
+            &f.id,
+            f.name
+                .clone()
+                .unwrap_or(format!("!!UNNAMED FOLDER - ID {}", f.id)),
+        ),
+        Item::WebLink(l) => (
+            &l.id,
+            l.name
+                .clone()
+                .unwrap_or(format!("!!UNNAMED WEB LINK - ID {}", l.id)),
+        ),
+    }
+}
+
+/// Apply `edit` to every item in `ids`, record it in the journal and save both.
+pub(crate) fn apply_edit(state: &mut State, ids: &[String], edit: Edit) -> Task<Message> {
+    if state.project.is_none() {
+        return Task::none();
+    }
+    if state.metadata_saves.load_error.is_some() {
+        tracing::error!("Not editing metadata: the project's saved metadata could not be read");
+        return Task::none();
+    }
+    let mut changes = Vec::with_capacity(ids.len());
+    for id in ids {
+        let before = state.metadata.get(id).cloned();
+        let mut entry = before.clone().unwrap_or_default();
+        edit.apply(&mut entry);
+        state.metadata.set(id, entry);
+        changes.push(journal::Change {
+            id: id.clone(),
+            before,
+            after: state.metadata.get(id).cloned(),
+        });
+    }
+    state.journal.record(edit, changes);
+    schedule_save(state)
+}
+
+/// Saves of the metadata store and edit history waiting for edits to settle.
+#[derive(Debug, Default)]
+pub(crate) struct SaveQueue {
+    /// Project the waiting save is for
+    project: Option<String>,
+    /// Bumped by every edit. Only the save scheduled by the latest edit writes.
+    generation: u64,
+    /// Why the project's saved metadata could not be read. Nothing is saved while this is
+    /// set, so the file is not overwritten with what little was entered since.
+    pub load_error: Option<String>,
+}
+
+/// Save the store and the edit history once edits have settled.
+pub(crate) fn schedule_save(state: &mut State) -> Task<Message> {
+    let Some(project) = &state.project else {
+        return Task::none();
+    };
+    let queue = &mut state.metadata_saves;
+    if let Some(e) = &queue.load_error {
+        tracing::error!(
+            "Not saving metadata for {}: the saved metadata could not be read ({})",
+            project.name,
+            e
+        );
+        return Task::none();
+    }
+    queue.project = Some(project.name.clone());
+    queue.generation += 1;
+    let generation = queue.generation;
+    Task::perform(tokio::time::sleep(SAVE_DELAY), move |_| {
+        Message::DataEntryMessage(DataEntryMessage::SaveDue(generation))
+    })
+}
+
+/// Write the store and history if no edit came after the one that scheduled save `generation`.
+pub(crate) fn save_due(state: &mut State, generation: u64) -> Task<Message> {
+    if state.metadata_saves.generation != generation {
+        return Task::none();
+    }
+    flush(state)
+}
+
+/// Write any waiting save now, such as before the project is closed.
+pub(crate) fn flush(state: &mut State) -> Task<Message> {
+    let queue = &mut state.metadata_saves;
+    let Some(project) = queue.project.take() else {
+        return Task::none();
+    };
+    if state
+        .project
+        .as_ref()
+        .is_none_or(|open| open.name != project)
+    {
+        return Task::none();
+    }
+    let snapshot = persist::snapshot();
+    Task::batch([
+        journal::save(project.clone(), state.journal.clone(), snapshot),
+        save(project, state.metadata.clone(), snapshot),
+    ])
+}
+
+/// The project's metadata, or why it could not be read. A project without any yet is empty.
+pub(crate) async fn load(project: String) -> Result<MetadataStore, String> {
+    match persist::load_project_store::<MetadataStore>(&project, "metadata").await {
+        Ok(store) => Ok(store),
+        Err(e)
+            if e.downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == ErrorKind::NotFound) =>
+        {
+            tracing::debug!("No metadata saved yet for {}", project);
+            Ok(MetadataStore::default())
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn save(project: String, store: MetadataStore, snapshot: u64) -> Task<Message> {
+    Task::perform(
+        async move {
+            if let Err(e) =
+                persist::save_project_store(&store, &project, "metadata", snapshot).await
+            {
+                tracing::error!("Error saving metadata for {}: {}", project, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use ::image::RgbaImage;
+use r#box::apis::{
+    configuration::Configuration,
+    downloads_api::{GetFilesIdContentParams, get_files_id_content},
+};
+use hayro::{
+    PixmapSettings, RenderCache, RenderSettings,
+    hayro_interpret::InterpreterSettings,
+    hayro_syntax::{LoadPdfError, Pdf},
+    vello_cpu::color::palette::css::WHITE,
+};
+
+/// Longest edge of a rendered page, in pixels.
+const PAGE_PIXELS: f32 = 1600.0;
+
+/// A PDF downloaded for previewing. Pages are rendered on the CPU as they are viewed, so
+/// nothing is sent to Box's converters.
+#[derive(Debug, Clone)]
+pub(crate) struct LocalPdf {
+    bytes: Arc<Vec<u8>>,
+    pub pages: usize,
+}
+
+impl LocalPdf {
+    /// Size of the downloaded file.
+    pub fn bytes(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+fn open(bytes: Arc<Vec<u8>>) -> Result<Pdf, String> {
+    Pdf::new(bytes).map_err(|e| match e {
+        LoadPdfError::Decryption(_) => "it is encrypted".to_string(),
+        LoadPdfError::Invalid => "it is not a PDF that can be read".to_string(),
+    })
+}
+
+/// Download file `id` and count its pages.
+pub(crate) async fn fetch(config: Configuration, id: String) -> Result<LocalPdf, String> {
+    let resp = get_files_id_content(
+        &config,
+        GetFilesIdContentParams {
+            file_id: id,
+            range: None,
+            boxapi: None,
+            version: None,
+            access_token: None,
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let bytes = Arc::new(resp.bytes().await.map_err(|e| e.to_string())?.to_vec());
+    tokio::task::spawn_blocking(move || {
+        let pages = open(bytes.clone())?.pages().len();
+        if pages == 0 {
+            return Err("it has no pages".to_string());
+        }
+        Ok(LocalPdf { bytes, pages })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Render a one-based page on a white background.
+pub(crate) async fn render(pdf: LocalPdf, page: usize) -> Result<RgbaImage, String> {
+    tokio::task::spawn_blocking(move || {
+        let document = open(pdf.bytes)?;
+        let page = page
+            .checked_sub(1)
+            .and_then(|i| document.pages().get(i))
+            .ok_or("the page does not exist")?;
+        let (width, height) = page.render_dimensions();
+        let scale = PAGE_PIXELS / width.max(height).max(1.0);
+        let pixmap = hayro::render(
+            page,
+            &RenderCache::new(),
+            &InterpreterSettings::default(),
+            &RenderSettings::default(),
+            &PixmapSettings {
+                x_scale: scale,
+                y_scale: scale,
+                bg_color: WHITE,
+            },
+        );
+        // The background is opaque, so the premultiplied pixels are plain RGBA
+        RgbaImage::from_raw(
+            pixmap.width().into(),
+            pixmap.height().into(),
+            pixmap.data_as_u8_slice().to_vec(),
+        )
+        .ok_or_else(|| "the page could not be drawn".to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
@@ -1,28 +1,102 @@
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
 
 use anyhow::{Ok, Result};
 use serde::{Deserialize, Serialize};
-use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::Mutex};
 
+use crate::CONFIG_DIR;
+
+/// Numbers handed out to snapshots, in the order they were taken.
+static SNAPSHOTS: AtomicU64 = AtomicU64::new(0);
+
+/// The newest snapshot written to each file. Held while writing, so saves go one at a time.
+static WRITTEN: LazyLock<Mutex<HashMap<PathBuf, u64>>> = LazyLock::new(Default::default);
+
+/// Write `data` to `{name}.json`. The file is written next to it and renamed into place, so
+/// a crash or a failed write leaves the previous contents intact.
 pub async fn persist<T: Serialize + for<'a> Deserialize<'a>>(
     data: &T,
     config_dir: &Path,
     name: &str,
 ) -> anyhow::Result<()> {
+    let path = config_dir.join(format!("{name}.json"));
+    let temp = config_dir.join(format!("{name}.json.tmp"));
     let mut file = OpenOptions::new()
-        .read(true)
         .write(true)
-        .append(true)
         .create(true)
-        .open(config_dir.join(format!("{name}.json")))
+        .truncate(true)
+        .open(&temp)
         .await?;
 
-    file.set_len(0).await?;
     let token_json = serde_json::to_string_pretty(&data)?;
     file.write_all(token_json.as_bytes()).await?;
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(&temp, &path).await?;
+    Ok(())
+}
+
+/// Number a snapshot of a store as it is taken, for [`persist_latest`].
+pub fn snapshot() -> u64 {
+    SNAPSHOTS.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+/// Write `data`, taken as snapshot `number`, unless a newer snapshot of the same file has
+/// already been written. Saves of stores that change often go through here, so two saves in
+/// flight can neither interleave nor finish out of order.
+pub async fn persist_latest<T: Serialize + for<'a> Deserialize<'a>>(
+    data: &T,
+    config_dir: &Path,
+    name: &str,
+    number: u64,
+) -> anyhow::Result<()> {
+    let mut written = WRITTEN.lock().await;
+    let path = config_dir.join(format!("{name}.json"));
+    if written.get(&path).is_some_and(|&newest| newest > number) {
+        return Ok(());
+    }
+    persist(data, config_dir, name).await?;
+    written.insert(path, number);
     Ok(())
 }
 
+/// Project `project`'s store `suffix`, such as its metadata, from `{project}_{suffix}.json`.
+pub async fn load_project_store<T: Default + Serialize + for<'a> Deserialize<'a>>(
+    project: &str,
+    suffix: &str,
+) -> Result<T> {
+    retrieve(&project_dir(project), &format!("{project}_{suffix}")).await
+}
+
+/// Write project `project`'s store `suffix`, taken as `snapshot` from [`snapshot`], unless a
+/// newer snapshot of it has already been written.
+pub async fn save_project_store<T: Serialize + for<'a> Deserialize<'a>>(
+    data: &T,
+    project: &str,
+    suffix: &str,
+    snapshot: u64,
+) -> Result<()> {
+    persist_latest(
+        data,
+        &project_dir(project),
+        &format!("{project}_{suffix}"),
+        snapshot,
+    )
+    .await
+}
+
+/// Where a project's stores are kept.
+fn project_dir(project: &str) -> PathBuf {
+    CONFIG_DIR.join("projects").join(project)
+}
+
 pub async fn retrieve<T: Default + Serialize + for<'a> Deserialize<'a>>(
     config_dir: &Path,
     name: &str,
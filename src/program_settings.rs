@@ -1,11 +1,17 @@
 use crate::{
     CONFIG_DIR, Message, State,
     box_login::{self},
+    format_risk::RiskRegistry,
     gapi_login,
     persist::persist,
+    sampling::SamplingSettings,
+    sensitive::DetectorSettings,
     subwindows::Subwindow,
+    templates::SheetTemplate,
     update,
 };
+use std::path::PathBuf;
+
 use anyhow::Error;
 use r#box::models::AccessToken;
 use google_sheets4::{
@@ -16,7 +22,7 @@ use iced::{
     Length::Fill,
     Padding, Task,
     futures::FutureExt,
-    widget::{Space, TextInput, button, column, row, space},
+    widget::{Space, TextInput, button, column, row, space, text},
 };
 use serde::{Deserialize, Serialize};
 
@@ -26,6 +32,21 @@ pub struct ProgramSettingsState {
     box_secret: String,
     pub gapi_key: String,
     pub gapi_secret: String,
+    /// Sheet templates new projects can be created from
+    #[serde(default)]
+    pub templates: Vec<SheetTemplate>,
+    /// DROID signature file used to identify PRONOM formats
+    #[serde(default)]
+    pub droid_signatures: Option<PathBuf>,
+    /// How much of each file to download when building a sheet
+    #[serde(default)]
+    pub sampling: SamplingSettings,
+    /// Formats considered at risk in format reports
+    #[serde(default)]
+    pub risk_registry: RiskRegistry,
+    /// What sensitive content scans look for
+    #[serde(default)]
+    pub sensitive: DetectorSettings,
 }
 
 #[derive(Clone)]
@@ -38,9 +59,12 @@ pub enum ProgramSettingsMessage {
     LoginBox(Result<AccessToken, String>),
     LoginGoogleButton,
     LoginGoogle(Result<Sheets<HttpsConnector<HttpConnector>>, String>),
+    PickDroidSignatures,
+    DroidSignaturesPicked(Option<PathBuf>),
+    ClearDroidSignatures,
 }
 
-fn save(ps: ProgramSettingsState) -> Task<Message> {
+pub(crate) fn save(ps: ProgramSettingsState) -> Task<Message> {
     Task::perform(
         async move {
             match persist(&ps, &CONFIG_DIR, "settings").await {
@@ -110,6 +134,27 @@ pub fn handle_prog_settings(state: &mut State, event: ProgramSettingsMessage) ->
                 update(state, { Message::None })
             }
         },
+        ProgramSettingsMessage::PickDroidSignatures => Task::perform(
+            rfd::AsyncFileDialog::new()
+                .add_filter("DROID signature file", &["xml"])
+                .pick_file(),
+            |f| {
+                Message::ProgSetMessage(ProgramSettingsMessage::DroidSignaturesPicked(
+                    f.map(|f| f.path().to_path_buf()),
+                ))
+            },
+        ),
+        ProgramSettingsMessage::DroidSignaturesPicked(path) => {
+            let Some(path) = path else {
+                return Task::none();
+            };
+            state.program_set_state.droid_signatures = Some(path);
+            save(state.program_set_state.clone())
+        }
+        ProgramSettingsMessage::ClearDroidSignatures => {
+            state.program_set_state.droid_signatures = None;
+            save(state.program_set_state.clone())
+        }
         ProgramSettingsMessage::LoginGoogleButton => {
             let key = state.program_set_state.gapi_key.to_string();
             let secret = state.program_set_state.gapi_secret.to_string();
@@ -134,6 +179,24 @@ pub(crate) fn program_settings(state: &State) -> Element<Message> {
     let gapi_secret = TextInput::new("Google secret", &state.program_set_state.gapi_secret)
         .on_input(|s| Message::ProgSetMessage(ProgramSettingsMessage::UpdateGapiSecret(s)));
 
+    let templates = button("Sheet templates").on_press(Message::OpenWindow(Subwindow::Templates));
+
+    let signatures = &state.program_set_state.droid_signatures;
+    let droid = row![
+        text(match signatures {
+            Some(path) => path.display().to_string(),
+            None => "No DROID signature file".to_string(),
+        })
+        .width(Fill),
+        button("Choose").on_press(Message::ProgSetMessage(
+            ProgramSettingsMessage::PickDroidSignatures
+        )),
+        button("Clear").on_press_maybe(signatures.is_some().then_some(Message::ProgSetMessage(
+            ProgramSettingsMessage::ClearDroidSignatures
+        ))),
+    ]
+    .spacing(10);
+
     let close = button("Close").on_press(Message::CloseWindow(Subwindow::ProgramSettings));
     let login_box = button("Login Box").on_press(Message::ProgSetMessage(
         ProgramSettingsMessage::LoginBoxButton,
@@ -151,6 +214,14 @@ pub(crate) fn program_settings(state: &State) -> Element<Message> {
         gapi_key,
         gapi_secret,
         login_google,
+        "Sheets",
+        templates,
+        "PRONOM format identification",
+        droid,
+        button("File sampling").on_press(Message::OpenWindow(Subwindow::Sampling)),
+        "Preservation",
+        button("Format risk registry").on_press(Message::OpenWindow(Subwindow::RiskRegistry)),
+        button("Sensitive content detectors").on_press(Message::OpenWindow(Subwindow::Detectors)),
         Space::new().height(Fill),
         close
     ]
@@ -1,5 +1,12 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use iced::{Task, task};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    schema::FieldSchema, sheet_format::SheetFormat, sheet_shard::Sharding, templates::SheetTemplate,
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct Project {
     pub name: String,
@@ -8,4 +15,59 @@ pub(crate) struct Project {
     pub sheets_url: String,
     pub spreadsheet_id: String,
     pub sheet_id: i32,
+    #[serde(default)]
+    pub sheet_format: SheetFormat,
+    #[serde(default)]
+    pub sharding: Sharding,
+    #[serde(default)]
+    pub template: SheetTemplate,
+    #[serde(default)]
+    pub schema: FieldSchema,
+    /// Google Drive folder that spreadsheets created for the project go in
+    #[serde(default)]
+    pub drive_folder: Option<String>,
+    /// Who spreadsheets created for the project are shared with
+    #[serde(default)]
+    pub collaborators: Vec<String>,
+}
+
+/// Numbers handed out to runs, so no two runs share one.
+static RUNS: AtomicU64 = AtomicU64::new(0);
+
+/// Background work over the open project's files, such as a fixity pass or a scan.
+///
+/// Results carry the number of the run that asked for them, so ones that arrive after the
+/// project was closed or the work started over can be told apart and dropped. Dropping the
+/// run, as resetting a project's state does, stops its tasks.
+#[derive(Debug)]
+pub(crate) struct Run {
+    id: u64,
+    tasks: Vec<task::Handle>,
+}
+
+impl Default for Run {
+    fn default() -> Self {
+        Run {
+            id: RUNS.fetch_add(1, Ordering::Relaxed) + 1,
+            tasks: vec![],
+        }
+    }
+}
+
+impl Run {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Whether results tagged `id` belong to this run.
+    pub fn is(&self, id: u64) -> bool {
+        self.id == id
+    }
+
+    /// Stop `task` when the run is dropped.
+    pub fn track<T: 'static>(&mut self, task: Task<T>) -> Task<T> {
+        let (task, handle) = task.abortable();
+        self.tasks.push(handle.abort_on_drop());
+        task
+    }
 }
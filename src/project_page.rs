@@ -1,17 +1,39 @@
 use std::{
+    collections::{HashMap, HashSet},
     env::current_dir,
-    io::{BufReader, Cursor},
+    io::BufReader,
     path::PathBuf,
     usize,
 };
 
 use crate::{
-    CONFIG_DIR, Message, Pane, State, TEMPLATE_ID, file_tree, homepage, persist, project::Project,
-    screens::Screen, subwindows::Subwindow, update,
+    CONFIG_DIR, Message, Pane, State,
+    box_metadata::{self, BoxMetadataMessage, BoxMetadataState, BoxMetadataStore},
+    classification::{self, ClassificationMessage, ClassificationState, ClassificationStore},
+    data_entry::{self, DataEntryMessage},
+    duplicates::{self, DuplicateStore, DuplicatesMessage, DuplicatesState},
+    embedded::{self, EmbeddedStore},
+    file_tree,
+    fixity::{self, FixityMessage, FixityState, FixityStore},
+    format_risk::{self, FormatRiskMessage, FormatRiskState, FormatStore},
+    gapi_drive, homepage,
+    journal::{self, Journal, JournalMessage},
+    metadata::{self, MetadataStore, SaveQueue},
+    persist,
+    project::Project,
+    project_settings,
+    schema::{FieldSchema, SchemaState},
+    screens::Screen,
+    sensitive::{self, SensitiveMessage, SensitiveState, SensitiveStore},
+    sheet_plan::{self, ReviewFlags, SheetPlan},
+    subwindows::Subwindow,
+    templates::{self, SheetTemplate},
+    update, urls,
+    viewer::{self, ViewerState},
+    vocabulary::{self, VocabularyMessage, VocabularyStore},
 };
 use r#box::{
     apis::{
-        downloads_api::{GetFilesIdContentParams, get_files_id_content},
         files_api::GetFilesIdParams,
         folders_api::{GetFoldersIdItemsParams, GetFoldersIdParams},
         users_api::GetUsersMeParams,
@@ -30,10 +52,10 @@ use iced::{
     Task, Theme,
     advanced::graphics::text::cosmic_text::Font,
     border::Radius,
-    futures::{FutureExt, TryFutureExt, future::join_all},
+    futures::{FutureExt, TryFutureExt},
     widget::{
-        self, Button, Column, Row, Space, TextInput, button, column, container, pane_grid, row,
-        scrollable, text, text_input,
+        self, Button, Column, Row, Space, TextInput, button, checkbox, column, container,
+        pane_grid, pick_list, row, scrollable, text, text_input,
     },
 };
 use pure_magic::MagicDb;
@@ -47,44 +69,129 @@ use tracing::{debug, error, info, warn};
 pub(crate) struct NewProjState {
     top_url: String,
     sheets_url: String,
+    create_sheet: bool,
+    sheet_title: String,
+    drive_folder: String,
+    collaborators: String,
+    preview_sheet: bool,
+    template: Option<SheetTemplate>,
+}
+
+/// Where a new project's sheet will be written.
+#[derive(Debug, Clone)]
+enum SheetTarget {
+    Existing {
+        url: String,
+        spreadsheet_id: String,
+        sheet_id: i32,
+    },
+    Create {
+        title: String,
+        folder: Option<String>,
+        collaborators: Vec<String>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub(crate) enum NewProjEvent {
     SetBoxUrl(String),
     SetSheetsUrl(String),
+    SetCreateSheet(bool),
+    SetSheetTitle(String),
+    SetDriveFolder(String),
+    SetCollaborators(String),
+    SetPreviewSheet(bool),
+    SetTemplate(SheetTemplate),
     MakeSheet(Project, Node),
+    PreviewSheet(Project, Node),
+    PlanReady(Box<SheetPlan>),
+    TreeLoaded(Node),
+    RunPlan,
+    ExportPlan,
     NewProjButton,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-struct Node {
-    name: String,
-    file_type: InternalType,
-    id: String,
-    idx: usize,
-    web_link: String,
-    children: Option<Vec<Node>>,
+pub(crate) struct Node {
+    pub name: String,
+    pub file_type: InternalType,
+    pub id: String,
+    pub idx: usize,
+    pub web_link: String,
+    pub children: Option<Vec<Node>>,
+}
+
+impl Node {
+    pub fn find(&self, id: &str) -> Option<&Node> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children
+            .iter()
+            .flatten()
+            .find_map(|child| child.find(id))
+    }
+
+    pub fn find_mut(&mut self, id: &str) -> Option<&mut Node> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .flatten()
+            .find_map(|child| child.find_mut(id))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-struct FlatItem {
-    name: String,
-    file_type: InternalType,
-    id: String,
-    idx: usize,
-    web_link: String,
-    children: (usize, usize),
+pub(crate) struct FlatItem {
+    pub name: String,
+    pub file_type: InternalType,
+    pub id: String,
+    pub idx: usize,
+    pub web_link: String,
+    pub children: (usize, usize),
+    /// Nesting level below the project root, which is depth 0
+    pub depth: usize,
+    /// Index of the last entry in the flattened list that lies inside this one
+    pub last_descendant: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
-enum InternalType {
+pub(crate) enum InternalType {
     File,
     Link,
     Folder,
+    /// A file inside an archive, listed by the viewer rather than by Box
+    ArchiveEntry,
+}
+
+/// Drops everything loaded for the open project, leaving the project itself
+fn reset_project_state(state: &mut State) {
+    state.project_tree = None;
+    state.metadata = MetadataStore::default();
+    state.metadata_saves = SaveQueue::default();
+    state.journal = Journal::default();
+    state.fixity = FixityStore::default();
+    state.fixity_state = FixityState::default();
+    state.duplicates = DuplicateStore::default();
+    state.duplicates_state = DuplicatesState::default();
+    state.embedded = EmbeddedStore::default();
+    state.formats = FormatStore::default();
+    state.format_risk_state = FormatRiskState::default();
+    state.sensitive = SensitiveStore::default();
+    state.sensitive_state = SensitiveState::default();
+    state.classifications = ClassificationStore::default();
+    state.classification_state = ClassificationState::default();
+    state.box_metadata = BoxMetadataStore::default();
+    state.box_metadata_state = BoxMetadataState::default();
+    state.schema_state = SchemaState::default();
+    state.viewer_state = ViewerState::default();
+    state.vocabularies = VocabularyStore::default();
 }
 
 pub(crate) fn close_project(state: &mut State) -> Task<Message> {
+    let flush = metadata::flush(state);
     if let Some(proj) = &state.project {
         tracing::info!("Closed project \"{}\"", proj.name);
     } else {
@@ -92,7 +199,10 @@ pub(crate) fn close_project(state: &mut State) -> Task<Message> {
     }
     state.screen = Screen::Home;
     state.project = None;
-    Task::none()
+    state.selected = None;
+    reset_project_state(state);
+    data_entry::selection_changed(state);
+    flush
 }
 
 async fn build_folder_tree(
@@ -246,6 +356,7 @@ pub(crate) fn new_project(state: &mut State, project: Project) -> Task<Message>
             let configuration = state.box_config.clone();
             let project_future = project.clone();
             let project_callback = project.clone();
+            let preview = state.new_proj_state.preview_sheet;
             let client = state.box_config.client.clone();
             let token = state
                 .box_token
@@ -293,8 +404,12 @@ pub(crate) fn new_project(state: &mut State, project: Project) -> Task<Message>
                     }
                 },
                 move |tree| match tree {
+                    Ok(t) if preview => {
+                        Message::NewProjMessage(NewProjEvent::PreviewSheet(project_callback, t))
+                    }
                     Ok(t) => Message::NewProjMessage(NewProjEvent::MakeSheet(project_callback, t)),
-                    Err(_) => todo!(),
+                    // Already logged above, and the project is usable without a sheet
+                    Err(_) => Message::None,
                 },
             )
         })
@@ -306,6 +421,7 @@ pub(crate) fn new_project(state: &mut State, project: Project) -> Task<Message>
         )),
     ]);
     state.project = Some(project);
+    reset_project_state(state);
     //state.file_tree_state.path = state.new_proj_state.top_url.clone();
     state.new_proj_state = NewProjState::default();
     state.screen = Screen::Project;
@@ -384,15 +500,58 @@ async fn download_zip(
 }
 
 pub(crate) fn open_project(state: &mut State, project: Project) -> Task<Message> {
+    // Edits to the project open until now are written under its name, not this one's
+    let flush = metadata::flush(state);
+    state.metadata_saves = SaveQueue::default();
     let name = project.name.clone();
     let id = project.top_folder_id;
     state.project = Some(project);
     state.screen = Screen::Project;
     tracing::info!("Opened project \"{name}\"");
-    update(
-        state,
-        Message::FileTreeMessage(file_tree::FileTreeMessage::InitFolder(id)),
-    )
+    Task::batch([
+        flush,
+        update(
+            state,
+            Message::FileTreeMessage(file_tree::FileTreeMessage::InitFolder(id)),
+        ),
+        Task::perform(metadata::load(name.clone()), |store| {
+            Message::DataEntryMessage(DataEntryMessage::Loaded(store))
+        }),
+        Task::perform(vocabulary::load(name.clone()), |store| {
+            Message::VocabularyMessage(VocabularyMessage::Loaded(store))
+        }),
+        Task::perform(journal::load(name.clone()), |journal| {
+            Message::JournalMessage(JournalMessage::Loaded(journal))
+        }),
+        Task::perform(fixity::load(name.clone()), |store| {
+            Message::FixityMessage(FixityMessage::Loaded(store))
+        }),
+        Task::perform(duplicates::load(name.clone()), |store| {
+            Message::DuplicatesMessage(DuplicatesMessage::Loaded(store))
+        }),
+        Task::perform(embedded::load(name.clone()), |store| {
+            Message::DataEntryMessage(DataEntryMessage::EmbeddedLoaded(store))
+        }),
+        Task::perform(format_risk::load(name.clone()), |store| {
+            Message::FormatRiskMessage(FormatRiskMessage::Loaded(store))
+        }),
+        Task::perform(sensitive::load(name.clone()), |store| {
+            Message::SensitiveMessage(SensitiveMessage::Loaded(store))
+        }),
+        Task::perform(classification::load(name.clone()), |store| {
+            Message::ClassificationMessage(ClassificationMessage::Loaded(store))
+        }),
+        Task::perform(box_metadata::load(name.clone()), |store| {
+            Message::BoxMetadataMessage(BoxMetadataMessage::Loaded(store))
+        }),
+        Task::perform(project_settings::load_tree(name), |tree| match tree {
+            Ok(tree) => Message::NewProjMessage(NewProjEvent::TreeLoaded(tree)),
+            Err(e) => {
+                tracing::warn!("Failed to load project tree: {}", e);
+                Message::None
+            }
+        }),
+    ])
 }
 
 #[derive(Debug)]
@@ -400,6 +559,7 @@ pub(crate) enum FetchJoinError<T> {
     BoxApi(r#box::apis::Error<T>),
     SheetsApi(google_sheets4::Error),
     SheetDoesNotExist(i32),
+    CreateSheet(String),
 }
 
 impl<T> std::fmt::Display for FetchJoinError<T> {
@@ -410,6 +570,7 @@ impl<T> std::fmt::Display for FetchJoinError<T> {
             FetchJoinError::SheetDoesNotExist(s) => {
                 write!(f, "Spreadsheet does not contain sheet {s}")
             }
+            FetchJoinError::CreateSheet(e) => write!(f, "Failed to create spreadsheet: {e}"),
         }
     }
 }
@@ -438,6 +599,30 @@ pub(crate) fn handle_new_proj_ev(state: &mut State, ev: NewProjEvent) -> Task<Me
             state.new_proj_state.sheets_url = url;
             Task::none()
         }
+        NewProjEvent::SetCreateSheet(create) => {
+            state.new_proj_state.create_sheet = create;
+            Task::none()
+        }
+        NewProjEvent::SetSheetTitle(title) => {
+            state.new_proj_state.sheet_title = title;
+            Task::none()
+        }
+        NewProjEvent::SetDriveFolder(folder) => {
+            state.new_proj_state.drive_folder = folder;
+            Task::none()
+        }
+        NewProjEvent::SetCollaborators(collaborators) => {
+            state.new_proj_state.collaborators = collaborators;
+            Task::none()
+        }
+        NewProjEvent::SetPreviewSheet(preview) => {
+            state.new_proj_state.preview_sheet = preview;
+            Task::none()
+        }
+        NewProjEvent::SetTemplate(template) => {
+            state.new_proj_state.template = Some(template);
+            Task::none()
+        }
         NewProjEvent::NewProjButton => {
             let _box_token = if let Some(t) = &state.box_token {
                 t
@@ -454,8 +639,7 @@ pub(crate) fn handle_new_proj_ev(state: &mut State, ev: NewProjEvent) -> Task<Me
             };
 
             let box_url = state.new_proj_state.top_url.clone();
-            let box_id: Result<usize, _> = box_url.split('/').last().unwrap_or_default().parse();
-            let box_id = match box_id {
+            let box_id = match urls::parse_box_folder_url(&box_url) {
                 Ok(id) => id,
                 Err(e) => {
                     return update(state, {
@@ -465,28 +649,78 @@ pub(crate) fn handle_new_proj_ev(state: &mut State, ev: NewProjEvent) -> Task<Me
                 }
             };
 
-            let sheets_url = state.new_proj_state.sheets_url.clone();
-            let spreadsheet_id = sheets_url.split('/').nth_back(1).map(|s| s.to_string());
-            let sheet_id: Result<i32, _> = sheets_url.split('=').last().unwrap_or_default().parse();
-            let (spreadsheet_id, sheet_id) = match (spreadsheet_id, sheet_id) {
-                (Some(sp), Ok(s)) => (sp.to_string(), s),
-                (None, _) => {
+            let target = if state.new_proj_state.create_sheet {
+                let folder = match state.new_proj_state.drive_folder.trim() {
+                    "" => None,
+                    f => match urls::parse_drive_folder(f) {
+                        Ok(id) => Some(id),
+                        Err(e) => {
+                            return update(state, {
+                                tracing::warn!("Invalid Google Drive folder {}: {}", f, e);
+                                Message::None
+                            });
+                        }
+                    },
+                };
+                let collaborators: Vec<String> = state
+                    .new_proj_state
+                    .collaborators
+                    .split([',', ';', ' ', '\n'])
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if let Some(bad) = collaborators.iter().find(|c| !c.contains('@')) {
                     return update(state, {
-                        tracing::warn!("Invalid Spreadsheet URL (no spreadsheet ID): {}", box_url);
+                        tracing::warn!("Invalid collaborator email address: {}", bad);
                         Message::None
                     });
                 }
-                (_, Err(e)) => {
-                    return update(state, {
-                        tracing::warn!("Invalid Spreadsheet URL (no sheet ID): {}", box_url);
-                        Message::None
-                    });
+                SheetTarget::Create {
+                    title: state.new_proj_state.sheet_title.trim().to_string(),
+                    folder,
+                    collaborators,
+                }
+            } else {
+                let sheets_url = state.new_proj_state.sheets_url.clone();
+                match urls::parse_sheets_url(&sheets_url) {
+                    Ok((spreadsheet_id, sheet_id)) => SheetTarget::Existing {
+                        url: sheets_url,
+                        spreadsheet_id,
+                        sheet_id,
+                    },
+                    Err(e) => {
+                        return update(state, {
+                            tracing::warn!("Invalid Spreadsheet URL {}: {}", sheets_url, e);
+                            Message::None
+                        });
+                    }
                 }
             };
 
-            let config = state.box_config.clone();
+            let template = state
+                .new_proj_state
+                .template
+                .clone()
+                .or_else(|| templates::usable(state).into_iter().next())
+                .unwrap_or_default();
+            if let Err(e) = template.google_source() {
+                return update(state, {
+                    tracing::warn!("{}", e);
+                    Message::None
+                });
+            }
 
-            let s_id = spreadsheet_id.clone();
+            let config = state.box_config.clone();
+            // Spreadsheets made later for shards go in the same folder, shared with the same people
+            let (drive_folder, shared_with) = match &target {
+                SheetTarget::Create {
+                    folder,
+                    collaborators,
+                    ..
+                } => (folder.clone(), collaborators.clone()),
+                SheetTarget::Existing { .. } => (None, vec![]),
+            };
 
             Task::perform(
                 async move {
@@ -505,41 +739,82 @@ pub(crate) fn handle_new_proj_ev(state: &mut State, ev: NewProjEvent) -> Task<Me
                     )
                     .await
                     .map_err(FetchJoinError::from)?;
-                    let sheet = hub
-                        .spreadsheets()
-                        .get(&s_id)
-                        .doit()
-                        .await
-                        .map_err(FetchJoinError::from)
-                        .and_then(|s| {
-                            let sheet_exists = s.1.sheets.as_ref().map_or(false, |sheets| {
-                                sheets.iter().any(|s| {
-                                    s.properties
-                                        .as_ref()
-                                        .map_or(false, |p| p.sheet_id == Some(sheet_id))
-                                })
-                            });
 
-                            if sheet_exists {
-                                Ok(s)
+                    let sheet = match target {
+                        SheetTarget::Existing {
+                            url,
+                            spreadsheet_id,
+                            sheet_id,
+                        } => {
+                            hub.spreadsheets()
+                                .get(&spreadsheet_id)
+                                .doit()
+                                .await
+                                .map_err(FetchJoinError::from)
+                                .and_then(|s| {
+                                    let sheet_exists =
+                                        s.1.sheets.as_ref().map_or(false, |sheets| {
+                                            sheets.iter().any(|s| {
+                                                s.properties
+                                                    .as_ref()
+                                                    .map_or(false, |p| p.sheet_id == Some(sheet_id))
+                                            })
+                                        });
+
+                                    if sheet_exists {
+                                        Ok(s)
+                                    } else {
+                                        Err(FetchJoinError::SheetDoesNotExist(sheet_id))
+                                    }
+                                })?;
+                            (url, spreadsheet_id, sheet_id)
+                        }
+                        SheetTarget::Create {
+                            title,
+                            folder: drive_folder,
+                            collaborators,
+                        } => {
+                            let title = if title.is_empty() {
+                                folder
+                                    .name
+                                    .clone()
+                                    .unwrap_or(format!("Folder {} Project", box_id))
                             } else {
-                                Err(FetchJoinError::SheetDoesNotExist(sheet_id))
-                            }
-                        })?;
+                                title
+                            };
+                            let created = gapi_drive::create_spreadsheet(
+                                hub,
+                                title,
+                                drive_folder,
+                                collaborators,
+                            )
+                            .await
+                            .map_err(|e| FetchJoinError::CreateSheet(e.to_string()))?;
+                            (created.url, created.spreadsheet_id, created.sheet_id)
+                        }
+                    };
                     Ok((folder, sheet))
                 },
                 {
                     move |x| match x {
-                        Ok((folder_res, sheet_res)) => Message::NewProj(Project {
-                            name: folder_res
-                                .name
-                                .unwrap_or(format!("Folder {} Project", box_id)),
-                            top_folder_id: box_id,
-                            box_url: box_url.clone(),
-                            sheets_url: sheets_url,
-                            spreadsheet_id: spreadsheet_id,
-                            sheet_id: sheet_id,
-                        }),
+                        Ok((folder_res, (sheets_url, spreadsheet_id, sheet_id))) => {
+                            Message::NewProj(Project {
+                                name: folder_res
+                                    .name
+                                    .unwrap_or(format!("Folder {} Project", box_id)),
+                                top_folder_id: box_id,
+                                box_url: box_url.clone(),
+                                sheets_url: sheets_url,
+                                spreadsheet_id: spreadsheet_id,
+                                sheet_id: sheet_id,
+                                sheet_format: Default::default(),
+                                sharding: Default::default(),
+                                template: template.clone(),
+                                schema: FieldSchema::default(),
+                                drive_folder: drive_folder.clone(),
+                                collaborators: shared_with.clone(),
+                            })
+                        }
                         Err(e) => match e {
                             FetchJoinError::BoxApi(error) => {
                                 tracing::error!("Error fetching folder {}: {}", box_id, error);
@@ -553,523 +828,228 @@ pub(crate) fn handle_new_proj_ev(state: &mut State, ev: NewProjEvent) -> Task<Me
                                 tracing::error!("Spreadsheet error: {}", error);
                                 Message::None
                             }
+                            FetchJoinError::CreateSheet(error) => {
+                                tracing::error!("Error creating spreadsheet: {}", error);
+                                Message::None
+                            }
                         },
                     }
                 },
             )
         }
-        NewProjEvent::MakeSheet(project, tree) => {
+        NewProjEvent::TreeLoaded(tree) => {
+            state.project_tree = Some(tree);
+            Task::none()
+        }
+        NewProjEvent::MakeSheet(mut project, tree) => {
+            state.project_tree = Some(tree.clone());
+            project.sheet_format.fill_tag_options(&state.vocabularies);
             let hub = if let Some(hub) = state.gapi_hub.clone() {
                 hub
             } else {
                 error!("Not logged in with google");
                 return Task::done(Message::None);
             };
+            if project.schema.block_invalid {
+                let invalid = sheet_plan::invalid_items(
+                    &project.schema,
+                    &sheet_plan::flatten(&tree),
+                    &state.metadata,
+                );
+                if !invalid.is_empty() {
+                    error!(
+                        "Not writing the sheet: {} items are incomplete or invalid. See the validation report in Project options.",
+                        invalid.len()
+                    );
+                    return Task::none();
+                }
+            }
 
             // Clone any parts of `state` we will need inside the 'static async task.
             let box_config = state.box_config.clone();
+            let metadata = state.metadata.clone();
+            let signatures = state.program_set_state.droid_signatures.clone();
+            let review = ReviewFlags {
+                duplicate_of: state.duplicates.duplicate_of(),
+                restricted: state.sensitive.restricted(),
+                classification: state.classifications.labels.clone(),
+            };
+            let sampling = state.program_set_state.sampling.clone();
 
             Task::perform(
                 async move {
-                    // Flatten the tree into a Vec with folders before files at each level.
-                    let mut flat: Vec<FlatItem> = Vec::new();
-
-                    fn flatten_node(node: &Node, out: &mut Vec<FlatItem>) {
-                        let mut child_counts = (0, 0);
-
-                        if let Some(children) = &node.children {
-                            for child in children {
-                                if child.file_type == InternalType::Folder {
-                                    child_counts.0 += 1;
-                                } else {
-                                    child_counts.1 += 1;
-                                }
-                            }
-                        }
-
-                        // push the folder/file itself (as a flattened entry with no children)
-                        out.push(FlatItem {
-                            name: node.name.clone(),
-                            file_type: node.file_type,
-                            id: node.id.clone(),
-                            idx: node.idx,
-                            web_link: node.web_link.clone(),
-                            children: child_counts,
-                        });
-
-                        if let Some(children) = &node.children {
-                            // separate folders and files
-                            let mut folders: Vec<&Node> = Vec::new();
-                            let mut files: Vec<&Node> = Vec::new();
-                            for c in children {
-                                if c.file_type == InternalType::Folder {
-                                    folders.push(c);
-                                } else {
-                                    files.push(c);
-                                }
-                            }
-
-                            // recurse into folders first
-                            for f in folders {
-                                flatten_node(f, out);
-                            }
-
-                            // then append files
-                            for file in files {
-                                out.push(FlatItem {
-                                    name: file.name.clone(),
-                                    id: file.id.clone(),
-                                    idx: file.idx,
-                                    web_link: file.web_link.clone(),
-                                    file_type: file.file_type,
-                                    children: (0, 0),
-                                });
-                            }
-                        }
-                    }
-
-                    flatten_node(&tree, &mut flat);
-
-                    tracing::info!(
-                        "Flattened tree for {}: {} entries",
-                        project.name,
-                        flat.len()
-                    );
-                    // persist flattened list for later use
-                    let _ = persist::persist(
-                        &flat,
-                        &CONFIG_DIR.join("projects").join(&project.name),
-                        &(project.name.clone() + "_flat"),
+                    let existing = sheet_plan::sheet_titles(&hub, &project.spreadsheet_id).await;
+                    let mut plan = match sheet_plan::build_plan(
+                        project, box_config, tree, metadata, signatures, review, sampling, existing,
                     )
-                    .await;
-
-                    let copy_req = google_sheets4::api::CopySheetToAnotherSpreadsheetRequest {
-                        destination_spreadsheet_id: Some(project.spreadsheet_id.clone()),
-                    };
-                    match hub
-                        .spreadsheets()
-                        .sheets_copy_to(copy_req, TEMPLATE_ID, 0)
-                        .doit()
-                        .await
+                    .await
                     {
-                        Ok((_resp, props)) => {
-                            tracing::info!(
-                                "Copied template sheet 0 from {} into {}",
-                                TEMPLATE_ID,
-                                project.spreadsheet_id
-                            );
-
-                            rename_new_sheet(&project, hub.clone(), props).await;
-
-                            // Set B1 to "(x Folders) (y Files)" for top-level children, and C1 to a hyperlink to the top-level folder.
-                            let top_children: &[Node] =
-                                tree.children.as_ref().map(|v| v.as_slice()).unwrap_or(&[]);
-
-                            let mut folder_count = 0usize;
-                            let mut file_count = 0usize;
-
-                            for c in top_children.iter() {
-                                if c.file_type == InternalType::Folder {
-                                    folder_count += 1;
-                                } else {
-                                    file_count += 1;
-                                }
-                            }
-
-                            let b1_text =
-                                format!("({} Folders) ({} Files)", folder_count, file_count);
-
-                            let safe_title = project.name.replace('\'', "''");
-                            let range_b1 = format!("'{}'!B1", safe_title);
-
-                            let vr_b1 = google_sheets4::api::ValueRange {
-                                range: Some(range_b1.clone()),
-                                major_dimension: None,
-                                values: Some(vec![vec![serde_json::Value::String(b1_text)]]),
-                            };
-
-                            match hub
-                                .spreadsheets()
-                                .values_update(vr_b1, project.spreadsheet_id.as_str(), &range_b1)
-                                .value_input_option("USER_ENTERED")
-                                .doit()
-                                .await
-                            {
-                                Ok((_resp, _)) => {
-                                    tracing::info!("Set {} -> {}", range_b1, "B1");
-                                }
-                                Err(e) => {
-                                    tracing::error!("Failed to set B1: {}", e);
-                                }
-                            }
-
-                            let esc_name = project.name.replace('"', "\\\"");
-                            let esc_url = project.box_url.replace('"', "\\\"");
-                            let formula = format!("=HYPERLINK(\"{}\",\"{}\")", esc_url, esc_name);
-                            let range_c1 = format!("'{}'!C1", safe_title);
-                            let vr_c1 = google_sheets4::api::ValueRange {
-                                range: Some(range_c1.clone()),
-                                major_dimension: None,
-                                values: Some(vec![vec![serde_json::Value::String(formula)]]),
-                            };
-
-                            match hub
-                                .spreadsheets()
-                                .values_update(vr_c1, &project.spreadsheet_id, &range_c1)
-                                .value_input_option("USER_ENTERED")
-                                .doit()
-                                .await
-                            {
-                                Ok((_resp, _)) => {
-                                    tracing::info!("Set {} -> {}", range_c1, "C1 (hyperlink)");
-                                }
-                                Err(e) => {
-                                    tracing::error!("Failed to set C1: {}", e);
-                                }
-                            }
-
-                            tokio::join!(
-                                create_filetype_tags(
-                                    &project,
-                                    hub.clone(),
-                                    box_config.clone(),
-                                    &flat
-                                ),
-                                create_folder_names(&project, hub.clone(), &flat,),
-                                create_file_names(&project, hub.clone(), &flat),
-                            );
-                            info!("Done making sheet");
-                        }
+                        Ok(plan) => plan,
                         Err(e) => {
-                            tracing::error!("Failed to copy template sheet: {}", e);
+                            error!("Failed to plan sheet: {}", e);
+                            return (HashMap::new(), HashMap::new());
                         }
+                    };
+                    let read = (
+                        std::mem::take(&mut plan.embedded),
+                        std::mem::take(&mut plan.formats),
+                    );
+                    if !plan.invalid.is_empty() {
+                        warn!(
+                            "{} items are incomplete or invalid, highlighting their rows",
+                            plan.invalid.len()
+                        );
                     }
-
-                    anyhow::Ok(())
+                    persist_flat(&plan).await;
+                    sheet_plan::execute_plan(plan, hub).await;
+                    read
                 },
-                |_| Message::None,
+                |read| read,
             )
+            .then(|(embedded, formats)| {
+                Task::batch([
+                    Task::done(Message::DataEntryMessage(DataEntryMessage::EmbeddedRead(
+                        embedded,
+                    ))),
+                    Task::done(Message::FormatRiskMessage(FormatRiskMessage::Read(formats))),
+                ])
+            })
         }
-    }
-}
-
-async fn create_folder_names(
-    project: &Project,
-    hub: google_sheets4::Sheets<
-        google_sheets4::hyper_rustls::HttpsConnector<
-            google_sheets4::hyper_util::client::legacy::connect::HttpConnector,
-        >,
-    >,
-    flat: &Vec<FlatItem>,
-) {
-    // Write folder hyperlinks and "Loose files:" markers in one pass.
-    let futures = flat.into_iter().enumerate().skip(1).map(|(i, node)| {
-        let safe_title = project.name.replace('\'', "''");
-        let hub = hub.clone();
-        async move {
-            let row = 2 + (i - 1);
-
-            match node.file_type {
-                InternalType::Folder => {
-                    let esc_name = node.name.replace('"', "\\\"");
-                    let esc_url = node.web_link.replace('"', "\\\"");
-                    let formula = format!("=HYPERLINK(\"{}\",\"{}\")", esc_url, esc_name);
-                    let folder_info =
-                        format!("({} Folders) ({} Files)", node.children.0, node.children.1);
-                    let range = format!("'{}'!B{}:C{}", safe_title, row, row);
-                    let vr = google_sheets4::api::ValueRange {
-                        range: Some(range.clone()),
-                        major_dimension: None,
-                        values: Some(vec![vec![
-                            serde_json::Value::String(folder_info),
-                            serde_json::Value::String(formula),
-                        ]]),
+        NewProjEvent::PreviewSheet(mut project, tree) => {
+            state.project_tree = Some(tree.clone());
+            project.sheet_format.fill_tag_options(&state.vocabularies);
+            let box_config = state.box_config.clone();
+            let metadata = state.metadata.clone();
+            let signatures = state.program_set_state.droid_signatures.clone();
+            let review = ReviewFlags {
+                duplicate_of: state.duplicates.duplicate_of(),
+                restricted: state.sensitive.restricted(),
+                classification: state.classifications.labels.clone(),
+            };
+            let sampling = state.program_set_state.sampling.clone();
+            let hub = state.gapi_hub.clone();
+            Task::perform(
+                async move {
+                    let existing = match &hub {
+                        Some(hub) => sheet_plan::sheet_titles(hub, &project.spreadsheet_id).await,
+                        None => HashSet::new(),
                     };
-
-                    match hub
-                        .spreadsheets()
-                        .values_update(vr, &project.spreadsheet_id, &range)
-                        .value_input_option("USER_ENTERED")
-                        .doit()
-                        .await
-                    {
-                        Ok((_r, _)) => tracing::info!("Wrote folder link to {}", range),
-                        Err(e) => tracing::error!("Failed to write {}: {}", range, e),
-                    }
-                }
-                InternalType::File | InternalType::Link => {
-                    if node.idx == 0 {
-                        let range = format!("'{}'!C{}", safe_title, row);
-                        let vr = google_sheets4::api::ValueRange {
-                            range: Some(range.clone()),
-                            major_dimension: None,
-                            values: Some(vec![vec![serde_json::Value::String(
-                                "Loose files:".to_string(),
-                            )]]),
-                        };
-
-                        match hub
-                            .spreadsheets()
-                            .values_update(vr, &project.spreadsheet_id, &range)
-                            .value_input_option("USER_ENTERED")
-                            .doit()
-                            .await
-                        {
-                            Ok((_r, _)) => tracing::info!("Wrote marker to {}", range),
-                            Err(e) => tracing::error!("Failed to write {}: {}", range, e),
-                        }
-                    }
-                }
-            }
-        }
-    });
-
-    join_all(futures).await;
-}
-
-async fn create_filetype_tags(
-    project: &Project,
-    hub: google_sheets4::Sheets<
-        google_sheets4::hyper_rustls::HttpsConnector<
-            google_sheets4::hyper_util::client::legacy::connect::HttpConnector,
-        >,
-    >,
-    box_config: r#box::apis::configuration::Configuration,
-    flat: &Vec<FlatItem>,
-) {
-    match magic_db::load() {
-        Ok(db) => {
-            {
-                // Iterate flattened entries (skip the root at index 0). Place results starting at row 2.
-                let client = reqwest::Client::new();
-                let futures = flat.into_iter().enumerate().skip(1).map(|(i, node)| {
-                    let box_config = box_config.clone();
-                    let client = client.clone();
-                    let db = db.clone();
-                    let safe_title = project.name.replace('\'', "''");
-                    let hub = hub.clone();
-                    let id = node.id.clone();
-                    async move {
-                        let row = 2 + (i - 1); // top-level is row 1, first child -> row 2
-
-                        let value = match node.file_type {
-                            InternalType::Folder => {
-                                // skip folders
-                                return;
-                            }
-                            InternalType::Link => "Web link".to_string(),
-                            InternalType::File => {
-                                // Download up to 100KiB from Box using the file ID
-                                let resp = get_files_id_content(
-                                    &box_config,
-                                    GetFilesIdContentParams {
-                                        file_id: id,
-                                        range: Some(format!("bytes=0-{}", (100 * 1024))),
-                                        boxapi: None,
-                                        version: None,
-                                        access_token: None,
-                                    },
-                                )
-                                .await
-                                .map(|f| f.url().to_owned());
-
-                                let url = match resp {
-                                    Ok(url) => url,
-                                    Err(e) => {
-                                        error!("Failed to get file download URL: {}", e);
-                                        return;
-                                    }
-                                };
-
-                                let token = match box_config.oauth_access_token.clone() {
-                                    Some(t) => t,
-                                    None => {
-                                        error!("Not logged in to Box");
-                                        return;
-                                    }
-                                };
-
-                                let resp = match client
-                                    .get(url.to_owned())
-                                    .bearer_auth(&token)
-                                    .send()
-                                    .await
-                                {
-                                    Ok(r) => r,
-                                    Err(e) => {
-                                        error!(
-                                            "Failed to download file {}: {}",
-                                            node.name,
-                                            e.to_string()
-                                        );
-                                        return;
-                                    }
-                                };
-
-                                let mut buf = if let Some(l) = resp.content_length() {
-                                    Vec::with_capacity(l.try_into().unwrap_or(usize::MAX)) // We might be on 32 bit who knows
-                                } else {
-                                    Vec::new()
-                                };
-
-                                let mut stream = resp.bytes_stream();
-
-                                while let Some(chunk) = stream.next().await {
-                                    let chunk = match chunk {
-                                        Ok(c) => c,
-                                        Err(e) => {
-                                            error!("Failed to download file {}: {}", &node.name, e);
-                                            return;
-                                        }
-                                    };
-                                    buf.extend_from_slice(&chunk);
-                                }
-
-                                debug!("downloaded file {}", &node.name);
-
-                                let mut cursor = Cursor::new(buf);
-
-                                // Analyze with MagicDb
-                                let detected = match db.best_magic(&mut cursor) {
-                                    Ok(result) => {
-                                        // pick the first sensible result if present
-                                        result.message()
-                                    }
-                                    Err(_) => {
-                                        warn!("Failed to analyze file {}", &node.name);
-                                        "Unknown".to_string()
-                                    }
-                                };
-                                detected
-                            }
-                        };
-
-                        // Write the result into column G for this row
-                        let range = format!("'{}'!G{}", safe_title, row);
-                        let vr = google_sheets4::api::ValueRange {
-                            range: Some(range.clone()),
-                            major_dimension: None,
-                            values: Some(vec![vec![serde_json::Value::String(value)]]),
-                        };
-                        match hub
-                            .spreadsheets()
-                            .values_update(vr, &project.spreadsheet_id, &range)
-                            .value_input_option("RAW")
-                            .doit()
-                            .await
-                        {
-                            Ok((_r, _)) => {
-                                tracing::info!("Wrote file type to {}", range);
-                            }
-                            Err(e) => {
-                                tracing::error!("Failed to write {}: {}", range, e);
-                            }
-                        }
+                    let plan = sheet_plan::build_plan(
+                        project, box_config, tree, metadata, signatures, review, sampling, existing,
+                    )
+                    .await?;
+                    persist_flat(&plan).await;
+                    Ok::<_, anyhow::Error>(plan)
+                },
+                |plan| match plan {
+                    Ok(plan) => Message::NewProjMessage(NewProjEvent::PlanReady(Box::new(plan))),
+                    Err(e) => {
+                        error!("Failed to plan sheet: {}", e);
+                        Message::None
                     }
-                });
-
-                join_all(futures).await;
-            }
+                },
+            )
         }
-        Err(e) => {
-            error!(
-                "Failed to load filetype detection database: {}",
-                e.to_string()
+        NewProjEvent::PlanReady(mut plan) => {
+            info!(
+                "Planned sheet for {}: {} rows, {} cells",
+                plan.project,
+                plan.rows(),
+                plan.cells()
             );
+            let embedded = std::mem::take(&mut plan.embedded);
+            let formats = std::mem::take(&mut plan.formats);
+            state.sheet_preview = Some(*plan);
+            Task::batch([
+                update(
+                    state,
+                    Message::DataEntryMessage(DataEntryMessage::EmbeddedRead(embedded)),
+                ),
+                update(
+                    state,
+                    Message::FormatRiskMessage(FormatRiskMessage::Read(formats)),
+                ),
+                update(state, Message::OpenWindow(Subwindow::SheetPreview)),
+            ])
         }
+        NewProjEvent::RunPlan => {
+            let hub = if let Some(hub) = state.gapi_hub.clone() {
+                hub
+            } else {
+                error!("Not logged in with google");
+                return Task::done(Message::None);
+            };
+            let Some(plan) = state.sheet_preview.take() else {
+                return Task::none();
+            };
+            update(state, Message::CloseWindow(Subwindow::SheetPreview))
+                .chain(Task::perform(sheet_plan::execute_plan(plan, hub), |_| {
+                    Message::None
+                }))
+        }
+        NewProjEvent::ExportPlan => match state.sheet_preview.clone() {
+            Some(plan) => sheet_plan::export_plan(plan),
+            None => Task::none(),
+        },
     }
 }
 
-// Write file and link names with hyperlinks
-async fn create_file_names(
-    project: &Project,
-    hub: google_sheets4::Sheets<
-        google_sheets4::hyper_rustls::HttpsConnector<
-            google_sheets4::hyper_util::client::legacy::connect::HttpConnector,
-        >,
-    >,
-    flat: &Vec<FlatItem>,
-) {
-    let futures = flat.into_iter().enumerate().skip(1).map(|(i, node)| {
-        let safe_title = project.name.replace('\'', "''");
-        let hub = hub.clone();
-        async move {
-            let row = 2 + (i - 1);
-
-            match node.file_type {
-                InternalType::File | InternalType::Link => {
-                    let esc_name = node.name.replace('"', "\\\"");
-                    let esc_url = node.web_link.replace('"', "\\\"");
-                    let formula = format!("=HYPERLINK(\"{}\",\"{}\")", esc_url, esc_name);
-                    let range = format!("'{}'!D{}", safe_title, row);
-                    let vr = google_sheets4::api::ValueRange {
-                        range: Some(range.clone()),
-                        major_dimension: None,
-                        values: Some(vec![vec![serde_json::Value::String(formula)]]),
-                    };
-
-                    match hub
-                        .spreadsheets()
-                        .values_update(vr, &project.spreadsheet_id, &range)
-                        .value_input_option("USER_ENTERED")
-                        .doit()
-                        .await
-                    {
-                        Ok((_r, _)) => tracing::info!("Wrote file link to {}", range),
-                        Err(e) => tracing::error!("Failed to write {}: {}", range, e),
-                    }
-                }
-                InternalType::Folder => {}
-            }
-        }
-    });
-    join_all(futures).await;
+// persist flattened list for later use
+async fn persist_flat(plan: &sheet_plan::SheetPlan) {
+    let _ = persist::persist(
+        &plan.flat,
+        &CONFIG_DIR.join("projects").join(&plan.project),
+        &(plan.project.clone() + "_flat"),
+    )
+    .await;
 }
 
-// Attempt to rename the newly copied sheet to the project name
-async fn rename_new_sheet(
-    project: &Project,
-    hub: google_sheets4::Sheets<
-        google_sheets4::hyper_rustls::HttpsConnector<
-            google_sheets4::hyper_util::client::legacy::connect::HttpConnector,
-        >,
-    >,
-    props: google_sheets4::api::SheetProperties,
-) {
-    let rename_req = BatchUpdateSpreadsheetRequest {
-        requests: Some(vec![google_sheets4::api::Request {
-            update_sheet_properties: Some(google_sheets4::api::UpdateSheetPropertiesRequest {
-                properties: Some(google_sheets4::api::SheetProperties {
-                    sheet_id: props.sheet_id,
-                    title: Some(project.name.clone()),
-                    ..Default::default()
-                }),
-                fields: Some(FieldMask::new(&["title"])),
-            }),
-            ..Default::default()
-        }]),
-        ..Default::default()
+pub(crate) fn new_project_view(state: &State) -> Element<Message> {
+    let sheet_inputs: Element<Message> = if state.new_proj_state.create_sheet {
+        column![
+            TextInput::new(
+                "Spreadsheet title (defaults to the folder name)",
+                &state.new_proj_state.sheet_title
+            )
+            .on_input(|t| Message::NewProjMessage(NewProjEvent::SetSheetTitle(t))),
+            TextInput::new(
+                "https://drive.google.com/drive/folders/123456789 (optional)",
+                &state.new_proj_state.drive_folder
+            )
+            .on_input(|f| Message::NewProjMessage(NewProjEvent::SetDriveFolder(f))),
+            TextInput::new(
+                "Collaborator emails, separated by commas (optional)",
+                &state.new_proj_state.collaborators
+            )
+            .on_input(|c| Message::NewProjMessage(NewProjEvent::SetCollaborators(c))),
+        ]
+        .spacing(10)
+        .into()
+    } else {
+        column![
+            TextInput::new(
+                "https://docs.google.com/spreadsheets/d/123456789/edit?gid=0#gid=0",
+                &state.new_proj_state.sheets_url
+            )
+            .on_input_maybe(
+                state
+                    .gapi_hub
+                    .as_ref()
+                    .map(|_| |u| Message::NewProjMessage(NewProjEvent::SetSheetsUrl(u)))
+            ),
+            text("Copy and paste the Google Sheets URL here"),
+        ]
+        .spacing(10)
+        .into()
     };
-    match hub
-        .spreadsheets()
-        .batch_update(rename_req, &project.spreadsheet_id)
-        .doit()
-        .await
-    {
-        Ok((_resp, _)) => {
-            tracing::info!(
-                "Renamed copied sheet to \"{}\" in {}",
-                project.name,
-                project.spreadsheet_id
-            );
-        }
-        Err(e) => {
-            tracing::error!("Failed to rename copied sheet: {}", e);
-        }
-    }
-}
 
-pub(crate) fn new_project_view(state: &State) -> Element<Message> {
+    let template_list = templates::usable(state);
+    let selected_template = state
+        .new_proj_state
+        .template
+        .clone()
+        .or_else(|| template_list.first().cloned());
+
     column![
         "Create a new project",
         column![
@@ -1085,17 +1065,23 @@ pub(crate) fn new_project_view(state: &State) -> Element<Message> {
             ),
             text("Copy and paste the box folder URL here"),
             Space::new().height(10),
-            TextInput::new(
-                "https://docs.google.com/spreadsheets/d/123456789/edit?gid=0#gid=0",
-                &state.new_proj_state.sheets_url
-            )
-            .on_input_maybe(
-                state
-                    .gapi_hub
-                    .as_ref()
-                    .map(|_| |u| Message::NewProjMessage(NewProjEvent::SetSheetsUrl(u)))
-            ),
-            text("Copy and paste the Google Sheets URL here"),
+            checkbox(state.new_proj_state.create_sheet)
+                .label("Create a new spreadsheet")
+                .on_toggle_maybe(state.gapi_hub.as_ref().map(|_| {
+                    |c| Message::NewProjMessage(NewProjEvent::SetCreateSheet(c))
+                })),
+            sheet_inputs,
+            row![
+                text("Template"),
+                pick_list(template_list, selected_template, |t| {
+                    Message::NewProjMessage(NewProjEvent::SetTemplate(t))
+                }),
+            ]
+            .align_y(Center)
+            .spacing(10),
+            checkbox(state.new_proj_state.preview_sheet)
+                .label("Preview the sheet before writing it")
+                .on_toggle(|p| Message::NewProjMessage(NewProjEvent::SetPreviewSheet(p))),
         ]
         .spacing(10),
         row![
@@ -1124,18 +1110,24 @@ pub(crate) fn new_project_view(state: &State) -> Element<Message> {
 pub(crate) fn project_page(state: &State) -> widget::Container<'_, Message> {
     container(
         pane_grid(&state.panes, |pane, current_pane, _| {
-            pane_grid::Content::new(
-                scrollable(
-                    match current_pane {
-                        Pane::FileList => container(file_tree::file_tree(&state)),
-                        Pane::DataEntry => container("Data Entry"),
-                        Pane::Viewer => container("Viewer"),
-                    }
-                    .padding(8),
-                )
-                .height(Length::Fill)
-                .width(Length::Fill),
-            )
+            pane_grid::Content::new(match current_pane {
+                // The image viewer zooms and pans itself, so it fills the pane instead of scrolling
+                Pane::Viewer => container(viewer::viewer(state))
+                    .padding(8)
+                    .height(Length::Fill)
+                    .width(Length::Fill),
+                Pane::FileList | Pane::DataEntry => container(
+                    scrollable(
+                        match current_pane {
+                            Pane::FileList => container(file_tree::file_tree(state)),
+                            _ => container(data_entry::data_entry(state)),
+                        }
+                        .padding(8),
+                    )
+                    .height(Length::Fill)
+                    .width(Length::Fill),
+                ),
+            })
             .style(|theme: &Theme| {
                 let palette = theme.extended_palette();
 
@@ -1,8 +1,275 @@
-use crate::{Message, State, subwindows::Subwindow};
-use iced::{Element, widget::button};
+use crate::{
+    CONFIG_DIR, Message, State, homepage,
+    persist::persist,
+    project::Project,
+    project_page::{NewProjEvent, Node},
+    sheet_format::TagColumn,
+    sheet_shard::ShardMode,
+    subwindows::Subwindow,
+    update,
+};
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    widget::{
+        Column, Space, TextInput, button, checkbox, column, pick_list, row, scrollable, text,
+    },
+};
+
+/// Rows per tab offered when switching to `ShardMode::RowsPerTab`.
+const DEFAULT_ROWS_PER_TAB: usize = 5000;
+
+#[derive(Debug, Clone)]
+pub(crate) enum ProjectSettingsMessage {
+    SetShardMode(ShardMode),
+    SetRowsPerTab(String),
+    SetSeparateSpreadsheets(bool),
+    SetIndexTab(bool),
+    SetGroupFolders(bool),
+    SetStyleFolders(bool),
+    SetFreezeHeader(bool),
+    SetHighlightUnknown(bool),
+    AddTagColumn,
+    /// Set the column letter of a tag dropdown, by position
+    SetTagColumn(usize, String),
+    /// Set the vocabulary a tag dropdown offers, by position
+    SetTagVocabulary(usize, String),
+    RemoveTagColumn(usize),
+    PreviewSheet,
+}
+
+pub(crate) fn save_project(project: Project) -> Task<Message> {
+    Task::perform(
+        async move {
+            match persist(&project, &CONFIG_DIR.join("projects"), &project.name).await {
+                Ok(_) => Message::None,
+                Err(e) => {
+                    tracing::error!("Error saving project {}: {}", project.name, e);
+                    Message::None
+                }
+            }
+        },
+        |x| x,
+    )
+}
+
+pub(crate) async fn load_tree(name: String) -> anyhow::Result<Node> {
+    let path = CONFIG_DIR
+        .join("projects")
+        .join(&name)
+        .join(format!("{name}_tree.json"));
+    let json = tokio::fs::read_to_string(path).await?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+pub(crate) fn save_tree(name: String, tree: Node) -> Task<Message> {
+    Task::perform(
+        async move {
+            let dir = CONFIG_DIR.join("projects").join(&name);
+            if let Err(e) = persist(&tree, &dir, &format!("{name}_tree")).await {
+                tracing::error!("Error saving project tree for {}: {}", name, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+pub(crate) fn handle_project_settings(
+    state: &mut State,
+    event: ProjectSettingsMessage,
+) -> Task<Message> {
+    let Some(project) = &mut state.project else {
+        return Task::none();
+    };
+    match event {
+        ProjectSettingsMessage::PreviewSheet => {
+            let project = project.clone();
+            return Task::perform(load_tree(project.name.clone()), move |tree| match tree {
+                Ok(tree) => Message::NewProjMessage(NewProjEvent::PreviewSheet(project, tree)),
+                Err(e) => {
+                    tracing::error!("Failed to load project tree: {}", e);
+                    Message::None
+                }
+            });
+        }
+        ProjectSettingsMessage::SetShardMode(mode) => {
+            project.sharding.mode = mode;
+        }
+        ProjectSettingsMessage::SetRowsPerTab(rows) => match rows.trim().parse::<usize>() {
+            Ok(rows) if rows > 0 => project.sharding.mode = ShardMode::RowsPerTab(rows),
+            _ => return Task::none(),
+        },
+        ProjectSettingsMessage::SetSeparateSpreadsheets(separate) => {
+            project.sharding.separate_spreadsheets = separate;
+        }
+        ProjectSettingsMessage::SetIndexTab(index) => {
+            project.sharding.index_tab = index;
+        }
+        ProjectSettingsMessage::SetGroupFolders(group) => {
+            project.sheet_format.group_folders = group;
+        }
+        ProjectSettingsMessage::SetStyleFolders(style) => {
+            project.sheet_format.style_folders = style;
+        }
+        ProjectSettingsMessage::SetFreezeHeader(freeze) => {
+            project.sheet_format.freeze_header = freeze;
+        }
+        ProjectSettingsMessage::SetHighlightUnknown(highlight) => {
+            project.sheet_format.highlight_unknown = highlight;
+        }
+        ProjectSettingsMessage::AddTagColumn => {
+            project.sheet_format.tag_columns.push(TagColumn::default());
+        }
+        ProjectSettingsMessage::SetTagColumn(i, column) => {
+            let Some(tag_column) = project.sheet_format.tag_columns.get_mut(i) else {
+                return Task::none();
+            };
+            tag_column.column = column.trim().to_ascii_uppercase();
+        }
+        ProjectSettingsMessage::SetTagVocabulary(i, vocabulary) => {
+            let Some(tag_column) = project.sheet_format.tag_columns.get_mut(i) else {
+                return Task::none();
+            };
+            tag_column.vocabulary = vocabulary;
+        }
+        ProjectSettingsMessage::RemoveTagColumn(i) => {
+            if i < project.sheet_format.tag_columns.len() {
+                project.sheet_format.tag_columns.remove(i);
+            }
+        }
+    }
+    let project = project.clone();
+    update(
+        state,
+        Message::HomepageMessage(homepage::HomepageMessage::UpdateProject(project.clone())),
+    )
+    .chain(save_project(project))
+}
 
 pub(crate) fn project_settings(state: &State) -> Element<Message> {
-    button("close")
-        .on_press(Message::CloseWindow(Subwindow::ProjectSettings))
+    let close = button("close").on_press(Message::CloseWindow(Subwindow::ProjectSettings));
+    let Some(project) = &state.project else {
+        return column![text("No project open"), Space::new().height(Fill), close]
+            .padding(Padding::new(15.0))
+            .spacing(15.0)
+            .into();
+    };
+    let sharding = &project.sharding;
+
+    let rows = match sharding.mode {
+        ShardMode::RowsPerTab(rows) => rows,
+        _ => DEFAULT_ROWS_PER_TAB,
+    };
+    let modes = vec![
+        ShardMode::Single,
+        ShardMode::TopLevelFolder,
+        ShardMode::RowsPerTab(rows),
+    ];
+    let mode = pick_list(modes, Some(sharding.mode.clone()), |m| {
+        Message::ProjSetMessage(ProjectSettingsMessage::SetShardMode(m))
+    });
+    let rows_input = TextInput::new("Rows per tab", &rows.to_string()).on_input_maybe(
+        matches!(sharding.mode, ShardMode::RowsPerTab(_))
+            .then_some(|r| Message::ProjSetMessage(ProjectSettingsMessage::SetRowsPerTab(r))),
+    );
+    let separate = checkbox(sharding.separate_spreadsheets)
+        .label("One spreadsheet per shard")
+        .on_toggle(|s| Message::ProjSetMessage(ProjectSettingsMessage::SetSeparateSpreadsheets(s)));
+    let index = checkbox(sharding.index_tab)
+        .label("Add an index tab")
+        .on_toggle(|i| Message::ProjSetMessage(ProjectSettingsMessage::SetIndexTab(i)));
+
+    let format = &project.sheet_format;
+    let group = checkbox(format.group_folders)
+        .label("Group rows under their folder")
+        .on_toggle(|g| Message::ProjSetMessage(ProjectSettingsMessage::SetGroupFolders(g)));
+    let style = checkbox(format.style_folders)
+        .label("Bold and indent folder rows")
+        .on_toggle(|s| Message::ProjSetMessage(ProjectSettingsMessage::SetStyleFolders(s)));
+    let freeze = checkbox(format.freeze_header)
+        .label("Freeze the header row")
+        .on_toggle(|f| Message::ProjSetMessage(ProjectSettingsMessage::SetFreezeHeader(f)));
+    let highlight = checkbox(format.highlight_unknown)
+        .label("Highlight unknown file types")
+        .on_toggle(|h| Message::ProjSetMessage(ProjectSettingsMessage::SetHighlightUnknown(h)));
+    let vocabularies: Vec<String> = state
+        .vocabularies
+        .vocabularies
+        .iter()
+        .map(|v| v.name.clone())
+        .collect();
+    let tag_columns = format.tag_columns.iter().enumerate().fold(
+        Column::new().spacing(5),
+        |col, (i, tag_column)| {
+            col.push(
+                row![
+                    TextInput::new("Column", &tag_column.column)
+                        .on_input(move |c| {
+                            Message::ProjSetMessage(ProjectSettingsMessage::SetTagColumn(i, c))
+                        })
+                        .width(80),
+                    pick_list(
+                        vocabularies.clone(),
+                        Some(tag_column.vocabulary.clone()).filter(|v| !v.is_empty()),
+                        move |v| {
+                            Message::ProjSetMessage(ProjectSettingsMessage::SetTagVocabulary(i, v))
+                        }
+                    )
+                    .placeholder("Vocabulary"),
+                    button("×")
+                        .style(button::danger)
+                        .on_press(Message::ProjSetMessage(
+                            ProjectSettingsMessage::RemoveTagColumn(i)
+                        )),
+                ]
+                .spacing(5)
+                .align_y(Center),
+            )
+        },
+    );
+    let add_tag_column = button("Add tag dropdown")
+        .style(button::secondary)
+        .on_press_maybe(
+            (!vocabularies.is_empty()).then_some(Message::ProjSetMessage(
+                ProjectSettingsMessage::AddTagColumn,
+            )),
+        );
+
+    let settings = column![
+        "Sheet formatting",
+        group,
+        style,
+        freeze,
+        highlight,
+        tag_columns,
+        add_tag_column,
+        "Splitting large projects",
+        mode,
+        rows_input,
+        separate,
+        index,
+        button("Preview sheet").on_press(Message::ProjSetMessage(
+            ProjectSettingsMessage::PreviewSheet
+        )),
+        "Tagging",
+        button("Controlled vocabularies").on_press(Message::OpenWindow(Subwindow::Vocabularies)),
+        button("Fields and validation").on_press(Message::OpenWindow(Subwindow::Schema)),
+        button("Validation report").on_press(Message::OpenWindow(Subwindow::ValidationReport)),
+        button("Box metadata").on_press(Message::OpenWindow(Subwindow::BoxMetadata)),
+        "Preservation",
+        button("Fixity").on_press(Message::OpenWindow(Subwindow::Fixity)),
+        button("Duplicates").on_press(Message::OpenWindow(Subwindow::Duplicates)),
+        button("Format risks").on_press(Message::OpenWindow(Subwindow::FormatRisks)),
+        button("File issues").on_press(Message::OpenWindow(Subwindow::FileIssues)),
+        button("Sensitive content").on_press(Message::OpenWindow(Subwindow::SensitiveContent)),
+        button("Classifications").on_press(Message::OpenWindow(Subwindow::Classifications)),
+    ]
+    .spacing(15.0);
+
+    column![scrollable(settings).height(Fill), close]
+        .padding(Padding::new(15.0))
+        .spacing(15.0)
         .into()
 }
@@ -0,0 +1,634 @@
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use regex::bytes::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::sampling::Sample;
+
+/// Compiled size allowed for one signature. Long gaps like `{0-65536}` take a lot of states.
+const REGEX_SIZE_LIMIT: usize = 32 * 1024 * 1024;
+
+/// How a format was identified, as DROID reports it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) enum Basis {
+    /// An internal signature matched, starting at this offset
+    Signature { offset: usize },
+    /// Only the file extension matched
+    Extension,
+}
+
+/// A PRONOM format a file was identified as.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Identification {
+    pub puid: String,
+    pub name: String,
+    pub version: String,
+    pub mime: String,
+    pub basis: Basis,
+    /// The signature matched but the format does not list the file's extension
+    pub extension_mismatch: bool,
+}
+
+impl Identification {
+    /// Name, version, MIME type and basis, e.g. "Acrobat PDF 1.4 - Portable Document Format
+    /// 1.4, application/pdf, signature at offset 0".
+    pub(crate) fn describe(&self) -> String {
+        let mut out = self.name.clone();
+        if !self.version.is_empty() {
+            let _ = write!(out, " {}", self.version);
+        }
+        if !self.mime.is_empty() {
+            let _ = write!(out, ", {}", self.mime);
+        }
+        match self.basis {
+            Basis::Signature { offset } => {
+                let _ = write!(out, ", signature at offset {offset}");
+            }
+            Basis::Extension => out.push_str(", extension only"),
+        }
+        if self.extension_mismatch {
+            out.push_str(", extension mismatch");
+        }
+        out
+    }
+}
+
+#[derive(Debug)]
+struct Format {
+    id: String,
+    puid: String,
+    name: String,
+    version: String,
+    mime: String,
+    extensions: Vec<String>,
+    signatures: Vec<String>,
+    /// IDs of formats this one wins over when both match
+    priority_over: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Signature {
+    id: String,
+    /// One pattern per byte sequence, all of which must match, and whether it is anchored to
+    /// the end of the file
+    sequences: Vec<(Regex, bool)>,
+}
+
+/// A DROID signature file, as published by The National Archives.
+#[derive(Debug)]
+pub(crate) struct SignatureFile {
+    pub version: String,
+    formats: Vec<Format>,
+    signatures: Vec<Signature>,
+    /// Signatures that use syntax this reader does not understand
+    pub skipped: usize,
+}
+
+/// Turn a PRONOM byte sequence such as `25504446{0-4}[30:39]` into a regex.
+fn sequence_pattern(seq: &str) -> Result<String, String> {
+    let chars: Vec<char> = seq.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut out = String::new();
+    let mut i = 0;
+    let hex = |i: usize| -> Result<u8, String> {
+        chars
+            .get(i..i + 2)
+            .and_then(|pair| u8::from_str_radix(&pair.iter().collect::<String>(), 16).ok())
+            .ok_or(format!("bad hex at {i} in \"{seq}\""))
+    };
+    while i < chars.len() {
+        match chars[i] {
+            '?' if chars.get(i + 1) == Some(&'?') => {
+                out.push('.');
+                i += 2;
+            }
+            '*' => {
+                out.push_str(".*");
+                i += 1;
+            }
+            '{' => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|c| *c == '}')
+                    .ok_or(format!("unclosed gap in \"{seq}\""))?;
+                let gap: String = chars[i + 1..i + end].iter().collect();
+                let (min, max) = match gap.split_once('-') {
+                    Some((min, "*")) => (min, None),
+                    Some((min, max)) => (min, Some(max)),
+                    None => (gap.as_str(), Some(gap.as_str())),
+                };
+                let min: usize = min.parse().map_err(|_| format!("bad gap in \"{seq}\""))?;
+                let max = max
+                    .map(|m| m.parse::<usize>())
+                    .transpose()
+                    .map_err(|_| format!("bad gap in \"{seq}\""))?;
+                out.push_str(&gap_pattern(min, max));
+                i += end + 1;
+            }
+            '[' => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|c| *c == ']')
+                    .ok_or(format!("unclosed range in \"{seq}\""))?;
+                let inner = &chars[i + 1..i + end];
+                let (negated, inner) = match inner.first() {
+                    Some('!') => (true, &inner[1..]),
+                    _ => (false, inner),
+                };
+                let byte = |j: usize| -> Result<u8, String> {
+                    inner
+                        .get(j..j + 2)
+                        .and_then(|p| u8::from_str_radix(&p.iter().collect::<String>(), 16).ok())
+                        .ok_or(format!("bad range in \"{seq}\""))
+                };
+                let class = match inner.len() {
+                    2 => format!("\\x{:02X}", byte(0)?),
+                    5 if inner[2] == ':' => {
+                        let (a, b) = (byte(0)?, byte(3)?);
+                        format!("\\x{:02X}-\\x{:02X}", a.min(b), a.max(b))
+                    }
+                    _ => return Err(format!("unsupported range in \"{seq}\"")),
+                };
+                out.push_str(&format!("[{}{class}]", if negated { "^" } else { "" }));
+                i += end + 1;
+            }
+            '(' => {
+                out.push_str("(?:");
+                i += 1;
+            }
+            '|' | ')' => {
+                out.push(chars[i]);
+                i += 1;
+            }
+            '\'' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|c| *c == '\'')
+                    .ok_or(format!("unclosed text in \"{seq}\""))?;
+                let literal: String = chars[i + 1..i + 1 + end].iter().collect();
+                out.push_str(&regex::escape(&literal));
+                i += end + 2;
+            }
+            _ => {
+                let _ = write!(out, "\\x{:02X}", hex(i)?);
+                i += 2;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Any `min` to `max` bytes, or at least `min` when there is no maximum.
+fn gap_pattern(min: usize, max: Option<usize>) -> String {
+    match max {
+        Some(0) if min == 0 => String::new(),
+        Some(max) if max == min => format!(".{{{min}}}"),
+        Some(max) => format!(".{{{min},{}}}", max.max(min)),
+        None => format!(".{{{min},}}"),
+    }
+}
+
+fn number(node: &roxmltree::Node, name: &str) -> Option<usize> {
+    node.attribute(name).and_then(|v| v.trim().parse().ok())
+}
+
+fn children<'a, 'input>(
+    node: &roxmltree::Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.children()
+        .filter(move |c| c.is_element() && c.tag_name().name() == name)
+}
+
+/// The pattern for one `SubSequence`: its anchor sequence with the fragments on either side.
+fn subsequence_pattern(sub: &roxmltree::Node) -> Result<String, String> {
+    let sequence = children(sub, "Sequence")
+        .next()
+        .and_then(|s| s.text())
+        .ok_or("subsequence without a sequence")?;
+    // Fragments at the same position are alternatives; position 1 is next to the sequence
+    let fragments = |side: &'static str| -> Result<Vec<Vec<String>>, String> {
+        let mut by_position: Vec<Vec<String>> = vec![];
+        for fragment in children(sub, side) {
+            let position = number(&fragment, "Position").unwrap_or(1).max(1);
+            let pattern = sequence_pattern(fragment.text().unwrap_or_default())?;
+            let gap = gap_pattern(
+                number(&fragment, "MinOffset").unwrap_or(0),
+                number(&fragment, "MaxOffset"),
+            );
+            if by_position.len() < position {
+                by_position.resize(position, vec![]);
+            }
+            by_position[position - 1].push(if side == "LeftFragment" {
+                format!("{pattern}{gap}")
+            } else {
+                format!("{gap}{pattern}")
+            });
+        }
+        Ok(by_position)
+    };
+    let alternatives = |alts: &Vec<String>| format!("(?:{})", alts.join("|"));
+
+    let mut out = String::new();
+    for alts in fragments("LeftFragment")?.iter().rev() {
+        out.push_str(&alternatives(alts));
+    }
+    out.push_str(&sequence_pattern(sequence)?);
+    for alts in fragments("RightFragment")?.iter() {
+        out.push_str(&alternatives(alts));
+    }
+    Ok(out)
+}
+
+/// The regex for one `ByteSequence`, and whether it is anchored to the end of the file.
+///
+/// Subsequences are chained in position order, separated by their offsets. The offsets of
+/// end-anchored sequences count back from the end of the file.
+fn byte_sequence(node: &roxmltree::Node) -> Result<(Regex, bool), String> {
+    let reference = node.attribute("Reference").unwrap_or("Variable");
+    let mut subs: Vec<_> = children(node, "SubSequence")
+        .map(|sub| {
+            let position = number(&sub, "Position").unwrap_or(1);
+            let min = number(&sub, "SubSeqMinOffset").unwrap_or(0);
+            let max = number(&sub, "SubSeqMaxOffset");
+            subsequence_pattern(&sub).map(|p| (position, min, max, p))
+        })
+        .collect::<Result<_, _>>()?;
+    subs.sort_by_key(|(position, ..)| *position);
+
+    let mut pattern = "(?s-u)".to_string();
+    let at_end = match reference {
+        "BOFoffset" => {
+            pattern.push_str("\\A");
+            for (i, (_, min, max, sub)) in subs.iter().enumerate() {
+                // The first subsequence sits at a fixed offset unless a maximum is given
+                let max = if i == 0 { max.or(Some(*min)) } else { *max };
+                pattern.push_str(&gap_pattern(*min, max));
+                pattern.push_str(sub);
+            }
+            false
+        }
+        "EOFoffset" => {
+            for (i, (_, min, max, sub)) in subs.iter().enumerate().rev() {
+                let max = if i == 0 { max.or(Some(*min)) } else { *max };
+                pattern.push_str(sub);
+                pattern.push_str(&gap_pattern(*min, max));
+            }
+            pattern.push_str("\\z");
+            true
+        }
+        "Variable" => {
+            for (i, (_, min, max, sub)) in subs.iter().enumerate() {
+                if i > 0 {
+                    pattern.push_str(&gap_pattern(*min, *max));
+                }
+                pattern.push_str(sub);
+            }
+            false
+        }
+        other => return Err(format!("unsupported reference {other}")),
+    };
+    let regex = RegexBuilder::new(&pattern)
+        .unicode(false)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok((regex, at_end))
+}
+
+/// Read a DROID signature file. Signatures that can't be compiled are skipped and counted.
+pub(crate) fn load(path: &Path) -> anyhow::Result<SignatureFile> {
+    let xml = std::fs::read_to_string(path)?;
+    let doc = roxmltree::Document::parse(&xml)?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "FFSignatureFile" {
+        anyhow::bail!("{} is not a DROID signature file", path.display());
+    }
+
+    let mut signatures = vec![];
+    let mut skipped = 0;
+    for collection in children(&root, "InternalSignatureCollection") {
+        for node in children(&collection, "InternalSignature") {
+            let id = node.attribute("ID").unwrap_or_default().to_string();
+            let compiled = children(&node, "ByteSequence")
+                .map(|seq| byte_sequence(&seq))
+                .collect::<Result<Vec<_>, _>>();
+            match compiled {
+                Ok(sequences) if !sequences.is_empty() => {
+                    signatures.push(Signature { id, sequences })
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::debug!("Skipped PRONOM signature {}: {}", id, e);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+
+    let mut formats = vec![];
+    for collection in children(&root, "FileFormatCollection") {
+        for node in children(&collection, "FileFormat") {
+            let attr = |name| node.attribute(name).unwrap_or_default().to_string();
+            let texts = |name| {
+                children(&node, name)
+                    .filter_map(|c| c.text())
+                    .map(|t| t.trim().to_string())
+                    .collect()
+            };
+            let extensions: Vec<String> = texts("Extension");
+            formats.push(Format {
+                id: attr("ID"),
+                puid: attr("PUID"),
+                name: attr("Name"),
+                version: attr("Version"),
+                mime: attr("MIMEType"),
+                extensions: extensions.iter().map(|e| e.to_ascii_lowercase()).collect(),
+                signatures: texts("InternalSignatureID"),
+                priority_over: texts("HasPriorityOverFileFormatID"),
+            });
+        }
+    }
+
+    Ok(SignatureFile {
+        version: root.attribute("Version").unwrap_or_default().to_string(),
+        formats,
+        signatures,
+        skipped,
+    })
+}
+
+impl SignatureFile {
+    /// Identify a file from its sampled bytes and its extension, the way DROID does.
+    ///
+    /// Sequences anchored to the end of the file are matched against the sample's tail, so
+    /// they can only match when a tail was read or the head is the whole file. Without a
+    /// signature match, every format with the extension is returned.
+    pub(crate) fn identify(&self, sample: &Sample, extension: Option<&str>) -> Vec<Identification> {
+        let extension = extension.map(str::to_ascii_lowercase);
+        let end = sample.end();
+        let matched: HashMap<&str, usize> = self
+            .signatures
+            .iter()
+            .filter_map(|sig| {
+                let mut offset = None;
+                for (regex, at_end) in &sig.sequences {
+                    let found = if *at_end {
+                        if end.is_empty() {
+                            return None;
+                        }
+                        sample.tail_start() as usize + regex.find(end)?.start()
+                    } else {
+                        regex.find(&sample.head)?.start()
+                    };
+                    offset.get_or_insert(found);
+                }
+                Some((sig.id.as_str(), offset.unwrap_or(0)))
+            })
+            .collect();
+
+        let by_signature: Vec<(&Format, usize)> = self
+            .formats
+            .iter()
+            .filter_map(|format| {
+                format
+                    .signatures
+                    .iter()
+                    .find_map(|id| matched.get(id.as_str()))
+                    .map(|offset| (format, *offset))
+            })
+            .collect();
+        // A match loses to any other match that has priority over it
+        let by_signature: Vec<_> = by_signature
+            .iter()
+            .filter(|(format, _)| {
+                !by_signature
+                    .iter()
+                    .any(|(other, _)| other.priority_over.contains(&format.id))
+            })
+            .collect();
+
+        let has_extension = |format: &Format| {
+            extension
+                .as_ref()
+                .is_some_and(|e| format.extensions.contains(e))
+        };
+        let identification =
+            |format: &Format, basis: Basis, extension_mismatch: bool| Identification {
+                puid: format.puid.clone(),
+                name: format.name.clone(),
+                version: format.version.clone(),
+                mime: format.mime.clone(),
+                basis,
+                extension_mismatch,
+            };
+
+        if by_signature.is_empty() {
+            self.formats
+                .iter()
+                .filter(|format| has_extension(format))
+                .map(|format| identification(format, Basis::Extension, false))
+                .collect()
+        } else {
+            by_signature
+                .into_iter()
+                .map(|(format, offset)| {
+                    identification(
+                        format,
+                        Basis::Signature { offset: *offset },
+                        extension.is_some() && !has_extension(format),
+                    )
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<FFSignatureFile xmlns="http://www.nationalarchives.gov.uk/pronom/SignatureFile" Version="42">
+  <InternalSignatureCollection>
+    <InternalSignature ID="1">
+      <ByteSequence Reference="BOFoffset">
+        <SubSequence Position="1" SubSeqMinOffset="0">
+          <Sequence>255044462D312E</Sequence>
+          <RightFragment Position="1" MinOffset="0" MaxOffset="0">[30:37]</RightFragment>
+        </SubSequence>
+      </ByteSequence>
+      <ByteSequence Reference="EOFoffset">
+        <SubSequence Position="1" SubSeqMinOffset="0" SubSeqMaxOffset="8">
+          <Sequence>2525454F46</Sequence>
+        </SubSequence>
+      </ByteSequence>
+    </InternalSignature>
+    <InternalSignature ID="2">
+      <ByteSequence Reference="BOFoffset">
+        <SubSequence Position="1" SubSeqMinOffset="0">
+          <Sequence>25504446</Sequence>
+        </SubSequence>
+      </ByteSequence>
+    </InternalSignature>
+    <InternalSignature ID="3">
+      <ByteSequence Reference="BOFoffset">
+        <SubSequence Position="1" SubSeqMinOffset="0">
+          <Sequence>[01:02:03]</Sequence>
+        </SubSequence>
+      </ByteSequence>
+    </InternalSignature>
+  </InternalSignatureCollection>
+  <FileFormatCollection>
+    <FileFormat ID="10" PUID="fmt/18" Name="Acrobat PDF 1.4" Version="1.4"
+        MIMEType="application/pdf">
+      <InternalSignatureID>1</InternalSignatureID>
+      <Extension>pdf</Extension>
+      <HasPriorityOverFileFormatID>11</HasPriorityOverFileFormatID>
+    </FileFormat>
+    <FileFormat ID="11" PUID="fmt/14" Name="Acrobat PDF" MIMEType="application/pdf">
+      <InternalSignatureID>2</InternalSignatureID>
+      <Extension>pdf</Extension>
+    </FileFormat>
+    <FileFormat ID="12" PUID="x-fmt/111" Name="Plain Text File">
+      <Extension>txt</Extension>
+    </FileFormat>
+  </FileFormatCollection>
+</FFSignatureFile>"#;
+
+    #[test]
+    fn sequences() {
+        let cases = [
+            ("25504446", r"\x25\x50\x44\x46"),
+            ("25 50 {0-4} [30:39]", r"\x25\x50.{0,4}[\x30-\x39]"),
+            ("0D??0A*FF", r"\x0D.\x0A.*\xFF"),
+            ("00{4}01{2-*}02", r"\x00.{4}\x01.{2,}\x02"),
+            ("[!0D][39:30]", r"[^\x0D][\x30-\x39]"),
+            ("(01|0203)", r"(?:\x01|\x02\x03)"),
+            ("'a.b'00", r"a\.b\x00"),
+            ("{0}", ""),
+        ];
+        for (sequence, pattern) in cases {
+            assert_eq!(
+                sequence_pattern(sequence).as_deref(),
+                Ok(pattern),
+                "{sequence}"
+            );
+        }
+    }
+
+    #[test]
+    fn bad_sequences() {
+        for sequence in ["2", "0G", "{1-", "{a}", "[01", "[01:02:03]", "'abc"] {
+            assert!(sequence_pattern(sequence).is_err(), "{sequence}");
+        }
+    }
+
+    #[test]
+    fn gaps() {
+        assert_eq!(gap_pattern(0, Some(0)), "");
+        assert_eq!(gap_pattern(3, Some(3)), ".{3}");
+        assert_eq!(gap_pattern(1, Some(5)), ".{1,5}");
+        assert_eq!(gap_pattern(5, Some(1)), ".{5,5}");
+        assert_eq!(gap_pattern(2, None), ".{2,}");
+    }
+
+    fn byte_sequence_of(xml: &str) -> (Regex, bool) {
+        let doc = roxmltree::Document::parse(xml).unwrap();
+        byte_sequence(&doc.root_element()).unwrap()
+    }
+
+    #[test]
+    fn anchored_sequences() {
+        let (bof, at_end) = byte_sequence_of(
+            r#"<ByteSequence Reference="BOFoffset">
+                <SubSequence Position="1" SubSeqMinOffset="2">
+                    <Sequence>4142</Sequence>
+                </SubSequence>
+            </ByteSequence>"#,
+        );
+        assert!(!at_end);
+        assert!(bof.is_match(b"..AB"));
+        assert!(!bof.is_match(b"...AB"));
+        assert!(!bof.is_match(b"AB"));
+
+        let (eof, at_end) = byte_sequence_of(
+            r#"<ByteSequence Reference="EOFoffset">
+                <SubSequence Position="1" SubSeqMinOffset="0" SubSeqMaxOffset="2">
+                    <Sequence>4142</Sequence>
+                </SubSequence>
+            </ByteSequence>"#,
+        );
+        assert!(at_end);
+        assert!(eof.is_match(b"xxAB\n"));
+        assert!(!eof.is_match(b"xxAB\n\n\n"));
+    }
+
+    #[test]
+    fn fragments_and_variable_sequences() {
+        let (regex, _) = byte_sequence_of(
+            r#"<ByteSequence Reference="Variable">
+                <SubSequence Position="1">
+                    <Sequence>4142</Sequence>
+                    <LeftFragment Position="1" MinOffset="0" MaxOffset="1">58</LeftFragment>
+                    <RightFragment Position="1" MinOffset="1" MaxOffset="1">59</RightFragment>
+                    <RightFragment Position="1" MinOffset="1" MaxOffset="1">5A</RightFragment>
+                </SubSequence>
+                <SubSequence Position="2" SubSeqMinOffset="0" SubSeqMaxOffset="0">
+                    <Sequence>21</Sequence>
+                </SubSequence>
+            </ByteSequence>"#,
+        );
+        assert!(regex.is_match(b"..XAB.Y!"));
+        assert!(regex.is_match(b"X.AB.Z!"));
+        assert!(!regex.is_match(b"XAB.Y"));
+        assert!(!regex.is_match(b"XABY!"));
+    }
+
+    fn load_test_file() -> SignatureFile {
+        let path = std::env::temp_dir().join(format!("pronom-test-{}.xml", std::process::id()));
+        std::fs::write(&path, SIGNATURES).unwrap();
+        let file = load(&path);
+        let _ = std::fs::remove_file(&path);
+        file.unwrap()
+    }
+
+    fn whole(bytes: &[u8]) -> Sample {
+        Sample {
+            head: bytes.to_vec(),
+            tail: vec![],
+            size: bytes.len() as u64,
+        }
+    }
+
+    fn puids(found: &[Identification]) -> Vec<&str> {
+        found.iter().map(|i| i.puid.as_str()).collect()
+    }
+
+    #[test]
+    fn signature_file() {
+        let file = load_test_file();
+        assert_eq!(file.version, "42");
+        assert_eq!(file.skipped, 1);
+
+        let pdf = whole(b"%PDF-1.4\n1 0 obj\n%%EOF\n");
+        let found = file.identify(&pdf, Some("PDF"));
+        // The more specific format has priority over the generic one
+        assert_eq!(puids(&found), ["fmt/18"]);
+        assert_eq!(found[0].basis, Basis::Signature { offset: 0 });
+        assert!(!found[0].extension_mismatch);
+        assert!(file.identify(&pdf, Some("txt"))[0].extension_mismatch);
+
+        // Without its end, only the generic signature matches
+        let truncated = Sample {
+            size: 1_000_000,
+            ..whole(b"%PDF-1.4\n1 0 obj\n")
+        };
+        assert_eq!(puids(&file.identify(&truncated, Some("pdf"))), ["fmt/14"]);
+
+        let text = whole(b"hello");
+        let found = file.identify(&text, Some("txt"));
+        assert_eq!(puids(&found), ["x-fmt/111"]);
+        assert_eq!(found[0].basis, Basis::Extension);
+        assert!(file.identify(&text, None).is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use r#box::{
+    apis::{
+        configuration::Configuration,
+        files_api::{GetFilesIdParams, get_files_id},
+    },
+    models::representations_entries_inner_status::State,
+};
+
+/// Paged PNGs for documents, with a single JPG as the fallback for formats Box can't page.
+const HINTS: &str = "[png?dimensions=1024x1024][jpg?dimensions=1024x1024]";
+
+/// Box renders representations on request, which can take a while for large documents.
+const POLL_ATTEMPTS: usize = 10;
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Pages of a document rendered to raster by Box.
+#[derive(Debug, Clone)]
+pub(crate) struct Rendition {
+    url_template: String,
+    /// File extension of each page's asset
+    format: String,
+    paged: bool,
+    /// Known once Box has finished rendering
+    pub pages: Option<usize>,
+}
+
+impl Rendition {
+    /// URL of a one-based page.
+    pub fn page_url(&self, page: usize) -> String {
+        let asset = if self.paged {
+            format!("{page}.{}", self.format)
+        } else {
+            String::new()
+        };
+        self.url_template.replace("{+asset_path}", &asset)
+    }
+
+    pub fn page_count(&self) -> Option<usize> {
+        if self.paged { self.pages } else { Some(1) }
+    }
+}
+
+async fn get_json(config: &Configuration, url: &str) -> Result<serde_json::Value, String> {
+    let token = config
+        .oauth_access_token
+        .clone()
+        .ok_or("Not logged in to Box")?;
+    config
+        .client
+        .get(url)
+        .bearer_auth(token)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Ask Box for a raster rendition of a file, waiting for it to be generated if needed.
+pub(crate) async fn find(config: Configuration, id: String) -> Result<Rendition, String> {
+    let file = get_files_id(
+        &config,
+        GetFilesIdParams {
+            file_id: id.clone(),
+            fields: Some(vec!["representations".to_string()]),
+            if_none_match: None,
+            boxapi: None,
+            x_rep_hints: Some(HINTS.to_string()),
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let entries = file
+        .representations
+        .and_then(|r| r.entries)
+        .unwrap_or_default();
+    let entry = ["png", "jpg"]
+        .into_iter()
+        .find_map(|rep| {
+            entries
+                .iter()
+                .find(|e| e.representation.as_deref() == Some(rep))
+        })
+        .ok_or("Box has no preview for this file")?;
+
+    let url_template = entry
+        .content
+        .as_ref()
+        .and_then(|c| c.url_template.clone())
+        .ok_or("Box did not say where the preview is")?;
+    let paged = entry.properties.as_ref().and_then(|p| p.paged.as_deref()) == Some("true");
+    let mut state = entry.status.as_ref().and_then(|s| s.state);
+    let mut pages = None;
+
+    // The info URL both starts rendering and reports the page count
+    if let Some(info_url) = entry.info.as_ref().and_then(|i| i.url.clone()) {
+        for attempt in 0..POLL_ATTEMPTS {
+            let info = get_json(&config, &info_url).await?;
+            state = serde_json::from_value(info["status"]["state"].clone()).ok();
+            pages = info["metadata"]["pages"].as_u64().map(|p| p as usize);
+            if matches!(state, Some(State::Success | State::Viewable)) {
+                break;
+            }
+            tracing::debug!("Waiting for Box to render {} (attempt {})", id, attempt + 1);
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+    if !matches!(state, Some(State::Success | State::Viewable)) {
+        return Err("Box is still rendering the preview. Select the file again later.".to_string());
+    }
+
+    Ok(Rendition {
+        url_template,
+        format: entry.representation.clone().unwrap_or_default(),
+        paged,
+        pages,
+    })
+}
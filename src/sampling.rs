@@ -0,0 +1,344 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use r#box::apis::configuration::Configuration;
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    widget::{Column, Space, TextInput, button, column, row, scrollable, text},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{Message, State, media, program_settings, subwindows::Subwindow};
+
+/// The most that can be read from either end of a file, in KiB.
+const MAX_KIB: u64 = 64 * 1024;
+
+/// How much of a file to download, from each end, to identify it and read its metadata.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ReadStrategy {
+    /// Extensions the strategy is for, separated by spaces or commas
+    pub extensions: String,
+    pub head_kib: u64,
+    pub tail_kib: u64,
+}
+
+impl ReadStrategy {
+    fn new(extensions: &str, head_kib: u64, tail_kib: u64) -> Self {
+        Self {
+            extensions: extensions.to_string(),
+            head_kib,
+            tail_kib,
+        }
+    }
+
+    fn matches(&self, extension: &str) -> bool {
+        self.extensions
+            .split([' ', ','])
+            .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+    }
+}
+
+/// Read strategies by format, configured in Program Settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct SamplingSettings {
+    pub strategies: Vec<ReadStrategy>,
+    /// Used for extensions no strategy lists
+    pub default_head_kib: u64,
+    pub default_tail_kib: u64,
+}
+
+impl Default for SamplingSettings {
+    fn default() -> Self {
+        Self {
+            strategies: vec![
+                // EXIF, XMP and IPTC sit before the image data, and the last bytes show whether
+                // the image was cut short
+                ReadStrategy::new("jpg jpeg tif tiff png webp heic heif", 128, 1),
+                // The Info dictionary and cross-reference table are usually at the end
+                ReadStrategy::new("pdf", 64, 64),
+                // The central directory is at the end
+                ReadStrategy::new(
+                    "zip jar epub docx docm xlsx xlsm pptx pptm odt ods odp odg",
+                    16,
+                    64,
+                ),
+                // ID3v1 is the last 128 bytes
+                ReadStrategy::new("mp3", 256, 1),
+                // The index of MP4s written without "fast start" is at the end
+                ReadStrategy::new("mp4 m4a m4v mov 3gp", 1024, 1024),
+                // Ogg durations come from the last page
+                ReadStrategy::new("ogg oga opus", 256, 64),
+                ReadStrategy::new("wav wave aif aiff aifc flac mkv mka webm avi", 1024, 0),
+                ReadStrategy::new("txt csv tsv md json xml html htm", 16, 0),
+            ],
+            default_head_kib: 100,
+            default_tail_kib: 0,
+        }
+    }
+}
+
+impl SamplingSettings {
+    /// Bytes to read from the start and the end of a file called `name`.
+    pub fn sizes(&self, name: &str) -> (u64, u64) {
+        let extension = name
+            .rsplit_once('.')
+            .map(|(_, ext)| ext)
+            .unwrap_or_default();
+        let (head, tail) = self
+            .strategies
+            .iter()
+            .find(|s| !extension.is_empty() && s.matches(extension))
+            .map_or((self.default_head_kib, self.default_tail_kib), |s| {
+                (s.head_kib, s.tail_kib)
+            });
+        (head.saturating_mul(1024), tail.saturating_mul(1024))
+    }
+}
+
+/// The parts of a file downloaded to identify it, shared by everything that looks at it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Sample {
+    pub head: Vec<u8>,
+    /// The last bytes of the file, empty when the head already reached them
+    pub tail: Vec<u8>,
+    /// Size of the whole file
+    pub size: u64,
+}
+
+impl Sample {
+    /// Whether the head is the whole file.
+    pub fn complete(&self) -> bool {
+        self.head.len() as u64 >= self.size
+    }
+
+    /// The last bytes read: the tail, or the whole file when the head reached the end.
+    pub fn end(&self) -> &[u8] {
+        if self.complete() {
+            &self.head
+        } else {
+            &self.tail
+        }
+    }
+
+    /// Where the tail starts in the file.
+    pub fn tail_start(&self) -> u64 {
+        self.size - self.end().len() as u64
+    }
+
+    /// Read the sample as if it were the file. Reads between the head and the tail fail.
+    pub fn reader(&self) -> SampleReader<'_> {
+        SampleReader {
+            sample: self,
+            pos: 0,
+        }
+    }
+}
+
+pub(crate) struct SampleReader<'a> {
+    sample: &'a Sample,
+    pos: u64,
+}
+
+impl Read for SampleReader<'_> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let sample = self.sample;
+        if self.pos >= sample.size || out.is_empty() {
+            return Ok(0);
+        }
+        let (bytes, start) = if self.pos < sample.head.len() as u64 {
+            (&sample.head[self.pos as usize..], self.pos)
+        } else if self.pos >= sample.tail_start() {
+            let at = (self.pos - sample.tail_start()) as usize;
+            (&sample.end()[at..], self.pos)
+        } else {
+            return Err(io::Error::other("that part of the file was not downloaded"));
+        };
+        let n = out.len().min(bytes.len());
+        out[..n].copy_from_slice(&bytes[..n]);
+        self.pos = start + n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for SampleReader<'_> {
+    fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+        let pos = match from {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(d) => self.sample.size.checked_add_signed(d),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+        };
+        self.pos = pos.ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "seek before the start of the file",
+        ))?;
+        Ok(self.pos)
+    }
+}
+
+/// Download the first `head` and last `tail` bytes of file `id`. The tail is skipped when the
+/// head already covers it.
+pub(crate) async fn fetch(
+    config: &Configuration,
+    id: &str,
+    head: u64,
+    tail: u64,
+) -> Result<Sample, String> {
+    let range = format!("bytes=0-{}", head.max(1) - 1);
+    let (head, total) = media::fetch_range(config, id, Some(range)).await?;
+    // Without a Content-Range the server sent the whole file
+    let size = total.unwrap_or(head.len() as u64);
+    let read = head.len() as u64;
+    let tail = if tail > 0 && size > read {
+        let start = size.saturating_sub(tail).max(read);
+        let range = format!("bytes={start}-{}", size - 1);
+        media::fetch_range(config, id, Some(range)).await?.0
+    } else {
+        vec![]
+    };
+    Ok(Sample { head, tail, size })
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum SamplingMessage {
+    SetExtensions(usize, String),
+    SetHead(usize, String),
+    SetTail(usize, String),
+    SetDefaultHead(String),
+    SetDefaultTail(String),
+    Add,
+    Remove(usize),
+    Reset,
+}
+
+/// Sizes are typed in KiB, up to [`MAX_KIB`]. An empty input is 0.
+fn parse_kib(input: &str) -> Option<u64> {
+    match input.trim() {
+        "" => Some(0),
+        kib => kib.parse::<u64>().ok().map(|kib| kib.min(MAX_KIB)),
+    }
+}
+
+pub(crate) fn sampling_handle(state: &mut State, event: SamplingMessage) -> Task<Message> {
+    let settings = &mut state.program_set_state.sampling;
+    match event {
+        SamplingMessage::SetExtensions(i, extensions) => match settings.strategies.get_mut(i) {
+            Some(strategy) => strategy.extensions = extensions,
+            None => return Task::none(),
+        },
+        SamplingMessage::SetHead(i, kib) => {
+            match (settings.strategies.get_mut(i), parse_kib(&kib)) {
+                (Some(strategy), Some(kib)) => strategy.head_kib = kib,
+                _ => return Task::none(),
+            }
+        }
+        SamplingMessage::SetTail(i, kib) => {
+            match (settings.strategies.get_mut(i), parse_kib(&kib)) {
+                (Some(strategy), Some(kib)) => strategy.tail_kib = kib,
+                _ => return Task::none(),
+            }
+        }
+        SamplingMessage::SetDefaultHead(kib) => match parse_kib(&kib) {
+            Some(kib) => settings.default_head_kib = kib,
+            None => return Task::none(),
+        },
+        SamplingMessage::SetDefaultTail(kib) => match parse_kib(&kib) {
+            Some(kib) => settings.default_tail_kib = kib,
+            None => return Task::none(),
+        },
+        SamplingMessage::Add => settings.strategies.push(ReadStrategy::new("", 100, 0)),
+        SamplingMessage::Remove(i) => {
+            if i < settings.strategies.len() {
+                settings.strategies.remove(i);
+            }
+        }
+        SamplingMessage::Reset => {
+            tracing::info!("Reset read strategies to the defaults");
+            *settings = SamplingSettings::default();
+        }
+    }
+    program_settings::save(state.program_set_state.clone())
+}
+
+fn size_input<'a>(
+    placeholder: &str,
+    kib: u64,
+    on_input: impl Fn(String) -> SamplingMessage + 'a,
+) -> Element<'a, Message> {
+    TextInput::new(placeholder, &kib.to_string())
+        .on_input(move |s| Message::SamplingMessage(on_input(s)))
+        .width(70)
+        .into()
+}
+
+pub(crate) fn sampling_settings(state: &State) -> Element<'_, Message> {
+    let settings = &state.program_set_state.sampling;
+    let header = row![
+        text("Extensions").size(12).width(Fill),
+        text("Head KiB").size(12).width(70),
+        text("Tail KiB").size(12).width(70),
+        Space::new().width(70),
+    ]
+    .spacing(5);
+    let strategies = settings.strategies.iter().enumerate().fold(
+        Column::new().spacing(5),
+        |col, (i, strategy)| {
+            col.push(
+                row![
+                    TextInput::new("e.g. mp4 mov", &strategy.extensions).on_input(move |e| {
+                        Message::SamplingMessage(SamplingMessage::SetExtensions(i, e))
+                    }),
+                    size_input("Head", strategy.head_kib, move |s| {
+                        SamplingMessage::SetHead(i, s)
+                    }),
+                    size_input("Tail", strategy.tail_kib, move |s| {
+                        SamplingMessage::SetTail(i, s)
+                    }),
+                    button("Remove")
+                        .style(button::text)
+                        .width(70)
+                        .on_press(Message::SamplingMessage(SamplingMessage::Remove(i))),
+                ]
+                .spacing(5)
+                .align_y(Center),
+            )
+        },
+    );
+    let default = row![
+        text("Anything else").width(Fill),
+        size_input(
+            "Head",
+            settings.default_head_kib,
+            SamplingMessage::SetDefaultHead
+        ),
+        size_input(
+            "Tail",
+            settings.default_tail_kib,
+            SamplingMessage::SetDefaultTail
+        ),
+        Space::new().width(70),
+    ]
+    .spacing(5)
+    .align_y(Center);
+
+    column![
+        text("Bytes downloaded from the start and end of each file when building a sheet"),
+        text("Containers such as ZIP, PDF and MP4 keep their directory or index at the end, so they need a tail. Files small enough to fit in the head are read once.").size(12),
+        header,
+        scrollable(column![strategies, default].spacing(5)).height(Fill),
+        row![
+            button("Add").on_press(Message::SamplingMessage(SamplingMessage::Add)),
+            button("Reset to defaults")
+                .style(button::secondary)
+                .on_press(Message::SamplingMessage(SamplingMessage::Reset)),
+            Space::new().width(Fill),
+            button("Close").on_press(Message::CloseWindow(Subwindow::Sampling)),
+        ]
+        .spacing(10),
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(10)
+    .into()
+}
@@ -0,0 +1,958 @@
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    widget::{
+        Column, Space, TextInput, button, checkbox, column, pick_list, row, scrollable, text,
+    },
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Message, State, homepage,
+    metadata::{Field, ItemMetadata},
+    project_page::InternalType,
+    project_settings::save_project,
+    sheet_format::column_index,
+    sheet_plan,
+    subwindows::Subwindow,
+    update,
+};
+
+/// Separates the values of a repeatable field, matching how tags are written to the sheet.
+pub(crate) const SEPARATOR: char = ';';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum FieldKind {
+    Text,
+    /// ISO 8601: YYYY, YYYY-MM or YYYY-MM-DD
+    Date,
+    /// Extended Date/Time Format levels 0 and 1
+    Edtf,
+    Enum,
+    Number,
+    Url,
+}
+
+impl FieldKind {
+    pub const ALL: [FieldKind; 6] = [
+        FieldKind::Text,
+        FieldKind::Date,
+        FieldKind::Edtf,
+        FieldKind::Enum,
+        FieldKind::Number,
+        FieldKind::Url,
+    ];
+
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            FieldKind::Text | FieldKind::Enum => "",
+            FieldKind::Date => "YYYY-MM-DD",
+            FieldKind::Edtf => "EDTF, e.g. 1968, 1968-05?, 196X or 1968/1972",
+            FieldKind::Number => "Number",
+            FieldKind::Url => "https://",
+        }
+    }
+}
+
+impl std::fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldKind::Text => write!(f, "Text"),
+            FieldKind::Date => write!(f, "Date"),
+            FieldKind::Edtf => write!(f, "EDTF date"),
+            FieldKind::Enum => write!(f, "Choice"),
+            FieldKind::Number => write!(f, "Number"),
+            FieldKind::Url => write!(f, "URL"),
+        }
+    }
+}
+
+/// Where a field's values are stored in [`ItemMetadata`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum FieldKey {
+    Builtin(Field),
+    Tags,
+    /// Defined by the project and kept in [`ItemMetadata::custom`]
+    Custom(String),
+}
+
+impl std::fmt::Display for FieldKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldKey::Builtin(field) => write!(f, "{field}"),
+            FieldKey::Tags => write!(f, "Tags"),
+            FieldKey::Custom(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// A descriptive field and the rules its values must follow.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct FieldDef {
+    pub key: FieldKey,
+    pub kind: FieldKind,
+    pub required: bool,
+    /// Allows several values separated by [`SEPARATOR`]
+    pub repeatable: bool,
+    /// Every value must match this in full, if set
+    pub pattern: String,
+    /// Bounds for numbers
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Allowed values for choices
+    pub options: Vec<String>,
+    /// Sheet column for custom fields. Built-in fields use the template's columns.
+    pub column: String,
+}
+
+impl Default for FieldDef {
+    fn default() -> Self {
+        Self::new(FieldKey::Custom(String::new()), FieldKind::Text)
+    }
+}
+
+impl FieldDef {
+    fn new(key: FieldKey, kind: FieldKind) -> Self {
+        Self {
+            key,
+            kind,
+            required: false,
+            repeatable: false,
+            pattern: String::new(),
+            min: None,
+            max: None,
+            options: vec![],
+            column: String::new(),
+        }
+    }
+
+    /// Every value entered for this field, split if it is repeatable.
+    pub fn values(&self, metadata: &ItemMetadata) -> Vec<String> {
+        let raw = match &self.key {
+            FieldKey::Tags => return metadata.tags.clone(),
+            FieldKey::Builtin(field) => field.value(metadata),
+            FieldKey::Custom(name) => metadata.custom.get(name).map_or("", |v| v.as_str()),
+        };
+        if self.repeatable {
+            raw.split(SEPARATOR)
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect()
+        } else if raw.trim().is_empty() {
+            vec![]
+        } else {
+            vec![raw.trim().to_string()]
+        }
+    }
+
+    fn check_value(&self, value: &str, pattern: Option<&Regex>) -> Result<(), String> {
+        match self.kind {
+            FieldKind::Text => {}
+            FieldKind::Date if !iso_date(value) => {
+                return Err(format!("\"{value}\" is not a YYYY-MM-DD date"));
+            }
+            FieldKind::Edtf if !edtf(value) => {
+                return Err(format!("\"{value}\" is not an EDTF date"));
+            }
+            FieldKind::Enum if !self.options.iter().any(|o| o == value) => {
+                return Err(format!("\"{value}\" is not one of the allowed values"));
+            }
+            FieldKind::Number => {
+                let n: f64 = value
+                    .parse()
+                    .map_err(|_| format!("\"{value}\" is not a number"))?;
+                if self.min.is_some_and(|min| n < min) || self.max.is_some_and(|max| n > max) {
+                    return Err(format!("{value} is outside {}", self.range()));
+                }
+            }
+            FieldKind::Url => {
+                url::Url::parse(value).map_err(|e| format!("\"{value}\" is not a URL: {e}"))?;
+            }
+            _ => {}
+        }
+        if let Some(pattern) = pattern
+            && !pattern.is_match(value)
+        {
+            return Err(format!("\"{value}\" does not match {}", self.pattern));
+        }
+        Ok(())
+    }
+
+    fn range(&self) -> String {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => format!("{min} to {max}"),
+            (Some(min), None) => format!("{min} or more"),
+            (None, Some(max)) => format!("{max} or less"),
+            (None, None) => "any".to_string(),
+        }
+    }
+}
+
+/// The project's descriptive fields, in the order they are entered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct FieldSchema {
+    pub fields: Vec<FieldDef>,
+    /// Refuse to write the sheet while any item is incomplete or invalid, rather than
+    /// writing it with those rows highlighted
+    pub block_invalid: bool,
+}
+
+impl Default for FieldSchema {
+    fn default() -> Self {
+        Self {
+            fields: vec![
+                FieldDef::new(FieldKey::Builtin(Field::Title), FieldKind::Text),
+                FieldDef::new(FieldKey::Builtin(Field::Description), FieldKind::Text),
+                // Free-text dates like "circa 1968" are common; projects can opt into EDTF
+                FieldDef::new(FieldKey::Builtin(Field::DateStart), FieldKind::Text),
+                FieldDef::new(FieldKey::Builtin(Field::DateEnd), FieldKind::Text),
+                FieldDef::new(FieldKey::Builtin(Field::Creator), FieldKind::Text),
+                FieldDef {
+                    repeatable: true,
+                    ..FieldDef::new(FieldKey::Tags, FieldKind::Text)
+                },
+                FieldDef::new(FieldKey::Builtin(Field::Notes), FieldKind::Text),
+            ],
+            block_invalid: false,
+        }
+    }
+}
+
+impl FieldSchema {
+    pub fn get(&self, key: &FieldKey) -> FieldDef {
+        self.fields
+            .iter()
+            .find(|d| &d.key == key)
+            .cloned()
+            .unwrap_or_else(|| FieldDef::new(key.clone(), FieldKind::Text))
+    }
+
+    pub fn custom(&self) -> impl Iterator<Item = &FieldDef> {
+        self.fields
+            .iter()
+            .filter(|d| matches!(d.key, FieldKey::Custom(_)))
+    }
+
+    /// Compile the patterns once so many items can be checked.
+    pub fn validator(&self) -> Validator<'_> {
+        Validator {
+            fields: self
+                .fields
+                .iter()
+                .map(|def| {
+                    let pattern = match def.pattern.trim() {
+                        "" => None,
+                        p => Regex::new(&format!("^(?:{p})$"))
+                            .inspect_err(|e| {
+                                tracing::warn!("Ignoring invalid pattern for {}: {}", def.key, e)
+                            })
+                            .ok(),
+                    };
+                    (def, pattern)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Problem {
+    Missing,
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Issue {
+    pub key: FieldKey,
+    pub problem: Problem,
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.problem {
+            Problem::Missing => write!(f, "{} is required", self.key),
+            Problem::Invalid(why) => write!(f, "{}: {why}", self.key),
+        }
+    }
+}
+
+pub(crate) struct Validator<'a> {
+    fields: Vec<(&'a FieldDef, Option<Regex>)>,
+}
+
+impl Validator<'_> {
+    pub fn validate(&self, metadata: &ItemMetadata) -> Vec<Issue> {
+        let mut issues = vec![];
+        for (def, pattern) in &self.fields {
+            let values = def.values(metadata);
+            let issue = |problem| Issue {
+                key: def.key.clone(),
+                problem,
+            };
+            if values.is_empty() {
+                if def.required {
+                    issues.push(issue(Problem::Missing));
+                }
+                continue;
+            }
+            if !def.repeatable && values.len() > 1 {
+                issues.push(issue(Problem::Invalid(
+                    "only one value is allowed".to_string(),
+                )));
+            }
+            issues.extend(
+                values
+                    .iter()
+                    .filter_map(|v| def.check_value(v, pattern.as_ref()).err())
+                    .map(|why| issue(Problem::Invalid(why))),
+            );
+        }
+        issues
+    }
+}
+
+fn number(s: &str, len: usize) -> Option<u32> {
+    (s.len() == len && s.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| s.parse().ok())
+        .flatten()
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn iso_date(s: &str) -> bool {
+    let parts: Vec<&str> = s.split('-').collect();
+    let Some(year) = number(parts[0], 4) else {
+        return false;
+    };
+    let month = parts.get(1).map(|m| number(m, 2));
+    let day = parts.get(2).map(|d| number(d, 2));
+    match (month, day) {
+        (None, None) => true,
+        (Some(Some(m)), None) => (1..=12).contains(&m),
+        (Some(Some(m)), Some(Some(d))) if parts.len() == 3 => {
+            (1..=12).contains(&m) && d >= 1 && d <= days_in_month(year, m)
+        }
+        _ => false,
+    }
+}
+
+/// One EDTF date, allowing unspecified digits (X), seasons and the ? ~ % qualifiers.
+fn edtf_date(s: &str) -> bool {
+    let s = s.strip_suffix(['?', '~', '%']).unwrap_or(s);
+    // Years with more than four digits
+    if let Some(year) = s.strip_prefix('Y') {
+        let year = year.strip_prefix('-').unwrap_or(year);
+        return year.len() > 4 && year.bytes().all(|b| b.is_ascii_digit());
+    }
+    let (date, time) = match s.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+    let date = date.strip_prefix('-').unwrap_or(date);
+    let digits = |p: &str| p.len() == 4 && p.bytes().all(|b| b.is_ascii_digit() || b == b'X');
+    let in_range =
+        |p: &str, max: u32| p == "XX" || number(p, 2).is_some_and(|n| (1..=max).contains(&n));
+    let date_ok = match *date.split('-').collect::<Vec<_>>().as_slice() {
+        [year] => digits(year),
+        [year, month] => {
+            digits(year)
+                && (in_range(month, 12) || number(month, 2).is_some_and(|n| (21..=24).contains(&n)))
+        }
+        [year, month, day] => digits(year) && in_range(month, 12) && in_range(day, 31),
+        _ => false,
+    };
+    date_ok
+        && time.is_none_or(|time| {
+            let time = time
+                .strip_suffix('Z')
+                .or_else(|| time.get(..8).filter(|_| time.len() == 14))
+                .unwrap_or(time);
+            matches!(*time.split(':').collect::<Vec<_>>().as_slice(),
+                [h, m, s] if number(h, 2).is_some_and(|h| h < 24)
+                    && number(m, 2).is_some_and(|m| m < 60)
+                    && number(s, 2).is_some_and(|s| s < 60))
+        })
+}
+
+/// An EDTF date or interval, where either end of an interval may be unknown or open ("..").
+fn edtf(s: &str) -> bool {
+    match s.split_once('/') {
+        Some((start, end)) => {
+            let end_ok = |e: &str| e.is_empty() || e == ".." || edtf_date(e);
+            !(start.is_empty() && end.is_empty()) && end_ok(start) && end_ok(end)
+        }
+        None => edtf_date(s),
+    }
+}
+
+/// An item that needs attention before export.
+#[derive(Debug, Clone)]
+pub(crate) struct ReportRow {
+    pub name: String,
+    pub issues: Vec<Issue>,
+}
+
+impl ReportRow {
+    fn incomplete(&self) -> bool {
+        self.issues.iter().any(|i| i.problem == Problem::Missing)
+    }
+
+    fn invalid(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|i| matches!(i.problem, Problem::Invalid(_)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum ReportFilter {
+    #[default]
+    All,
+    Incomplete,
+    Invalid,
+}
+
+impl std::fmt::Display for ReportFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportFilter::All => write!(f, "All problems"),
+            ReportFilter::Incomplete => write!(f, "Missing required fields"),
+            ReportFilter::Invalid => write!(f, "Invalid values"),
+        }
+    }
+}
+
+/// Check every file in the project tree. Folders are not described, so they are skipped.
+pub(crate) fn report(state: &State) -> Option<Vec<ReportRow>> {
+    let project = state.project.as_ref()?;
+    let tree = state.project_tree.as_ref()?;
+    let validator = project.schema.validator();
+    let empty = ItemMetadata::default();
+    Some(
+        sheet_plan::flatten(tree)
+            .into_iter()
+            .filter(|item| item.file_type == InternalType::File)
+            .filter_map(|item| {
+                let issues = validator.validate(state.metadata.get(&item.id).unwrap_or(&empty));
+                (!issues.is_empty()).then_some(ReportRow {
+                    name: item.name,
+                    issues,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Form for adding or changing one field.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SchemaState {
+    /// Index of the field being edited, or `None` for a new custom field
+    selected: Option<usize>,
+    name: String,
+    kind: Option<FieldKind>,
+    required: bool,
+    repeatable: bool,
+    pattern: String,
+    min: String,
+    max: String,
+    /// Allowed values, one per line
+    options: String,
+    column: String,
+    pub report: Option<Vec<ReportRow>>,
+    filter: ReportFilter,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum SchemaMessage {
+    Select(usize),
+    New,
+    SetName(String),
+    SetKind(FieldKind),
+    SetRequired(bool),
+    SetRepeatable(bool),
+    SetPattern(String),
+    SetMin(String),
+    SetMax(String),
+    SetOptions(String),
+    SetColumn(String),
+    Save,
+    Remove,
+    MoveUp(usize),
+    RunReport,
+    SetFilter(ReportFilter),
+    SetBlockInvalid(bool),
+}
+
+fn parse_bound(bound: &str) -> Result<Option<f64>, String> {
+    match bound.trim() {
+        "" => Ok(None),
+        b => b
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("\"{b}\" is not a number")),
+    }
+}
+
+fn draft_field(schema: &FieldSchema, draft: &SchemaState) -> Result<FieldDef, String> {
+    let key = match draft.selected.and_then(|i| schema.fields.get(i)) {
+        Some(FieldDef {
+            key: FieldKey::Custom(_),
+            ..
+        })
+        | None => {
+            let name = draft.name.trim();
+            if name.is_empty() {
+                return Err("The field needs a name".to_string());
+            }
+            let taken = schema.fields.iter().enumerate().any(|(i, d)| {
+                Some(i) != draft.selected && d.key.to_string().eq_ignore_ascii_case(name)
+            });
+            if taken {
+                return Err(format!("There is already a field called \"{name}\""));
+            }
+            FieldKey::Custom(name.to_string())
+        }
+        Some(def) => def.key.clone(),
+    };
+    let kind = draft.kind.unwrap_or(FieldKind::Text);
+    let pattern = draft.pattern.trim().to_string();
+    if !pattern.is_empty() {
+        Regex::new(&pattern).map_err(|e| format!("Invalid pattern: {e}"))?;
+    }
+    let min = parse_bound(&draft.min)?;
+    let max = parse_bound(&draft.max)?;
+    if let (Some(min), Some(max)) = (min, max)
+        && min > max
+    {
+        return Err("The minimum is larger than the maximum".to_string());
+    }
+    let options: Vec<String> = draft
+        .options
+        .lines()
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+        .map(str::to_string)
+        .collect();
+    if kind == FieldKind::Enum && options.is_empty() {
+        return Err("A choice field needs at least one allowed value".to_string());
+    }
+    let column = draft.column.trim().to_ascii_uppercase();
+    if !column.is_empty() && column_index(&column).is_none() {
+        return Err(format!("\"{column}\" is not a column letter"));
+    }
+    Ok(FieldDef {
+        key,
+        kind,
+        required: draft.required,
+        repeatable: draft.repeatable,
+        pattern,
+        min,
+        max,
+        options,
+        column,
+    })
+}
+
+fn load_draft(def: &FieldDef, selected: usize, draft: &mut SchemaState) {
+    draft.selected = Some(selected);
+    draft.name = def.key.to_string();
+    draft.kind = Some(def.kind);
+    draft.required = def.required;
+    draft.repeatable = def.repeatable;
+    draft.pattern = def.pattern.clone();
+    draft.min = def.min.map(|m| m.to_string()).unwrap_or_default();
+    draft.max = def.max.map(|m| m.to_string()).unwrap_or_default();
+    draft.options = def.options.join("\n");
+    draft.column = def.column.clone();
+}
+
+pub(crate) fn schema_handle(state: &mut State, event: SchemaMessage) -> Task<Message> {
+    let Some(project) = &mut state.project else {
+        return Task::none();
+    };
+    let schema = &mut project.schema;
+    let draft = &mut state.schema_state;
+    match event {
+        SchemaMessage::Select(i) => {
+            if let Some(def) = schema.fields.get(i) {
+                load_draft(def, i, draft);
+            }
+            return Task::none();
+        }
+        SchemaMessage::New => {
+            *draft = SchemaState {
+                report: draft.report.take(),
+                filter: draft.filter,
+                ..Default::default()
+            };
+            return Task::none();
+        }
+        SchemaMessage::SetName(n) => draft.name = n,
+        SchemaMessage::SetKind(k) => draft.kind = Some(k),
+        SchemaMessage::SetRequired(r) => draft.required = r,
+        SchemaMessage::SetRepeatable(r) => draft.repeatable = r,
+        SchemaMessage::SetPattern(p) => draft.pattern = p,
+        SchemaMessage::SetMin(m) => draft.min = m,
+        SchemaMessage::SetMax(m) => draft.max = m,
+        SchemaMessage::SetOptions(o) => draft.options = o,
+        SchemaMessage::SetColumn(c) => draft.column = c,
+        SchemaMessage::RunReport => {
+            state.schema_state.report = report(state);
+            if state.schema_state.report.is_none() {
+                tracing::warn!("Generate the project's sheet once so its items are known");
+            }
+            return Task::none();
+        }
+        SchemaMessage::SetFilter(f) => draft.filter = f,
+        SchemaMessage::SetBlockInvalid(block) => {
+            schema.block_invalid = block;
+            return save(state);
+        }
+        SchemaMessage::Save => match draft_field(schema, draft) {
+            Ok(def) => {
+                match draft.selected {
+                    Some(i) if i < schema.fields.len() => {
+                        tracing::info!("Updated field \"{}\"", def.key);
+                        schema.fields[i] = def;
+                    }
+                    _ => {
+                        tracing::info!("Added field \"{}\"", def.key);
+                        schema.fields.push(def);
+                        draft.selected = Some(schema.fields.len() - 1);
+                    }
+                }
+                return save(state);
+            }
+            Err(e) => tracing::warn!("Invalid field: {}", e),
+        },
+        SchemaMessage::Remove => {
+            if let Some(i) = draft.selected
+                && matches!(
+                    schema.fields.get(i),
+                    Some(FieldDef {
+                        key: FieldKey::Custom(_),
+                        ..
+                    })
+                )
+            {
+                let removed = schema.fields.remove(i);
+                tracing::info!(
+                    "Removed field \"{}\". Values already entered are kept.",
+                    removed.key
+                );
+                *draft = SchemaState {
+                    report: draft.report.take(),
+                    filter: draft.filter,
+                    ..Default::default()
+                };
+                return save(state);
+            }
+        }
+        SchemaMessage::MoveUp(i) => {
+            if i > 0 && i < schema.fields.len() {
+                schema.fields.swap(i - 1, i);
+                if draft.selected == Some(i) {
+                    draft.selected = Some(i - 1);
+                } else if draft.selected == Some(i - 1) {
+                    draft.selected = Some(i);
+                }
+                return save(state);
+            }
+        }
+    }
+    Task::none()
+}
+
+fn save(state: &mut State) -> Task<Message> {
+    let Some(project) = state.project.clone() else {
+        return Task::none();
+    };
+    update(
+        state,
+        Message::HomepageMessage(homepage::HomepageMessage::UpdateProject(project.clone())),
+    )
+    .chain(save_project(project))
+}
+
+pub(crate) fn schema(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::Schema));
+    let Some(project) = &state.project else {
+        return column![text("No project open"), Space::new().height(Fill), close]
+            .padding(Padding::new(15.0))
+            .spacing(15.0)
+            .into();
+    };
+    let draft = &state.schema_state;
+    let schema = &project.schema;
+
+    let fields =
+        schema
+            .fields
+            .iter()
+            .enumerate()
+            .fold(Column::new().spacing(5), |col, (i, def)| {
+                let mut rules = vec![def.kind.to_string()];
+                if def.required {
+                    rules.push("required".to_string());
+                }
+                if def.repeatable {
+                    rules.push("repeatable".to_string());
+                }
+                if !def.pattern.is_empty() {
+                    rules.push(format!("matches {}", def.pattern));
+                }
+                col.push(
+                    row![
+                        button(text(def.key.to_string()))
+                            .style(if draft.selected == Some(i) {
+                                button::primary
+                            } else {
+                                button::secondary
+                            })
+                            .on_press(Message::SchemaMessage(SchemaMessage::Select(i))),
+                        text(rules.join(", ")).size(12).width(Fill),
+                        button("↑").style(button::text).on_press_maybe(
+                            (i > 0).then_some(Message::SchemaMessage(SchemaMessage::MoveUp(i)))
+                        ),
+                    ]
+                    .align_y(Center)
+                    .spacing(10),
+                )
+            });
+
+    let custom = draft
+        .selected
+        .and_then(|i| schema.fields.get(i))
+        .is_none_or(|d| matches!(d.key, FieldKey::Custom(_)));
+    let kind = draft.kind.unwrap_or(FieldKind::Text);
+    let form = column![
+        text(match draft.selected {
+            Some(_) => "Edit field",
+            None => "New custom field",
+        })
+        .size(18),
+        TextInput::new("Field name", &draft.name).on_input_maybe(
+            custom.then_some(|n| Message::SchemaMessage(SchemaMessage::SetName(n)))
+        ),
+        pick_list(FieldKind::ALL, Some(kind), |k| {
+            Message::SchemaMessage(SchemaMessage::SetKind(k))
+        }),
+        row![
+            checkbox(draft.required)
+                .label("Required")
+                .on_toggle(|r| Message::SchemaMessage(SchemaMessage::SetRequired(r))),
+            checkbox(draft.repeatable)
+                .label(format!("Repeatable, separated by \"{SEPARATOR}\""))
+                .on_toggle(|r| Message::SchemaMessage(SchemaMessage::SetRepeatable(r))),
+        ]
+        .spacing(15),
+        TextInput::new("Pattern (regular expression, optional)", &draft.pattern)
+            .on_input(|p| Message::SchemaMessage(SchemaMessage::SetPattern(p))),
+        row![
+            TextInput::new("Minimum", &draft.min).on_input_maybe(
+                (kind == FieldKind::Number)
+                    .then_some(|m| Message::SchemaMessage(SchemaMessage::SetMin(m)))
+            ),
+            TextInput::new("Maximum", &draft.max).on_input_maybe(
+                (kind == FieldKind::Number)
+                    .then_some(|m| Message::SchemaMessage(SchemaMessage::SetMax(m)))
+            ),
+        ]
+        .spacing(5),
+        TextInput::new("Allowed values, separated by new lines", &draft.options).on_input_maybe(
+            (kind == FieldKind::Enum)
+                .then_some(|o| Message::SchemaMessage(SchemaMessage::SetOptions(o)))
+        ),
+        TextInput::new("Sheet column, e.g. N (optional)", &draft.column).on_input_maybe(
+            custom.then_some(|c| Message::SchemaMessage(SchemaMessage::SetColumn(c)))
+        ),
+        row![
+            button("Save field").on_press(Message::SchemaMessage(SchemaMessage::Save)),
+            button("New custom field")
+                .style(button::secondary)
+                .on_press(Message::SchemaMessage(SchemaMessage::New)),
+            Space::new().width(Fill),
+            button("Remove").style(button::danger).on_press_maybe(
+                (custom && draft.selected.is_some())
+                    .then_some(Message::SchemaMessage(SchemaMessage::Remove))
+            ),
+        ]
+        .spacing(10),
+    ]
+    .spacing(8);
+
+    column![
+        text("Fields").size(20),
+        scrollable(fields).height(Fill),
+        form,
+        checkbox(schema.block_invalid)
+            .label("Don't write the sheet while items are incomplete or invalid")
+            .on_toggle(|b| Message::SchemaMessage(SchemaMessage::SetBlockInvalid(b))),
+        row![
+            button("Validation report").on_press(Message::OpenWindow(Subwindow::ValidationReport)),
+            Space::new().width(Fill),
+            close,
+        ],
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
+
+pub(crate) fn validation_report(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::ValidationReport));
+    let draft = &state.schema_state;
+    let header = row![
+        button("Check all items").on_press(Message::SchemaMessage(SchemaMessage::RunReport)),
+        pick_list(
+            [
+                ReportFilter::All,
+                ReportFilter::Incomplete,
+                ReportFilter::Invalid
+            ],
+            Some(draft.filter),
+            |f| Message::SchemaMessage(SchemaMessage::SetFilter(f))
+        ),
+    ]
+    .spacing(10);
+
+    let body: Element<Message> = match &draft.report {
+        None => text("Check all items to list the ones that need attention").into(),
+        Some(rows) if rows.is_empty() => text("Every item is complete and valid").into(),
+        Some(rows) => {
+            let shown: Vec<&ReportRow> = rows
+                .iter()
+                .filter(|r| match draft.filter {
+                    ReportFilter::All => true,
+                    ReportFilter::Incomplete => r.incomplete(),
+                    ReportFilter::Invalid => r.invalid(),
+                })
+                .collect();
+            let list = shown.iter().fold(Column::new().spacing(8), |col, r| {
+                col.push(
+                    r.issues
+                        .iter()
+                        .fold(column![text(r.name.clone())].spacing(2), |c, issue| {
+                            c.push(text(format!("    {issue}")).size(12).style(text::danger))
+                        }),
+                )
+            });
+            column![
+                text(format!(
+                    "{} of {} items with problems shown",
+                    shown.len(),
+                    rows.len()
+                )),
+                scrollable(list).height(Fill),
+            ]
+            .spacing(10)
+            .into()
+        }
+    };
+
+    column![text("Validation report").size(20), header, body, close]
+        .padding(Padding::new(15.0))
+        .spacing(15.0)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edtf_dates() {
+        for valid in [
+            "1968",
+            "1968-05",
+            "1968-05-14",
+            "1968-05?",
+            "1968-05-14~",
+            "196X",
+            "19XX-XX",
+            "1968-21",
+            "-0044",
+            "Y170000002",
+            "1985-04-12T23:20:30",
+            "1985-04-12T23:20:30Z",
+            "1985-04-12T23:20:30+04:00",
+        ] {
+            assert!(edtf(valid), "{valid} should be EDTF");
+        }
+        for invalid in [
+            "",
+            "68",
+            "May 1968",
+            "1968-13",
+            "1968-00",
+            "1968-05-32",
+            "1968/05/14",
+            "Y1968",
+            "1985-04-12T24:00:00",
+        ] {
+            assert!(!edtf(invalid), "{invalid} should not be EDTF");
+        }
+    }
+
+    #[test]
+    fn edtf_intervals() {
+        assert!(edtf("1968/1972"));
+        assert!(edtf("1968-05/1968-06?"));
+        assert!(edtf("../1985"));
+        assert!(edtf("1985/.."));
+        assert!(edtf("/1985"));
+        assert!(!edtf("/"));
+        assert!(!edtf("1968/circa 1972"));
+    }
+
+    #[test]
+    fn iso_dates() {
+        assert!(iso_date("2024"));
+        assert!(iso_date("2024-02-29"));
+        assert!(!iso_date("2023-02-29"));
+        assert!(!iso_date("2024-2-1"));
+    }
+
+    #[test]
+    fn free_text_dates_are_valid_by_default() {
+        let schema = FieldSchema::default();
+        let metadata = ItemMetadata {
+            date_start: "circa 1968".to_string(),
+            date_end: "early 1970s".to_string(),
+            ..Default::default()
+        };
+        assert!(schema.validator().validate(&metadata).is_empty());
+    }
+
+    #[test]
+    fn edtf_fields_reject_free_text() {
+        let mut schema = FieldSchema::default();
+        for def in &mut schema.fields {
+            if def.key == FieldKey::Builtin(Field::DateStart) {
+                def.kind = FieldKind::Edtf;
+            }
+        }
+        let metadata = ItemMetadata {
+            date_start: "circa 1968".to_string(),
+            ..Default::default()
+        };
+        let issues = schema.validator().validate(&metadata);
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(issues[0].problem, Problem::Invalid(_)));
+    }
+}
@@ -0,0 +1,1700 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{Cursor, Read},
+    sync::{Arc, LazyLock},
+};
+
+use r#box::apis::configuration::Configuration;
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    futures::{StreamExt, stream},
+    widget::{
+        Column, Space, TextInput, button, checkbox, column, pick_list, row, scrollable, text,
+    },
+};
+use regex::{Regex, bytes};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Message, State,
+    archive::{self, size_text},
+    embedded::{self, ODF_EXTENSIONS, OOXML_EXTENSIONS},
+    media, persist, program_settings,
+    project::Run,
+    project_page::{InternalType, Node},
+    sheet_plan,
+    subwindows::Subwindow,
+};
+
+/// Files downloaded and scanned at the same time.
+const CONCURRENT_FILES: usize = 4;
+
+/// Larger files are not downloaded.
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Largest part of an Office document, or PDF stream, that is decompressed.
+const MAX_PART_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Matches kept per file. The rest are only counted.
+const MAX_HITS: usize = 200;
+
+/// Plain text formats, read whole.
+const TEXT_EXTENSIONS: [&str; 12] = [
+    "txt", "csv", "tsv", "md", "json", "xml", "html", "htm", "rtf", "log", "tex", "yaml",
+];
+
+static OBJECT: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"(\d+)\s+\d+\s+obj\b").expect("valid regex"));
+static REFERENCE: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"(\d+)\s+\d+\s+R\b").expect("valid regex"));
+static PAGES: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"/Pages\s+(\d+)\s+\d+\s+R").expect("valid regex"));
+static KIDS: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"/Kids\s*\[([^\]]*)\]").expect("valid regex"));
+static CONTENTS: LazyLock<bytes::Regex> = LazyLock::new(|| {
+    bytes::Regex::new(r"/Contents\s*(?:\[([^\]]*)\]|(\d+)\s+\d+\s+R)").expect("valid regex")
+});
+static CATALOG: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"/Type\s*/Catalog\b").expect("valid regex"));
+static PAGE: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"/Type\s*/Page\b").expect("valid regex"));
+static OBJECT_STREAM: LazyLock<bytes::Regex> = LazyLock::new(|| {
+    bytes::Regex::new(r"/N\s+(\d+)|/First\s+(\d+)|/Type\s*/ObjStm").expect("valid regex")
+});
+static PARENT: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"/Parent\s+(\d+)\s+\d+\s+R").expect("valid regex"));
+static RESOURCES: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"/Resources\s+(\d+)\s+\d+\s+R").expect("valid regex"));
+static FONTS: LazyLock<bytes::Regex> = LazyLock::new(|| {
+    bytes::Regex::new(r"/Font\s*(?:<<([^>]*)>>|(\d+)\s+\d+\s+R)").expect("valid regex")
+});
+static FONT_ENTRY: LazyLock<bytes::Regex> = LazyLock::new(|| {
+    bytes::Regex::new(r"/([^\s/<>\[\]()]+)\s+(\d+)\s+\d+\s+R").expect("valid regex")
+});
+static TO_UNICODE: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"/ToUnicode\s+(\d+)\s+\d+\s+R").expect("valid regex"));
+static TWO_BYTE_FONT: LazyLock<bytes::Regex> = LazyLock::new(|| {
+    bytes::Regex::new(r"/Subtype\s*/Type0\b|/Identity-[HV]\b").expect("valid regex")
+});
+static CODESPACE: LazyLock<bytes::Regex> = LazyLock::new(|| {
+    bytes::Regex::new(r"(?s)begincodespacerange\s*<([0-9A-Fa-f\s]*)>").expect("valid regex")
+});
+static BFCHAR: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"(?s)beginbfchar(.*?)endbfchar").expect("valid regex"));
+static BFRANGE: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"(?s)beginbfrange(.*?)endbfrange").expect("valid regex"));
+static HEX_PAIR: LazyLock<bytes::Regex> = LazyLock::new(|| {
+    bytes::Regex::new(r"<([0-9A-Fa-f\s]*)>\s*<([0-9A-Fa-f\s]*)>").expect("valid regex")
+});
+static HEX_RANGE: LazyLock<bytes::Regex> = LazyLock::new(|| {
+    bytes::Regex::new(
+        r"<([0-9A-Fa-f\s]*)>\s*<([0-9A-Fa-f\s]*)>\s*(?:<([0-9A-Fa-f\s]*)>|\[([^\]]*)\])",
+    )
+    .expect("valid regex")
+});
+static HEX: LazyLock<bytes::Regex> =
+    LazyLock::new(|| bytes::Regex::new(r"<([0-9A-Fa-f\s]*)>").expect("valid regex"));
+
+/// Codes a single ToUnicode range may map. Larger ranges are damaged and skipped.
+const MAX_CMAP_RANGE: u32 = 0xFFFF;
+
+/// What a match must pass besides the pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) enum Check {
+    #[default]
+    None,
+    /// The digits pass the Luhn checksum, as card numbers do
+    Luhn,
+    /// The area, group and serial are ones the SSA issues
+    Ssn,
+}
+
+impl Check {
+    pub const ALL: [Check; 3] = [Check::None, Check::Luhn, Check::Ssn];
+
+    fn passes(&self, found: &str) -> bool {
+        let digits: Vec<u32> = found.chars().filter_map(|c| c.to_digit(10)).collect();
+        match self {
+            Check::None => true,
+            Check::Luhn => {
+                let sum: u32 = digits
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(i, &d)| match (i % 2, d * 2) {
+                        (0, _) => d,
+                        (_, doubled) if doubled > 9 => doubled - 9,
+                        (_, doubled) => doubled,
+                    })
+                    .sum();
+                (13..=19).contains(&digits.len()) && sum.is_multiple_of(10)
+            }
+            Check::Ssn => {
+                let number =
+                    |range: std::ops::Range<usize>| digits[range].iter().fold(0, |n, d| n * 10 + d);
+                digits.len() == 9
+                    && !matches!(number(0..3), 0 | 666 | 900..)
+                    && number(3..5) != 0
+                    && number(5..9) != 0
+            }
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Check::None => "Pattern only",
+            Check::Luhn => "Luhn checksum",
+            Check::Ssn => "Valid SSN",
+        })
+    }
+}
+
+/// A kind of sensitive content, found by a regular expression.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Detector {
+    pub name: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub check: Check,
+    pub enabled: bool,
+}
+
+impl Detector {
+    fn new(name: &str, pattern: &str, check: Check, enabled: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            check,
+            enabled,
+        }
+    }
+}
+
+/// The detectors sensitive content scans run, configured in Program Settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct DetectorSettings {
+    pub detectors: Vec<Detector>,
+}
+
+impl Default for DetectorSettings {
+    fn default() -> Self {
+        Self {
+            detectors: vec![
+                Detector::new("SSN", r"\b\d{3}[- ]\d{2}[- ]\d{4}\b", Check::Ssn, true),
+                Detector::new(
+                    "Card number",
+                    r"\b\d(?:[ -]?\d){12,18}\b",
+                    Check::Luhn,
+                    true,
+                ),
+                Detector::new(
+                    "Email",
+                    r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b",
+                    Check::None,
+                    true,
+                ),
+                Detector::new(
+                    "Phone number",
+                    r"(?:\+1[-. ]?)?(?:\(\d{3}\)\s?|\b\d{3}[-. ])\d{3}[-. ]\d{4}\b",
+                    Check::None,
+                    true,
+                ),
+                // Every institution numbers its students differently
+                Detector::new("Student ID", r"\b[A-Z]\d{8}\b", Check::None, false),
+            ],
+        }
+    }
+}
+
+/// An enabled detector, ready to run.
+#[derive(Debug, Clone)]
+struct Compiled {
+    name: String,
+    regex: Regex,
+    check: Check,
+}
+
+impl DetectorSettings {
+    /// The enabled detectors whose patterns compile. The others are logged and skipped.
+    fn compiled(&self) -> Vec<Compiled> {
+        self.detectors
+            .iter()
+            .filter(|d| d.enabled && !d.pattern.is_empty())
+            .filter_map(|d| match Regex::new(&d.pattern) {
+                Ok(regex) => Some(Compiled {
+                    name: d.name.clone(),
+                    regex,
+                    check: d.check,
+                }),
+                Err(e) => {
+                    tracing::warn!("Skipping detector {}: {}", d.name, e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// A match, with where in the file it is. The match itself is kept masked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Hit {
+    pub detector: String,
+    /// Such as "page 3", "line 12" or "Sheet1!B2"
+    pub location: String,
+    pub masked: String,
+}
+
+/// What the last scan found in a file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct ScannedFile {
+    pub name: String,
+    pub web_link: String,
+    /// At most `MAX_HITS`
+    pub hits: Vec<Hit>,
+    /// Every match, including those not kept
+    pub found: usize,
+    /// Why the file was not, or not fully, screened
+    pub note: Option<String>,
+}
+
+impl ScannedFile {
+    /// Matches by detector, such as "SSN (2); Email (1)".
+    fn summary(&self) -> String {
+        let mut counts: Vec<(&str, usize)> = vec![];
+        for hit in &self.hits {
+            match counts.iter_mut().find(|(name, _)| *name == hit.detector) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((&hit.detector, 1)),
+            }
+        }
+        let mut parts: Vec<String> = counts
+            .iter()
+            .map(|(name, count)| format!("{name} ({count})"))
+            .collect();
+        if self.found > self.hits.len() {
+            parts.push(format!("{} more", self.found - self.hits.len()));
+        }
+        parts.join("; ")
+    }
+}
+
+/// Files screened by the last scan, by Box file ID.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct SensitiveStore {
+    pub files: HashMap<String, ScannedFile>,
+}
+
+impl SensitiveStore {
+    /// What each file with matches needs reviewing for, by item ID.
+    pub(crate) fn restricted(&self) -> HashMap<String, String> {
+        self.files
+            .iter()
+            .filter(|(_, file)| file.found > 0)
+            .map(|(id, file)| (id.clone(), file.summary()))
+            .collect()
+    }
+}
+
+/// A piece of a file's text and where it came from.
+struct Segment {
+    location: String,
+    text: String,
+}
+
+/// The text read out of a file.
+struct Extracted {
+    segments: Vec<Segment>,
+    /// What text could not be read, so the file cannot be called clean
+    unreadable: Option<String>,
+}
+
+impl From<Vec<Segment>> for Extracted {
+    fn from(segments: Vec<Segment>) -> Self {
+        Self {
+            segments,
+            unreadable: None,
+        }
+    }
+}
+
+/// Hide all but the last four letters and digits of a match.
+fn mask(found: &str) -> String {
+    let shown = found.chars().filter(|c| c.is_alphanumeric()).count();
+    // Short matches, such as brief emails, still get half hidden
+    let mut hide = shown.saturating_sub(4).max(shown / 2);
+    found
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() && hide > 0 {
+                hide -= 1;
+                '*'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Run every detector over the segments, keeping the first `MAX_HITS` matches.
+fn detect(segments: &[Segment], detectors: &[Compiled]) -> (Vec<Hit>, usize) {
+    let mut hits = vec![];
+    let mut found = 0;
+    for segment in segments {
+        for detector in detectors {
+            for m in detector.regex.find_iter(&segment.text) {
+                if !detector.check.passes(m.as_str()) {
+                    continue;
+                }
+                found += 1;
+                if hits.len() < MAX_HITS {
+                    hits.push(Hit {
+                        detector: detector.name.clone(),
+                        location: segment.location.clone(),
+                        masked: mask(m.as_str()),
+                    });
+                }
+            }
+        }
+    }
+    (hits, found)
+}
+
+fn extension(name: &str) -> String {
+    name.rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// Whether text can be read out of a file called `name`.
+fn scannable(name: &str) -> bool {
+    let extension = extension(name);
+    let extension = extension.as_str();
+    extension == "pdf"
+        || TEXT_EXTENSIONS.contains(&extension)
+        || OOXML_EXTENSIONS.contains(&extension)
+        || ODF_EXTENSIONS.contains(&extension)
+}
+
+/// Plain text, a line at a time.
+fn plain_text(b: &[u8]) -> Vec<Segment> {
+    let decoded = if b.starts_with(&[0xFF, 0xFE]) || b.starts_with(&[0xFE, 0xFF]) {
+        embedded::utf16(b, false)
+    } else {
+        String::from_utf8_lossy(b).into_owned()
+    };
+    decoded
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| Segment {
+            location: format!("line {}", i + 1),
+            text: line.to_string(),
+        })
+        .collect()
+}
+
+fn inflate(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    // A damaged stream still gives the text before the damage
+    let _ = flate2::read::ZlibDecoder::new(data)
+        .take(MAX_PART_BYTES)
+        .read_to_end(&mut out);
+    out
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// A PDF object: its dictionary or value, and its stream decoded when it is uncompressed or
+/// Flate-compressed.
+struct PdfObject {
+    dictionary: Vec<u8>,
+    stream: Option<Vec<u8>>,
+}
+
+/// The objects of a PDF by number, including those packed into object streams. Later
+/// definitions replace earlier ones, as incremental updates do.
+fn pdf_objects(b: &[u8]) -> HashMap<u64, PdfObject> {
+    let mut objects = HashMap::new();
+    for caps in OBJECT.captures_iter(b) {
+        let (Some(whole), Some(number)) = (caps.get(0), caps.get(1)) else {
+            continue;
+        };
+        let Some(number) = std::str::from_utf8(number.as_bytes())
+            .ok()
+            .and_then(|n| n.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let body = &b[whole.end()..];
+        let end = find(body, b"endobj").unwrap_or(body.len());
+        let object = match find(&body[..end], b"stream") {
+            Some(at) => {
+                let dictionary = body[..at].to_vec();
+                let data = &body[at + 6..];
+                let data = data
+                    .strip_prefix(b"\r\n")
+                    .or(data.strip_prefix(b"\n"))
+                    .unwrap_or(data);
+                let data = &data[..find(data, b"endstream").unwrap_or(data.len())];
+                let filtered = find(&dictionary, b"/Filter").is_some();
+                let stream = if find(&dictionary, b"/FlateDecode").is_some() {
+                    Some(inflate(data))
+                } else if !filtered {
+                    Some(data.to_vec())
+                } else {
+                    // Images and other filters have no text
+                    None
+                };
+                PdfObject { dictionary, stream }
+            }
+            None => PdfObject {
+                dictionary: body[..end].to_vec(),
+                stream: None,
+            },
+        };
+        objects.insert(number, object);
+    }
+
+    let packed: Vec<(Vec<u64>, Vec<Vec<u8>>)> =
+        objects.values().filter_map(unpack_object_stream).collect();
+    for (numbers, bodies) in packed {
+        for (number, dictionary) in numbers.into_iter().zip(bodies) {
+            objects.entry(number).or_insert(PdfObject {
+                dictionary,
+                stream: None,
+            });
+        }
+    }
+    objects
+}
+
+/// The objects packed into an object stream, as their numbers and their bodies.
+fn unpack_object_stream(object: &PdfObject) -> Option<(Vec<u64>, Vec<Vec<u8>>)> {
+    let stream = object.stream.as_ref()?;
+    let (mut count, mut first, mut is_object_stream) = (None, None, false);
+    for caps in OBJECT_STREAM.captures_iter(&object.dictionary) {
+        let number = |i| {
+            caps.get(i)
+                .and_then(|m| std::str::from_utf8(m.as_bytes()).ok())
+                .and_then(|n| n.parse::<usize>().ok())
+        };
+        count = count.or(number(1));
+        first = first.or(number(2));
+        is_object_stream |= caps.get(1).is_none() && caps.get(2).is_none();
+    }
+    let (count, first) = (count?, first?);
+    if !is_object_stream || first > stream.len() {
+        return None;
+    }
+    let header: Vec<usize> = String::from_utf8_lossy(&stream[..first])
+        .split_ascii_whitespace()
+        .filter_map(|n| n.parse().ok())
+        .collect();
+    let pairs: Vec<(u64, usize)> = header
+        .chunks_exact(2)
+        .take(count)
+        .map(|pair| (pair[0] as u64, first + pair[1]))
+        .collect();
+    let mut numbers = vec![];
+    let mut bodies = vec![];
+    for (i, (number, start)) in pairs.iter().enumerate() {
+        let end = pairs.get(i + 1).map_or(stream.len(), |next| next.1);
+        if *start <= end && end <= stream.len() {
+            numbers.push(*number);
+            bodies.push(stream[*start..end].to_vec());
+        }
+    }
+    Some((numbers, bodies))
+}
+
+/// Object numbers of every reference in `b`.
+fn references(b: &[u8]) -> Vec<u64> {
+    REFERENCE
+        .captures_iter(b)
+        .filter_map(|caps| {
+            std::str::from_utf8(caps.get(1)?.as_bytes())
+                .ok()?
+                .parse()
+                .ok()
+        })
+        .collect()
+}
+
+/// The object number captured by group `i`.
+fn captured_number(caps: &bytes::Captures, i: usize) -> Option<u64> {
+    std::str::from_utf8(caps.get(i)?.as_bytes())
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// How the strings a font shows become text.
+#[derive(Debug, Clone, PartialEq)]
+enum FontText {
+    /// One byte per character, read as Latin-1
+    Simple,
+    /// Codes of `width` bytes, mapped to text by the font's ToUnicode CMap
+    Mapped {
+        width: usize,
+        map: HashMap<u32, String>,
+    },
+    /// Two-byte codes, such as Identity-H, with nothing to map them to text
+    Unreadable,
+}
+
+/// The bytes of a hex string from a CMap.
+fn cmap_hex(digits: &[u8]) -> Vec<u8> {
+    embedded::pdf_hex(digits).0
+}
+
+fn code(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |n, &b| (n << 8) | u32::from(b))
+}
+
+/// The text of a CMap destination, which is UTF-16BE, with its last unit moved on by
+/// `offset` as ranges do.
+fn cmap_text(bytes: &[u8], offset: u32) -> String {
+    let mut units: Vec<u16> = bytes
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]))
+        .collect();
+    if let Some(last) = units.last_mut() {
+        *last = last.wrapping_add(offset as u16);
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// Read a ToUnicode CMap. `None` when it maps nothing.
+fn to_unicode(cmap: &[u8]) -> Option<FontText> {
+    let mut map = HashMap::new();
+    let mut width = CODESPACE
+        .captures(cmap)
+        .and_then(|caps| Some(cmap_hex(caps.get(1)?.as_bytes()).len()));
+    for section in BFCHAR.captures_iter(cmap) {
+        let Some(section) = section.get(1) else {
+            continue;
+        };
+        for pair in HEX_PAIR.captures_iter(section.as_bytes()) {
+            let (Some(source), Some(text)) = (pair.get(1), pair.get(2)) else {
+                continue;
+            };
+            let source = cmap_hex(source.as_bytes());
+            width.get_or_insert(source.len());
+            map.insert(code(&source), cmap_text(&cmap_hex(text.as_bytes()), 0));
+        }
+    }
+    for section in BFRANGE.captures_iter(cmap) {
+        let Some(section) = section.get(1) else {
+            continue;
+        };
+        for range in HEX_RANGE.captures_iter(section.as_bytes()) {
+            let (Some(low), Some(high)) = (range.get(1), range.get(2)) else {
+                continue;
+            };
+            let low = cmap_hex(low.as_bytes());
+            width.get_or_insert(low.len());
+            let (low, high) = (code(&low), code(&cmap_hex(high.as_bytes())));
+            if high < low || high - low > MAX_CMAP_RANGE {
+                continue;
+            }
+            match (range.get(3), range.get(4)) {
+                (Some(start), _) => {
+                    let start = cmap_hex(start.as_bytes());
+                    for c in low..=high {
+                        map.insert(c, cmap_text(&start, c - low));
+                    }
+                }
+                (None, Some(texts)) => {
+                    for (c, text) in (low..=high).zip(HEX.captures_iter(texts.as_bytes())) {
+                        if let Some(text) = text.get(1) {
+                            map.insert(c, cmap_text(&cmap_hex(text.as_bytes()), 0));
+                        }
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+    }
+    (!map.is_empty()).then(|| FontText::Mapped {
+        width: width.unwrap_or(1).clamp(1, 4),
+        map,
+    })
+}
+
+/// How the font in object `font` shows text.
+fn font_text(objects: &HashMap<u64, PdfObject>, font: &PdfObject) -> FontText {
+    let mapped = TO_UNICODE
+        .captures(&font.dictionary)
+        .and_then(|caps| objects.get(&captured_number(&caps, 1)?)?.stream.as_deref())
+        .and_then(to_unicode);
+    match mapped {
+        Some(mapped) => mapped,
+        None if TWO_BYTE_FONT.is_match(&font.dictionary) => FontText::Unreadable,
+        None => FontText::Simple,
+    }
+}
+
+/// The fonts page `page` uses, by resource name. A page without its own resources inherits
+/// those of the page tree above it.
+fn page_fonts(objects: &HashMap<u64, PdfObject>, page: u64) -> HashMap<Vec<u8>, FontText> {
+    let mut seen = HashSet::new();
+    let mut next = Some(page);
+    while let Some(number) = next.filter(|n| seen.insert(*n)) {
+        let Some(object) = objects.get(&number) else {
+            break;
+        };
+        let resources = match RESOURCES.captures(&object.dictionary) {
+            Some(caps) => captured_number(&caps, 1)
+                .and_then(|r| objects.get(&r))
+                .map(|o| o.dictionary.as_slice()),
+            None => Some(object.dictionary.as_slice()),
+        };
+        if let Some(fonts) = resources.and_then(|r| FONTS.captures(r)) {
+            let entries = match (fonts.get(1), captured_number(&fonts, 2)) {
+                (Some(inline), _) => inline.as_bytes(),
+                (None, Some(r)) => objects.get(&r).map_or(&[][..], |o| &o.dictionary),
+                (None, None) => &[],
+            };
+            return FONT_ENTRY
+                .captures_iter(entries)
+                .filter_map(|entry| {
+                    let font = objects.get(&captured_number(&entry, 2)?)?;
+                    Some((entry.get(1)?.as_bytes().to_vec(), font_text(objects, font)))
+                })
+                .collect();
+        }
+        next = PARENT
+            .captures(&object.dictionary)
+            .and_then(|caps| captured_number(&caps, 1));
+    }
+    HashMap::new()
+}
+
+/// The page objects of a PDF, in page order. Follows the page tree from the catalog, or takes
+/// every page object in number order when the tree cannot be followed.
+fn pdf_pages(objects: &HashMap<u64, PdfObject>) -> Vec<u64> {
+    let root = objects
+        .values()
+        .find(|o| CATALOG.is_match(&o.dictionary))
+        .and_then(|catalog| PAGES.captures(&catalog.dictionary))
+        .and_then(|caps| references(caps.get(0)?.as_bytes()).first().copied());
+    let mut pages = vec![];
+    let mut seen = HashSet::new();
+    let mut pending = root.into_iter().collect::<Vec<_>>();
+    while let Some(number) = pending.pop() {
+        if !seen.insert(number) {
+            continue;
+        }
+        let Some(object) = objects.get(&number) else {
+            continue;
+        };
+        match KIDS.captures(&object.dictionary) {
+            Some(caps) => {
+                let kids = caps.get(1).map(|m| references(m.as_bytes()));
+                // Popped from the end, so pushed in reverse
+                pending.extend(kids.unwrap_or_default().into_iter().rev());
+            }
+            None if PAGE.is_match(&object.dictionary) => pages.push(number),
+            None => {}
+        }
+    }
+    if pages.is_empty() {
+        pages = objects
+            .iter()
+            .filter(|(_, o)| PAGE.is_match(&o.dictionary))
+            .map(|(number, _)| *number)
+            .collect();
+        pages.sort();
+    }
+    pages
+}
+
+/// A string shown on a page in `font`, or `None` when its text cannot be read.
+fn shown_text(b: &[u8], font: Option<&FontText>) -> Option<String> {
+    if b.starts_with(&[0xFE, 0xFF]) {
+        return Some(embedded::utf16(b, true));
+    }
+    match font.unwrap_or(&FontText::Simple) {
+        FontText::Simple => {
+            let text: String = b.iter().map(|&c| c as char).collect();
+            // Fonts with their own encodings show control codes rather than letters
+            let garbled = text
+                .chars()
+                .filter(|c| c.is_ascii_control() && !c.is_ascii_whitespace())
+                .count();
+            (garbled * 2 <= text.chars().count()).then_some(text)
+        }
+        FontText::Mapped { width, map } => {
+            let codes = b.chunks(*width);
+            let total = codes.len();
+            let mut missing = 0;
+            let text: String = codes
+                .filter_map(|c| {
+                    let text = map.get(&code(c)).map(String::as_str);
+                    missing += usize::from(text.is_none());
+                    text
+                })
+                .collect();
+            (missing * 2 <= total).then_some(text)
+        }
+        FontText::Unreadable => None,
+    }
+}
+
+/// The text a content stream shows, a line at a time, and whether any of it was in a font
+/// whose text could not be read.
+fn content_text(b: &[u8], fonts: &HashMap<Vec<u8>, FontText>) -> (Vec<String>, bool) {
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut in_array = false;
+    let mut name: &[u8] = &[];
+    let mut font = None;
+    let mut unreadable = false;
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'(' => {
+                let (s, len) = embedded::pdf_literal(&b[i + 1..]);
+                match shown_text(&s, font) {
+                    Some(text) => line.push_str(&text),
+                    None => unreadable = true,
+                }
+                i += 1 + len;
+            }
+            b'<' if b.get(i + 1) == Some(&b'<') => i += 2,
+            b'<' => {
+                let (s, len) = embedded::pdf_hex(&b[i + 1..]);
+                match shown_text(&s, font) {
+                    Some(text) => line.push_str(&text),
+                    None => unreadable = true,
+                }
+                i += 1 + len;
+            }
+            b'/' => {
+                let len = b[i + 1..]
+                    .iter()
+                    .position(|c| c.is_ascii_whitespace() || b"/[]<>(){}%".contains(c))
+                    .unwrap_or(b.len() - i - 1);
+                name = &b[i + 1..i + 1 + len];
+                i += 1 + len;
+            }
+            b'[' => {
+                in_array = true;
+                i += 1;
+            }
+            b']' => {
+                in_array = false;
+                i += 1;
+            }
+            b'%' => {
+                i += b[i..]
+                    .iter()
+                    .position(|&c| c == b'\n' || c == b'\r')
+                    .unwrap_or(b.len() - i);
+            }
+            b'-' | b'+' | b'.' | b'0'..=b'9' => {
+                let len = b[i..]
+                    .iter()
+                    .position(|c| !matches!(c, b'-' | b'+' | b'.' | b'0'..=b'9'))
+                    .unwrap_or(b.len() - i);
+                let number = std::str::from_utf8(&b[i..i + len])
+                    .ok()
+                    .and_then(|n| n.parse::<f32>().ok());
+                // Wide gaps between the strings of a TJ array separate words
+                if in_array && number.is_some_and(|n| n < -200.0) {
+                    line.push(' ');
+                }
+                i += len;
+            }
+            c if c.is_ascii_alphabetic() || c == b'\'' || c == b'"' || c == b'*' => {
+                let len = b[i..]
+                    .iter()
+                    .position(|c| !(c.is_ascii_alphabetic() || b"'\"*".contains(c)))
+                    .unwrap_or(b.len() - i);
+                let operator = &b[i..i + len];
+                i += len;
+                match operator {
+                    b"Td" | b"TD" | b"T*" | b"Tm" | b"ET" | b"'" | b"\""
+                        if !line.trim().is_empty() =>
+                    {
+                        lines.push(std::mem::take(&mut line));
+                    }
+                    b"Tf" => font = fonts.get(name),
+                    // Inline image data is binary, and ends at EI
+                    b"ID" => i += find(&b[i..], b"EI").unwrap_or(b.len() - i),
+                    _ => {}
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    if !line.trim().is_empty() {
+        lines.push(line);
+    }
+    (lines, unreadable)
+}
+
+/// The text of a PDF, a page at a time. Pages with text in fonts that cannot be decoded are
+/// reported as not readable rather than as having no matches.
+fn pdf_text(b: &[u8]) -> Result<Extracted, String> {
+    if find(b, b"/Encrypt").is_some() {
+        return Err("it is encrypted, so its text could not be read".to_string());
+    }
+    let objects = pdf_objects(b);
+    let mut segments = vec![];
+    let mut unreadable = vec![];
+    for (page, number) in pdf_pages(&objects).iter().enumerate() {
+        let fonts = page_fonts(&objects, *number);
+        let contents = objects.get(number).and_then(|object| {
+            let caps = CONTENTS.captures(&object.dictionary)?;
+            Some(references(caps.get(0)?.as_bytes()))
+        });
+        let mut text = vec![];
+        let mut readable = true;
+        for content in contents.unwrap_or_default() {
+            if let Some(stream) = objects.get(&content).and_then(|o| o.stream.as_deref()) {
+                let (lines, undecoded) = content_text(stream, &fonts);
+                text.extend(lines);
+                readable &= !undecoded;
+            }
+        }
+        if !readable {
+            unreadable.push((page + 1).to_string());
+        }
+        let text = text.join("\n");
+        if !text.trim().is_empty() {
+            segments.push(Segment {
+                location: format!("page {}", page + 1),
+                text,
+            });
+        }
+    }
+    let unreadable = match unreadable.len() {
+        0 => None,
+        _ if segments.is_empty() => Some(
+            "Text not readable: its fonts could not be decoded, so it was not screened".to_string(),
+        ),
+        1 => Some(format!(
+            "Text not readable on page {}, so that page was not screened",
+            unreadable[0]
+        )),
+        _ => Some(format!(
+            "Text not readable on pages {}, so those pages were not screened",
+            unreadable.join(", ")
+        )),
+    };
+    Ok(Extracted {
+        segments,
+        unreadable,
+    })
+}
+
+/// The text of each paragraph in an XML part, skipping paragraphs inside others since their
+/// text is already in the outer one. Tabs, breaks and ODF's space elements read as spaces.
+fn paragraphs<'a, 'input>(
+    doc: &'a roxmltree::Document<'input>,
+) -> Vec<(roxmltree::Node<'a, 'input>, String)> {
+    let is_paragraph =
+        |n: &roxmltree::Node| n.is_element() && matches!(n.tag_name().name(), "p" | "h");
+    doc.descendants()
+        .filter(|n| is_paragraph(n) && !n.ancestors().skip(1).any(|a| is_paragraph(&a)))
+        .map(|p| {
+            let text: String = p
+                .descendants()
+                .filter_map(|n| match n.tag_name().name() {
+                    _ if n.is_text() => n.text(),
+                    "tab" | "br" | "s" | "line-break" if n.is_element() => Some(" "),
+                    _ => None,
+                })
+                .collect();
+            (p, text)
+        })
+        .filter(|(_, text)| !text.trim().is_empty())
+        .collect()
+}
+
+/// A part's file name without its extension, such as "slide3".
+fn stem(path: &str) -> &str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.split_once('.').map_or(name, |(stem, _)| stem)
+}
+
+/// Parts sorted so that slide2 comes before slide10.
+fn natural_key(path: &str) -> (String, u64) {
+    let digits: String = stem(path).chars().filter(char::is_ascii_digit).collect();
+    let letters: String = path.chars().filter(|c| !c.is_ascii_digit()).collect();
+    (letters, digits.parse().unwrap_or_default())
+}
+
+/// Where a paragraph of an Office part is, for the report.
+fn paragraph_location(path: &str, index: usize) -> String {
+    let stem = stem(path);
+    let number: String = stem.chars().filter(char::is_ascii_digit).collect();
+    if path.starts_with("ppt/slides/") {
+        format!("slide {number}")
+    } else if path.starts_with("ppt/notesSlides/") {
+        format!("speaker notes {number}")
+    } else if stem == "document" {
+        format!("paragraph {}", index + 1)
+    } else {
+        format!("{stem} paragraph {}", index + 1)
+    }
+}
+
+/// Sheet names by worksheet part, from the workbook and its relationships.
+fn sheet_names(read: &mut dyn FnMut(&str) -> Option<String>) -> HashMap<String, String> {
+    let (Some(workbook), Some(rels)) =
+        (read("xl/workbook.xml"), read("xl/_rels/workbook.xml.rels"))
+    else {
+        return HashMap::new();
+    };
+    let (Ok(workbook), Ok(rels)) = (
+        roxmltree::Document::parse(&workbook),
+        roxmltree::Document::parse(&rels),
+    ) else {
+        return HashMap::new();
+    };
+    let targets: HashMap<&str, String> = rels
+        .descendants()
+        .filter(|n| n.has_tag_name("Relationship"))
+        .filter_map(|n| {
+            let target = n.attribute("Target")?.trim_start_matches('/');
+            let target = target.strip_prefix("xl/").unwrap_or(target);
+            Some((n.attribute("Id")?, format!("xl/{target}")))
+        })
+        .collect();
+    workbook
+        .descendants()
+        .filter(|n| n.is_element() && n.tag_name().name() == "sheet")
+        .filter_map(|n| {
+            let id = n.attributes().find(|a| a.name() == "id")?.value();
+            Some((targets.get(id)?.clone(), n.attribute("name")?.to_string()))
+        })
+        .collect()
+}
+
+/// The text of each cell in a worksheet, located by sheet name and cell reference.
+fn cells(doc: &roxmltree::Document, sheet: &str, shared: &[String]) -> Vec<Segment> {
+    doc.descendants()
+        .filter(|n| n.is_element() && n.tag_name().name() == "c")
+        .filter_map(|cell| {
+            let child_text = |name: &str| -> String {
+                cell.descendants()
+                    .filter(|n| n.is_element() && n.tag_name().name() == name)
+                    .filter_map(|n| n.text())
+                    .collect()
+            };
+            let text = match cell.attribute("t") {
+                Some("s") => shared
+                    .get(child_text("v").trim().parse::<usize>().ok()?)?
+                    .clone(),
+                Some("inlineStr") => child_text("t"),
+                _ => child_text("v"),
+            };
+            Some(Segment {
+                location: format!("{sheet}!{}", cell.attribute("r").unwrap_or_default()),
+                text,
+            })
+        })
+        .filter(|segment| !segment.text.trim().is_empty())
+        .collect()
+}
+
+/// Whether an Office part holds document text, rather than styles, themes or settings.
+fn text_part(path: &str) -> bool {
+    let word = path.starts_with("word/")
+        && [
+            "document",
+            "header",
+            "footer",
+            "footnotes",
+            "endnotes",
+            "comments",
+        ]
+        .iter()
+        .any(|part| stem(path).starts_with(part));
+    let slides = path.starts_with("ppt/slides/") || path.starts_with("ppt/notesSlides/");
+    let sheets = path.starts_with("xl/worksheets/") || path.starts_with("xl/comments");
+    path.ends_with(".xml") && (word || slides || sheets)
+}
+
+/// The text of an Office Open XML document, by paragraph, slide or cell.
+fn ooxml_text(b: &[u8]) -> Result<Vec<Segment>, String> {
+    let mut reader = Cursor::new(b);
+    let listing = archive::list_zip(&mut reader).map_err(|e| e.to_string())?;
+    let mut read = |path: &str| -> Option<String> {
+        match archive::zip_entry(&mut reader, path, MAX_PART_BYTES) {
+            Ok(xml) => Some(String::from_utf8_lossy(&xml).into_owned()),
+            Err(e) => {
+                tracing::debug!("Could not read {}: {}", path, e);
+                None
+            }
+        }
+    };
+    let shared: Vec<String> = read("xl/sharedStrings.xml")
+        .and_then(|xml| {
+            let doc = roxmltree::Document::parse(&xml).ok()?;
+            let strings = doc
+                .descendants()
+                .filter(|n| n.is_element() && n.tag_name().name() == "si")
+                .map(|si| {
+                    si.descendants()
+                        .filter(|n| n.is_element() && n.tag_name().name() == "t")
+                        .filter_map(|n| n.text())
+                        .collect()
+                })
+                .collect();
+            Some(strings)
+        })
+        .unwrap_or_default();
+    let sheets = sheet_names(&mut read);
+
+    let mut parts: Vec<&str> = listing
+        .entries
+        .iter()
+        .map(|e| e.path.as_str())
+        .filter(|path| text_part(path))
+        .collect();
+    parts.sort_by_key(|path| natural_key(path));
+    let mut segments = vec![];
+    for path in parts {
+        let Some(xml) = read(path) else {
+            continue;
+        };
+        let doc = match roxmltree::Document::parse(&xml) {
+            Ok(doc) => doc,
+            Err(e) => {
+                tracing::debug!("Could not parse {}: {}", path, e);
+                continue;
+            }
+        };
+        if path.starts_with("xl/worksheets/") {
+            let sheet = sheets.get(path).map_or(stem(path), String::as_str);
+            segments.extend(cells(&doc, sheet, &shared));
+        } else {
+            let found = paragraphs(&doc).into_iter().enumerate();
+            segments.extend(found.map(|(i, (_, text))| Segment {
+                location: paragraph_location(path, i),
+                text,
+            }));
+        }
+    }
+    Ok(segments)
+}
+
+/// The text of an OpenDocument file, by paragraph, or by table for spreadsheets.
+fn odf_text(b: &[u8]) -> Result<Vec<Segment>, String> {
+    let xml = archive::zip_entry(&mut Cursor::new(b), "content.xml", MAX_PART_BYTES)
+        .map_err(|e| e.to_string())?;
+    let xml = String::from_utf8_lossy(&xml);
+    let doc = roxmltree::Document::parse(&xml).map_err(|e| e.to_string())?;
+    let segments = paragraphs(&doc)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (p, text))| {
+            let table = p
+                .ancestors()
+                .find(|a| a.is_element() && a.tag_name().name() == "table")
+                .and_then(|t| t.attributes().find(|a| a.name() == "name"))
+                .map(|a| a.value().to_string());
+            Segment {
+                location: table.unwrap_or(format!("paragraph {}", i + 1)),
+                text,
+            }
+        })
+        .collect();
+    Ok(segments)
+}
+
+/// The text of a file called `name`, in pieces a reviewer can find again.
+fn extract(name: &str, b: &[u8]) -> Result<Extracted, String> {
+    let extension = extension(name);
+    if b.starts_with(b"%PDF") {
+        pdf_text(b)
+    } else if b.starts_with(b"PK") && OOXML_EXTENSIONS.contains(&extension.as_str()) {
+        ooxml_text(b).map(Extracted::from)
+    } else if b.starts_with(b"PK") && ODF_EXTENSIONS.contains(&extension.as_str()) {
+        odf_text(b).map(Extracted::from)
+    } else if TEXT_EXTENSIONS.contains(&extension.as_str()) {
+        Ok(plain_text(b).into())
+    } else {
+        Err("its content does not match its extension".to_string())
+    }
+}
+
+/// What scanning one file found.
+#[derive(Debug, Clone)]
+pub(crate) struct Scan {
+    hits: Vec<Hit>,
+    found: usize,
+    /// Why the file was not, or not fully, screened
+    note: Option<String>,
+}
+
+/// Download file `id` and run the detectors over its text. Nothing leaves the computer.
+async fn scan(
+    config: Configuration,
+    id: String,
+    name: String,
+    detectors: Arc<Vec<Compiled>>,
+) -> Result<Scan, String> {
+    let range = format!("bytes=0-{}", MAX_FILE_BYTES - 1);
+    let (bytes, total) = media::fetch_range(&config, &id, Some(range)).await?;
+    if total.is_some_and(|total| total > MAX_FILE_BYTES) {
+        return Err(format!(
+            "it is larger than {}, so it was not scanned",
+            size_text(MAX_FILE_BYTES)
+        ));
+    }
+    tokio::task::spawn_blocking(move || {
+        let extracted = extract(&name, &bytes)?;
+        let (hits, found) = detect(&extracted.segments, &detectors);
+        let note = match extracted.unreadable {
+            Some(why) => Some(why),
+            None if extracted.segments.is_empty() => {
+                Some("No text found, so it was not screened".to_string())
+            }
+            None => None,
+        };
+        Ok(Scan { hits, found, note })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SensitiveState {
+    run: Run,
+    scanning: bool,
+    total: usize,
+    scanned: usize,
+    /// Files scanned so far, which replace the last scan's once every file is done
+    files: HashMap<String, ScannedFile>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum SensitiveMessage {
+    Loaded(SensitiveStore),
+    Scan,
+    /// A file scanned during run `u64`, by ID, name and link
+    Scanned(u64, String, String, String, Result<Scan, String>),
+    Export,
+    SetName(usize, String),
+    SetPattern(usize, String),
+    SetCheck(usize, Check),
+    Enable(usize, bool),
+    AddDetector,
+    RemoveDetector(usize),
+    ResetDetectors,
+}
+
+pub(crate) async fn load(project: String) -> SensitiveStore {
+    match persist::load_project_store::<SensitiveStore>(&project, "sensitive").await {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::debug!("No sensitive content scan loaded for {}: {}", project, e);
+            SensitiveStore::default()
+        }
+    }
+}
+
+fn save(project: String, store: SensitiveStore) -> Task<Message> {
+    let snapshot = persist::snapshot();
+    Task::perform(
+        async move {
+            if let Err(e) =
+                persist::save_project_store(&store, &project, "sensitive", snapshot).await
+            {
+                tracing::error!("Error saving sensitive content scan for {}: {}", project, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+/// Every Box file in the project tree that text can be read from, as (ID, name, link).
+fn files(tree: &Node) -> Vec<(String, String, String)> {
+    sheet_plan::flatten(tree)
+        .into_iter()
+        .filter(|item| item.file_type == InternalType::File && scannable(&item.name))
+        .map(|item| (item.id, item.name, item.web_link))
+        .collect()
+}
+
+pub(crate) fn sensitive_handle(state: &mut State, event: SensitiveMessage) -> Task<Message> {
+    let settings = &mut state.program_set_state.sensitive;
+    match event {
+        SensitiveMessage::Loaded(store) => {
+            state.sensitive = store;
+            return Task::none();
+        }
+        SensitiveMessage::Scan => {
+            if state.sensitive_state.scanning {
+                return Task::none();
+            }
+            let Some(tree) = &state.project_tree else {
+                tracing::warn!("The project tree is not loaded, so there are no files to scan");
+                return Task::none();
+            };
+            let detectors = Arc::new(settings.compiled());
+            if detectors.is_empty() {
+                tracing::warn!("No detectors are enabled, so there is nothing to scan for");
+                return Task::none();
+            }
+            let files = files(tree);
+            tracing::info!("Scanning {} files for sensitive content", files.len());
+            state.sensitive_state = SensitiveState {
+                scanning: !files.is_empty(),
+                total: files.len(),
+                ..Default::default()
+            };
+            let run = state.sensitive_state.run.id();
+            let config = state.box_config.clone();
+            let scans = stream::iter(files)
+                .map(move |(id, name, web_link)| {
+                    let config = config.clone();
+                    let detectors = detectors.clone();
+                    async move {
+                        let result = scan(config, id.clone(), name.clone(), detectors).await;
+                        (id, name, web_link, result)
+                    }
+                })
+                .buffer_unordered(CONCURRENT_FILES);
+            return state.sensitive_state.run.track(Task::run(
+                scans,
+                move |(id, name, web_link, result)| {
+                    Message::SensitiveMessage(SensitiveMessage::Scanned(
+                        run, id, name, web_link, result,
+                    ))
+                },
+            ));
+        }
+        SensitiveMessage::Scanned(run, id, name, web_link, result) => {
+            if !state.sensitive_state.run.is(run) {
+                return Task::none();
+            }
+            let file = match result {
+                Ok(scan) => ScannedFile {
+                    note: scan.note,
+                    hits: scan.hits,
+                    found: scan.found,
+                    name,
+                    web_link,
+                },
+                Err(e) => {
+                    tracing::warn!("Could not scan {}: {}", name, e);
+                    ScannedFile {
+                        note: Some(format!("Not screened: {e}")),
+                        name,
+                        web_link,
+                        ..Default::default()
+                    }
+                }
+            };
+            let run = &mut state.sensitive_state;
+            run.files.insert(id, file);
+            run.scanned += 1;
+            if run.scanned == run.total {
+                run.scanning = false;
+                // A fresh scan replaces the last one, so removed files drop out of the report
+                state.sensitive.files = std::mem::take(&mut run.files);
+                let flagged = state.sensitive.restricted().len();
+                tracing::info!(
+                    "Scanned {} files, {} need restricted review",
+                    run.scanned,
+                    flagged
+                );
+                if let Some(project) = &state.project {
+                    return save(project.name.clone(), state.sensitive.clone());
+                }
+            }
+            return Task::none();
+        }
+        SensitiveMessage::Export => {
+            let Some(project) = &state.project else {
+                return Task::none();
+            };
+            let name = project.name.clone();
+            let store = state.sensitive.clone();
+            return Task::perform(export(name, store), |_| Message::None);
+        }
+        SensitiveMessage::SetName(i, name) => match settings.detectors.get_mut(i) {
+            Some(detector) => detector.name = name,
+            None => return Task::none(),
+        },
+        SensitiveMessage::SetPattern(i, pattern) => match settings.detectors.get_mut(i) {
+            Some(detector) => detector.pattern = pattern,
+            None => return Task::none(),
+        },
+        SensitiveMessage::SetCheck(i, check) => match settings.detectors.get_mut(i) {
+            Some(detector) => detector.check = check,
+            None => return Task::none(),
+        },
+        SensitiveMessage::Enable(i, enabled) => match settings.detectors.get_mut(i) {
+            Some(detector) => detector.enabled = enabled,
+            None => return Task::none(),
+        },
+        SensitiveMessage::AddDetector => {
+            settings
+                .detectors
+                .push(Detector::new("", "", Check::None, true));
+        }
+        SensitiveMessage::RemoveDetector(i) => {
+            if i < settings.detectors.len() {
+                settings.detectors.remove(i);
+            }
+        }
+        SensitiveMessage::ResetDetectors => {
+            tracing::info!("Reset the sensitive content detectors to the defaults");
+            *settings = DetectorSettings::default();
+        }
+    }
+    // Only detector edits get this far
+    program_settings::save(state.program_set_state.clone())
+}
+
+/// Write every match, and every file that was not screened, to a CSV file the user picks.
+async fn export(project: String, store: SensitiveStore) {
+    let Some(file) = rfd::AsyncFileDialog::new()
+        .add_filter("CSV", &["csv"])
+        .set_file_name(format!("{project}_sensitive_content.csv"))
+        .save_file()
+        .await
+    else {
+        return;
+    };
+    let mut files: Vec<_> = store.files.iter().collect();
+    files.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let _ = writer.write_record([
+        "Box ID", "Name", "Link", "Detector", "Location", "Match", "Note",
+    ]);
+    for (id, f) in files {
+        let note = f.note.as_deref().unwrap_or_default();
+        for hit in &f.hits {
+            let _ = writer.write_record([
+                id.as_str(),
+                &f.name,
+                &f.web_link,
+                &hit.detector,
+                &hit.location,
+                &hit.masked,
+                note,
+            ]);
+        }
+        if f.found > f.hits.len() {
+            let more = format!("{} more matches not listed", f.found - f.hits.len());
+            let _ = writer.write_record([id.as_str(), &f.name, &f.web_link, "", "", "", &more]);
+        }
+        if f.hits.is_empty() && !note.is_empty() {
+            let _ = writer.write_record([id.as_str(), &f.name, &f.web_link, "", "", "", note]);
+        }
+    }
+    let result = match writer.into_inner() {
+        Ok(bytes) => tokio::fs::write(file.path(), bytes)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match result {
+        Ok(()) => tracing::info!("Exported the sensitive content report for {}", project),
+        Err(e) => tracing::error!("Failed to export the sensitive content report: {}", e),
+    }
+}
+
+fn file_link(file: &ScannedFile) -> Element<'_, Message> {
+    button(text(file.name.clone()))
+        .style(button::text)
+        .on_press_maybe(
+            (!file.web_link.is_empty()).then(|| Message::OpenLink(file.web_link.clone())),
+        )
+        .into()
+}
+
+pub(crate) fn sensitive_report(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::SensitiveContent));
+    let run = &state.sensitive_state;
+    let header = row![
+        button("Scan files").on_press_maybe(
+            (!run.scanning).then_some(Message::SensitiveMessage(SensitiveMessage::Scan))
+        ),
+        button("Export report").on_press_maybe(
+            (!state.sensitive.files.is_empty())
+                .then_some(Message::SensitiveMessage(SensitiveMessage::Export))
+        ),
+        button("Detectors").on_press(Message::OpenWindow(Subwindow::Detectors)),
+    ]
+    .spacing(10);
+
+    let mut flagged: Vec<&ScannedFile> = state
+        .sensitive
+        .files
+        .values()
+        .filter(|f| f.found > 0)
+        .collect();
+    flagged.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut unscreened: Vec<&ScannedFile> = state
+        .sensitive
+        .files
+        .values()
+        .filter(|f| f.note.is_some())
+        .collect();
+    unscreened.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let status = if run.scanning {
+        format!("Scanned {} of {} files", run.scanned, run.total)
+    } else {
+        format!(
+            "{} of {} scanned files need restricted review, {} could not be screened",
+            flagged.len(),
+            state.sensitive.files.len(),
+            unscreened.len()
+        )
+    };
+
+    let body: Element<Message> = if state.sensitive.files.is_empty() {
+        text(
+            "Scan the project's text, PDF and Office files for sensitive content. Files are \
+             downloaded and read on this computer only.",
+        )
+        .into()
+    } else {
+        let flagged = flagged.iter().fold(Column::new().spacing(8), |col, file| {
+            let hits = file.hits.iter().fold(Column::new(), |hits, hit| {
+                hits.push(
+                    text(format!(
+                        "    {}: {} ({})",
+                        hit.location, hit.masked, hit.detector
+                    ))
+                    .size(12)
+                    .style(text::danger),
+                )
+            });
+            let hits = if file.found > file.hits.len() {
+                let more = file.found - file.hits.len();
+                hits.push(text(format!("    and {more} more")).size(12))
+            } else {
+                hits
+            };
+            col.push(column![file_link(file), hits])
+        });
+        let unscreened = unscreened
+            .iter()
+            .fold(Column::new().spacing(8), |col, file| {
+                col.push(column![
+                    file_link(file),
+                    text(format!("    {}", file.note.clone().unwrap_or_default()))
+                        .size(12)
+                        .style(text::secondary),
+                ])
+            });
+        scrollable(column![flagged, unscreened].spacing(15))
+            .height(Fill)
+            .into()
+    };
+
+    column![
+        text("Sensitive content").size(20),
+        header,
+        text(status),
+        body,
+        close
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
+
+pub(crate) fn detectors(state: &State) -> Element<'_, Message> {
+    let settings = &state.program_set_state.sensitive;
+    let rows = settings.detectors.iter().enumerate().fold(
+        Column::new().spacing(10),
+        |col, (i, detector)| {
+            let valid = detector.pattern.is_empty() || Regex::new(&detector.pattern).is_ok();
+            let row = row![
+                checkbox(detector.enabled).on_toggle(move |on| {
+                    Message::SensitiveMessage(SensitiveMessage::Enable(i, on))
+                }),
+                TextInput::new("Name", &detector.name)
+                    .on_input(move |n| Message::SensitiveMessage(SensitiveMessage::SetName(i, n)))
+                    .width(130),
+                TextInput::new("Regular expression", &detector.pattern).on_input(move |p| {
+                    Message::SensitiveMessage(SensitiveMessage::SetPattern(i, p))
+                }),
+                pick_list(Check::ALL, Some(detector.check), move |c| {
+                    Message::SensitiveMessage(SensitiveMessage::SetCheck(i, c))
+                })
+                .width(140),
+                button("Remove")
+                    .style(button::text)
+                    .on_press(Message::SensitiveMessage(SensitiveMessage::RemoveDetector(
+                        i
+                    ))),
+            ]
+            .spacing(5)
+            .align_y(Center);
+            if valid {
+                col.push(row)
+            } else {
+                col.push(column![
+                    row,
+                    text("    Not a valid regular expression, so it is skipped")
+                        .size(12)
+                        .style(text::danger),
+                ])
+            }
+        },
+    );
+
+    column![
+        text("Sensitive content detectors").size(20),
+        text("Each enabled pattern is run over the text of text, PDF and Office files. A check, where set, must also pass: Luhn for card numbers, or the SSA's rules for social security numbers.")
+            .size(12)
+            .style(text::secondary),
+        scrollable(rows).height(Fill),
+        row![
+            button("Add").on_press(Message::SensitiveMessage(SensitiveMessage::AddDetector)),
+            button("Reset to defaults")
+                .style(button::secondary)
+                .on_press(Message::SensitiveMessage(SensitiveMessage::ResetDetectors)),
+            Space::new().width(Fill),
+            button("Close").on_press(Message::CloseWindow(Subwindow::Detectors)),
+        ]
+        .spacing(10),
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luhn() {
+        assert!(Check::Luhn.passes("4111 1111 1111 1111"));
+        assert!(Check::Luhn.passes("5500-0000-0000-0004"));
+        assert!(!Check::Luhn.passes("4111 1111 1111 1112"));
+        // Passes the checksum but is too short for a card
+        assert!(!Check::Luhn.passes("79927398713"));
+    }
+
+    #[test]
+    fn ssn() {
+        assert!(Check::Ssn.passes("123-45-6789"));
+        for invalid in [
+            "000-12-3456",
+            "666-12-3456",
+            "900-12-3456",
+            "123-00-4567",
+            "123-45-0000",
+            "123-45-678",
+        ] {
+            assert!(!Check::Ssn.passes(invalid), "{invalid} should not pass");
+        }
+    }
+
+    #[test]
+    fn default_detectors() {
+        let detectors = DetectorSettings::default().compiled();
+        let segments = [Segment {
+            location: "line 1".to_string(),
+            text: "SSN 123-45-6789, card 4111 1111 1111 1112, mail a.b@example.org".to_string(),
+        }];
+        let (hits, found) = detect(&segments, &detectors);
+        let names: Vec<&str> = hits.iter().map(|h| h.detector.as_str()).collect();
+        assert_eq!(names, ["SSN", "Email"]);
+        assert_eq!(found, 2);
+        assert_eq!(hits[0].masked, "***-**-6789");
+    }
+
+    /// A one-page PDF whose page inherits font `F1` from the page tree.
+    fn pdf(font: &str, extra: &str, content: &str) -> Vec<u8> {
+        format!(
+            "%PDF-1.4\n\
+             1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj\n\
+             2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 \
+             /Resources << /Font << /F1 5 0 R >> >> >> endobj\n\
+             3 0 obj << /Type /Page /Parent 2 0 R /Contents 4 0 R >> endobj\n\
+             4 0 obj << /Length {} >> stream\n{content}\nendstream endobj\n\
+             5 0 obj << /Type /Font {font} >> endobj\n\
+             {extra}%%EOF\n",
+            content.len()
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn pdf_simple_font() {
+        let b = pdf(
+            "/Subtype /Type1 /BaseFont /Helvetica",
+            "",
+            "BT /F1 12 Tf (SSN 123-45-6789) Tj ET",
+        );
+        let extracted = pdf_text(&b).unwrap();
+        assert!(extracted.unreadable.is_none());
+        assert_eq!(extracted.segments.len(), 1);
+        assert_eq!(extracted.segments[0].location, "page 1");
+        assert_eq!(extracted.segments[0].text, "SSN 123-45-6789");
+    }
+
+    #[test]
+    fn pdf_identity_font_without_map_is_not_readable() {
+        let b = pdf(
+            "/Subtype /Type0 /Encoding /Identity-H",
+            "",
+            "BT /F1 12 Tf <00140015> Tj ET",
+        );
+        let extracted = pdf_text(&b).unwrap();
+        assert!(extracted.segments.is_empty());
+        assert!(
+            extracted
+                .unreadable
+                .unwrap()
+                .starts_with("Text not readable")
+        );
+    }
+
+    #[test]
+    fn pdf_identity_font_with_to_unicode() {
+        let cmap = "begincmap\n1 begincodespacerange <0000> <FFFF> endcodespacerange\n\
+                    2 beginbfchar <0001> <0053> <0002> <004E> endbfchar\n\
+                    2 beginbfrange <0010> <0019> <0030> <0020> <0021> [<0020> <002D>] \
+                    endbfrange\nendcmap";
+        let extra = format!(
+            "6 0 obj << /Length {} >> stream\n{cmap}\nendstream endobj\n",
+            cmap.len()
+        );
+        let b = pdf(
+            "/Subtype /Type0 /Encoding /Identity-H /ToUnicode 6 0 R",
+            &extra,
+            "BT /F1 12 Tf [<000100010002>-250<001100120013002100140015>] TJ ET",
+        );
+        let extracted = pdf_text(&b).unwrap();
+        assert!(extracted.unreadable.is_none());
+        assert_eq!(extracted.segments[0].text, "SSN 123-45");
+    }
+
+    #[test]
+    fn cmap_ranges() {
+        let Some(FontText::Mapped { width, map }) =
+            to_unicode(b"1 beginbfrange <41> <43> <0061> endbfrange")
+        else {
+            panic!("the range should map");
+        };
+        assert_eq!(width, 1);
+        assert_eq!(map.get(&0x42).map(String::as_str), Some("b"));
+        assert_eq!(to_unicode(b"begincmap endcmap"), None);
+    }
+}
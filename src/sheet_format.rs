@@ -0,0 +1,480 @@
+use std::collections::HashSet;
+
+use google_sheets4::{
+    FieldMask,
+    api::{
+        AddConditionalFormatRuleRequest, AddDimensionGroupRequest, BatchUpdateSpreadsheetRequest,
+        BooleanCondition, BooleanRule, CellData, CellFormat, Color, ConditionValue,
+        ConditionalFormatRule, DataValidationRule, DimensionRange, GridProperties, GridRange,
+        Padding, RepeatCellRequest, Request, SetDataValidationRequest, SheetProperties, TextFormat,
+        UpdateSheetPropertiesRequest,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    project_page::{FlatItem, InternalType},
+    templates::SheetTemplate,
+    vocabulary::VocabularyStore,
+};
+
+/// Google Sheets refuses row groups nested deeper than this.
+const MAX_GROUP_DEPTH: usize = 8;
+
+/// Pixels of left padding added to a folder row per level of nesting.
+const INDENT_PER_LEVEL: i32 = 12;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct SheetFormat {
+    pub group_folders: bool,
+    pub style_folders: bool,
+    pub freeze_header: bool,
+    pub highlight_unknown: bool,
+    pub tag_columns: Vec<TagColumn>,
+}
+
+impl Default for SheetFormat {
+    fn default() -> Self {
+        Self {
+            group_folders: true,
+            style_folders: true,
+            freeze_header: true,
+            highlight_unknown: true,
+            tag_columns: vec![],
+        }
+    }
+}
+
+impl SheetFormat {
+    /// Fill each tag column's dropdown with the current terms of its vocabulary.
+    pub fn fill_tag_options(&mut self, vocabularies: &VocabularyStore) {
+        for tag_column in &mut self.tag_columns {
+            if let Some(vocabulary) = vocabularies
+                .vocabularies
+                .iter()
+                .find(|v| v.name == tag_column.vocabulary)
+            {
+                tag_column.options = vocabulary.terms.iter().map(|t| t.label.clone()).collect();
+            }
+        }
+    }
+}
+
+/// A sheet column restricted to a dropdown of tag values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct TagColumn {
+    /// Column letter, e.g. "H"
+    pub column: String,
+    /// Name of the project vocabulary the options come from
+    pub vocabulary: String,
+    pub options: Vec<String>,
+}
+
+/// Convert a column letter such as "H" or "AB" into a zero-based index.
+pub(crate) fn column_index(column: &str) -> Option<i32> {
+    if column.is_empty() {
+        return None;
+    }
+    column
+        .chars()
+        .try_fold(0i32, |acc, c| {
+            if c.is_ascii_alphabetic() {
+                Some(acc * 26 + (c.to_ascii_uppercase() as i32 - 'A' as i32 + 1))
+            } else {
+                None
+            }
+        })
+        .map(|i| i - 1)
+}
+
+fn rows(sheet_id: i32, start: usize, end: usize) -> GridRange {
+    GridRange {
+        sheet_id: Some(sheet_id),
+        start_row_index: Some(start as i32),
+        end_row_index: Some(end as i32),
+        ..Default::default()
+    }
+}
+
+fn column(sheet_id: i32, column: i32, start_row: usize, end_row: usize) -> GridRange {
+    GridRange {
+        start_column_index: Some(column),
+        end_column_index: Some(column + 1),
+        ..rows(sheet_id, start_row, end_row)
+    }
+}
+
+/// Build the `batch_update` requests that format a sheet written from `flat`.
+///
+/// `flat[0]` is the root folder on the template's header row, and the rest follow from
+/// its starting row. Rows of the items in `invalid` are highlighted.
+pub(crate) fn format_requests(
+    format: &SheetFormat,
+    template: &SheetTemplate,
+    sheet_id: i32,
+    flat: &[FlatItem],
+    invalid: &HashSet<String>,
+) -> Vec<Request> {
+    let mut requests = vec![];
+    let first = template.row(1);
+    let end = template.row(flat.len());
+
+    if format.freeze_header {
+        requests.push(Request {
+            update_sheet_properties: Some(UpdateSheetPropertiesRequest {
+                properties: Some(SheetProperties {
+                    sheet_id: Some(sheet_id),
+                    grid_properties: Some(GridProperties {
+                        frozen_row_count: Some(template.header_row as i32),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                fields: Some(FieldMask::new(&["gridProperties.frozenRowCount"])),
+            }),
+            ..Default::default()
+        });
+    }
+
+    let folder_column = column_index(&template.columns.folder_link).unwrap_or(2);
+    for (i, item) in flat.iter().enumerate().skip(1) {
+        if item.file_type != InternalType::Folder {
+            continue;
+        }
+
+        if format.group_folders && item.last_descendant > i && item.depth <= MAX_GROUP_DEPTH {
+            requests.push(Request {
+                add_dimension_group: Some(AddDimensionGroupRequest {
+                    range: Some(DimensionRange {
+                        sheet_id: Some(sheet_id),
+                        dimension: Some("ROWS".to_string()),
+                        start_index: Some(template.row(i + 1) as i32),
+                        end_index: Some(template.row(item.last_descendant + 1) as i32),
+                    }),
+                }),
+                ..Default::default()
+            });
+        }
+
+        if format.style_folders {
+            let row = template.row(i);
+            requests.push(Request {
+                repeat_cell: Some(RepeatCellRequest {
+                    range: Some(rows(sheet_id, row, row + 1)),
+                    cell: Some(CellData {
+                        user_entered_format: Some(CellFormat {
+                            text_format: Some(TextFormat {
+                                bold: Some(true),
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }),
+                    fields: Some(FieldMask::new(&["userEnteredFormat.textFormat.bold"])),
+                }),
+                ..Default::default()
+            });
+            requests.push(Request {
+                repeat_cell: Some(RepeatCellRequest {
+                    range: Some(column(sheet_id, folder_column, row, row + 1)),
+                    cell: Some(CellData {
+                        user_entered_format: Some(CellFormat {
+                            padding: Some(Padding {
+                                left: Some(item.depth as i32 * INDENT_PER_LEVEL),
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }),
+                    fields: Some(FieldMask::new(&["userEnteredFormat.padding.left"])),
+                }),
+                ..Default::default()
+            });
+        }
+    }
+
+    let file_type_column = column_index(&template.columns.file_type);
+    if let (true, Some(file_type_column)) =
+        (format.highlight_unknown && end > first, file_type_column)
+    {
+        requests.push(Request {
+            add_conditional_format_rule: Some(AddConditionalFormatRuleRequest {
+                index: Some(0),
+                rule: Some(ConditionalFormatRule {
+                    ranges: Some(vec![column(sheet_id, file_type_column, first, end)]),
+                    boolean_rule: Some(BooleanRule {
+                        condition: Some(BooleanCondition {
+                            type_: Some("TEXT_EQ".to_string()),
+                            values: Some(vec![ConditionValue {
+                                user_entered_value: Some("Unknown".to_string()),
+                                ..Default::default()
+                            }]),
+                        }),
+                        format: Some(CellFormat {
+                            background_color: Some(Color {
+                                red: Some(0.96),
+                                green: Some(0.8),
+                                blue: Some(0.8),
+                                alpha: None,
+                            }),
+                            ..Default::default()
+                        }),
+                    }),
+                    ..Default::default()
+                }),
+            }),
+            ..Default::default()
+        });
+    }
+
+    // One request per run of consecutive invalid rows
+    let mut i = 0;
+    while i < flat.len() {
+        if !invalid.contains(&flat[i].id) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < flat.len() && invalid.contains(&flat[i].id) {
+            i += 1;
+        }
+        requests.push(Request {
+            repeat_cell: Some(RepeatCellRequest {
+                range: Some(rows(sheet_id, template.row(start), template.row(i))),
+                cell: Some(CellData {
+                    user_entered_format: Some(CellFormat {
+                        background_color: Some(Color {
+                            red: Some(1.0),
+                            green: Some(0.95),
+                            blue: Some(0.7),
+                            alpha: None,
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                fields: Some(FieldMask::new(&["userEnteredFormat.backgroundColor"])),
+            }),
+            ..Default::default()
+        });
+    }
+
+    if end > first {
+        for tag_column in &format.tag_columns {
+            if tag_column.options.is_empty() {
+                continue;
+            }
+            let Some(col) = column_index(&tag_column.column) else {
+                tracing::warn!("Invalid tag column \"{}\"", tag_column.column);
+                continue;
+            };
+            requests.push(Request {
+                set_data_validation: Some(SetDataValidationRequest {
+                    range: Some(column(sheet_id, col, first, end)),
+                    rule: Some(DataValidationRule {
+                        condition: Some(BooleanCondition {
+                            type_: Some("ONE_OF_LIST".to_string()),
+                            values: Some(
+                                tag_column
+                                    .options
+                                    .iter()
+                                    .map(|o| ConditionValue {
+                                        user_entered_value: Some(o.clone()),
+                                        ..Default::default()
+                                    })
+                                    .collect(),
+                            ),
+                        }),
+                        show_custom_ui: Some(true),
+                        strict: Some(false),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            });
+        }
+    }
+
+    requests
+}
+
+pub(crate) async fn apply_sheet_format(
+    spreadsheet_id: &str,
+    hub: google_sheets4::Sheets<
+        google_sheets4::hyper_rustls::HttpsConnector<
+            google_sheets4::hyper_util::client::legacy::connect::HttpConnector,
+        >,
+    >,
+    format: &SheetFormat,
+    template: &SheetTemplate,
+    sheet_id: i32,
+    flat: &[FlatItem],
+    invalid: &HashSet<String>,
+) {
+    let requests = format_requests(format, template, sheet_id, flat, invalid);
+    if requests.is_empty() {
+        return;
+    }
+    let count = requests.len();
+    let req = BatchUpdateSpreadsheetRequest {
+        requests: Some(requests),
+        ..Default::default()
+    };
+    match hub
+        .spreadsheets()
+        .batch_update(req, spreadsheet_id)
+        .doit()
+        .await
+    {
+        Ok((_resp, _)) => {
+            tracing::info!(
+                "Applied {} formatting requests to sheet {}",
+                count,
+                sheet_id
+            );
+        }
+        Err(e) => {
+            tracing::error!("Failed to format sheet {}: {}", sheet_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, file_type: InternalType, depth: usize, last_descendant: usize) -> FlatItem {
+        FlatItem {
+            name: name.to_string(),
+            file_type,
+            id: name.to_string(),
+            idx: 0,
+            web_link: String::new(),
+            children: (0, 0),
+            depth,
+            last_descendant,
+        }
+    }
+
+    /// Project/
+    ///   A/ (a1, empty/)
+    ///   loose
+    ///   B/ (b1)
+    fn project() -> Vec<FlatItem> {
+        use InternalType::{File, Folder};
+        vec![
+            item("Project", Folder, 0, 6),
+            item("A", Folder, 1, 3),
+            item("a1", File, 2, 2),
+            item("empty", Folder, 2, 3),
+            item("loose", File, 1, 4),
+            item("B", Folder, 1, 6),
+            item("b1", File, 2, 6),
+        ]
+    }
+
+    /// Folders nested `depth` deep, with a file at the bottom.
+    fn nested(depth: usize) -> Vec<FlatItem> {
+        let mut flat: Vec<_> = (0..=depth)
+            .map(|d| item(&format!("f{d}"), InternalType::Folder, d, depth + 1))
+            .collect();
+        flat.push(item("file", InternalType::File, depth + 1, depth + 1));
+        flat
+    }
+
+    /// A template, the items written with it and the groups expected.
+    type Case<'a> = (&'a SheetTemplate, Vec<FlatItem>, &'a [(i32, i32)]);
+
+    fn groups(
+        format: &SheetFormat,
+        template: &SheetTemplate,
+        flat: &[FlatItem],
+    ) -> Vec<(i32, i32)> {
+        format_requests(format, template, 0, flat, &HashSet::new())
+            .into_iter()
+            .filter_map(|r| r.add_dimension_group?.range)
+            .map(|r| (r.start_index.unwrap(), r.end_index.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn column_letters() {
+        for (column, index) in [
+            ("A", Some(0)),
+            ("Z", Some(25)),
+            ("AA", Some(26)),
+            ("ab", Some(27)),
+            ("", None),
+            ("A1", None),
+            ("Ä", None),
+        ] {
+            assert_eq!(column_index(column), index, "{column}");
+        }
+    }
+
+    #[test]
+    fn folder_groups() {
+        let format = SheetFormat::default();
+        let offset = SheetTemplate {
+            header_row: 3,
+            start_row: 6,
+            ..Default::default()
+        };
+        let cases: [Case; 5] = [
+            // A's children are rows 2 and 3; empty folders and the root aren't grouped
+            (&SheetTemplate::default(), project(), &[(2, 4), (6, 7)]),
+            (&offset, project(), &[(6, 8), (10, 11)]),
+            (&SheetTemplate::default(), project()[..1].to_vec(), &[]),
+            (
+                &SheetTemplate::default(),
+                nested(3),
+                &[(2, 5), (3, 5), (4, 5)],
+            ),
+            // Sheets allows 8 levels of groups
+            (
+                &SheetTemplate::default(),
+                nested(10),
+                &[
+                    (2, 12),
+                    (3, 12),
+                    (4, 12),
+                    (5, 12),
+                    (6, 12),
+                    (7, 12),
+                    (8, 12),
+                    (9, 12),
+                ],
+            ),
+        ];
+        for (template, flat, expected) in cases {
+            assert_eq!(groups(&format, template, &flat), expected);
+        }
+
+        let ungrouped = SheetFormat {
+            group_folders: false,
+            ..Default::default()
+        };
+        assert!(groups(&ungrouped, &SheetTemplate::default(), &project()).is_empty());
+    }
+
+    #[test]
+    fn invalid_rows_are_highlighted_in_runs() {
+        let format = SheetFormat {
+            style_folders: false,
+            ..Default::default()
+        };
+        let invalid = ["a1", "empty", "loose", "b1"].map(String::from).into();
+        let highlighted: Vec<_> =
+            format_requests(&format, &SheetTemplate::default(), 0, &project(), &invalid)
+                .into_iter()
+                .filter_map(|r| r.repeat_cell?.range)
+                .map(|r| (r.start_row_index.unwrap(), r.end_row_index.unwrap()))
+                .collect();
+        assert_eq!(highlighted, [(2, 5), (6, 7)]);
+    }
+}
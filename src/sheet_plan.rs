@@ -0,0 +1,1406 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Cursor,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use r#box::apis::configuration::Configuration;
+use google_sheets4::{
+    FieldMask, Sheets,
+    api::{BatchUpdateSpreadsheetRequest, BatchUpdateValuesRequest, ValueRange},
+    hyper_rustls::HttpsConnector,
+    hyper_util::client::legacy::connect::HttpConnector,
+};
+use iced::{
+    Element,
+    Length::{self, Fill},
+    Padding, Task,
+    futures::future::join_all,
+    widget::{Column, Row, Space, button, column, container, row, scrollable, text},
+};
+use serde::Serialize;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    Message, State,
+    embedded::{self, Embedded},
+    format_risk::FileFormat,
+    gapi_drive,
+    integrity::{self, FileIssue},
+    media::{self, MEDIA_EXTENSIONS, MediaInfo},
+    metadata::{ItemMetadata, MetadataStore},
+    project::Project,
+    project_page::{FlatItem, InternalType, NewProjEvent, Node},
+    pronom::{self, Identification, SignatureFile},
+    sampling::{self, SamplingSettings},
+    schema::{FieldKey, FieldSchema, SEPARATOR},
+    sheet_format, sheet_shard,
+    subwindows::Subwindow,
+    templates::SheetColumns,
+};
+
+/// Sheets accepts large batches, but very large requests time out.
+const WRITES_PER_BATCH: usize = 500;
+
+/// Rows of each tab shown in the preview window. The exported plan has all of them.
+const PREVIEW_ROWS: usize = 500;
+
+/// The sheet Google Sheets puts in every spreadsheet it creates.
+const DEFAULT_SHEET_TITLE: &str = "Sheet1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(crate) enum ValueInput {
+    #[serde(rename = "RAW")]
+    Raw,
+    #[serde(rename = "USER_ENTERED")]
+    UserEntered,
+}
+
+impl ValueInput {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ValueInput::Raw => "RAW",
+            ValueInput::UserEntered => "USER_ENTERED",
+        }
+    }
+}
+
+/// One `values_update` worth of cells, all on the same row.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CellWrite {
+    pub range: String,
+    /// Zero-based row of the first cell
+    pub row: usize,
+    /// Zero-based column of the first cell
+    pub column: usize,
+    pub values: Vec<String>,
+    pub value_input: ValueInput,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Destination {
+    Existing { spreadsheet_id: String },
+    New { title: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct TabPlan {
+    pub destination: Destination,
+    pub title: String,
+    pub rows: usize,
+    pub formatting_requests: usize,
+    pub writes: Vec<CellWrite>,
+    #[serde(skip)]
+    pub items: Vec<FlatItem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct IndexPlan {
+    pub title: String,
+    pub shards: Vec<String>,
+}
+
+/// What reviewers and scans flagged about items, written next to them, keyed by item ID.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ReviewFlags {
+    /// The canonical item each duplicate points to
+    pub duplicate_of: HashMap<String, String>,
+    /// What sensitive content was found in a file
+    pub restricted: HashMap<String, String>,
+    /// The Box classification label of a file
+    pub classification: HashMap<String, String>,
+}
+
+/// Everything `MakeSheet` would do to the spreadsheet, computed without writing anything.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SheetPlan {
+    pub project: String,
+    pub spreadsheet_id: String,
+    pub template_spreadsheet_id: String,
+    pub template_sheet_id: i32,
+    pub tabs: Vec<TabPlan>,
+    pub index_tab: Option<IndexPlan>,
+    /// Items that are incomplete or invalid under the project's field schema, by ID
+    pub invalid: HashSet<String>,
+    #[serde(skip)]
+    pub source: Project,
+    /// The whole project, before it was split into tabs
+    #[serde(skip)]
+    pub flat: Vec<FlatItem>,
+    /// Metadata found embedded in files, keyed by item ID, for pre-filling data entry
+    #[serde(skip)]
+    pub embedded: HashMap<String, Embedded>,
+    /// What each file was identified as, keyed by item ID, for the format risk report
+    #[serde(skip)]
+    pub formats: HashMap<String, FileFormat>,
+}
+
+impl SheetPlan {
+    pub fn rows(&self) -> usize {
+        self.tabs.iter().map(|t| t.rows).sum()
+    }
+
+    pub fn cells(&self) -> usize {
+        self.tabs
+            .iter()
+            .flat_map(|t| t.writes.iter())
+            .map(|w| w.values.len())
+            .sum()
+    }
+}
+
+/// A tab that the project has been written into.
+#[derive(Debug, Clone)]
+struct SheetTab {
+    spreadsheet_id: String,
+    title: String,
+    sheet_id: Option<i32>,
+}
+
+impl SheetTab {
+    fn url(&self) -> String {
+        format!(
+            "https://docs.google.com/spreadsheets/d/{}/edit#gid={}",
+            self.spreadsheet_id,
+            self.sheet_id.unwrap_or(0)
+        )
+    }
+}
+
+/// Flatten the tree into a Vec with folders before files at each level.
+pub(crate) fn flatten(tree: &Node) -> Vec<FlatItem> {
+    fn flatten_node(node: &Node, depth: usize, out: &mut Vec<FlatItem>) {
+        let mut child_counts = (0, 0);
+
+        if let Some(children) = &node.children {
+            for child in children {
+                if child.file_type == InternalType::Folder {
+                    child_counts.0 += 1;
+                } else {
+                    child_counts.1 += 1;
+                }
+            }
+        }
+
+        // push the folder/file itself (as a flattened entry with no children)
+        let own_idx = out.len();
+        out.push(FlatItem {
+            name: node.name.clone(),
+            file_type: node.file_type,
+            id: node.id.clone(),
+            idx: node.idx,
+            web_link: node.web_link.clone(),
+            children: child_counts,
+            depth,
+            last_descendant: own_idx,
+        });
+
+        if let Some(children) = &node.children {
+            // separate folders and files
+            let mut folders: Vec<&Node> = Vec::new();
+            let mut files: Vec<&Node> = Vec::new();
+            for c in children {
+                if c.file_type == InternalType::Folder {
+                    folders.push(c);
+                } else {
+                    files.push(c);
+                }
+            }
+
+            // recurse into folders first
+            for f in folders {
+                flatten_node(f, depth + 1, out);
+            }
+
+            // then append files, each followed by its archive entries if it was expanded
+            for file in files {
+                let file_idx = out.len();
+                out.push(FlatItem {
+                    name: file.name.clone(),
+                    id: file.id.clone(),
+                    idx: file.idx,
+                    web_link: file.web_link.clone(),
+                    file_type: file.file_type,
+                    children: (0, 0),
+                    depth: depth + 1,
+                    last_descendant: file_idx,
+                });
+                for entry in file.children.iter().flatten() {
+                    out.push(FlatItem {
+                        name: entry.name.clone(),
+                        id: entry.id.clone(),
+                        idx: entry.idx,
+                        web_link: entry.web_link.clone(),
+                        file_type: entry.file_type,
+                        children: (0, 0),
+                        depth: depth + 2,
+                        last_descendant: out.len(),
+                    });
+                }
+                out[file_idx].last_descendant = out.len() - 1;
+            }
+        }
+
+        out[own_idx].last_descendant = out.len() - 1;
+    }
+
+    let mut flat = Vec::new();
+    flatten_node(tree, 0, &mut flat);
+    flat
+}
+
+fn column_letter(mut column: usize) -> String {
+    let mut letters = vec![];
+    loop {
+        letters.push((b'A' + (column % 26) as u8) as char);
+        if column < 26 {
+            break;
+        }
+        column = column / 26 - 1;
+    }
+    letters.iter().rev().collect()
+}
+
+fn hyperlink(url: &str, name: &str) -> String {
+    let esc_name = name.replace('"', "\\\"");
+    let esc_url = url.replace('"', "\\\"");
+    format!("=HYPERLINK(\"{}\",\"{}\")", esc_url, esc_name)
+}
+
+fn cell_write(
+    title: &str,
+    row: usize,
+    column: usize,
+    values: Vec<String>,
+    value_input: ValueInput,
+) -> CellWrite {
+    let safe_title = title.replace('\'', "''");
+    let start = format!("{}{}", column_letter(column), row + 1);
+    let range = if values.len() > 1 {
+        format!(
+            "'{}'!{}:{}{}",
+            safe_title,
+            start,
+            column_letter(column + values.len() - 1),
+            row + 1
+        )
+    } else {
+        format!("'{}'!{}", safe_title, start)
+    };
+    CellWrite {
+        range,
+        row,
+        column,
+        values,
+        value_input,
+    }
+}
+
+/// Compute every cell written into a tab holding `items`, laid out by the project template.
+fn tab_writes(
+    project: &Project,
+    title: &str,
+    items: &[FlatItem],
+    file_types: &HashMap<String, DetectedType>,
+    duplicates: &HashMap<String, String>,
+    review: &ReviewFlags,
+    metadata: &MetadataStore,
+) -> Vec<CellWrite> {
+    let template = &project.template;
+    let columns = &template.columns;
+    let col = |letter: &str| sheet_format::column_index(letter).unwrap_or(0) as usize;
+    let mut writes = vec![];
+
+    // The header row gets "(x Folders) (y Files)" for the header's children, and a hyperlink to it.
+    let header = &items[0];
+    let header_row = template.row(0);
+    writes.push(cell_write(
+        title,
+        header_row,
+        col(&columns.folder_info),
+        vec![format!(
+            "({} Folders) ({} Files)",
+            header.children.0, header.children.1
+        )],
+        ValueInput::UserEntered,
+    ));
+    let header_url = if header.id == project.top_folder_id.to_string() {
+        &project.box_url
+    } else {
+        &header.web_link
+    };
+    writes.push(cell_write(
+        title,
+        header_row,
+        col(&columns.folder_link),
+        vec![hyperlink(header_url, &header.name)],
+        ValueInput::UserEntered,
+    ));
+
+    for (i, node) in items.iter().enumerate().skip(1) {
+        let row = template.row(i);
+        match node.file_type {
+            InternalType::Folder => {
+                writes.push(cell_write(
+                    title,
+                    row,
+                    col(&columns.folder_info),
+                    vec![format!(
+                        "({} Folders) ({} Files)",
+                        node.children.0, node.children.1
+                    )],
+                    ValueInput::UserEntered,
+                ));
+                writes.push(cell_write(
+                    title,
+                    row,
+                    col(&columns.folder_link),
+                    vec![hyperlink(&node.web_link, &node.name)],
+                    ValueInput::UserEntered,
+                ));
+            }
+            InternalType::File | InternalType::Link | InternalType::ArchiveEntry => {
+                if node.idx == 0 && node.file_type != InternalType::ArchiveEntry {
+                    writes.push(cell_write(
+                        title,
+                        row,
+                        col(&columns.loose_files),
+                        vec!["Loose files:".to_string()],
+                        ValueInput::UserEntered,
+                    ));
+                }
+                writes.push(cell_write(
+                    title,
+                    row,
+                    col(&columns.file_link),
+                    vec![hyperlink(&node.web_link, &node.name)],
+                    ValueInput::UserEntered,
+                ));
+                // A format identified by hand beats the detected one
+                let identified = metadata
+                    .get(&node.id)
+                    .map(|m| &m.format)
+                    .filter(|f| !f.is_empty());
+                let detected = file_types.get(&node.id);
+                let magic = detected.map(|d| &d.magic).filter(|m| !m.is_empty());
+                if let Some(file_type) = identified.or(magic) {
+                    writes.push(cell_write(
+                        title,
+                        row,
+                        col(&columns.file_type),
+                        vec![file_type.clone()],
+                        ValueInput::Raw,
+                    ));
+                }
+                if let Some(detected) = detected {
+                    writes.extend(pronom_writes(columns, title, row, &detected.pronom));
+                    writes.extend(embedded_writes(columns, title, row, &detected.embedded));
+                    if let Some(info) = &detected.media {
+                        writes.extend(media_writes(columns, title, row, info));
+                    }
+                    if !detected.issues.is_empty()
+                        && let Some(column) = sheet_format::column_index(&columns.file_status)
+                    {
+                        let issues: Vec<String> =
+                            detected.issues.iter().map(|i| i.to_string()).collect();
+                        writes.push(cell_write(
+                            title,
+                            row,
+                            column as usize,
+                            vec![issues.join("; ")],
+                            ValueInput::Raw,
+                        ));
+                    }
+                }
+                if let Some(canonical) = duplicates.get(&node.id)
+                    && let Some(column) = sheet_format::column_index(&columns.duplicate_of)
+                {
+                    writes.push(cell_write(
+                        title,
+                        row,
+                        column as usize,
+                        vec![canonical.clone()],
+                        ValueInput::UserEntered,
+                    ));
+                }
+                if let Some(found) = review.restricted.get(&node.id)
+                    && let Some(column) = sheet_format::column_index(&columns.restricted_review)
+                {
+                    writes.push(cell_write(
+                        title,
+                        row,
+                        column as usize,
+                        vec![found.clone()],
+                        ValueInput::Raw,
+                    ));
+                }
+                if let Some(label) = review.classification.get(&node.id)
+                    && let Some(column) = sheet_format::column_index(&columns.classification)
+                {
+                    writes.push(cell_write(
+                        title,
+                        row,
+                        column as usize,
+                        vec![label.clone()],
+                        ValueInput::Raw,
+                    ));
+                }
+            }
+        }
+        if let Some(entry) = metadata.get(&node.id) {
+            writes.extend(metadata_writes(project, title, row, entry));
+        }
+    }
+
+    writes
+}
+
+/// Cells for a file's PRONOM identifications, in whichever of their columns the template sets.
+fn pronom_writes(
+    columns: &SheetColumns,
+    title: &str,
+    row: usize,
+    identifications: &[Identification],
+) -> Vec<CellWrite> {
+    let join = |part: fn(&Identification) -> String| {
+        identifications
+            .iter()
+            .map(part)
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+    [
+        (&columns.puid, join(|i| i.puid.clone())),
+        (&columns.pronom, join(Identification::describe)),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_empty())
+    .filter_map(|(column, value)| {
+        let column = sheet_format::column_index(column)?;
+        Some(cell_write(
+            title,
+            row,
+            column as usize,
+            vec![value],
+            ValueInput::Raw,
+        ))
+    })
+    .collect()
+}
+
+/// Cells for the metadata embedded in a file, in whichever of its columns the template sets.
+fn embedded_writes(
+    columns: &SheetColumns,
+    title: &str,
+    row: usize,
+    found: &Embedded,
+) -> Vec<CellWrite> {
+    [
+        (&columns.embedded_title, found.title.clone()),
+        (&columns.embedded_creator, found.creator.clone()),
+        (&columns.embedded_date, found.date.clone()),
+        (&columns.embedded, found.summary()),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_empty())
+    .filter_map(|(column, value)| {
+        let column = sheet_format::column_index(column)?;
+        Some(cell_write(
+            title,
+            row,
+            column as usize,
+            vec![value],
+            ValueInput::Raw,
+        ))
+    })
+    .collect()
+}
+
+/// Cells for a media file's technical metadata, in whichever of its columns the template sets.
+fn media_writes(
+    columns: &SheetColumns,
+    title: &str,
+    row: usize,
+    info: &MediaInfo,
+) -> Vec<CellWrite> {
+    [
+        (&columns.duration, info.duration_text().unwrap_or_default()),
+        (&columns.technical, info.summary()),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_empty())
+    .filter_map(|(column, value)| {
+        let column = sheet_format::column_index(column)?;
+        Some(cell_write(
+            title,
+            row,
+            column as usize,
+            vec![value],
+            ValueInput::Raw,
+        ))
+    })
+    .collect()
+}
+
+/// Cells for an item's entered metadata. Written raw so notes are never taken as formulas.
+fn metadata_writes(
+    project: &Project,
+    title: &str,
+    row: usize,
+    entry: &ItemMetadata,
+) -> Vec<CellWrite> {
+    let columns = &project.template.columns;
+    let custom = project.schema.custom().filter_map(|def| match &def.key {
+        FieldKey::Custom(name) if !def.column.is_empty() => Some((
+            &def.column,
+            def.values(entry).join(&format!("{SEPARATOR} ")),
+        )),
+        _ => None,
+    });
+    [
+        (&columns.title, entry.title.clone()),
+        (&columns.description, entry.description.clone()),
+        (&columns.dates, entry.dates()),
+        (&columns.creator, entry.creator.clone()),
+        (&columns.tags, entry.tags.join("; ")),
+        (&columns.notes, entry.notes.trim_end().to_string()),
+    ]
+    .into_iter()
+    .chain(custom)
+    .filter(|(_, value)| !value.is_empty())
+    .filter_map(|(column, value)| {
+        let column = sheet_format::column_index(column)?;
+        Some(cell_write(
+            title,
+            row,
+            column as usize,
+            vec![value],
+            ValueInput::Raw,
+        ))
+    })
+    .collect()
+}
+
+/// What a file was detected as, by the magic database and by PRONOM signatures, and the
+/// metadata embedded in it.
+#[derive(Debug, Clone, Default)]
+struct DetectedType {
+    magic: String,
+    pronom: Vec<Identification>,
+    embedded: Embedded,
+    /// Technical metadata, for audio and video when the template has a column for it
+    media: Option<MediaInfo>,
+    /// MIME type from the magic database
+    mime: String,
+    size: u64,
+    /// Why the file may not open, if anything
+    issues: Vec<FileIssue>,
+}
+
+impl DetectedType {
+    /// The format a downloaded file was identified as, for the format risk report. Links have
+    /// none.
+    fn file_format(&self, name: &str, web_link: &str) -> Option<FileFormat> {
+        if self.magic == "Web link" {
+            return None;
+        }
+        let pronom = self.pronom.first();
+        Some(FileFormat {
+            name: name.to_string(),
+            size: self.size,
+            puid: pronom.map(|p| p.puid.clone()).unwrap_or_default(),
+            format: pronom
+                .map(|p| format!("{} {}", p.name, p.version).trim().to_string())
+                .unwrap_or_default(),
+            mime: pronom
+                .map(|p| p.mime.clone())
+                .filter(|m| !m.is_empty())
+                .unwrap_or(self.mime.clone()),
+            issues: self.issues.clone(),
+            web_link: web_link.to_string(),
+        })
+    }
+}
+
+/// Read a DROID signature file for identifying PRONOM formats.
+///
+/// Skipped unless a signature file is configured and the template has a column for it.
+async fn load_signatures(
+    columns: &SheetColumns,
+    path: Option<PathBuf>,
+) -> Option<Arc<SignatureFile>> {
+    if columns.puid.is_empty() && columns.pronom.is_empty() {
+        return None;
+    }
+    let Some(path) = path else {
+        warn!("The template has PRONOM columns but no DROID signature file is configured");
+        return None;
+    };
+    read_signatures(path).await
+}
+
+async fn read_signatures(path: PathBuf) -> Option<Arc<SignatureFile>> {
+    match tokio::task::spawn_blocking(move || pronom::load(&path)).await {
+        Ok(Ok(signatures)) => {
+            info!(
+                "Loaded DROID signature file version {}, skipped {} unsupported signatures",
+                signatures.version, signatures.skipped
+            );
+            Some(Arc::new(signatures))
+        }
+        Ok(Err(e)) => {
+            error!("Failed to load DROID signature file: {}", e);
+            None
+        }
+        Err(e) => {
+            error!("Failed to load DROID signature file: {}", e);
+            None
+        }
+    }
+}
+
+/// Work out the file type of every file and link, and read any metadata embedded in files,
+/// keyed by item ID. Each file is sampled once, by its format's read strategy, and every
+/// detector reads from that sample. Audio and video are only probed when `probe_media`.
+///
+/// Files that could not be downloaded are only marked unreadable, so their type cell stays
+/// empty.
+async fn detect_file_types(
+    box_config: &Configuration,
+    flat: &[FlatItem],
+    signatures: Option<Arc<SignatureFile>>,
+    sampling: &SamplingSettings,
+    probe_media: bool,
+) -> HashMap<String, DetectedType> {
+    let db = match magic_db::load() {
+        Ok(db) => db,
+        Err(e) => {
+            error!(
+                "Failed to load filetype detection database: {}",
+                e.to_string()
+            );
+            return HashMap::new();
+        }
+    };
+
+    let futures = flat.iter().skip(1).map(|node| {
+        let box_config = box_config.clone();
+        let db = db.clone();
+        let signatures = signatures.clone();
+        let id = node.id.clone();
+        async move {
+            let value = match node.file_type {
+                InternalType::Folder => {
+                    // skip folders
+                    return None;
+                }
+                InternalType::Link => DetectedType {
+                    magic: "Web link".to_string(),
+                    ..Default::default()
+                },
+                // Entries can't be downloaded on their own
+                InternalType::ArchiveEntry => return None,
+                InternalType::File => {
+                    let (head, tail) = sampling.sizes(&node.name);
+                    let sample = match sampling::fetch(&box_config, &id, head, tail).await {
+                        Ok(sample) => sample,
+                        Err(e) => {
+                            error!("Failed to download file {}: {}", node.name, e);
+                            let value = DetectedType {
+                                issues: vec![FileIssue::Unreadable],
+                                ..Default::default()
+                            };
+                            return Some((id, value));
+                        }
+                    };
+                    debug!(
+                        "sampled {} bytes of file {}",
+                        sample.head.len() + sample.tail.len(),
+                        &node.name
+                    );
+
+                    let extension = node.name.rsplit_once('.').map(|(_, ext)| ext);
+                    let pronom = signatures
+                        .map(|s| s.identify(&sample, extension))
+                        .unwrap_or_default();
+                    let embedded = embedded::extract(&box_config, &id, &node.name, &sample).await;
+                    let issues = integrity::check(&box_config, &id, &node.name, &sample).await;
+                    let is_media = extension.is_some_and(|ext| {
+                        MEDIA_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str())
+                    });
+                    let media = if probe_media && is_media {
+                        match media::probe_sample(&box_config, &id, &sample).await {
+                            Ok(info) => Some(info),
+                            Err(e) => {
+                                warn!("Could not read media file {}: {}", node.name, e);
+                                None
+                            }
+                        }
+                    } else {
+                        None
+                    };
+
+                    // Analyze with MagicDb
+                    let (magic, mime) = match db.best_magic(&mut Cursor::new(&sample.head)) {
+                        Ok(result) => {
+                            // pick the first sensible result if present
+                            (result.message(), result.mime_type().to_string())
+                        }
+                        Err(_) => {
+                            warn!("Failed to analyze file {}", &node.name);
+                            ("Unknown".to_string(), String::new())
+                        }
+                    };
+                    DetectedType {
+                        magic,
+                        pronom,
+                        embedded,
+                        media,
+                        mime,
+                        size: sample.size,
+                        issues,
+                    }
+                }
+            };
+            Some((id, value))
+        }
+    });
+
+    join_all(futures).await.into_iter().flatten().collect()
+}
+
+/// IDs of the files in `flat` that are incomplete or invalid under `schema`.
+pub(crate) fn invalid_items(
+    schema: &FieldSchema,
+    flat: &[FlatItem],
+    metadata: &MetadataStore,
+) -> HashSet<String> {
+    let validator = schema.validator();
+    let empty = ItemMetadata::default();
+    flat.iter()
+        .filter(|item| item.file_type == InternalType::File)
+        .filter(|item| {
+            !validator
+                .validate(metadata.get(&item.id).unwrap_or(&empty))
+                .is_empty()
+        })
+        .map(|item| item.id.clone())
+        .collect()
+}
+
+/// Titles of the sheets already in a spreadsheet, or none if they can't be read.
+pub(crate) async fn sheet_titles(
+    hub: &Sheets<HttpsConnector<HttpConnector>>,
+    spreadsheet_id: &str,
+) -> HashSet<String> {
+    match hub
+        .spreadsheets()
+        .get(spreadsheet_id)
+        .param("fields", "sheets.properties.title")
+        .doit()
+        .await
+    {
+        Ok((_r, spreadsheet)) => spreadsheet
+            .sheets
+            .into_iter()
+            .flatten()
+            .filter_map(|sheet| sheet.properties?.title)
+            .collect(),
+        Err(e) => {
+            warn!("Failed to read the sheets in {}: {}", spreadsheet_id, e);
+            HashSet::new()
+        }
+    }
+}
+
+/// Work out everything that generating the sheet would write. Only reads from Box.
+///
+/// Tab titles avoid each other and the sheets in `existing`, the titles already in the
+/// project's spreadsheet. Fails if the project's template cannot be copied into Google Sheets.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn build_plan(
+    project: Project,
+    box_config: Configuration,
+    tree: Node,
+    metadata: MetadataStore,
+    signatures: Option<PathBuf>,
+    review: ReviewFlags,
+    sampling: SamplingSettings,
+    existing: HashSet<String>,
+) -> anyhow::Result<SheetPlan> {
+    let (template_spreadsheet_id, template_sheet_id) = project.template.google_source()?;
+    let flat = flatten(&tree);
+    tracing::info!(
+        "Flattened tree for {}: {} entries",
+        project.name,
+        flat.len()
+    );
+
+    let invalid = invalid_items(&project.schema, &flat, &metadata);
+
+    let columns = &project.template.columns;
+    let metadata_columns = [
+        &columns.title,
+        &columns.description,
+        &columns.dates,
+        &columns.creator,
+        &columns.tags,
+        &columns.notes,
+    ];
+    if !metadata.items.is_empty()
+        && metadata_columns.iter().all(|c| c.is_empty())
+        && project.schema.custom().all(|def| def.column.is_empty())
+    {
+        tracing::warn!(
+            "Descriptive metadata is not written: template {} has no columns set for it",
+            project.template.name
+        );
+    }
+
+    // Duplicates link to their canonical item, wherever in the project it is
+    let duplicates: HashMap<String, String> = review
+        .duplicate_of
+        .iter()
+        .filter_map(|(id, canonical)| {
+            let canonical = flat.iter().find(|item| &item.id == canonical)?;
+            Some((id.clone(), hyperlink(&canonical.web_link, &canonical.name)))
+        })
+        .collect();
+
+    let signatures = load_signatures(&project.template.columns, signatures).await;
+    let probe_media = !(columns.duration.is_empty() && columns.technical.is_empty());
+    let file_types =
+        detect_file_types(&box_config, &flat, signatures, &sampling, probe_media).await;
+
+    let mut used = existing;
+    used.insert(DEFAULT_SHEET_TITLE.to_string());
+    let shards = sheet_shard::shard(&project.name, &flat, &project.sharding, &mut used);
+    let separate = project.sharding.separate_spreadsheets && shards.len() > 1;
+    let tabs: Vec<TabPlan> = shards
+        .into_iter()
+        .map(|shard| TabPlan {
+            destination: if separate {
+                Destination::New {
+                    title: format!("{} - {}", project.name, shard.title),
+                }
+            } else {
+                Destination::Existing {
+                    spreadsheet_id: project.spreadsheet_id.clone(),
+                }
+            },
+            rows: shard.items.len(),
+            formatting_requests: sheet_format::format_requests(
+                &project.sheet_format,
+                &project.template,
+                0,
+                &shard.items,
+                &invalid,
+            )
+            .len(),
+            writes: tab_writes(
+                &project,
+                &shard.title,
+                &shard.items,
+                &file_types,
+                &duplicates,
+                &review,
+                &metadata,
+            ),
+            title: shard.title,
+            items: shard.items,
+        })
+        .collect();
+
+    let index_tab = (tabs.len() > 1 && project.sharding.index_tab).then(|| IndexPlan {
+        title: sheet_shard::unique_title(&format!("{} index", project.name), &mut used),
+        shards: tabs.iter().map(|t| t.title.clone()).collect(),
+    });
+
+    Ok(SheetPlan {
+        project: project.name.clone(),
+        spreadsheet_id: project.spreadsheet_id.clone(),
+        template_spreadsheet_id,
+        template_sheet_id,
+        tabs,
+        index_tab,
+        invalid,
+        source: project,
+        formats: formats(&flat, &file_types),
+        flat,
+        embedded: embedded(file_types),
+    })
+}
+
+fn embedded(file_types: HashMap<String, DetectedType>) -> HashMap<String, Embedded> {
+    file_types
+        .into_iter()
+        .filter(|(_, detected)| !detected.embedded.is_empty())
+        .map(|(id, detected)| (id, detected.embedded))
+        .collect()
+}
+
+fn formats(
+    flat: &[FlatItem],
+    file_types: &HashMap<String, DetectedType>,
+) -> HashMap<String, FileFormat> {
+    flat.iter()
+        .filter_map(|item| {
+            let detected = file_types.get(&item.id)?;
+            let format = detected.file_format(&item.name, &item.web_link)?;
+            Some((item.id.clone(), format))
+        })
+        .collect()
+}
+
+/// Identify the format of every file in the project without planning a sheet, keyed by item
+/// ID, along with the metadata embedded in them.
+///
+/// PRONOM identification runs whenever a signature file is configured.
+pub(crate) async fn identify_formats(
+    box_config: Configuration,
+    tree: Node,
+    signatures: Option<PathBuf>,
+    sampling: SamplingSettings,
+) -> (HashMap<String, FileFormat>, HashMap<String, Embedded>) {
+    let flat = flatten(&tree);
+    let signatures = match signatures {
+        Some(path) => read_signatures(path).await,
+        None => None,
+    };
+    let file_types = detect_file_types(&box_config, &flat, signatures, &sampling, false).await;
+    (formats(&flat, &file_types), embedded(file_types))
+}
+
+async fn write_values(
+    hub: &Sheets<HttpsConnector<HttpConnector>>,
+    spreadsheet_id: &str,
+    writes: &[CellWrite],
+    value_input: ValueInput,
+) {
+    let writes: Vec<&CellWrite> = writes
+        .iter()
+        .filter(|w| w.value_input == value_input)
+        .collect();
+    for batch in writes.chunks(WRITES_PER_BATCH) {
+        let req = BatchUpdateValuesRequest {
+            data: Some(
+                batch
+                    .iter()
+                    .map(|w| ValueRange {
+                        range: Some(w.range.clone()),
+                        major_dimension: None,
+                        values: Some(vec![
+                            w.values
+                                .iter()
+                                .map(|v| serde_json::Value::String(v.clone()))
+                                .collect(),
+                        ]),
+                    })
+                    .collect(),
+            ),
+            value_input_option: Some(value_input.as_str().to_string()),
+            ..Default::default()
+        };
+        match hub
+            .spreadsheets()
+            .values_batch_update(req, spreadsheet_id)
+            .doit()
+            .await
+        {
+            Ok((_r, _)) => tracing::info!("Wrote {} ranges to {}", batch.len(), spreadsheet_id),
+            Err(e) => tracing::error!("Failed to write {} ranges: {}", batch.len(), e),
+        }
+    }
+}
+
+// Attempt to rename the newly copied sheet to the tab title
+async fn rename_new_sheet(
+    tab: &SheetTab,
+    hub: Sheets<HttpsConnector<HttpConnector>>,
+    props: google_sheets4::api::SheetProperties,
+) {
+    let rename_req = BatchUpdateSpreadsheetRequest {
+        requests: Some(vec![google_sheets4::api::Request {
+            update_sheet_properties: Some(google_sheets4::api::UpdateSheetPropertiesRequest {
+                properties: Some(google_sheets4::api::SheetProperties {
+                    sheet_id: props.sheet_id,
+                    title: Some(tab.title.clone()),
+                    ..Default::default()
+                }),
+                fields: Some(FieldMask::new(&["title"])),
+            }),
+            ..Default::default()
+        }]),
+        ..Default::default()
+    };
+    match hub
+        .spreadsheets()
+        .batch_update(rename_req, &tab.spreadsheet_id)
+        .doit()
+        .await
+    {
+        Ok((_resp, _)) => {
+            tracing::info!(
+                "Renamed copied sheet to \"{}\" in {}",
+                tab.title,
+                tab.spreadsheet_id
+            );
+        }
+        Err(e) => {
+            tracing::error!("Failed to rename copied sheet: {}", e);
+        }
+    }
+}
+
+/// Copy the template into the tab's spreadsheet and apply its writes.
+///
+/// Returns the written tab, or `None` if the template could not be copied.
+async fn execute_tab(
+    plan: &SheetPlan,
+    hub: Sheets<HttpsConnector<HttpConnector>>,
+    tab_plan: &TabPlan,
+) -> Option<SheetTab> {
+    let spreadsheet_id = match &tab_plan.destination {
+        Destination::Existing { spreadsheet_id } => spreadsheet_id.clone(),
+        Destination::New { title } => {
+            let created = gapi_drive::create_spreadsheet(
+                hub.clone(),
+                title.clone(),
+                plan.source.drive_folder.clone(),
+                plan.source.collaborators.clone(),
+            )
+            .await;
+            match created {
+                Ok(created) => created.spreadsheet_id,
+                Err(e) => {
+                    error!("Failed to create spreadsheet for {}: {}", tab_plan.title, e);
+                    return None;
+                }
+            }
+        }
+    };
+
+    let copy_req = google_sheets4::api::CopySheetToAnotherSpreadsheetRequest {
+        destination_spreadsheet_id: Some(spreadsheet_id.clone()),
+    };
+    let props = match hub
+        .spreadsheets()
+        .sheets_copy_to(
+            copy_req,
+            &plan.template_spreadsheet_id,
+            plan.template_sheet_id,
+        )
+        .doit()
+        .await
+    {
+        Ok((_resp, props)) => props,
+        Err(e) => {
+            tracing::error!("Failed to copy template sheet: {}", e);
+            return None;
+        }
+    };
+    tracing::info!(
+        "Copied template sheet {} from {} into {}",
+        plan.template_sheet_id,
+        plan.template_spreadsheet_id,
+        spreadsheet_id
+    );
+
+    let tab = SheetTab {
+        spreadsheet_id,
+        title: tab_plan.title.clone(),
+        sheet_id: props.sheet_id,
+    };
+    rename_new_sheet(&tab, hub.clone(), props).await;
+
+    tokio::join!(
+        write_values(
+            &hub,
+            &tab.spreadsheet_id,
+            &tab_plan.writes,
+            ValueInput::UserEntered
+        ),
+        write_values(&hub, &tab.spreadsheet_id, &tab_plan.writes, ValueInput::Raw),
+    );
+
+    if let Some(sheet_id) = tab.sheet_id {
+        sheet_format::apply_sheet_format(
+            &tab.spreadsheet_id,
+            hub.clone(),
+            &plan.source.sheet_format,
+            &plan.source.template,
+            sheet_id,
+            &tab_plan.items,
+            &plan.invalid,
+        )
+        .await;
+    } else {
+        warn!("Copied sheet has no ID, skipping formatting");
+    }
+    info!("Wrote tab \"{}\"", tab.title);
+    Some(tab)
+}
+
+/// Add a tab to the project spreadsheet linking to every shard.
+async fn write_index_tab(
+    plan: &SheetPlan,
+    index: &IndexPlan,
+    hub: Sheets<HttpsConnector<HttpConnector>>,
+    shards: &[(SheetTab, usize)],
+) {
+    let add_req = BatchUpdateSpreadsheetRequest {
+        requests: Some(vec![google_sheets4::api::Request {
+            add_sheet: Some(google_sheets4::api::AddSheetRequest {
+                properties: Some(google_sheets4::api::SheetProperties {
+                    title: Some(index.title.clone()),
+                    index: Some(0),
+                    ..Default::default()
+                }),
+            }),
+            ..Default::default()
+        }]),
+        ..Default::default()
+    };
+    if let Err(e) = hub
+        .spreadsheets()
+        .batch_update(add_req, &plan.spreadsheet_id)
+        .doit()
+        .await
+    {
+        tracing::error!("Failed to add index tab: {}", e);
+        return;
+    }
+
+    let mut values = vec![vec![
+        serde_json::Value::String("Shard".to_string()),
+        serde_json::Value::String("Rows".to_string()),
+    ]];
+    for (tab, rows) in shards {
+        values.push(vec![
+            serde_json::Value::String(hyperlink(&tab.url(), &tab.title)),
+            serde_json::Value::from(*rows),
+        ]);
+    }
+    let range = format!("'{}'!A1:B{}", index.title.replace('\'', "''"), values.len());
+    let vr = ValueRange {
+        range: Some(range.clone()),
+        major_dimension: None,
+        values: Some(values),
+    };
+    match hub
+        .spreadsheets()
+        .values_update(vr, &plan.spreadsheet_id, &range)
+        .value_input_option("USER_ENTERED")
+        .doit()
+        .await
+    {
+        Ok((_r, _)) => tracing::info!("Wrote index of {} shards to {}", shards.len(), range),
+        Err(e) => tracing::error!("Failed to write {}: {}", range, e),
+    }
+}
+
+/// Carry out a plan made by [`build_plan`].
+pub(crate) async fn execute_plan(plan: SheetPlan, hub: Sheets<HttpsConnector<HttpConnector>>) {
+    if plan.tabs.len() > 1 {
+        info!("Splitting {} into {} shards", plan.project, plan.tabs.len());
+    }
+
+    let mut written = Vec::with_capacity(plan.tabs.len());
+    for tab_plan in &plan.tabs {
+        if let Some(tab) = execute_tab(&plan, hub.clone(), tab_plan).await {
+            written.push((tab, tab_plan.rows - 1));
+        }
+    }
+
+    if let Some(index) = &plan.index_tab {
+        write_index_tab(&plan, index, hub.clone(), &written).await;
+    }
+    info!("Done making sheet");
+}
+
+pub(crate) fn export_plan(plan: SheetPlan) -> Task<Message> {
+    Task::perform(
+        async move {
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .add_filter("JSON", &["json"])
+                .set_file_name(format!("{}_sheet_plan.json", plan.project))
+                .save_file()
+                .await
+            else {
+                return;
+            };
+            let json = match serde_json::to_string_pretty(&plan) {
+                Ok(json) => json,
+                Err(e) => {
+                    error!("Failed to serialize sheet plan: {}", e);
+                    return;
+                }
+            };
+            match tokio::fs::write(file.path(), json).await {
+                Ok(()) => info!("Exported sheet plan for {}", plan.project),
+                Err(e) => error!("Failed to export sheet plan: {}", e),
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+fn preview_grid(tab: &TabPlan) -> Element<'_, Message> {
+    let columns = tab
+        .writes
+        .iter()
+        .map(|w| w.column + w.values.len())
+        .max()
+        .unwrap_or(0);
+    // Writes can land below `rows`, e.g. after a template's header rows
+    let rows = tab
+        .writes
+        .iter()
+        .map(|w| w.row + 1)
+        .max()
+        .unwrap_or(0)
+        .max(tab.rows);
+    let shown = rows.min(PREVIEW_ROWS);
+    let mut grid = vec![vec![""; columns]; shown];
+    for write in &tab.writes {
+        if write.row >= shown {
+            continue;
+        }
+        for (i, value) in write.values.iter().enumerate() {
+            grid[write.row][write.column + i] = value.as_str();
+        }
+    }
+
+    let header = (0..columns).fold(Row::new().push(container(text("")).width(40)), |r, c| {
+        r.push(container(text(column_letter(c)).size(12)).width(180))
+    });
+    let body = grid.into_iter().enumerate().fold(
+        Column::new().push(header).spacing(2),
+        |col, (i, cells)| {
+            col.push(cells.into_iter().fold(
+                Row::new().push(container(text(i + 1).size(12)).width(40)),
+                |r, cell| r.push(container(text(cell).size(12)).width(180).clip(true)),
+            ))
+        },
+    );
+
+    let mut out = column![body];
+    if rows > shown {
+        out = out.push(text(format!(
+            "... and {} more rows. Export the plan to see all of them.",
+            rows - shown
+        )));
+    }
+    out.into()
+}
+
+pub(crate) fn sheet_preview(state: &State) -> Element<'_, Message> {
+    let Some(plan) = &state.sheet_preview else {
+        return column![
+            text("Nothing to preview"),
+            button("Close").on_press(Message::CloseWindow(Subwindow::SheetPreview))
+        ]
+        .padding(Padding::new(15.0))
+        .into();
+    };
+
+    let tabs = plan
+        .tabs
+        .iter()
+        .fold(Column::new().spacing(20), |col, tab| {
+            let destination = match &tab.destination {
+                Destination::Existing { spreadsheet_id } => format!(
+                    "Copy sheet {} of template {} into spreadsheet {}",
+                    plan.template_sheet_id, plan.template_spreadsheet_id, spreadsheet_id
+                ),
+                Destination::New { title } => format!(
+                    "Create spreadsheet \"{}\" and copy sheet {} of template {} into it",
+                    title, plan.template_sheet_id, plan.template_spreadsheet_id
+                ),
+            };
+            col.push(
+                column![
+                    text(format!("Tab \"{}\"", tab.title)).size(18),
+                    text(destination),
+                    text(format!("Rename the copied sheet to \"{}\"", tab.title)),
+                    text(format!(
+                        "Write {} ranges over {} rows, then apply {} formatting requests",
+                        tab.writes.len(),
+                        tab.rows,
+                        tab.formatting_requests
+                    )),
+                    preview_grid(tab),
+                ]
+                .spacing(5),
+            )
+        });
+    let blocked = plan.source.schema.block_invalid && !plan.invalid.is_empty();
+    let tabs = match &plan.index_tab {
+        Some(index) => tabs.push(text(format!(
+            "Add tab \"{}\" linking to {} shards",
+            index.title,
+            index.shards.len()
+        ))),
+        None => tabs,
+    };
+
+    column![
+        text(format!("Sheet preview for {}", plan.project)).size(20),
+        text(format!(
+            "{} tab(s), {} rows, {} cells. Nothing has been written yet.",
+            plan.tabs.len(),
+            plan.rows(),
+            plan.cells()
+        )),
+        if !plan.invalid.is_empty() {
+            row![
+                text(if blocked {
+                    format!(
+                        "{} items are incomplete or invalid, so the plan can't be written. \
+                         Fix them or change the project's fields first.",
+                        plan.invalid.len()
+                    )
+                } else {
+                    format!(
+                        "{} items are incomplete or invalid. Their rows will be highlighted.",
+                        plan.invalid.len()
+                    )
+                })
+                .style(text::danger)
+                .width(Fill),
+                button("Validation report")
+                    .style(button::secondary)
+                    .on_press(Message::OpenWindow(Subwindow::ValidationReport)),
+            ]
+            .spacing(10)
+        } else {
+            row![]
+        },
+        row![
+            button("Write to spreadsheet")
+                .style(button::primary)
+                .on_press_maybe(
+                    (!blocked).then_some(Message::NewProjMessage(NewProjEvent::RunPlan))
+                ),
+            button("Export plan as JSON")
+                .on_press(Message::NewProjMessage(NewProjEvent::ExportPlan)),
+            Space::new().width(Fill),
+            button("Cancel")
+                .style(button::secondary)
+                .on_press(Message::CloseWindow(Subwindow::SheetPreview)),
+        ]
+        .spacing(10),
+        scrollable(tabs)
+            .direction(scrollable::Direction::Both {
+                vertical: scrollable::Scrollbar::default(),
+                horizontal: scrollable::Scrollbar::default(),
+            })
+            .height(Length::Fill)
+            .width(Length::Fill),
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
@@ -0,0 +1,279 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::project_page::{FlatItem, InternalType};
+
+/// Sheets rejects tab titles longer than this.
+const MAX_TITLE_LEN: usize = 100;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) enum ShardMode {
+    /// Everything goes into one tab
+    #[default]
+    Single,
+    /// One tab per top-level folder, plus one for loose files at the top level
+    TopLevelFolder,
+    /// A new tab every N rows
+    RowsPerTab(usize),
+}
+
+impl std::fmt::Display for ShardMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShardMode::Single => write!(f, "Single tab"),
+            ShardMode::TopLevelFolder => write!(f, "One tab per top-level folder"),
+            ShardMode::RowsPerTab(_) => write!(f, "Fixed number of rows per tab"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Sharding {
+    pub mode: ShardMode,
+    /// Put every shard into its own spreadsheet instead of its own tab
+    pub separate_spreadsheets: bool,
+    /// Add a tab of hyperlinks to every shard
+    pub index_tab: bool,
+}
+
+impl Default for Sharding {
+    fn default() -> Self {
+        Self {
+            mode: ShardMode::Single,
+            separate_spreadsheets: false,
+            index_tab: true,
+        }
+    }
+}
+
+/// A piece of the flattened project written to its own tab.
+///
+/// `items[0]` is the header row and everything after it is written from row 2 down,
+/// exactly like an unsharded sheet.
+#[derive(Debug, Clone)]
+pub(crate) struct Shard {
+    pub title: String,
+    pub items: Vec<FlatItem>,
+}
+
+/// Copy `flat[range]` so that indices and depths are relative to a new header row.
+fn rebase(header: &FlatItem, body: &[FlatItem], offset: usize, depth: usize) -> Vec<FlatItem> {
+    let mut items = Vec::with_capacity(body.len() + 1);
+    let mut header = header.clone();
+    header.last_descendant = body.len();
+    items.push(header);
+    let end = body.len();
+    items.extend(body.iter().map(|item| {
+        let mut item = item.clone();
+        item.depth = item.depth.saturating_sub(depth);
+        item.last_descendant = (item.last_descendant + 1).saturating_sub(offset).min(end);
+        item
+    }));
+    items
+}
+
+/// Shorten `base` to fit a tab title and number it if it is already in `used`, then add it.
+pub(crate) fn unique_title(base: &str, used: &mut HashSet<String>) -> String {
+    let mut title: String = base.chars().take(MAX_TITLE_LEN - 6).collect();
+    if title.trim().is_empty() {
+        title = "Untitled".to_string();
+    }
+    let mut candidate = title.clone();
+    let mut n = 2;
+    while used.contains(&candidate) {
+        candidate = format!("{title} ({n})");
+        n += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+/// Split a flattened project into the shards described by `sharding`.
+///
+/// Titles avoid everything in `used`, and are added to it.
+pub(crate) fn shard(
+    project_name: &str,
+    flat: &[FlatItem],
+    sharding: &Sharding,
+    used: &mut HashSet<String>,
+) -> Vec<Shard> {
+    let Some(root) = flat.first() else {
+        return vec![];
+    };
+
+    match &sharding.mode {
+        ShardMode::Single => vec![Shard {
+            title: unique_title(project_name, used),
+            items: flat.to_vec(),
+        }],
+        ShardMode::RowsPerTab(rows) => {
+            let rows = (*rows).max(1);
+            flat[1..]
+                .chunks(rows)
+                .enumerate()
+                .map(|(n, chunk)| Shard {
+                    title: unique_title(&format!("{project_name} {}", n + 1), used),
+                    items: rebase(root, chunk, 1 + n * rows, 0),
+                })
+                .collect()
+        }
+        ShardMode::TopLevelFolder => {
+            let mut shards = vec![];
+            let mut loose = vec![];
+            let mut i = 1;
+            while i < flat.len() {
+                let item = &flat[i];
+                if item.depth == 1 && item.file_type == InternalType::Folder {
+                    let end = item.last_descendant.max(i);
+                    shards.push(Shard {
+                        title: unique_title(&item.name, used),
+                        items: rebase(item, &flat[i + 1..=end], i + 1, 1),
+                    });
+                    i = end + 1;
+                } else {
+                    loose.push(item.clone());
+                    i += 1;
+                }
+            }
+            if !loose.is_empty() {
+                let mut items = vec![root.clone()];
+                items[0].last_descendant = loose.len();
+                for (n, mut item) in loose.into_iter().enumerate() {
+                    item.last_descendant = n + 1;
+                    items.push(item);
+                }
+                shards.insert(
+                    0,
+                    Shard {
+                        title: unique_title(project_name, used),
+                        items,
+                    },
+                );
+            }
+            shards
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, file_type: InternalType, depth: usize, last_descendant: usize) -> FlatItem {
+        FlatItem {
+            name: name.to_string(),
+            file_type,
+            id: name.to_string(),
+            idx: 0,
+            web_link: String::new(),
+            children: (0, 0),
+            depth,
+            last_descendant,
+        }
+    }
+
+    /// Project/
+    ///   A/ (a1, sub/)
+    ///   loose
+    ///   B/ (b1)
+    fn project() -> Vec<FlatItem> {
+        use InternalType::{File, Folder};
+        vec![
+            item("Project", Folder, 0, 6),
+            item("A", Folder, 1, 3),
+            item("a1", File, 2, 2),
+            item("sub", Folder, 2, 3),
+            item("loose", File, 1, 4),
+            item("B", Folder, 1, 6),
+            item("b1", File, 2, 6),
+        ]
+    }
+
+    /// Each shard's rows as (name, depth, last descendant).
+    fn rows(shard: &Shard) -> Vec<(&str, usize, usize)> {
+        shard
+            .items
+            .iter()
+            .map(|i| (i.name.as_str(), i.depth, i.last_descendant))
+            .collect()
+    }
+
+    #[test]
+    fn single_tab_is_unchanged() {
+        let sharding = Sharding::default();
+        let shards = shard("Project", &project(), &sharding, &mut HashSet::new());
+        assert_eq!(shards.len(), 1);
+        assert_eq!(shards[0].title, "Project");
+        assert_eq!(
+            rows(&shards[0]),
+            rows(&Shard {
+                title: String::new(),
+                items: project()
+            })
+        );
+    }
+
+    #[test]
+    fn top_level_folders_are_rebased() {
+        let sharding = Sharding {
+            mode: ShardMode::TopLevelFolder,
+            ..Default::default()
+        };
+        let shards = shard("Project", &project(), &sharding, &mut HashSet::new());
+        let titles: Vec<_> = shards.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["Project", "A", "B"]);
+        assert_eq!(rows(&shards[0]), [("Project", 0, 1), ("loose", 1, 1)]);
+        assert_eq!(rows(&shards[1]), [("A", 1, 2), ("a1", 1, 1), ("sub", 1, 2)]);
+        assert_eq!(rows(&shards[2]), [("B", 1, 1), ("b1", 1, 1)]);
+    }
+
+    #[test]
+    fn fixed_rows_are_rebased() {
+        let sharding = Sharding {
+            mode: ShardMode::RowsPerTab(2),
+            ..Default::default()
+        };
+        let shards = shard("P", &project(), &sharding, &mut HashSet::new());
+        let titles: Vec<_> = shards.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["P 1", "P 2", "P 3"]);
+        // Folders that continue into the next tab end at the last row of this one
+        assert_eq!(
+            rows(&shards[0]),
+            [("Project", 0, 2), ("A", 1, 2), ("a1", 2, 2)]
+        );
+        assert_eq!(
+            rows(&shards[1]),
+            [("Project", 0, 2), ("sub", 2, 1), ("loose", 1, 2)]
+        );
+        assert_eq!(
+            rows(&shards[2]),
+            [("Project", 0, 2), ("B", 1, 2), ("b1", 2, 2)]
+        );
+    }
+
+    #[test]
+    fn titles_are_unique_and_short() {
+        let mut used = HashSet::new();
+        assert_eq!(unique_title("A", &mut used), "A");
+        assert_eq!(unique_title("A", &mut used), "A (2)");
+        assert_eq!(unique_title("A", &mut used), "A (3)");
+        assert_eq!(unique_title("  ", &mut used), "Untitled");
+        let long = unique_title(&"x".repeat(200), &mut used);
+        assert!(long.chars().count() <= MAX_TITLE_LEN);
+    }
+
+    #[test]
+    fn titles_avoid_existing_sheets() {
+        let mut used = HashSet::from(["Sheet1".to_string(), "Project".to_string()]);
+        let shards = shard("Project", &project(), &Sharding::default(), &mut used);
+        assert_eq!(shards[0].title, "Project (2)");
+        assert!(used.contains("Project (2)"));
+    }
+
+    #[test]
+    fn empty_projects_have_no_shards() {
+        assert!(shard("P", &[], &Sharding::default(), &mut HashSet::new()).is_empty());
+    }
+}
@@ -11,6 +11,22 @@ pub(crate) enum Subwindow {
     NewProject,
     ProjectSettings,
     ProgramSettings,
+    SheetPreview,
+    Templates,
+    Vocabularies,
+    History,
+    Schema,
+    ValidationReport,
+    Fixity,
+    Duplicates,
+    Sampling,
+    FormatRisks,
+    RiskRegistry,
+    FileIssues,
+    SensitiveContent,
+    Detectors,
+    Classifications,
+    BoxMetadata,
 }
 
 pub(crate) fn open_window(state: &mut State, sw: Subwindow) -> Task<Message> {
@@ -33,8 +49,8 @@ pub(crate) fn open_window(state: &mut State, sw: Subwindow) -> Task<Message> {
             if state.windows.iter().find(|x| x.1 == sw).is_none() {
                 let window = window::open(Settings {
                     size: iced::Size {
-                        width: 300.0,
-                        height: 500.0,
+                        width: 360.0,
+                        height: 600.0,
                     },
                     level: window::Level::AlwaysOnTop,
                     ..Default::default()
@@ -68,7 +84,7 @@ pub(crate) fn open_window(state: &mut State, sw: Subwindow) -> Task<Message> {
                 let window = window::open(Settings {
                     size: iced::Size {
                         width: 600.0,
-                        height: 400.0,
+                        height: 560.0,
                     },
                     level: window::Level::AlwaysOnTop,
                     ..Default::default()
@@ -80,6 +96,278 @@ pub(crate) fn open_window(state: &mut State, sw: Subwindow) -> Task<Message> {
                 Task::none()
             }
         }
+        Subwindow::SheetPreview => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 1000.0,
+                        height: 700.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened sheet preview window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::Templates => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 700.0,
+                        height: 650.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened templates window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::Vocabularies => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 500.0,
+                        height: 650.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened vocabularies window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::History => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 500.0,
+                        height: 600.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened history window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::Schema => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 600.0,
+                        height: 750.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened fields window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::ValidationReport => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 500.0,
+                        height: 600.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened validation report window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::Fixity => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 500.0,
+                        height: 600.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened fixity window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::Duplicates => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 600.0,
+                        height: 700.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened duplicates window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::Sampling => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 650.0,
+                        height: 550.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened sampling window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::FormatRisks => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 850.0,
+                        height: 650.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened format risks window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::RiskRegistry => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 800.0,
+                        height: 600.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened risk registry window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::FileIssues => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 600.0,
+                        height: 650.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened file issues window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::SensitiveContent => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 650.0,
+                        height: 700.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened sensitive content window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::Detectors => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 800.0,
+                        height: 500.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened sensitive content detectors window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::Classifications => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 600.0,
+                        height: 650.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened classifications window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
+        Subwindow::BoxMetadata => {
+            if state.windows.iter().find(|x| x.1 == sw).is_none() {
+                let window = window::open(Settings {
+                    size: iced::Size {
+                        width: 650.0,
+                        height: 600.0,
+                    },
+                    level: window::Level::AlwaysOnTop,
+                    ..Default::default()
+                });
+                state.windows.push((window.0, sw));
+                tracing::debug!("Opened Box metadata window");
+                window.1
+            } else {
+                Task::none()
+            }
+        }
     };
     window.then(|id| {
         let icon = icon::from_file_data(include_bytes!("../icon.png"), Some(ImageFormat::Png));
@@ -0,0 +1,667 @@
+use std::path::PathBuf;
+
+use iced::{
+    Element,
+    Length::Fill,
+    Padding, Task,
+    widget::{Column, Space, TextInput, button, column, row, scrollable, text},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Message, State, program_settings, sheet_format::column_index, subwindows::Subwindow, urls,
+};
+
+/// The GenEq collection template that TagMonster originally shipped with.
+const GENEQ_TEMPLATE_ID: &str = "1q_tfznc0LUGesvm2Yb5EqUCdhhpimJFLUkrZZJ8XvWY";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum TemplateSource {
+    GoogleSheet {
+        spreadsheet_id: String,
+        sheet_id: i32,
+    },
+    /// Nothing writes local files yet, so these can't be added or used for new projects.
+    /// Kept so settings that already name one still load.
+    LocalXlsx { path: PathBuf },
+}
+
+impl std::fmt::Display for TemplateSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateSource::GoogleSheet {
+                spreadsheet_id,
+                sheet_id,
+            } => write!(f, "Google sheet {sheet_id} of {spreadsheet_id}"),
+            TemplateSource::LocalXlsx { path } => write!(f, "Local file {}", path.display()),
+        }
+    }
+}
+
+/// Which column each generated value goes in, as column letters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct SheetColumns {
+    pub folder_info: String,
+    pub folder_link: String,
+    pub loose_files: String,
+    pub file_link: String,
+    pub file_type: String,
+    /// Descriptive metadata columns are only filled in when set, since templates may already
+    /// use any column past the ones TagMonster has always written
+    pub title: String,
+    pub description: String,
+    pub dates: String,
+    pub creator: String,
+    pub tags: String,
+    pub notes: String,
+    /// Audio and video columns are only filled in when set
+    pub duration: String,
+    pub technical: String,
+    /// PRONOM columns are only filled in when set and a DROID signature file is configured
+    pub puid: String,
+    pub pronom: String,
+    /// Link to the canonical copy of a duplicate, only filled in when set
+    pub duplicate_of: String,
+    /// Why a file may not open, such as encryption or corruption, only filled in when set
+    pub file_status: String,
+    /// What sensitive content a scan found in a file, only filled in when set
+    pub restricted_review: String,
+    /// The Box classification label of a file, only filled in when set
+    pub classification: String,
+    /// Metadata embedded in files, only filled in when set
+    pub embedded_title: String,
+    pub embedded_creator: String,
+    pub embedded_date: String,
+    /// Everything embedded in a file in one cell
+    pub embedded: String,
+}
+
+impl Default for SheetColumns {
+    fn default() -> Self {
+        Self {
+            folder_info: "B".to_string(),
+            folder_link: "C".to_string(),
+            loose_files: "C".to_string(),
+            file_link: "D".to_string(),
+            file_type: "G".to_string(),
+            title: String::new(),
+            description: String::new(),
+            dates: String::new(),
+            creator: String::new(),
+            tags: String::new(),
+            notes: String::new(),
+            duration: String::new(),
+            technical: String::new(),
+            puid: String::new(),
+            pronom: String::new(),
+            duplicate_of: String::new(),
+            file_status: String::new(),
+            restricted_review: String::new(),
+            classification: String::new(),
+            embedded_title: String::new(),
+            embedded_creator: String::new(),
+            embedded_date: String::new(),
+            embedded: String::new(),
+        }
+    }
+}
+
+impl SheetColumns {
+    fn all(&self) -> [(&'static str, &str); 23] {
+        [
+            ("Folder info", &self.folder_info),
+            ("Folder link", &self.folder_link),
+            ("Loose files marker", &self.loose_files),
+            ("File link", &self.file_link),
+            ("File type", &self.file_type),
+            ("Title", &self.title),
+            ("Description", &self.description),
+            ("Dates", &self.dates),
+            ("Creator", &self.creator),
+            ("Tags", &self.tags),
+            ("Notes", &self.notes),
+            ("Duration", &self.duration),
+            ("Technical", &self.technical),
+            ("PUID", &self.puid),
+            ("PRONOM format", &self.pronom),
+            ("Duplicate of", &self.duplicate_of),
+            ("File status", &self.file_status),
+            ("Restricted review", &self.restricted_review),
+            ("Classification", &self.classification),
+            ("Embedded title", &self.embedded_title),
+            ("Embedded creator", &self.embedded_creator),
+            ("Embedded date", &self.embedded_date),
+            ("Embedded metadata", &self.embedded),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct SheetTemplate {
+    pub name: String,
+    pub source: TemplateSource,
+    pub columns: SheetColumns,
+    /// One-based row describing the project's top folder
+    pub header_row: usize,
+    /// One-based row of the first item below the top folder
+    pub start_row: usize,
+}
+
+impl Default for SheetTemplate {
+    fn default() -> Self {
+        Self {
+            name: "GenEq collection".to_string(),
+            source: TemplateSource::GoogleSheet {
+                spreadsheet_id: GENEQ_TEMPLATE_ID.to_string(),
+                sheet_id: 0,
+            },
+            columns: SheetColumns::default(),
+            header_row: 1,
+            start_row: 2,
+        }
+    }
+}
+
+impl std::fmt::Display for SheetTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl SheetTemplate {
+    /// Zero-based sheet row for `flat[i]`, where `flat[0]` is the header.
+    pub fn row(&self, i: usize) -> usize {
+        if i == 0 {
+            self.header_row.saturating_sub(1)
+        } else {
+            self.start_row.saturating_sub(1) + (i - 1)
+        }
+    }
+
+    /// The Google sheet to copy, or an error for templates only local sinks understand.
+    pub fn google_source(&self) -> anyhow::Result<(String, i32)> {
+        match &self.source {
+            TemplateSource::GoogleSheet {
+                spreadsheet_id,
+                sheet_id,
+            } => Ok((spreadsheet_id.clone(), *sheet_id)),
+            TemplateSource::LocalXlsx { path } => Err(anyhow::format_err!(
+                "Template \"{}\" is the local file {}, which cannot be copied into Google Sheets",
+                self.name,
+                path.display()
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TemplatesState {
+    name: String,
+    sheets_url: String,
+    columns: SheetColumns,
+    header_row: String,
+    start_row: String,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum ColumnField {
+    FolderInfo,
+    FolderLink,
+    LooseFiles,
+    FileLink,
+    FileType,
+    Title,
+    Description,
+    Dates,
+    Creator,
+    Tags,
+    Notes,
+    Duration,
+    Technical,
+    Puid,
+    Pronom,
+    DuplicateOf,
+    FileStatus,
+    RestrictedReview,
+    Classification,
+    EmbeddedTitle,
+    EmbeddedCreator,
+    EmbeddedDate,
+    Embedded,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum TemplatesMessage {
+    SetName(String),
+    SetSheetsUrl(String),
+    SetColumn(ColumnField, String),
+    SetHeaderRow(String),
+    SetStartRow(String),
+    Add,
+    Remove(usize),
+}
+
+/// Templates configured in Program Settings, falling back to the built-in one.
+///
+/// Includes templates that can't be used for new projects; see [`usable`].
+pub(crate) fn available(state: &State) -> Vec<SheetTemplate> {
+    if state.program_set_state.templates.is_empty() {
+        vec![SheetTemplate::default()]
+    } else {
+        state.program_set_state.templates.clone()
+    }
+}
+
+/// Templates a new project can be made from.
+pub(crate) fn usable(state: &State) -> Vec<SheetTemplate> {
+    available(state)
+        .into_iter()
+        .filter(|t| t.google_source().is_ok())
+        .collect()
+}
+
+fn parse_row(row: &str, default: usize) -> Result<usize, String> {
+    match row.trim() {
+        "" => Ok(default),
+        r => match r.parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!("\"{r}\" is not a row number")),
+            Ok(r) => Ok(r),
+        },
+    }
+}
+
+fn draft_template(draft: &TemplatesState) -> Result<SheetTemplate, String> {
+    let name = draft.name.trim();
+    if name.is_empty() {
+        return Err("The template needs a name".to_string());
+    }
+    let (spreadsheet_id, sheet_id) = urls::parse_sheets_url(draft.sheets_url.trim())
+        .map_err(|e| format!("Invalid template URL: {e}"))?;
+    let source = TemplateSource::GoogleSheet {
+        spreadsheet_id,
+        sheet_id,
+    };
+    for (label, entered) in draft.columns.all() {
+        let entered = entered.trim();
+        if !entered.is_empty() && column_index(entered).is_none() {
+            return Err(format!(
+                "{label} column \"{entered}\" is not a column letter"
+            ));
+        }
+    }
+    // Blank inputs keep the default column
+    let pick = |entered: &str, default: String| {
+        let entered = entered.trim().to_ascii_uppercase();
+        if entered.is_empty() { default } else { entered }
+    };
+    let defaults = SheetColumns::default();
+    let columns = SheetColumns {
+        folder_info: pick(&draft.columns.folder_info, defaults.folder_info),
+        folder_link: pick(&draft.columns.folder_link, defaults.folder_link),
+        loose_files: pick(&draft.columns.loose_files, defaults.loose_files),
+        file_link: pick(&draft.columns.file_link, defaults.file_link),
+        file_type: pick(&draft.columns.file_type, defaults.file_type),
+        title: pick(&draft.columns.title, defaults.title),
+        description: pick(&draft.columns.description, defaults.description),
+        dates: pick(&draft.columns.dates, defaults.dates),
+        creator: pick(&draft.columns.creator, defaults.creator),
+        tags: pick(&draft.columns.tags, defaults.tags),
+        notes: pick(&draft.columns.notes, defaults.notes),
+        duration: pick(&draft.columns.duration, defaults.duration),
+        technical: pick(&draft.columns.technical, defaults.technical),
+        puid: pick(&draft.columns.puid, defaults.puid),
+        pronom: pick(&draft.columns.pronom, defaults.pronom),
+        duplicate_of: pick(&draft.columns.duplicate_of, defaults.duplicate_of),
+        file_status: pick(&draft.columns.file_status, defaults.file_status),
+        restricted_review: pick(&draft.columns.restricted_review, defaults.restricted_review),
+        classification: pick(&draft.columns.classification, defaults.classification),
+        embedded_title: pick(&draft.columns.embedded_title, defaults.embedded_title),
+        embedded_creator: pick(&draft.columns.embedded_creator, defaults.embedded_creator),
+        embedded_date: pick(&draft.columns.embedded_date, defaults.embedded_date),
+        embedded: pick(&draft.columns.embedded, defaults.embedded),
+    };
+
+    let header_row = parse_row(&draft.header_row, 1)?;
+    let start_row = parse_row(&draft.start_row, header_row + 1)?;
+    if start_row <= header_row {
+        return Err("The first item row must come after the header row".to_string());
+    }
+
+    Ok(SheetTemplate {
+        name: name.to_string(),
+        source,
+        columns,
+        header_row,
+        start_row,
+    })
+}
+
+pub(crate) fn templates_handle(state: &mut State, event: TemplatesMessage) -> Task<Message> {
+    let draft = &mut state.templates_state;
+    match event {
+        TemplatesMessage::SetName(n) => draft.name = n,
+        TemplatesMessage::SetSheetsUrl(u) => draft.sheets_url = u,
+        TemplatesMessage::SetColumn(field, c) => match field {
+            ColumnField::FolderInfo => draft.columns.folder_info = c,
+            ColumnField::FolderLink => draft.columns.folder_link = c,
+            ColumnField::LooseFiles => draft.columns.loose_files = c,
+            ColumnField::FileLink => draft.columns.file_link = c,
+            ColumnField::FileType => draft.columns.file_type = c,
+            ColumnField::Title => draft.columns.title = c,
+            ColumnField::Description => draft.columns.description = c,
+            ColumnField::Dates => draft.columns.dates = c,
+            ColumnField::Creator => draft.columns.creator = c,
+            ColumnField::Tags => draft.columns.tags = c,
+            ColumnField::Notes => draft.columns.notes = c,
+            ColumnField::Duration => draft.columns.duration = c,
+            ColumnField::Technical => draft.columns.technical = c,
+            ColumnField::Puid => draft.columns.puid = c,
+            ColumnField::Pronom => draft.columns.pronom = c,
+            ColumnField::DuplicateOf => draft.columns.duplicate_of = c,
+            ColumnField::FileStatus => draft.columns.file_status = c,
+            ColumnField::RestrictedReview => draft.columns.restricted_review = c,
+            ColumnField::Classification => draft.columns.classification = c,
+            ColumnField::EmbeddedTitle => draft.columns.embedded_title = c,
+            ColumnField::EmbeddedCreator => draft.columns.embedded_creator = c,
+            ColumnField::EmbeddedDate => draft.columns.embedded_date = c,
+            ColumnField::Embedded => draft.columns.embedded = c,
+        },
+        TemplatesMessage::SetHeaderRow(r) => draft.header_row = r,
+        TemplatesMessage::SetStartRow(r) => draft.start_row = r,
+        TemplatesMessage::Add => match draft_template(draft) {
+            Ok(template) => {
+                if state.program_set_state.templates.is_empty() {
+                    state.program_set_state.templates = available(state);
+                }
+                let templates = &mut state.program_set_state.templates;
+                if let Some(existing) = templates.iter_mut().find(|t| t.name == template.name) {
+                    tracing::info!("Replaced template \"{}\"", template.name);
+                    *existing = template;
+                } else {
+                    tracing::info!("Added template \"{}\"", template.name);
+                    templates.push(template);
+                }
+                state.templates_state = TemplatesState::default();
+                return program_settings::save(state.program_set_state.clone());
+            }
+            Err(e) => tracing::warn!("Invalid template: {}", e),
+        },
+        TemplatesMessage::Remove(i) => {
+            if state.program_set_state.templates.is_empty() {
+                state.program_set_state.templates = available(state);
+            }
+            if i < state.program_set_state.templates.len() {
+                let removed = state.program_set_state.templates.remove(i);
+                tracing::info!("Removed template \"{}\"", removed.name);
+                return program_settings::save(state.program_set_state.clone());
+            }
+        }
+    }
+    Task::none()
+}
+
+fn column_input<'a>(label: &str, value: &'a str, field: ColumnField) -> TextInput<'a, Message> {
+    TextInput::new(label, value)
+        .on_input(move |c| Message::TemplatesMessage(TemplatesMessage::SetColumn(field.clone(), c)))
+}
+
+pub(crate) fn templates(state: &State) -> Element<'_, Message> {
+    let list = available(state).into_iter().enumerate().fold(
+        Column::new().spacing(10),
+        |col, (i, template)| {
+            let columns = template
+                .columns
+                .all()
+                .iter()
+                .filter(|(_, c)| !c.is_empty())
+                .map(|(label, c)| format!("{label}: {c}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            col.push(
+                row![
+                    column![
+                        text(template.name.clone()).size(18),
+                        text(template.source.to_string()),
+                        if let Err(e) = template.google_source() {
+                            text(e.to_string()).style(text::danger)
+                        } else {
+                            text("")
+                        },
+                        text(format!(
+                            "Header row {}, items from row {}",
+                            template.header_row, template.start_row
+                        )),
+                        text(columns).size(12),
+                    ]
+                    .width(Fill),
+                    button("Remove")
+                        .style(button::danger)
+                        .on_press(Message::TemplatesMessage(TemplatesMessage::Remove(i))),
+                ]
+                .spacing(10),
+            )
+        },
+    );
+
+    let draft = &state.templates_state;
+    let defaults = SheetColumns::default();
+    let form = column![
+        text("New template").size(18),
+        TextInput::new("Template name", &draft.name)
+            .on_input(|n| Message::TemplatesMessage(TemplatesMessage::SetName(n))),
+        TextInput::new(
+            "https://docs.google.com/spreadsheets/d/123456789/edit?gid=0#gid=0",
+            &draft.sheets_url
+        )
+        .on_input(|u| Message::TemplatesMessage(TemplatesMessage::SetSheetsUrl(u))),
+        row![
+            column_input(
+                "Folder info",
+                &draft.columns.folder_info,
+                ColumnField::FolderInfo
+            ),
+            column_input(
+                "Folder link",
+                &draft.columns.folder_link,
+                ColumnField::FolderLink
+            ),
+            column_input(
+                "Loose files",
+                &draft.columns.loose_files,
+                ColumnField::LooseFiles
+            ),
+            column_input("File link", &draft.columns.file_link, ColumnField::FileLink),
+            column_input("File type", &draft.columns.file_type, ColumnField::FileType),
+        ]
+        .spacing(5),
+        row![
+            column_input("Title", &draft.columns.title, ColumnField::Title),
+            column_input(
+                "Description",
+                &draft.columns.description,
+                ColumnField::Description
+            ),
+            column_input("Dates", &draft.columns.dates, ColumnField::Dates),
+            column_input("Creator", &draft.columns.creator, ColumnField::Creator),
+            column_input("Tags", &draft.columns.tags, ColumnField::Tags),
+            column_input("Notes", &draft.columns.notes, ColumnField::Notes),
+        ]
+        .spacing(5),
+        row![
+            column_input("Duration", &draft.columns.duration, ColumnField::Duration),
+            column_input(
+                "Technical metadata",
+                &draft.columns.technical,
+                ColumnField::Technical
+            ),
+            column_input("PUID", &draft.columns.puid, ColumnField::Puid),
+            column_input("PRONOM format", &draft.columns.pronom, ColumnField::Pronom),
+            column_input(
+                "Duplicate of",
+                &draft.columns.duplicate_of,
+                ColumnField::DuplicateOf
+            ),
+            column_input(
+                "File status",
+                &draft.columns.file_status,
+                ColumnField::FileStatus
+            ),
+        ]
+        .spacing(5),
+        row![
+            column_input(
+                "Embedded title",
+                &draft.columns.embedded_title,
+                ColumnField::EmbeddedTitle
+            ),
+            column_input(
+                "Embedded creator",
+                &draft.columns.embedded_creator,
+                ColumnField::EmbeddedCreator
+            ),
+            column_input(
+                "Embedded date",
+                &draft.columns.embedded_date,
+                ColumnField::EmbeddedDate
+            ),
+            column_input(
+                "All embedded metadata",
+                &draft.columns.embedded,
+                ColumnField::Embedded
+            ),
+            column_input(
+                "Restricted review",
+                &draft.columns.restricted_review,
+                ColumnField::RestrictedReview
+            ),
+            column_input(
+                "Classification",
+                &draft.columns.classification,
+                ColumnField::Classification
+            ),
+        ]
+        .spacing(5),
+        text(format!(
+            "Columns default to {}",
+            defaults
+                .all()
+                .iter()
+                .filter(|(_, c)| !c.is_empty())
+                .map(|(label, c)| format!("{label} {c}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+        .size(12),
+        row![
+            TextInput::new("Header row (1)", &draft.header_row)
+                .on_input(|r| Message::TemplatesMessage(TemplatesMessage::SetHeaderRow(r))),
+            TextInput::new("First item row (2)", &draft.start_row)
+                .on_input(|r| Message::TemplatesMessage(TemplatesMessage::SetStartRow(r))),
+        ]
+        .spacing(5),
+        button("Add template")
+            .style(button::primary)
+            .on_press(Message::TemplatesMessage(TemplatesMessage::Add)),
+    ]
+    .spacing(10);
+
+    column![
+        scrollable(column![list, form].spacing(25)).height(Fill),
+        Space::new().height(10),
+        button("Close").on_press(Message::CloseWindow(Subwindow::Templates)),
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(header_row: usize, start_row: usize) -> SheetTemplate {
+        SheetTemplate {
+            header_row,
+            start_row,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rows() {
+        for (header_row, start_row, i, row) in [
+            (1, 2, 0, 0),
+            (1, 2, 1, 1),
+            (1, 2, 5, 5),
+            (3, 6, 0, 2),
+            (3, 6, 1, 5),
+            (3, 6, 4, 8),
+            // Items may start above the header
+            (10, 2, 0, 9),
+            (10, 2, 1, 1),
+            // Rows saved as 0 are treated as the first row
+            (0, 0, 0, 0),
+            (0, 0, 1, 0),
+            (0, 0, 3, 2),
+        ] {
+            assert_eq!(
+                template(header_row, start_row).row(i),
+                row,
+                "header {header_row}, start {start_row}, item {i}"
+            );
+        }
+    }
+
+    #[test]
+    fn drafts() {
+        let draft = |header_row: &str, start_row: &str, title: &str| TemplatesState {
+            name: " Oral histories ".to_string(),
+            sheets_url: "https://docs.google.com/spreadsheets/d/abc123/edit?gid=7#gid=7"
+                .to_string(),
+            columns: SheetColumns {
+                folder_info: String::new(),
+                title: title.to_string(),
+                ..Default::default()
+            },
+            header_row: header_row.to_string(),
+            start_row: start_row.to_string(),
+        };
+
+        let made = draft_template(&draft("", "", " h ")).unwrap();
+        assert_eq!(made.name, "Oral histories");
+        assert_eq!(
+            made.source,
+            TemplateSource::GoogleSheet {
+                spreadsheet_id: "abc123".to_string(),
+                sheet_id: 7
+            }
+        );
+        assert_eq!(made.columns.folder_info, "B");
+        assert_eq!(made.columns.title, "H");
+        assert_eq!((made.header_row, made.start_row), (1, 2));
+        let made = draft_template(&draft("3", "", "")).unwrap();
+        assert_eq!((made.header_row, made.start_row), (3, 4));
+
+        for (header_row, start_row, title) in [
+            ("2", "2", ""),
+            ("0", "", ""),
+            ("x", "", ""),
+            ("", "-1", ""),
+            ("", "", "H1"),
+        ] {
+            assert!(draft_template(&draft(header_row, start_row, title)).is_err());
+        }
+        let unnamed = TemplatesState {
+            name: "  ".to_string(),
+            ..draft("", "", "")
+        };
+        assert!(draft_template(&unnamed).is_err());
+        let no_url = TemplatesState {
+            sheets_url: String::new(),
+            ..draft("", "", "")
+        };
+        assert!(draft_template(&no_url).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use iced::{
+    Element, Font,
+    Length::Fill,
+    widget::{Column, Row, column, container, scrollable, text},
+};
+
+use crate::Message;
+
+/// Extensions previewed as text.
+pub(crate) const TEXT_EXTENSIONS: [&str; 6] = ["txt", "csv", "tsv", "md", "markdown", "rtf"];
+
+/// Only the start of the file is downloaded.
+pub(crate) const PREVIEW_BYTES: usize = 256 * 1024;
+
+/// Rows of a CSV shown as a table.
+const TABLE_ROWS: usize = 200;
+
+/// Characters shown per CSV cell.
+const CELL_CHARS: usize = 40;
+
+#[derive(Debug, Clone)]
+pub(crate) enum TextPreview {
+    Plain {
+        text: String,
+        truncated: bool,
+    },
+    Table {
+        rows: Vec<Vec<String>>,
+        truncated: bool,
+    },
+}
+
+/// Turn the start of a file into something readable.
+///
+/// `truncated` is whether `bytes` is only the start of the file.
+pub(crate) fn parse(extension: &str, bytes: &[u8], truncated: bool) -> TextPreview {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match extension {
+        "csv" | "tsv" => {
+            let mut reader = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .delimiter(if extension == "tsv" { b'\t' } else { b',' })
+                .from_reader(bytes);
+            let mut rows = vec![];
+            for record in reader.records() {
+                // The last record of a truncated file is usually cut off
+                let Ok(record) = record else { break };
+                if rows.len() == TABLE_ROWS {
+                    return TextPreview::Table {
+                        rows,
+                        truncated: true,
+                    };
+                }
+                rows.push(record.iter().map(str::to_string).collect());
+            }
+            TextPreview::Table { rows, truncated }
+        }
+        "rtf" => TextPreview::Plain {
+            text: rtf_to_text(&String::from_utf8_lossy(bytes)),
+            truncated,
+        },
+        _ => TextPreview::Plain {
+            text: String::from_utf8_lossy(bytes).into_owned(),
+            truncated,
+        },
+    }
+}
+
+/// Groups that hold formatting tables rather than text.
+const RTF_SKIPPED_GROUPS: [&str; 5] = ["fonttbl", "colortbl", "stylesheet", "info", "pict"];
+
+/// The visible text of an RTF document. Formatting is dropped.
+pub(crate) fn rtf_to_text(rtf: &str) -> String {
+    let mut out = String::new();
+    // Whether each open group is skipped
+    let mut groups: Vec<bool> = vec![];
+    let mut skipping = false;
+    // Characters to drop after a \u escape, standing in for readers without Unicode
+    let mut fallback = 0;
+    let mut chars = rtf.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                groups.push(skipping);
+            }
+            '}' => {
+                skipping = groups.pop().unwrap_or(false);
+            }
+            '\\' => {
+                let Some(&next) = chars.peek() else { break };
+                if !next.is_ascii_alphabetic() {
+                    chars.next();
+                    let literal = match next {
+                        '\\' | '{' | '}' => Some(next),
+                        '~' => Some('\u{a0}'),
+                        '\'' => {
+                            let hex: String = chars.by_ref().take(2).collect();
+                            u8::from_str_radix(&hex, 16).ok().map(char::from)
+                        }
+                        '*' => {
+                            skipping = true;
+                            None
+                        }
+                        _ => None,
+                    };
+                    if let Some(ch) = literal
+                        && !skipping
+                    {
+                        if fallback > 0 {
+                            fallback -= 1;
+                        } else {
+                            out.push(ch);
+                        }
+                    }
+                    continue;
+                }
+                let mut word = String::new();
+                while let Some(&ch) = chars.peek()
+                    && ch.is_ascii_alphabetic()
+                {
+                    word.push(ch);
+                    chars.next();
+                }
+                let mut param = String::new();
+                while let Some(&ch) = chars.peek()
+                    && (ch.is_ascii_digit() || (ch == '-' && param.is_empty()))
+                {
+                    param.push(ch);
+                    chars.next();
+                }
+                // A single space ends a control word and is not part of the text
+                if chars.peek() == Some(&' ') {
+                    chars.next();
+                }
+                if RTF_SKIPPED_GROUPS.contains(&word.as_str()) {
+                    skipping = true;
+                }
+                if skipping {
+                    continue;
+                }
+                match word.as_str() {
+                    "par" | "line" | "row" => out.push('\n'),
+                    "tab" | "cell" => out.push('\t'),
+                    "u" => {
+                        if let Ok(code) = param.parse::<i32>() {
+                            // Negative values are code points above 32767
+                            let code = if code < 0 { code + 65536 } else { code };
+                            if let Some(ch) = char::from_u32(code as u32) {
+                                out.push(ch);
+                            }
+                            fallback = 1;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            '\r' | '\n' => {}
+            _ if skipping => {}
+            _ if fallback > 0 => fallback -= 1,
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn cell(value: &str) -> String {
+    if value.chars().count() > CELL_CHARS {
+        format!("{}...", value.chars().take(CELL_CHARS).collect::<String>())
+    } else {
+        value.to_string()
+    }
+}
+
+pub(crate) fn view(preview: &TextPreview) -> Element<'_, Message> {
+    let (body, truncated) = match preview {
+        // Prose wraps, so it only scrolls vertically
+        TextPreview::Plain { text: t, truncated } => {
+            (scrollable(text(t).width(Fill)).height(Fill), *truncated)
+        }
+        TextPreview::Table { rows, truncated } => {
+            let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+            let table = rows.iter().fold(Column::new().spacing(2), |col, values| {
+                col.push((0..columns).fold(Row::new().spacing(10), |row, i| {
+                    row.push(
+                        container(
+                            text(cell(values.get(i).map_or("", |v| v.as_str())))
+                                .font(Font::MONOSPACE)
+                                .size(12),
+                        )
+                        .width(CELL_CHARS as f32 * 7.5),
+                    )
+                }))
+            });
+            (
+                scrollable(table)
+                    .direction(scrollable::Direction::Both {
+                        vertical: scrollable::Scrollbar::default(),
+                        horizontal: scrollable::Scrollbar::default(),
+                    })
+                    .height(Fill),
+                *truncated,
+            )
+        }
+    };
+    let note = if truncated {
+        text("Only the start of the file is shown").size(12)
+    } else {
+        text("")
+    };
+    column![body.width(Fill), note].spacing(5).into()
+}
@@ -0,0 +1,198 @@
+use reqwest::Url;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum UrlError {
+    Empty,
+    Malformed(String),
+    WrongHost {
+        expected: &'static str,
+        found: String,
+    },
+    MissingId(&'static str),
+    InvalidId(String),
+}
+
+impl std::fmt::Display for UrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UrlError::Empty => write!(f, "no URL was entered"),
+            UrlError::Malformed(e) => write!(f, "not a valid URL: {e}"),
+            UrlError::WrongHost { expected, found } => {
+                write!(f, "expected a {expected} link, but the host is \"{found}\"")
+            }
+            UrlError::MissingId(what) => write!(f, "the URL does not contain a {what} ID"),
+            UrlError::InvalidId(id) => write!(f, "\"{id}\" is not a valid ID"),
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+fn parse(url: &str) -> Result<Url, UrlError> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Err(UrlError::Empty);
+    }
+    // People often copy links without the scheme
+    let with_scheme = if url.contains("://") {
+        url.to_string()
+    } else {
+        format!("https://{url}")
+    };
+    Url::parse(&with_scheme).map_err(|e| UrlError::Malformed(e.to_string()))
+}
+
+fn check_host(url: &Url, domain: &'static str, expected: &'static str) -> Result<(), UrlError> {
+    let host = url.host_str().unwrap_or_default();
+    if host == domain || host.ends_with(&format!(".{domain}")) {
+        Ok(())
+    } else {
+        Err(UrlError::WrongHost {
+            expected,
+            found: host.to_string(),
+        })
+    }
+}
+
+/// Returns the segment following `marker` in the URL path.
+fn segment_after<'a>(url: &'a Url, marker: &str) -> Option<&'a str> {
+    let mut segments = url.path_segments()?;
+    segments.find(|s| *s == marker)?;
+    segments.next().filter(|s| !s.is_empty())
+}
+
+/// Extract the folder ID from a link such as `https://berkeley.app.box.com/folder/123456789`.
+pub(crate) fn parse_box_folder_url(url: &str) -> Result<usize, UrlError> {
+    let url = parse(url)?;
+    check_host(&url, "box.com", "Box")?;
+    let id = segment_after(&url, "folder").ok_or(UrlError::MissingId("folder"))?;
+    id.parse().map_err(|_| UrlError::InvalidId(id.to_string()))
+}
+
+/// Extract the spreadsheet ID and sheet ID from a Google Sheets link.
+///
+/// The sheet ID comes from the `gid` parameter in either the query or the fragment.
+/// Links without one point at the first sheet, which is `0` unless it was deleted.
+pub(crate) fn parse_sheets_url(url: &str) -> Result<(String, i32), UrlError> {
+    let url = parse(url)?;
+    check_host(&url, "docs.google.com", "Google Sheets")?;
+    let spreadsheet_id = segment_after(&url, "d").ok_or(UrlError::MissingId("spreadsheet"))?;
+    if !spreadsheet_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(UrlError::InvalidId(spreadsheet_id.to_string()));
+    }
+
+    let gid = url
+        .query_pairs()
+        .find(|(k, _)| k == "gid")
+        .map(|(_, v)| v.into_owned())
+        .or_else(|| {
+            url.fragment().and_then(|f| {
+                f.split('&')
+                    .find_map(|p| p.strip_prefix("gid="))
+                    .map(str::to_string)
+            })
+        });
+    let sheet_id = match gid {
+        Some(gid) => gid.parse().map_err(|_| UrlError::InvalidId(gid))?,
+        None => 0,
+    };
+
+    Ok((spreadsheet_id.to_string(), sheet_id))
+}
+
+/// Extract a folder ID from a Google Drive folder link, or accept a bare ID.
+pub(crate) fn parse_drive_folder(input: &str) -> Result<String, UrlError> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err(UrlError::Empty);
+    }
+    if trimmed
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Ok(trimmed.to_string());
+    }
+    let url = parse(trimmed)?;
+    check_host(&url, "drive.google.com", "Google Drive")?;
+    segment_after(&url, "folders")
+        .map(str::to_string)
+        .ok_or(UrlError::MissingId("folder"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_folders() {
+        let id = parse_box_folder_url("https://berkeley.app.box.com/folder/123456789");
+        assert_eq!(id, Ok(123456789));
+        assert_eq!(
+            parse_box_folder_url("  app.box.com/folder/42?s=abc "),
+            Ok(42)
+        );
+        assert_eq!(parse_box_folder_url(""), Err(UrlError::Empty));
+        assert_eq!(
+            parse_box_folder_url("https://box.example.com/folder/1"),
+            Err(UrlError::WrongHost {
+                expected: "Box",
+                found: "box.example.com".to_string()
+            })
+        );
+        assert_eq!(
+            parse_box_folder_url("https://app.box.com/file/1"),
+            Err(UrlError::MissingId("folder"))
+        );
+        assert_eq!(
+            parse_box_folder_url("https://app.box.com/folder/abc"),
+            Err(UrlError::InvalidId("abc".to_string()))
+        );
+    }
+
+    #[test]
+    fn sheets() {
+        let base = "https://docs.google.com/spreadsheets/d/1AbC-d_E/edit";
+        assert_eq!(parse_sheets_url(base), Ok(("1AbC-d_E".to_string(), 0)));
+        assert_eq!(
+            parse_sheets_url(&format!("{base}#gid=1234")),
+            Ok(("1AbC-d_E".to_string(), 1234))
+        );
+        assert_eq!(
+            parse_sheets_url(&format!("{base}?gid=7#gid=8")),
+            Ok(("1AbC-d_E".to_string(), 7))
+        );
+        assert_eq!(
+            parse_sheets_url(&format!("{base}#gid=abc")),
+            Err(UrlError::InvalidId("abc".to_string()))
+        );
+        assert_eq!(
+            parse_sheets_url("https://docs.google.com/spreadsheets/"),
+            Err(UrlError::MissingId("spreadsheet"))
+        );
+        assert!(matches!(
+            parse_sheets_url("https://drive.google.com/spreadsheets/d/1AbC/edit"),
+            Err(UrlError::WrongHost { .. })
+        ));
+        assert!(matches!(
+            parse_sheets_url("https://exa mple.com"),
+            Err(UrlError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn drive_folders() {
+        assert_eq!(parse_drive_folder(" 1a2B-c_3 "), Ok("1a2B-c_3".to_string()));
+        assert_eq!(
+            parse_drive_folder("https://drive.google.com/drive/folders/1a2B?usp=sharing"),
+            Ok("1a2B".to_string())
+        );
+        assert_eq!(
+            parse_drive_folder("https://drive.google.com/drive/my-drive?x=1"),
+            Err(UrlError::MissingId("folder"))
+        );
+        assert_eq!(parse_drive_folder("   "), Err(UrlError::Empty));
+    }
+}
@@ -0,0 +1,1024 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::Cursor,
+    path::PathBuf,
+};
+
+use ::image::{
+    DynamicImage, ImageDecoder, ImageReader, RgbaImage, imageops::FilterType, metadata::Orientation,
+};
+use r#box::{
+    apis::{
+        configuration::Configuration,
+        downloads_api::{GetFilesIdContentParams, get_files_id_content},
+        files_api::{GetFilesIdThumbnailIdParams, get_files_id_thumbnail_id},
+    },
+    models::{FileFull, Item},
+};
+use iced::{
+    ContentFit, Element,
+    Length::Fill,
+    Task,
+    widget::{Column, button, column, container, image, row, text},
+};
+
+use crate::{
+    Message, State,
+    archive::{self, ARCHIVE_EXTENSIONS, ArchiveEntry, Listing},
+    inspect::{self, Inspection},
+    media::{self, MEDIA_EXTENSIONS, MediaInfo},
+    metadata::{self, Edit, item_info},
+    pdf_render::{self, LocalPdf},
+    project_page::Node,
+    project_settings,
+    representations::{self, Rendition},
+    text_preview::{self, PREVIEW_BYTES, TEXT_EXTENSIONS, TextPreview},
+};
+
+/// Extensions shown as images. Anything `image` can decode with its default features.
+pub(crate) const IMAGE_EXTENSIONS: [&str; 10] = [
+    "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "ico", "tga",
+];
+
+/// Office documents, which Box renders to pages.
+const DOCUMENT_EXTENSIONS: [&str; 12] = [
+    "doc", "docx", "odt", "ppt", "pptx", "odp", "xls", "xlsx", "ods", "pages", "key", "numbers",
+];
+
+/// Larger images are scaled down after decoding to keep the cache small.
+const MAX_DIMENSION: u32 = 4096;
+
+/// Previews kept in memory: decoded pixels, downloaded PDFs, text, listings and the like.
+/// The least recently viewed files are dropped first.
+const CACHE_BYTES: usize = 512 * 1024 * 1024;
+
+/// Files larger than this only get Box's thumbnail.
+const MAX_DOWNLOAD: i64 = 100 * 1024 * 1024;
+
+/// Edge length requested from Box's thumbnail endpoint.
+const THUMBNAIL_SIZE: i32 = 320;
+
+/// Height in pixels of the drawn waveform.
+const WAVEFORM_HEIGHT: u32 = 120;
+
+/// What a decoded thumbnail takes at most.
+const THUMBNAIL_BYTES: usize = (THUMBNAIL_SIZE * THUMBNAIL_SIZE * 4) as usize;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Decoded {
+    handle: image::Handle,
+    width: u32,
+    height: u32,
+}
+
+impl Decoded {
+    fn bytes(&self) -> usize {
+        self.width as usize * self.height as usize * 4
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ViewerState {
+    images: HashMap<String, Decoded>,
+    /// Cache keys of everything cached below, least recently viewed first
+    order: VecDeque<String>,
+    /// Roughly how much memory each key's entries take
+    sizes: HashMap<String, usize>,
+    cached_bytes: usize,
+    thumbnails: HashMap<String, image::Handle>,
+    texts: HashMap<String, TextPreview>,
+    renditions: HashMap<String, Rendition>,
+    pdfs: HashMap<String, LocalPdf>,
+    media: HashMap<String, MediaInfo>,
+    waveforms: HashMap<String, image::Handle>,
+    inspections: HashMap<String, Inspection>,
+    archives: HashMap<String, Listing>,
+    /// Show the bytes of files that have a preview too
+    inspecting: bool,
+    /// Format being typed for the selected file
+    identification: String,
+    /// The page shown for each document, from 1
+    pages: HashMap<String, usize>,
+    pending: HashSet<String>,
+    failed: HashMap<String, String>,
+}
+
+impl ViewerState {
+    /// Count `bytes` just cached under `key`, then drop the least recently viewed entries
+    /// until the cache is back under budget.
+    fn cached(&mut self, key: &str, bytes: usize) {
+        *self.sizes.entry(key.to_string()).or_default() += bytes;
+        self.cached_bytes += bytes;
+        self.order.retain(|k| k != key);
+        self.order.push_back(key.to_string());
+        while self.cached_bytes > CACHE_BYTES && self.order.len() > 1 {
+            if let Some(evicted) = self.order.pop_front() {
+                self.evict(&evicted);
+            }
+        }
+    }
+
+    fn evict(&mut self, key: &str) {
+        self.images.remove(key);
+        self.thumbnails.remove(key);
+        self.texts.remove(key);
+        self.renditions.remove(key);
+        self.pdfs.remove(key);
+        self.media.remove(key);
+        self.waveforms.remove(key);
+        self.inspections.remove(key);
+        self.archives.remove(key);
+        self.cached_bytes -= self.sizes.remove(key).unwrap_or_default();
+    }
+
+    fn touch(&mut self, id: &str) {
+        if let Some(pos) = self.order.iter().position(|i| i == id)
+            && let Some(id) = self.order.remove(pos)
+        {
+            self.order.push_back(id);
+        }
+    }
+
+    fn is_known(&self, key: &str) -> bool {
+        self.images.contains_key(key)
+            || self.texts.contains_key(key)
+            || self.renditions.contains_key(key)
+            || self.pdfs.contains_key(key)
+            || self.media.contains_key(key)
+            || self.inspections.contains_key(key)
+            || self.archives.contains_key(key)
+            || self.pending.contains(key)
+            || self.failed.contains_key(key)
+    }
+
+    fn page(&self, id: &str) -> usize {
+        self.pages.get(id).copied().unwrap_or(1)
+    }
+
+    /// A document's page count, `Some(None)` while it is still unknown, or `None` before
+    /// the document itself has loaded.
+    fn page_count(&self, id: &str) -> Option<Option<usize>> {
+        match (self.pdfs.get(id), self.renditions.get(id)) {
+            (Some(pdf), _) => Some(Some(pdf.pages)),
+            (None, Some(rendition)) => Some(rendition.page_count()),
+            (None, None) => None,
+        }
+    }
+}
+
+fn text_bytes(preview: &TextPreview) -> usize {
+    match preview {
+        TextPreview::Plain { text, .. } => text.len(),
+        TextPreview::Table { rows, .. } => rows.iter().flatten().map(String::len).sum(),
+    }
+}
+
+/// Key under which a waveform that couldn't be drawn is recorded as failed.
+fn waveform_key(id: &str) -> String {
+    format!("{id}#waveform")
+}
+
+/// Cache key of a file's inspection.
+fn inspect_key(id: &str) -> String {
+    format!("{id}#inspect")
+}
+
+/// Cache key of a rendered document page.
+fn page_key(id: &str, page: usize) -> String {
+    format!("{id}#{page}")
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum ViewerMessage {
+    /// An image or a document page, keyed by ID or [`page_key`]
+    Loaded(String, Result<Decoded, String>),
+    ThumbnailLoaded(String, image::Handle),
+    TextLoaded(String, Result<TextPreview, String>),
+    RenditionFound(String, Result<Rendition, String>),
+    PdfLoaded(String, Result<LocalPdf, String>),
+    SetPage(String, usize),
+    MediaLoaded(String, Result<MediaInfo, String>),
+    WaveformLoaded(String, Result<Vec<f32>, String>),
+    /// Play a file by ID and name, audio in the app and video in the system's player
+    Play(String, String),
+    Downloaded(String, Result<PathBuf, String>),
+    StopPlayback,
+    PlaybackEnded,
+    ToggleInspect,
+    Inspected(String, Result<Inspection, String>),
+    SetIdentification(String),
+    /// Record a file's format by ID, clearing it when empty
+    Identify(String, String),
+    ArchiveListed(String, Result<Listing, String>),
+    /// Add an archive's entries to the project tree, by the archive's ID
+    ExpandArchive(String),
+    CollapseArchive(String),
+    /// Select an entry of an expanded archive for tagging, by archive ID and path
+    SelectEntry(String, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Image,
+    Text,
+    Document,
+    Pdf,
+    Media,
+    Archive,
+}
+
+fn extension(item: &Item) -> Option<String> {
+    let Item::FileFull(file) = item else {
+        return None;
+    };
+    file.extension.as_deref().map(str::to_ascii_lowercase)
+}
+
+fn kind(item: &Item) -> Option<Kind> {
+    let extension = extension(item)?;
+    let extension = extension.as_str();
+    if IMAGE_EXTENSIONS.contains(&extension) {
+        Some(Kind::Image)
+    } else if TEXT_EXTENSIONS.contains(&extension) {
+        Some(Kind::Text)
+    } else if DOCUMENT_EXTENSIONS.contains(&extension) {
+        Some(Kind::Document)
+    } else if extension == "pdf" {
+        Some(Kind::Pdf)
+    } else if MEDIA_EXTENSIONS.contains(&extension) {
+        Some(Kind::Media)
+    } else if ARCHIVE_EXTENSIONS.contains(&extension) {
+        Some(Kind::Archive)
+    } else {
+        None
+    }
+}
+
+fn is_image(item: &Item) -> bool {
+    kind(item) == Some(Kind::Image)
+}
+
+fn decode(bytes: &[u8]) -> Result<Decoded, String> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?
+        .into_decoder()
+        .map_err(|e| e.to_string())?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    img.apply_orientation(orientation);
+    if img.width() > MAX_DIMENSION || img.height() > MAX_DIMENSION {
+        img = img.resize(MAX_DIMENSION, MAX_DIMENSION, FilterType::Triangle);
+    }
+    Ok(decoded(img.into_rgba8()))
+}
+
+fn decoded(rgba: RgbaImage) -> Decoded {
+    let (width, height) = rgba.dimensions();
+    Decoded {
+        handle: image::Handle::from_rgba(width, height, rgba.into_raw()),
+        width,
+        height,
+    }
+}
+
+async fn fetch_text(
+    config: Configuration,
+    id: String,
+    extension: String,
+    size: Option<i64>,
+) -> Result<TextPreview, String> {
+    let resp = get_files_id_content(
+        &config,
+        GetFilesIdContentParams {
+            file_id: id,
+            range: Some(format!("bytes=0-{}", PREVIEW_BYTES - 1)),
+            boxapi: None,
+            version: None,
+            access_token: None,
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+    let truncated = size.is_some_and(|s| s as usize > bytes.len());
+    Ok(text_preview::parse(&extension, &bytes, truncated))
+}
+
+/// Download and decode one page of a Box rendition.
+async fn fetch_page(config: Configuration, url: String) -> Result<Decoded, String> {
+    let token = config
+        .oauth_access_token
+        .clone()
+        .ok_or("Not logged in to Box")?;
+    let bytes = config
+        .client
+        .get(url)
+        .bearer_auth(token)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .bytes()
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || decode(&bytes))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+async fn fetch_image(config: Configuration, id: String) -> Result<Decoded, String> {
+    let resp = get_files_id_content(
+        &config,
+        GetFilesIdContentParams {
+            file_id: id,
+            range: None,
+            boxapi: None,
+            version: None,
+            access_token: None,
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+    // Decoding a large photo takes long enough to stall the UI
+    tokio::task::spawn_blocking(move || decode(&bytes))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+async fn fetch_media(config: Configuration, id: String) -> Result<MediaInfo, String> {
+    media::probe(&config, &id).await
+}
+
+/// Draw peak levels as a mirrored bar per column.
+fn draw_waveform(peaks: &[f32]) -> image::Handle {
+    let width = peaks.len().max(1) as u32;
+    let mut pixels = vec![0u8; (width * WAVEFORM_HEIGHT * 4) as usize];
+    let middle = WAVEFORM_HEIGHT as f32 / 2.0;
+    for (x, peak) in peaks.iter().enumerate() {
+        let reach = (peak * middle).max(0.5);
+        for y in 0..WAVEFORM_HEIGHT {
+            if (y as f32 + 0.5 - middle).abs() <= reach {
+                let at = ((y * width + x as u32) * 4) as usize;
+                pixels[at..at + 4].copy_from_slice(&[70, 130, 180, 255]);
+            }
+        }
+    }
+    image::Handle::from_rgba(width, WAVEFORM_HEIGHT, pixels)
+}
+
+/// Box answers 202 while it is still generating a thumbnail, so that counts as none.
+async fn fetch_thumbnail(config: Configuration, id: String) -> Option<image::Handle> {
+    let resp = get_files_id_thumbnail_id(
+        &config,
+        GetFilesIdThumbnailIdParams {
+            file_id: id.clone(),
+            extension: "png".to_string(),
+            min_height: None,
+            min_width: None,
+            max_height: Some(THUMBNAIL_SIZE),
+            max_width: Some(THUMBNAIL_SIZE),
+        },
+    )
+    .await
+    .inspect_err(|e| tracing::debug!("No thumbnail for {}: {}", id, e))
+    .ok()?;
+    if resp.status() != reqwest::StatusCode::OK {
+        return None;
+    }
+    let bytes = resp.bytes().await.ok()?;
+    Some(image::Handle::from_bytes(bytes))
+}
+
+fn thumbnail(state: &State, id: &str) -> Task<Message> {
+    if state.viewer_state.thumbnails.contains_key(id) {
+        return Task::none();
+    }
+    let id = id.to_string();
+    Task::perform(
+        fetch_thumbnail(state.box_config.clone(), id.clone()),
+        move |t| match t {
+            Some(handle) => {
+                Message::ViewerMessage(ViewerMessage::ThumbnailLoaded(id.clone(), handle))
+            }
+            None => Message::None,
+        },
+    )
+}
+
+fn load(state: &mut State, item: &Item) -> Task<Message> {
+    let Item::FileFull(file) = item else {
+        return Task::none();
+    };
+    let (Some(kind), Some(extension)) = (kind(item), extension(item)) else {
+        return Task::none();
+    };
+    let id = file.id.clone();
+    if state.viewer_state.is_known(&id) {
+        return Task::none();
+    }
+    state.viewer_state.pending.insert(id.clone());
+    let config = state.box_config.clone();
+    match kind {
+        Kind::Image if file.size.is_some_and(|s| s > MAX_DOWNLOAD) => {
+            thumbnail(state, &id).chain(Task::done(Message::ViewerMessage(ViewerMessage::Loaded(
+                id,
+                Err("Too large to preview in full".to_string()),
+            ))))
+        }
+        Kind::Image => Task::batch([
+            thumbnail(state, &id),
+            Task::perform(fetch_image(config, id.clone()), move |result| {
+                Message::ViewerMessage(ViewerMessage::Loaded(id.clone(), result))
+            }),
+        ]),
+        Kind::Text => Task::perform(
+            fetch_text(config, id.clone(), extension, file.size),
+            move |result| Message::ViewerMessage(ViewerMessage::TextLoaded(id.clone(), result)),
+        ),
+        Kind::Document => Task::batch([
+            thumbnail(state, &id),
+            Task::perform(representations::find(config, id.clone()), move |result| {
+                Message::ViewerMessage(ViewerMessage::RenditionFound(id.clone(), result))
+            }),
+        ]),
+        Kind::Pdf if file.size.is_some_and(|s| s > MAX_DOWNLOAD) => {
+            thumbnail(state, &id).chain(Task::done(Message::ViewerMessage(
+                ViewerMessage::PdfLoaded(id, Err("Too large to preview in full".to_string())),
+            )))
+        }
+        Kind::Pdf => Task::batch([
+            thumbnail(state, &id),
+            Task::perform(pdf_render::fetch(config, id.clone()), move |result| {
+                Message::ViewerMessage(ViewerMessage::PdfLoaded(id.clone(), result))
+            }),
+        ]),
+        Kind::Archive => Task::perform(
+            archive::list(config, id.clone(), file.name.clone().unwrap_or_default()),
+            move |result| Message::ViewerMessage(ViewerMessage::ArchiveListed(id.clone(), result)),
+        ),
+        // Box's thumbnail of a video is a frame from it
+        Kind::Media => Task::batch([
+            thumbnail(state, &id),
+            Task::perform(fetch_media(config, id.clone()), move |result| {
+                Message::ViewerMessage(ViewerMessage::MediaLoaded(id.clone(), result))
+            }),
+        ]),
+    }
+}
+
+/// Start loading one page of a document that has loaded.
+fn load_page(state: &mut State, id: &str, page: usize) -> Task<Message> {
+    let key = page_key(id, page);
+    let viewer = &state.viewer_state;
+    let Some(count) = viewer.page_count(id) else {
+        return Task::none();
+    };
+    if viewer.is_known(&key) || count.is_some_and(|n| page > n) {
+        return Task::none();
+    }
+    let task = if let Some(pdf) = viewer.pdfs.get(id) {
+        Task::perform(pdf_render::render(pdf.clone(), page), move |result| {
+            Message::ViewerMessage(ViewerMessage::Loaded(key.clone(), result.map(decoded)))
+        })
+    } else if let Some(rendition) = viewer.renditions.get(id) {
+        let url = rendition.page_url(page);
+        Task::perform(fetch_page(state.box_config.clone(), url), move |result| {
+            Message::ViewerMessage(ViewerMessage::Loaded(key.clone(), result))
+        })
+    } else {
+        return Task::none();
+    };
+    state.viewer_state.pending.insert(page_key(id, page));
+    task
+}
+
+/// Start inspecting the bytes of a file.
+fn load_inspection(state: &mut State, item: &Item) -> Task<Message> {
+    let Item::FileFull(file) = item else {
+        return Task::none();
+    };
+    let key = inspect_key(&file.id);
+    state.viewer_state.touch(&key);
+    if state.viewer_state.is_known(&key) {
+        return Task::none();
+    }
+    state.viewer_state.pending.insert(key.clone());
+    Task::perform(
+        inspect::fetch(state.box_config.clone(), file.id.clone(), file.size),
+        move |result| Message::ViewerMessage(ViewerMessage::Inspected(key.clone(), result)),
+    )
+}
+
+/// Load a page and the one after it, so paging forward is instant.
+fn show_page(state: &mut State, id: &str, page: usize) -> Task<Message> {
+    state.viewer_state.pages.insert(id.to_string(), page);
+    state.viewer_state.touch(id);
+    state.viewer_state.touch(&page_key(id, page));
+    Task::batch([load_page(state, id, page), load_page(state, id, page + 1)])
+}
+
+/// Start loading the selected file. For images, the ones either side of it are loaded too
+/// so flipping through a folder doesn't wait on the network.
+pub(crate) fn selection_changed(state: &mut State) -> Task<Message> {
+    let Some(selected) = state.selected.clone() else {
+        return Task::none();
+    };
+    let id = item_info(&selected).0.to_string();
+    if archive::is_entry(&id) {
+        return Task::none();
+    }
+    state.viewer_state.touch(&id);
+    // Selecting a file again retries it, e.g. once Box has finished rendering
+    state.viewer_state.failed.remove(&id);
+    state.viewer_state.failed.remove(&inspect_key(&id));
+    state.viewer_state.identification = state
+        .metadata
+        .get(&id)
+        .map(|m| m.format.clone())
+        .unwrap_or_default();
+    // Files without a preview are inspected instead
+    if state.viewer_state.inspecting || kind(&selected).is_none() {
+        return load_inspection(state, &selected);
+    }
+    if !is_image(&selected) {
+        return load(state, &selected);
+    }
+
+    let contents = &state.file_tree_state.contents;
+    let neighbours: Vec<Item> = match contents.iter().position(|i| item_info(i).0 == id) {
+        Some(pos) => {
+            let before = contents[..pos].iter().rev().find(|i| is_image(i));
+            let after = contents[pos + 1..].iter().find(|i| is_image(i));
+            before.into_iter().chain(after).cloned().collect()
+        }
+        None => vec![],
+    };
+
+    let mut tasks = vec![load(state, &selected)];
+    for item in &neighbours {
+        tasks.push(load(state, item));
+    }
+    Task::batch(tasks)
+}
+
+pub(crate) fn viewer_handle(state: &mut State, event: ViewerMessage) -> Task<Message> {
+    let viewer = &mut state.viewer_state;
+    match event {
+        ViewerMessage::Loaded(key, result) => {
+            viewer.pending.remove(&key);
+            match result {
+                Ok(decoded) => {
+                    let bytes = decoded.bytes();
+                    viewer.images.insert(key.clone(), decoded);
+                    viewer.cached(&key, bytes);
+                }
+                Err(e) => {
+                    tracing::warn!("Could not preview {}: {}", key, e);
+                    viewer.failed.insert(key, e);
+                }
+            }
+        }
+        ViewerMessage::ThumbnailLoaded(id, handle) => {
+            viewer.thumbnails.insert(id.clone(), handle);
+            viewer.cached(&id, THUMBNAIL_BYTES);
+        }
+        ViewerMessage::TextLoaded(id, result) => {
+            viewer.pending.remove(&id);
+            match result {
+                Ok(preview) => {
+                    let bytes = text_bytes(&preview);
+                    viewer.texts.insert(id.clone(), preview);
+                    viewer.cached(&id, bytes);
+                }
+                Err(e) => {
+                    tracing::warn!("Could not preview file {}: {}", id, e);
+                    viewer.failed.insert(id, e);
+                }
+            }
+        }
+        ViewerMessage::RenditionFound(id, result) => {
+            viewer.pending.remove(&id);
+            match result {
+                Ok(rendition) => {
+                    viewer.renditions.insert(id.clone(), rendition);
+                    viewer.cached(&id, size_of::<Rendition>());
+                    let page = viewer.page(&id);
+                    return show_page(state, &id, page);
+                }
+                Err(e) => {
+                    tracing::warn!("Could not preview file {}: {}", id, e);
+                    viewer.failed.insert(id, e);
+                }
+            }
+        }
+        ViewerMessage::PdfLoaded(id, result) => {
+            viewer.pending.remove(&id);
+            match result {
+                Ok(pdf) => {
+                    let bytes = pdf.bytes();
+                    viewer.pdfs.insert(id.clone(), pdf);
+                    viewer.cached(&id, bytes);
+                    let page = viewer.page(&id);
+                    return show_page(state, &id, page);
+                }
+                Err(e) => {
+                    tracing::warn!("Could not preview file {}: {}", id, e);
+                    viewer.failed.insert(id, e);
+                }
+            }
+        }
+        ViewerMessage::SetPage(id, page) => {
+            if page >= 1 {
+                return show_page(state, &id, page);
+            }
+        }
+        ViewerMessage::MediaLoaded(id, result) => {
+            viewer.pending.remove(&id);
+            match result {
+                Ok(info) => {
+                    let pcm = info.pcm;
+                    let video = !info.video.is_empty();
+                    viewer.media.insert(id.clone(), info);
+                    viewer.cached(&id, size_of::<MediaInfo>());
+                    let config = state.box_config.clone();
+                    let key = id.clone();
+                    let done = move |result| {
+                        Message::ViewerMessage(ViewerMessage::WaveformLoaded(key, result))
+                    };
+                    return match pcm {
+                        Some(layout) => Task::perform(media::waveform(config, id, layout), done),
+                        None if !video => Task::perform(media::decoded_waveform(config, id), done),
+                        // Box's thumbnail of a video stands in for a waveform
+                        None => Task::none(),
+                    };
+                }
+                Err(e) => {
+                    tracing::warn!("Could not read media file {}: {}", id, e);
+                    viewer.failed.insert(id, e);
+                }
+            }
+        }
+        ViewerMessage::WaveformLoaded(id, result) => match result {
+            Ok(peaks) if !peaks.is_empty() => {
+                viewer.waveforms.insert(id.clone(), draw_waveform(&peaks));
+                viewer.cached(&id, peaks.len() * WAVEFORM_HEIGHT as usize * 4);
+            }
+            Ok(_) => {
+                let e = "no samples could be read".to_string();
+                viewer.failed.insert(waveform_key(&id), e);
+            }
+            Err(e) => {
+                tracing::warn!("Could not draw the waveform of {}: {}", id, e);
+                viewer.failed.insert(waveform_key(&id), e);
+            }
+        },
+        ViewerMessage::ToggleInspect => {
+            viewer.inspecting = !viewer.inspecting;
+            return selection_changed(state);
+        }
+        ViewerMessage::Inspected(key, result) => {
+            viewer.pending.remove(&key);
+            match result {
+                Ok(inspection) => {
+                    let bytes = inspection.bytes();
+                    viewer.inspections.insert(key.clone(), inspection);
+                    viewer.cached(&key, bytes);
+                }
+                Err(e) => {
+                    tracing::warn!("Could not inspect {}: {}", key, e);
+                    viewer.failed.insert(key, e);
+                }
+            }
+        }
+        ViewerMessage::SetIdentification(format) => viewer.identification = format,
+        ViewerMessage::Identify(id, format) => {
+            viewer.identification = format.clone();
+            return metadata::apply_edit(state, &[id], Edit::Identify(format));
+        }
+        ViewerMessage::ArchiveListed(id, result) => {
+            viewer.pending.remove(&id);
+            match result {
+                Ok(listing) => {
+                    let bytes = listing
+                        .entries
+                        .iter()
+                        .map(|e| size_of::<ArchiveEntry>() + e.path.len())
+                        .sum();
+                    viewer.archives.insert(id.clone(), listing);
+                    viewer.cached(&id, bytes);
+                }
+                Err(e) => {
+                    tracing::warn!("Could not list archive {}: {}", id, e);
+                    viewer.failed.insert(id, e);
+                }
+            }
+        }
+        ViewerMessage::ExpandArchive(id) => {
+            let Some(listing) = viewer.archives.get(&id).cloned() else {
+                return Task::none();
+            };
+            return set_entries(state, &id, |node| {
+                Some(archive::entry_nodes(node, &listing))
+            });
+        }
+        ViewerMessage::CollapseArchive(id) => return set_entries(state, &id, |_| None),
+        ViewerMessage::SelectEntry(archive_id, path) => {
+            let entry = FileFull {
+                id: archive::entry_id(&archive_id, &path),
+                name: Some(path),
+                ..Default::default()
+            };
+            return Task::done(Message::Select(Item::FileFull(Box::new(entry))));
+        }
+        ViewerMessage::Play(id, name) => {
+            // Stopping first removes the copy, so the same file is downloaded afresh
+            state.playback = None;
+            return Task::perform(
+                media::download(state.box_config.clone(), id.clone(), name.clone()),
+                move |result| {
+                    if let Err(e) = &result {
+                        tracing::error!("Could not download {} to play it: {}", name, e);
+                    }
+                    Message::ViewerMessage(ViewerMessage::Downloaded(id.clone(), result))
+                },
+            );
+        }
+        ViewerMessage::Downloaded(id, Ok(path)) => {
+            let video = viewer
+                .media
+                .get(&id)
+                .is_some_and(|info| !info.video.is_empty());
+            if !video {
+                match media::Playback::start(id.clone(), path.clone()) {
+                    Ok(playback) => {
+                        let finished = playback.finished();
+                        state.playback = Some(playback);
+                        return Task::perform(finished, |_| {
+                            Message::ViewerMessage(ViewerMessage::PlaybackEnded)
+                        });
+                    }
+                    Err(e) => tracing::info!(
+                        "Could not play {} in the app, so it opens in the system's player: {}",
+                        id,
+                        e
+                    ),
+                }
+            }
+            if let Err(e) = media::open_externally(&path) {
+                tracing::error!("Could not open {} in the system's player: {}", id, e);
+            }
+        }
+        ViewerMessage::Downloaded(_, Err(_)) => {}
+        ViewerMessage::StopPlayback => state.playback = None,
+        // A later playback may have started since
+        ViewerMessage::PlaybackEnded => {
+            if state.playback.as_ref().is_some_and(|p| !p.is_playing()) {
+                state.playback = None;
+            }
+        }
+    }
+    Task::none()
+}
+
+/// Replace the children of archive `id` in the project tree and save the tree.
+fn set_entries(
+    state: &mut State,
+    id: &str,
+    entries: impl FnOnce(&Node) -> Option<Vec<Node>>,
+) -> Task<Message> {
+    let (Some(project), Some(tree)) = (&state.project, &mut state.project_tree) else {
+        tracing::warn!("The project tree is not loaded, so the archive can't be changed");
+        return Task::none();
+    };
+    let Some(node) = tree.find_mut(id) else {
+        tracing::warn!("Archive {} is not in the project tree", id);
+        return Task::none();
+    };
+    node.children = entries(node);
+    tracing::info!(
+        "{} {} entries of archive \"{}\"",
+        if node.children.is_some() {
+            "Added"
+        } else {
+            "Removed"
+        },
+        node.children.as_ref().map_or(0, Vec::len),
+        node.name
+    );
+    project_settings::save_tree(project.name.clone(), tree.clone())
+}
+
+fn image_view(decoded: &Decoded) -> Element<'_, Message> {
+    column![
+        image::viewer(decoded.handle.clone())
+            .width(Fill)
+            .height(Fill)
+            .min_scale(0.1)
+            .max_scale(20.0),
+        text(format!(
+            "{} × {}. Scroll to zoom, drag to pan.",
+            decoded.width, decoded.height
+        ))
+        .size(12),
+    ]
+    .spacing(5)
+    .into()
+}
+
+/// What to show while `key` loads, over the thumbnail if there is one.
+fn loading<'a>(viewer: &'a ViewerState, id: &str, key: &str) -> Element<'a, Message> {
+    let status = match viewer.failed.get(key) {
+        Some(e) => text(format!("Could not load the preview: {e}")).style(text::danger),
+        None => text("Loading..."),
+    };
+    match viewer.thumbnails.get(id) {
+        Some(thumbnail) => column![
+            container(image(thumbnail.clone())).center(Fill),
+            status.size(12),
+        ]
+        .spacing(5)
+        .into(),
+        None => status.into(),
+    }
+}
+
+fn document_view<'a>(viewer: &'a ViewerState, id: &str) -> Element<'a, Message> {
+    let Some(count) = viewer.page_count(id) else {
+        return loading(viewer, id, id);
+    };
+    let page = viewer.page(id);
+    let key = page_key(id, page);
+    let page_message = |page| Message::ViewerMessage(ViewerMessage::SetPage(id.to_string(), page));
+    // Without a page count, the last page is the one that fails to load
+    let has_next = match count {
+        Some(n) => page < n,
+        None => !viewer.failed.contains_key(&page_key(id, page + 1)),
+    };
+    let nav = row![
+        button("Previous")
+            .style(button::secondary)
+            .on_press_maybe((page > 1).then(|| page_message(page - 1))),
+        text(match count {
+            Some(n) => format!("Page {page} of {n}"),
+            None => format!("Page {page}"),
+        }),
+        button("Next")
+            .style(button::secondary)
+            .on_press_maybe(has_next.then(|| page_message(page + 1))),
+    ]
+    .align_y(iced::Alignment::Center)
+    .spacing(10);
+    let body = match viewer.images.get(&key) {
+        Some(decoded) => image_view(decoded),
+        None if page == 1 => loading(viewer, id, &key),
+        None => loading(viewer, "", &key),
+    };
+    column![nav, body].spacing(5).into()
+}
+
+fn media_view<'a>(
+    viewer: &'a ViewerState,
+    id: &str,
+    name: &str,
+    playing: bool,
+) -> Element<'a, Message> {
+    let Some(info) = viewer.media.get(id) else {
+        return loading(viewer, id, id);
+    };
+    let line = |label: &str, value: String| {
+        row![text(format!("{label}:")).width(90.0), text(value)].spacing(10)
+    };
+    let mut details = Column::new()
+        .spacing(5)
+        .push(line("Container", info.container.clone()))
+        .push(line(
+            "Duration",
+            info.duration_text().unwrap_or("Unknown".to_string()),
+        ));
+    for video in &info.video {
+        details = details.push(line("Video", video.describe()));
+    }
+    for audio in &info.audio {
+        details = details.push(line("Audio", audio.describe()));
+    }
+
+    let failed = viewer.failed.get(&waveform_key(id));
+    let picture: Element<'a, Message> = match (viewer.waveforms.get(id), failed) {
+        (Some(waveform), _) => image(waveform.clone())
+            .width(Fill)
+            .height(WAVEFORM_HEIGHT as f32)
+            .content_fit(ContentFit::Fill)
+            .into(),
+        (None, _) if !info.video.is_empty() => match viewer.thumbnails.get(id) {
+            Some(thumbnail) => container(image(thumbnail.clone())).center(Fill).into(),
+            None => text("No still available")
+                .style(text::secondary)
+                .size(12)
+                .into(),
+        },
+        (None, Some(e)) => text(format!("No waveform: {e}"))
+            .style(text::secondary)
+            .size(12)
+            .into(),
+        (None, None) => text("Drawing waveform...").size(12).into(),
+    };
+
+    let play = if playing {
+        button("Stop")
+            .style(button::secondary)
+            .on_press(Message::ViewerMessage(ViewerMessage::StopPlayback))
+    } else {
+        button("Play")
+            .style(button::primary)
+            .on_press(Message::ViewerMessage(ViewerMessage::Play(
+                id.to_string(),
+                name.to_string(),
+            )))
+    };
+    let note = if info.video.is_empty() {
+        "Plays in the app. Formats it can't decode, such as Opus, open in the system's player."
+    } else {
+        "Only a single still is shown here. The video plays in the system's default player."
+    };
+
+    column![details, picture, play, text(note).size(12)]
+        .spacing(10)
+        .into()
+}
+
+fn inspection_view<'a>(state: &'a State, id: &str) -> Element<'a, Message> {
+    let viewer = &state.viewer_state;
+    let key = inspect_key(id);
+    match viewer.inspections.get(&key) {
+        Some(inspection) => inspect::view(
+            inspection,
+            id,
+            state.metadata.get(id).map_or("", |m| m.format.as_str()),
+            &viewer.identification,
+        ),
+        None => loading(viewer, "", &key),
+    }
+}
+
+pub(crate) fn viewer(state: &State) -> Element<'_, Message> {
+    let Some(item) = &state.selected else {
+        return text("Select a file to preview it").into();
+    };
+    let Item::FileFull(file) = item else {
+        return text("Only files can be previewed").into();
+    };
+    let viewer = &state.viewer_state;
+    let id = &file.id;
+    if archive::is_entry(id) {
+        return text(format!(
+            "\"{}\" is inside an archive. Select the archive to see its listing.",
+            file.name.as_deref().unwrap_or_default()
+        ))
+        .into();
+    }
+    let Some(kind) = kind(item) else {
+        return inspection_view(state, id);
+    };
+    let toggle = button(if viewer.inspecting {
+        "Show preview"
+    } else {
+        "Inspect bytes"
+    })
+    .style(button::secondary)
+    .on_press(Message::ViewerMessage(ViewerMessage::ToggleInspect));
+    let body = if viewer.inspecting {
+        inspection_view(state, id)
+    } else {
+        match kind {
+            Kind::Image => match viewer.images.get(id) {
+                Some(decoded) => image_view(decoded),
+                None => loading(viewer, id, id),
+            },
+            Kind::Text => match viewer.texts.get(id) {
+                Some(preview) => text_preview::view(preview),
+                None => loading(viewer, id, id),
+            },
+            Kind::Document | Kind::Pdf => document_view(viewer, id),
+            Kind::Media => media_view(
+                viewer,
+                id,
+                file.name.as_deref().unwrap_or_default(),
+                state.playback.as_ref().is_some_and(|p| p.id == *id),
+            ),
+            Kind::Archive => match viewer.archives.get(id) {
+                Some(listing) => {
+                    let expanded = state
+                        .project_tree
+                        .as_ref()
+                        .and_then(|tree| tree.find(id))
+                        .is_some_and(|node| node.children.is_some());
+                    archive::view(listing, id, expanded)
+                }
+                None => loading(viewer, id, id),
+            },
+        }
+    };
+    column![toggle, body].spacing(5).into()
+}
@@ -0,0 +1,721 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    widget::{Column, Space, TextInput, button, column, pick_list, row, scrollable, text},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{Message, State, persist, subwindows::Subwindow};
+
+const SKOS: &str = "http://www.w3.org/2004/02/skos/core#";
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+/// Suggestions shown under the tag input.
+pub(crate) const MAX_SUGGESTIONS: usize = 8;
+
+/// What happens when a tag is not in any of the project's vocabularies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) enum VocabularyPolicy {
+    /// Any tag is accepted
+    #[default]
+    Free,
+    /// The tag is accepted but flagged
+    Warn,
+    /// The tag is rejected
+    Block,
+}
+
+impl std::fmt::Display for VocabularyPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VocabularyPolicy::Free => write!(f, "Allow any tag"),
+            VocabularyPolicy::Warn => write!(f, "Warn on unknown tags"),
+            VocabularyPolicy::Block => write!(f, "Only allow vocabulary terms"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Term {
+    /// Preferred label, which is what gets stored as the tag
+    pub label: String,
+    /// Variants that resolve to this term, e.g. "OH" for "Oral history"
+    #[serde(default)]
+    pub alt_labels: Vec<String>,
+    /// Label of the broader term in a hierarchical vocabulary
+    #[serde(default)]
+    pub broader: Option<String>,
+    #[serde(default)]
+    pub uri: Option<String>,
+}
+
+impl Term {
+    fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            alt_labels: vec![],
+            broader: None,
+            uri: None,
+        }
+    }
+
+    fn matches(&self, tag: &str) -> bool {
+        self.label.eq_ignore_ascii_case(tag)
+            || self.alt_labels.iter().any(|a| a.eq_ignore_ascii_case(tag))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Vocabulary {
+    pub name: String,
+    pub terms: Vec<Term>,
+}
+
+impl Vocabulary {
+    pub fn is_hierarchical(&self) -> bool {
+        self.terms.iter().any(|t| t.broader.is_some())
+    }
+
+    /// Terms in hierarchy order with their depth. Terms whose broader term is
+    /// missing are treated as top-level.
+    pub fn tree(&self) -> Vec<(usize, &Term)> {
+        let labels: HashSet<&str> = self.terms.iter().map(|t| t.label.as_str()).collect();
+        let mut children: HashMap<Option<&str>, Vec<&Term>> = HashMap::new();
+        for term in &self.terms {
+            let parent = term
+                .broader
+                .as_deref()
+                .filter(|b| labels.contains(b) && *b != term.label);
+            children.entry(parent).or_default().push(term);
+        }
+
+        fn walk<'a>(
+            parent: Option<&'a str>,
+            depth: usize,
+            children: &HashMap<Option<&'a str>, Vec<&'a Term>>,
+            seen: &mut HashSet<&'a str>,
+            out: &mut Vec<(usize, &'a Term)>,
+        ) {
+            for &term in children.get(&parent).into_iter().flatten() {
+                if seen.insert(term.label.as_str()) {
+                    out.push((depth, term));
+                    walk(Some(&term.label), depth + 1, children, seen, out);
+                }
+            }
+        }
+
+        let mut out = Vec::with_capacity(self.terms.len());
+        let mut seen = HashSet::new();
+        walk(None, 0, &children, &mut seen, &mut out);
+        // Terms stuck in a broader/narrower cycle never hang off a top-level term
+        for term in &self.terms {
+            if seen.insert(term.label.as_str()) {
+                out.push((0, term));
+            }
+        }
+        out
+    }
+}
+
+/// The project's vocabularies and how strictly tags must follow them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct VocabularyStore {
+    pub policy: VocabularyPolicy,
+    pub vocabularies: Vec<Vocabulary>,
+}
+
+impl VocabularyStore {
+    fn terms(&self) -> impl Iterator<Item = &Term> {
+        self.vocabularies.iter().flat_map(|v| v.terms.iter())
+    }
+
+    /// Whether tags are checked against the vocabularies at all.
+    pub fn is_enforced(&self) -> bool {
+        self.policy != VocabularyPolicy::Free && self.terms().next().is_some()
+    }
+
+    /// The term a tag or one of its variants refers to.
+    pub fn resolve(&self, tag: &str) -> Option<&Term> {
+        let tag = tag.trim();
+        self.terms().find(|t| t.matches(tag))
+    }
+
+    /// Whether a stored tag would be flagged under the current policy.
+    pub fn is_unknown(&self, tag: &str) -> bool {
+        self.is_enforced() && self.resolve(tag).is_none()
+    }
+
+    /// The tag to store for `input` under the current policy, or `None` if it is rejected.
+    ///
+    /// Variants are replaced with their preferred label.
+    pub fn accept(&self, input: &str) -> Option<String> {
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+        match self.resolve(input) {
+            Some(term) => Some(term.label.clone()),
+            None if self.is_enforced() => {
+                if self.policy == VocabularyPolicy::Block {
+                    tracing::warn!("\"{}\" is not in the project's vocabularies", input);
+                    return None;
+                }
+                tracing::warn!(
+                    "Added \"{}\", which is not in the project's vocabularies",
+                    input
+                );
+                Some(input.to_string())
+            }
+            None => Some(input.to_string()),
+        }
+    }
+
+    /// Preferred labels matching what has been typed so far, prefix matches first.
+    pub fn suggest(&self, input: &str) -> Vec<String> {
+        let input = input.trim().to_lowercase();
+        if input.is_empty() {
+            return vec![];
+        }
+        let mut prefix = vec![];
+        let mut contains = vec![];
+        for term in self.terms() {
+            let names = std::iter::once(&term.label).chain(term.alt_labels.iter());
+            let mut best = None;
+            for name in names {
+                let name = name.to_lowercase();
+                if name.starts_with(&input) {
+                    best = Some(true);
+                    break;
+                } else if name.contains(&input) {
+                    best = Some(false);
+                }
+            }
+            match best {
+                Some(true) => prefix.push(term.label.clone()),
+                Some(false) => contains.push(term.label.clone()),
+                None => {}
+            }
+        }
+        prefix.extend(contains);
+        let mut seen = HashSet::new();
+        prefix.retain(|l| seen.insert(l.clone()));
+        prefix.truncate(MAX_SUGGESTIONS);
+        prefix
+    }
+}
+
+pub(crate) async fn load(project: String) -> VocabularyStore {
+    match persist::load_project_store::<VocabularyStore>(&project, "vocabularies").await {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::debug!("No vocabularies loaded for {}: {}", project, e);
+            VocabularyStore::default()
+        }
+    }
+}
+
+fn save(project: String, store: VocabularyStore) -> Task<Message> {
+    let snapshot = persist::snapshot();
+    Task::perform(
+        async move {
+            if let Err(e) =
+                persist::save_project_store(&store, &project, "vocabularies", snapshot).await
+            {
+                tracing::error!("Error saving vocabularies for {}: {}", project, e);
+            }
+        },
+        |_| Message::None,
+    )
+}
+
+/// Read a CSV of `label, broader, alternative labels, URI`, where only the label is required
+/// and alternative labels are separated by `|`. A header row starting with "label" is skipped.
+fn parse_csv(name: String, data: &[u8], delimiter: u8) -> Result<Vocabulary, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data);
+    let mut terms = vec![];
+    for (i, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("Line {}: {}", i + 1, e))?;
+        let label = record.get(0).unwrap_or_default();
+        if label.is_empty() || (i == 0 && label.eq_ignore_ascii_case("label")) {
+            continue;
+        }
+        let field = |n: usize| record.get(n).filter(|f| !f.is_empty()).map(str::to_string);
+        terms.push(Term {
+            label: label.to_string(),
+            broader: field(1),
+            alt_labels: field(2)
+                .map(|a| {
+                    a.split('|')
+                        .map(str::trim)
+                        .filter(|a| !a.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            uri: field(3),
+        });
+    }
+    Ok(Vocabulary { name, terms })
+}
+
+/// Read the `skos:Concept`s out of an RDF/XML file.
+///
+/// English or untagged preferred labels win over other languages.
+fn parse_skos(name: String, data: &str) -> Result<Vocabulary, String> {
+    let doc = roxmltree::Document::parse(data).map_err(|e| e.to_string())?;
+
+    let is_concept = |node: &roxmltree::Node| {
+        node.has_tag_name((SKOS, "Concept"))
+            || (node.has_tag_name((RDF, "Description"))
+                && node.children().any(|c| {
+                    c.has_tag_name((RDF, "type"))
+                        && c.attribute((RDF, "resource")) == Some(format!("{SKOS}Concept").as_str())
+                }))
+    };
+    let english = |node: &roxmltree::Node| {
+        node.attribute(("http://www.w3.org/XML/1998/namespace", "lang"))
+            .is_none_or(|l| l.to_ascii_lowercase().starts_with("en"))
+    };
+
+    let mut concepts = vec![];
+    for node in doc.descendants().filter(is_concept) {
+        let uri = node.attribute((RDF, "about")).map(str::to_string);
+        let mut pref = None;
+        let mut alt_labels = vec![];
+        let mut broader = None;
+        for child in node.children().filter(|c| c.is_element()) {
+            let value = child.text().map(str::trim).unwrap_or_default();
+            if child.has_tag_name((SKOS, "prefLabel")) && !value.is_empty() {
+                if pref.is_none() || english(&child) {
+                    pref = Some(value.to_string());
+                }
+            } else if child.has_tag_name((SKOS, "altLabel")) && !value.is_empty() {
+                alt_labels.push(value.to_string());
+            } else if child.has_tag_name((SKOS, "broader")) {
+                broader = child.attribute((RDF, "resource")).map(str::to_string);
+            }
+        }
+        if let Some(label) = pref {
+            concepts.push((uri, label, alt_labels, broader));
+        }
+    }
+    if concepts.is_empty() {
+        return Err("No SKOS concepts with a preferred label were found".to_string());
+    }
+
+    let labels: HashMap<String, String> = concepts
+        .iter()
+        .filter_map(|(uri, label, _, _)| uri.clone().map(|u| (u, label.clone())))
+        .collect();
+    let terms = concepts
+        .into_iter()
+        .map(|(uri, label, alt_labels, broader)| Term {
+            label,
+            alt_labels,
+            broader: broader.and_then(|b| labels.get(&b).cloned()),
+            uri,
+        })
+        .collect();
+    Ok(Vocabulary { name, terms })
+}
+
+async fn import(path: PathBuf) -> Result<Vocabulary, String> {
+    let name = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or("Imported vocabulary".to_string());
+    let data = tokio::fs::read(&path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("csv") => parse_csv(name, &data, b','),
+        Some("tsv") => parse_csv(name, &data, b'\t'),
+        _ => parse_skos(name, &String::from_utf8_lossy(&data)),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct VocabularyState {
+    selected: Option<usize>,
+    new_name: String,
+    term_input: String,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum VocabularyMessage {
+    Loaded(VocabularyStore),
+    SetPolicy(VocabularyPolicy),
+    Select(usize),
+    SetNewName(String),
+    Create,
+    Remove(usize),
+    SetTermInput(String),
+    AddTerm,
+    RemoveTerm(String),
+    Import,
+    Imported(Result<Vocabulary, String>),
+}
+
+pub(crate) fn vocabulary_handle(state: &mut State, event: VocabularyMessage) -> Task<Message> {
+    let Some(project) = &state.project else {
+        return Task::none();
+    };
+    let project = project.name.clone();
+    let store = &mut state.vocabularies;
+    let ui = &mut state.vocabulary_state;
+    match event {
+        VocabularyMessage::Loaded(loaded) => {
+            *store = loaded;
+            *ui = VocabularyState::default();
+            return Task::none();
+        }
+        VocabularyMessage::SetPolicy(policy) => store.policy = policy,
+        VocabularyMessage::Select(i) => {
+            ui.selected = Some(i);
+            return Task::none();
+        }
+        VocabularyMessage::SetNewName(n) => {
+            ui.new_name = n;
+            return Task::none();
+        }
+        VocabularyMessage::Create => {
+            let name = ui.new_name.trim().to_string();
+            if name.is_empty() {
+                return Task::none();
+            }
+            store.vocabularies.push(Vocabulary {
+                name,
+                terms: vec![],
+            });
+            ui.selected = Some(store.vocabularies.len() - 1);
+            ui.new_name.clear();
+        }
+        VocabularyMessage::Remove(i) => {
+            if i >= store.vocabularies.len() {
+                return Task::none();
+            }
+            let removed = store.vocabularies.remove(i);
+            tracing::info!("Removed vocabulary \"{}\"", removed.name);
+            ui.selected = None;
+        }
+        VocabularyMessage::SetTermInput(t) => {
+            ui.term_input = t;
+            return Task::none();
+        }
+        VocabularyMessage::AddTerm => {
+            let Some(vocabulary) = ui.selected.and_then(|i| store.vocabularies.get_mut(i)) else {
+                return Task::none();
+            };
+            // "Broader > Narrower" adds a term under an existing one
+            let mut parts = ui.term_input.rsplitn(2, '>').map(str::trim);
+            let label = parts.next().unwrap_or_default().to_string();
+            let broader = parts.next().filter(|b| !b.is_empty()).map(str::to_string);
+            if label.is_empty() {
+                return Task::none();
+            }
+            if vocabulary.terms.iter().any(|t| t.label == label) {
+                tracing::warn!("\"{}\" is already in {}", label, vocabulary.name);
+                return Task::none();
+            }
+            if let Some(broader) = &broader
+                && !vocabulary.terms.iter().any(|t| &t.label == broader)
+            {
+                vocabulary.terms.push(Term::new(broader));
+            }
+            vocabulary.terms.push(Term {
+                broader,
+                ..Term::new(&label)
+            });
+            ui.term_input.clear();
+        }
+        VocabularyMessage::RemoveTerm(label) => {
+            let Some(vocabulary) = ui.selected.and_then(|i| store.vocabularies.get_mut(i)) else {
+                return Task::none();
+            };
+            vocabulary.terms.retain(|t| t.label != label);
+            // Narrower terms move up to the removed term's level
+            for term in &mut vocabulary.terms {
+                if term.broader.as_ref() == Some(&label) {
+                    term.broader = None;
+                }
+            }
+        }
+        VocabularyMessage::Import => {
+            return Task::perform(
+                async {
+                    let file = rfd::AsyncFileDialog::new()
+                        .add_filter("SKOS or CSV", &["rdf", "xml", "skos", "owl", "csv", "tsv"])
+                        .pick_file()
+                        .await?;
+                    Some(import(file.path().to_path_buf()).await)
+                },
+                |r| match r {
+                    Some(r) => Message::VocabularyMessage(VocabularyMessage::Imported(r)),
+                    None => Message::None,
+                },
+            );
+        }
+        VocabularyMessage::Imported(result) => match result {
+            Ok(vocabulary) => {
+                tracing::info!(
+                    "Imported {} terms into vocabulary \"{}\"",
+                    vocabulary.terms.len(),
+                    vocabulary.name
+                );
+                store.vocabularies.push(vocabulary);
+                ui.selected = Some(store.vocabularies.len() - 1);
+            }
+            Err(e) => {
+                tracing::error!("Failed to import vocabulary: {}", e);
+                return Task::none();
+            }
+        },
+    }
+    save(project, store.clone())
+}
+
+pub(crate) fn vocabularies(state: &State) -> Element<'_, Message> {
+    let close = button("Close").on_press(Message::CloseWindow(Subwindow::Vocabularies));
+    if state.project.is_none() {
+        return column![text("No project open"), Space::new().height(Fill), close]
+            .padding(Padding::new(15.0))
+            .spacing(15.0)
+            .into();
+    }
+    let store = &state.vocabularies;
+    let ui = &state.vocabulary_state;
+
+    let policies = vec![
+        VocabularyPolicy::Free,
+        VocabularyPolicy::Warn,
+        VocabularyPolicy::Block,
+    ];
+    let list = store.vocabularies.iter().enumerate().fold(
+        Column::new().spacing(5),
+        |col, (i, vocabulary)| {
+            let label = format!(
+                "{} ({} terms{})",
+                vocabulary.name,
+                vocabulary.terms.len(),
+                if vocabulary.is_hierarchical() {
+                    ", hierarchical"
+                } else {
+                    ""
+                }
+            );
+            col.push(
+                row![
+                    button(text(label))
+                        .width(Fill)
+                        .style(if ui.selected == Some(i) {
+                            button::primary
+                        } else {
+                            button::secondary
+                        })
+                        .on_press(Message::VocabularyMessage(VocabularyMessage::Select(i))),
+                    button("Remove")
+                        .style(button::danger)
+                        .on_press(Message::VocabularyMessage(VocabularyMessage::Remove(i))),
+                ]
+                .spacing(5),
+            )
+        },
+    );
+
+    let terms: Element<Message> = match ui.selected.and_then(|i| store.vocabularies.get(i)) {
+        Some(vocabulary) => column![
+            row![
+                TextInput::new("Term, or Broader term > Term", &ui.term_input)
+                    .on_input(|t| Message::VocabularyMessage(VocabularyMessage::SetTermInput(t)))
+                    .on_submit(Message::VocabularyMessage(VocabularyMessage::AddTerm)),
+                button("Add term").on_press(Message::VocabularyMessage(VocabularyMessage::AddTerm)),
+            ]
+            .spacing(5),
+            scrollable(vocabulary.tree().into_iter().fold(
+                Column::new().spacing(2),
+                |col, (depth, term)| {
+                    let alt = if term.alt_labels.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" (also {})", term.alt_labels.join(", "))
+                    };
+                    col.push(
+                        row![
+                            Space::new().width(depth as f32 * 16.0),
+                            text(format!("{}{}", term.label, alt)).width(Fill),
+                            button(text("×").size(12)).style(button::text).on_press(
+                                Message::VocabularyMessage(VocabularyMessage::RemoveTerm(
+                                    term.label.clone()
+                                ))
+                            ),
+                        ]
+                        .align_y(Center),
+                    )
+                }
+            ))
+            .height(Fill),
+        ]
+        .spacing(10)
+        .into(),
+        None => text("Select a vocabulary to edit its terms").into(),
+    };
+
+    column![
+        text("Controlled vocabularies").size(20),
+        row![
+            text("Tags"),
+            pick_list(policies, Some(store.policy), |p| {
+                Message::VocabularyMessage(VocabularyMessage::SetPolicy(p))
+            }),
+        ]
+        .align_y(Center)
+        .spacing(10),
+        list,
+        row![
+            TextInput::new("New vocabulary name", &ui.new_name)
+                .on_input(|n| Message::VocabularyMessage(VocabularyMessage::SetNewName(n)))
+                .on_submit(Message::VocabularyMessage(VocabularyMessage::Create)),
+            button("Create").on_press(Message::VocabularyMessage(VocabularyMessage::Create)),
+            button("Import SKOS/CSV...")
+                .on_press(Message::VocabularyMessage(VocabularyMessage::Import)),
+        ]
+        .spacing(5),
+        terms,
+        close,
+    ]
+    .padding(Padding::new(15.0))
+    .spacing(15.0)
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(vocabulary: &Vocabulary) -> Vec<&str> {
+        vocabulary.terms.iter().map(|t| t.label.as_str()).collect()
+    }
+
+    #[test]
+    fn csv_terms() {
+        let data = b"Label,Broader,Alternatives,URI\n\
+            Oral history,,OH | oral histories,http://example.org/oh\n\
+            Interview, Oral history ,,\n\
+            ,ignored\n\
+            Photograph\n";
+        let vocabulary = parse_csv("Types".to_string(), data, b',').unwrap();
+        assert_eq!(vocabulary.name, "Types");
+        assert_eq!(
+            labels(&vocabulary),
+            ["Oral history", "Interview", "Photograph"]
+        );
+        let oral = &vocabulary.terms[0];
+        assert_eq!(oral.alt_labels, ["OH", "oral histories"]);
+        assert_eq!(oral.uri.as_deref(), Some("http://example.org/oh"));
+        assert_eq!(vocabulary.terms[1].broader.as_deref(), Some("Oral history"));
+        assert_eq!(vocabulary.terms[2], Term::new("Photograph"));
+    }
+
+    #[test]
+    fn tsv_terms() {
+        let vocabulary = parse_csv(String::new(), b"Maps\tPlaces\nPlaces", b'\t').unwrap();
+        assert_eq!(labels(&vocabulary), ["Maps", "Places"]);
+        assert_eq!(vocabulary.terms[0].broader.as_deref(), Some("Places"));
+    }
+
+    #[test]
+    fn skos_concepts() {
+        let data = r#"<?xml version="1.0"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+    xmlns:skos="http://www.w3.org/2004/02/skos/core#">
+  <skos:Concept rdf:about="http://example.org/places">
+    <skos:prefLabel xml:lang="fr">Lieux</skos:prefLabel>
+    <skos:prefLabel xml:lang="en">Places</skos:prefLabel>
+  </skos:Concept>
+  <rdf:Description rdf:about="http://example.org/cities">
+    <rdf:type rdf:resource="http://www.w3.org/2004/02/skos/core#Concept"/>
+    <skos:prefLabel>Cities</skos:prefLabel>
+    <skos:altLabel>Towns</skos:altLabel>
+    <skos:broader rdf:resource="http://example.org/places"/>
+  </rdf:Description>
+  <rdf:Description rdf:about="http://example.org/other">
+    <skos:prefLabel>Not a concept</skos:prefLabel>
+  </rdf:Description>
+</rdf:RDF>"#;
+        let vocabulary = parse_skos("Places".to_string(), data).unwrap();
+        assert_eq!(labels(&vocabulary), ["Places", "Cities"]);
+        let cities = &vocabulary.terms[1];
+        assert_eq!(cities.alt_labels, ["Towns"]);
+        assert_eq!(cities.broader.as_deref(), Some("Places"));
+        assert_eq!(cities.uri.as_deref(), Some("http://example.org/cities"));
+        let tree: Vec<_> = vocabulary
+            .tree()
+            .into_iter()
+            .map(|(depth, t)| (depth, t.label.as_str()))
+            .collect();
+        assert_eq!(tree, [(0, "Places"), (1, "Cities")]);
+    }
+
+    #[test]
+    fn skos_without_concepts() {
+        assert!(parse_skos(String::new(), "<rdf:RDF/>").is_err());
+        assert!(parse_skos(String::new(), "not xml").is_err());
+    }
+
+    #[test]
+    fn cycles_stay_in_the_tree() {
+        let mut a = Term::new("A");
+        a.broader = Some("B".to_string());
+        let mut b = Term::new("B");
+        b.broader = Some("A".to_string());
+        let vocabulary = Vocabulary {
+            name: String::new(),
+            terms: vec![a, b, Term::new("C")],
+        };
+        let tree: Vec<_> = vocabulary
+            .tree()
+            .into_iter()
+            .map(|(depth, t)| (depth, t.label.as_str()))
+            .collect();
+        assert_eq!(tree, [(0, "C"), (0, "A"), (0, "B")]);
+    }
+
+    #[test]
+    fn policies() {
+        let mut term = Term::new("Oral history");
+        term.alt_labels = vec!["OH".to_string()];
+        let mut store = VocabularyStore {
+            policy: VocabularyPolicy::Free,
+            vocabularies: vec![Vocabulary {
+                name: String::new(),
+                terms: vec![term],
+            }],
+        };
+        assert_eq!(store.accept(" oh "), Some("Oral history".to_string()));
+        assert_eq!(store.accept("Maps"), Some("Maps".to_string()));
+        assert!(!store.is_unknown("Maps"));
+        store.policy = VocabularyPolicy::Warn;
+        assert_eq!(store.accept("Maps"), Some("Maps".to_string()));
+        assert!(store.is_unknown("Maps"));
+        store.policy = VocabularyPolicy::Block;
+        assert_eq!(store.accept("Maps"), None);
+        assert_eq!(store.accept(""), None);
+        assert_eq!(store.suggest("hist"), ["Oral history"]);
+    }
+}